@@ -0,0 +1,867 @@
+//! Server configuration.
+//!
+//! Parameters are read from a `redis.conf` style file and then from command line arguments in
+//! the same form as Redis accepts them: `--name value...`. The parsed configuration is stored
+//! globally, so any part of the server can read it. Clients inspect and change it with the
+//! CONFIG command.
+use crate::{
+    error::{ConfigError, RedisError},
+    glob::glob_match,
+    protocol::split_args,
+};
+use lazy_static::lazy_static;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+lazy_static! {
+    static ref CONFIG: RwLock<Config> = RwLock::new(Config::default());
+}
+
+/// Replaces the global configuration.
+pub(crate) fn init(config: Config) {
+    *CONFIG.write().expect("Config lock is poisoned") = config;
+}
+
+/// Returns a copy of the global configuration.
+pub(crate) fn current() -> Config {
+    CONFIG.read().expect("Config lock is poisoned").clone()
+}
+
+/// Sets parameters of the running server. Either all parameters are set or none of them.
+pub(crate) fn set_at_runtime(pairs: &[(String, String)]) -> Result<(), RedisError> {
+    let mut global = CONFIG.write().expect("Config lock is poisoned");
+    let mut config = global.clone();
+    let mut seen = HashSet::new();
+    for (name, value) in pairs {
+        let parameter = find_parameter(name).ok_or_else(|| {
+            RedisError::Other(format!(
+                "Unknown option or number of arguments for CONFIG SET - '{}'",
+                name
+            ))
+        })?;
+        let result = if !parameter.mutable {
+            Err(ConfigError::Invalid(
+                "can't set immutable config".to_owned(),
+            ))
+        } else if !seen.insert(parameter.name) {
+            Err(ConfigError::Invalid("duplicate parameter".to_owned()))
+        } else {
+            (parameter.set)(&mut config, std::slice::from_ref(value))
+        };
+        result.map_err(|error| {
+            RedisError::Other(format!(
+                "CONFIG SET failed (possibly related to argument '{}') - {}",
+                name, error
+            ))
+        })?;
+    }
+    if config.dir != global.dir {
+        std::env::set_current_dir(&config.dir).map_err(|error| {
+            RedisError::Other(format!(
+                "CONFIG SET failed (possibly related to argument 'dir') - {}",
+                error
+            ))
+        })?;
+    }
+    *global = config;
+    Ok(())
+}
+
+/// Rewrites the configuration file the server was started with, so that it reflects the
+/// current configuration.
+pub(crate) fn rewrite() -> Result<(), RedisError> {
+    let config = current();
+    let path = config.config_file.as_ref().ok_or_else(|| {
+        RedisError::Other("The server is running without a config file".to_owned())
+    })?;
+    let io_error =
+        |error: std::io::Error| RedisError::Other(format!("Rewriting config file: {}", error));
+    let old_content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(error) => return Err(io_error(error)),
+    };
+    let new_content = config.rewrite_file(&old_content);
+    let temporary_path = path.with_extension("rewrite.tmp");
+    std::fs::write(&temporary_path, new_content).map_err(io_error)?;
+    std::fs::rename(&temporary_path, path).map_err(io_error)
+}
+
+/// Address of a master this server replicates.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Replicaof {
+    pub(crate) host: String,
+    pub(crate) port: u16,
+}
+
+/// What to do when the memory limit is reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MaxmemoryPolicy {
+    VolatileLru,
+    AllkeysLru,
+    VolatileLfu,
+    AllkeysLfu,
+    VolatileRandom,
+    AllkeysRandom,
+    VolatileTtl,
+    Noeviction,
+}
+
+impl MaxmemoryPolicy {
+    const ALL: [MaxmemoryPolicy; 8] = [
+        MaxmemoryPolicy::VolatileLru,
+        MaxmemoryPolicy::AllkeysLru,
+        MaxmemoryPolicy::VolatileLfu,
+        MaxmemoryPolicy::AllkeysLfu,
+        MaxmemoryPolicy::VolatileRandom,
+        MaxmemoryPolicy::AllkeysRandom,
+        MaxmemoryPolicy::VolatileTtl,
+        MaxmemoryPolicy::Noeviction,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            MaxmemoryPolicy::VolatileLru => "volatile-lru",
+            MaxmemoryPolicy::AllkeysLru => "allkeys-lru",
+            MaxmemoryPolicy::VolatileLfu => "volatile-lfu",
+            MaxmemoryPolicy::AllkeysLfu => "allkeys-lfu",
+            MaxmemoryPolicy::VolatileRandom => "volatile-random",
+            MaxmemoryPolicy::AllkeysRandom => "allkeys-random",
+            MaxmemoryPolicy::VolatileTtl => "volatile-ttl",
+            MaxmemoryPolicy::Noeviction => "noeviction",
+        }
+    }
+}
+
+/// Typed server configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Config {
+    /// Addresses to listen on.
+    pub(crate) bind: Vec<String>,
+    pub(crate) port: u16,
+    /// Working directory. Persistence files are stored there.
+    pub(crate) dir: PathBuf,
+    /// Name of the snapshot file.
+    pub(crate) dbfilename: String,
+    pub(crate) replicaof: Option<Replicaof>,
+    /// Memory limit in bytes. Zero means no limit.
+    pub(crate) maxmemory: u64,
+    pub(crate) maxmemory_policy: MaxmemoryPolicy,
+    /// Frequency of background tasks, e.g. active expiration, per second.
+    pub(crate) hz: u32,
+    /// Sets of integers up to this size are kept in the compact integer set encoding.
+    pub(crate) set_max_intset_entries: usize,
+    /// Approximate trimming of streams removes entries only in chunks of this size, the same as
+    /// Redis removes whole nodes of its radix tree.
+    pub(crate) stream_node_max_entries: usize,
+    /// HyperLogLogs bigger than this in the sparse encoding are converted to the dense one.
+    pub(crate) hll_sparse_max_bytes: usize,
+    /// Absolute path of the configuration file the server was started with.
+    pub(crate) config_file: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: vec!["127.0.0.1".to_owned()],
+            port: 6379,
+            dir: PathBuf::from("."),
+            dbfilename: "dump.rdb".to_owned(),
+            replicaof: None,
+            maxmemory: 0,
+            maxmemory_policy: MaxmemoryPolicy::Noeviction,
+            hz: 10,
+            set_max_intset_entries: 512,
+            stream_node_max_entries: 100,
+            hll_sparse_max_bytes: 3000,
+            config_file: None,
+        }
+    }
+}
+
+/// Line after which CONFIG REWRITE appends parameters missing in the file.
+const REWRITE_SIGNATURE: &str = "# Generated by CONFIG REWRITE";
+
+/// Description of a configuration parameter.
+struct Parameter {
+    name: &'static str,
+    /// Whether the parameter can be changed with CONFIG SET.
+    mutable: bool,
+    /// Returns arguments of the parameter as they would be written in the configuration file.
+    /// No arguments mean the parameter is not set.
+    args: fn(&Config) -> Vec<String>,
+    set: fn(&mut Config, &[String]) -> Result<(), ConfigError>,
+}
+
+/// All supported parameters.
+const PARAMETERS: &[Parameter] = &[
+    Parameter {
+        name: "bind",
+        mutable: false,
+        args: |config| config.bind.clone(),
+        set: |config, args| {
+            if args.is_empty() {
+                return Err(ConfigError::WrongArity);
+            }
+            config.bind = args.to_vec();
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "port",
+        mutable: false,
+        args: |config| vec![config.port.to_string()],
+        set: |config, args| {
+            config.port = parse_integer(single(args)?, 0, u16::MAX as i64)? as u16;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "dir",
+        mutable: true,
+        args: |config| vec![config.dir.display().to_string()],
+        set: |config, args| {
+            let dir = PathBuf::from(single(args)?);
+            if !dir.is_dir() {
+                return Err(ConfigError::Invalid(format!(
+                    "Can't chdir to '{}': No such directory",
+                    dir.display()
+                )));
+            }
+            config.dir = dir;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "dbfilename",
+        mutable: true,
+        args: |config| vec![config.dbfilename.clone()],
+        set: |config, args| {
+            let name = single(args)?;
+            if name.contains('/') {
+                return Err(ConfigError::Invalid(
+                    "dbfilename can't be a path, just a filename".to_owned(),
+                ));
+            }
+            config.dbfilename = name.to_owned();
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "replicaof",
+        mutable: false,
+        args: |config| {
+            config
+                .replicaof
+                .iter()
+                .flat_map(|master| [master.host.clone(), master.port.to_string()])
+                .collect()
+        },
+        set: |config, args| {
+            // Both `--replicaof host port` and `--replicaof "host port"` are accepted.
+            let args: Vec<&str> = args.iter().flat_map(|arg| arg.split_whitespace()).collect();
+            config.replicaof = match args.as_slice() {
+                [no, one] if no.eq_ignore_ascii_case("no") && one.eq_ignore_ascii_case("one") => {
+                    None
+                }
+                [host, port] => Some(Replicaof {
+                    host: host.to_string(),
+                    port: parse_integer(port, 0, u16::MAX as i64)? as u16,
+                }),
+                _ => return Err(ConfigError::WrongArity),
+            };
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "maxmemory",
+        mutable: true,
+        args: |config| vec![config.maxmemory.to_string()],
+        set: |config, args| {
+            config.maxmemory = parse_memory(single(args)?)?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "maxmemory-policy",
+        mutable: true,
+        args: |config| vec![config.maxmemory_policy.name().to_owned()],
+        set: |config, args| {
+            let name = single(args)?;
+            config.maxmemory_policy = MaxmemoryPolicy::ALL
+                .into_iter()
+                .find(|policy| policy.name().eq_ignore_ascii_case(name))
+                .ok_or_else(|| {
+                    ConfigError::NotOneOf(
+                        MaxmemoryPolicy::ALL
+                            .iter()
+                            .map(|policy| policy.name())
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    )
+                })?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "hz",
+        mutable: true,
+        args: |config| vec![config.hz.to_string()],
+        set: |config, args| {
+            config.hz = parse_integer(single(args)?, 1, 500)? as u32;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "set-max-intset-entries",
+        mutable: true,
+        args: |config| vec![config.set_max_intset_entries.to_string()],
+        set: |config, args| {
+            config.set_max_intset_entries = parse_integer(single(args)?, 0, i64::MAX)? as usize;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "stream-node-max-entries",
+        mutable: true,
+        args: |config| vec![config.stream_node_max_entries.to_string()],
+        set: |config, args| {
+            config.stream_node_max_entries = parse_integer(single(args)?, 0, i64::MAX)? as usize;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "hll-sparse-max-bytes",
+        mutable: true,
+        args: |config| vec![config.hll_sparse_max_bytes.to_string()],
+        set: |config, args| {
+            config.hll_sparse_max_bytes = parse_memory(single(args)?)? as usize;
+            Ok(())
+        },
+    },
+];
+
+fn find_parameter(name: &str) -> Option<&'static Parameter> {
+    PARAMETERS
+        .iter()
+        .find(|parameter| parameter.name.eq_ignore_ascii_case(name))
+}
+
+impl Config {
+    /// Parses command line arguments, e.g. `redis.conf --port 7000 --replicaof localhost 6379`.
+    /// The optional first argument is a path to a configuration file. Every `--name` is followed
+    /// by the values of the parameter. Command line parameters override the ones from the file.
+    pub(crate) fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, ConfigError> {
+        let mut config = Config::default();
+        let mut args = args.into_iter().peekable();
+        if let Some(path) = args.next_if(|arg| !arg.starts_with("--")) {
+            config.load_file(Path::new(&path))?;
+        }
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| ConfigError::UnexpectedArgument(arg.clone()))?;
+            let mut values = Vec::new();
+            while let Some(value) = args.next_if(|value| !value.starts_with("--")) {
+                values.push(value);
+            }
+            config
+                .set(name, &values)
+                .map_err(|error| ConfigError::InOption {
+                    option: format!("--{} {}", name, values.join(" ")),
+                    error: Box::new(error),
+                })?;
+        }
+        Ok(config)
+    }
+
+    /// Reads parameters from a `redis.conf` style file and remembers the file for CONFIG REWRITE.
+    fn load_file(&mut self, path: &Path) -> Result<(), ConfigError> {
+        let read_error = |error: std::io::Error| {
+            ConfigError::Invalid(format!(
+                "Fatal error, can't open config file '{}': {}",
+                path.display(),
+                error
+            ))
+        };
+        let content = std::fs::read_to_string(path).map_err(read_error)?;
+        self.load(&content)?;
+        // The server changes its working directory, so the path must be absolute.
+        self.config_file = Some(path.canonicalize().map_err(read_error)?);
+        Ok(())
+    }
+
+    /// Reads parameters from the content of a configuration file. Each line contains a
+    /// parameter name followed by its arguments. Lines starting with `#` are comments.
+    fn load(&mut self, content: &str) -> Result<(), ConfigError> {
+        for (index, line) in content.lines().enumerate() {
+            let in_line = |error| ConfigError::InLine {
+                number: index + 1,
+                line: line.to_owned(),
+                error: Box::new(error),
+            };
+            let args = match parse_line(line) {
+                Some(Ok(args)) => args,
+                Some(Err(error)) => return Err(in_line(error)),
+                None => continue,
+            };
+            self.set(&args[0], &args[1..]).map_err(in_line)?;
+        }
+        Ok(())
+    }
+
+    /// Sets a parameter by its case insensitive name.
+    pub(crate) fn set(&mut self, name: &str, args: &[String]) -> Result<(), ConfigError> {
+        let parameter =
+            find_parameter(name).ok_or_else(|| ConfigError::UnknownParameter(name.to_owned()))?;
+        (parameter.set)(self, args)
+    }
+
+    /// Returns all parameters with their values.
+    pub(crate) fn parameters(&self) -> impl Iterator<Item = (&'static str, String)> + '_ {
+        PARAMETERS
+            .iter()
+            .map(move |parameter| (parameter.name, (parameter.args)(self).join(" ")))
+    }
+
+    /// Returns parameters whose names match any of the glob patterns.
+    pub(crate) fn matching_parameters(&self, patterns: &[&[u8]]) -> Vec<(&'static str, String)> {
+        self.parameters()
+            .filter(|(name, _)| {
+                patterns
+                    .iter()
+                    .any(|pattern| glob_match(pattern, name.as_bytes(), true))
+            })
+            .collect()
+    }
+
+    /// Returns the content of the configuration file updated with the current configuration.
+    /// Lines of parameters are replaced with the current values, comments and unknown lines are
+    /// preserved. Parameters missing in the file are appended if they differ from defaults.
+    fn rewrite_file(&self, old_content: &str) -> String {
+        let mut lines = Vec::new();
+        let mut rewritten = HashSet::new();
+        for line in old_content.lines() {
+            let parameter = match parse_line(line) {
+                Some(Ok(args)) => find_parameter(&args[0]),
+                _ => None,
+            };
+            match parameter {
+                Some(parameter) => {
+                    // Duplicates of a parameter are dropped, the first occurrence is replaced.
+                    if rewritten.insert(parameter.name) {
+                        lines.extend(format_parameter(parameter, self));
+                    }
+                }
+                None => lines.push(line.to_owned()),
+            }
+        }
+
+        let defaults = Config::default();
+        let missing: Vec<String> = PARAMETERS
+            .iter()
+            .filter(|parameter| !rewritten.contains(parameter.name))
+            .filter(|parameter| (parameter.args)(self) != (parameter.args)(&defaults))
+            .filter_map(|parameter| format_parameter(parameter, self))
+            .collect();
+        if !missing.is_empty() {
+            if !lines.iter().any(|line| line == REWRITE_SIGNATURE) {
+                lines.push(REWRITE_SIGNATURE.to_owned());
+            }
+            lines.extend(missing);
+        }
+
+        let mut content = lines.join("\n");
+        content.push('\n');
+        content
+    }
+}
+
+/// Splits a configuration file line into a parameter name and its arguments.
+/// Returns `None` for empty lines and comments.
+fn parse_line(line: &str) -> Option<Result<Vec<String>, ConfigError>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    Some(
+        split_args(line.as_bytes())
+            .map(|args| {
+                args.iter()
+                    .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                    .collect()
+            })
+            .ok_or_else(|| {
+                ConfigError::Invalid("Unbalanced quotes in configuration line".to_owned())
+            }),
+    )
+}
+
+/// Formats a configuration file line for a parameter. Returns `None` if the parameter is not set.
+fn format_parameter(parameter: &Parameter, config: &Config) -> Option<String> {
+    let args = (parameter.args)(config);
+    if args.is_empty() {
+        return None;
+    }
+    let mut line = parameter.name.to_owned();
+    for arg in args {
+        line.push(' ');
+        line.push_str(&quote(&arg));
+    }
+    Some(line)
+}
+
+/// Quotes an argument if it can't be written to the configuration file as is.
+fn quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .bytes()
+            .all(|c| c.is_ascii_graphic() && c != b'"' && c != b'\'' && c != b'\\');
+    if plain {
+        return arg.to_owned();
+    }
+    let mut quoted = String::from("\"");
+    for c in arg.bytes() {
+        match c {
+            b'\\' => quoted.push_str("\\\\"),
+            b'"' => quoted.push_str("\\\""),
+            b'\n' => quoted.push_str("\\n"),
+            b'\r' => quoted.push_str("\\r"),
+            b'\t' => quoted.push_str("\\t"),
+            c if c.is_ascii_graphic() || c == b' ' => quoted.push(c as char),
+            c => quoted.push_str(&format!("\\x{:02x}", c)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Returns the only argument of a parameter.
+fn single(args: &[String]) -> Result<&str, ConfigError> {
+    match args {
+        [arg] => Ok(arg),
+        _ => Err(ConfigError::WrongArity),
+    }
+}
+
+fn parse_integer(arg: &str, min: i64, max: i64) -> Result<i64, ConfigError> {
+    let value = arg.parse::<i64>().map_err(|_| ConfigError::NotInteger)?;
+    if (min..=max).contains(&value) {
+        Ok(value)
+    } else {
+        Err(ConfigError::OutOfRange { min, max })
+    }
+}
+
+/// Parses memory amount with an optional unit: `b`, `k`, `kb`, `m`, `mb`, `g`, `gb`.
+/// Units without `b` are powers of 1000, units with `b` are powers of 1024.
+fn parse_memory(arg: &str) -> Result<u64, ConfigError> {
+    let arg = arg.to_ascii_lowercase();
+    let digits_end = arg.find(|c: char| !c.is_ascii_digit()).unwrap_or(arg.len());
+    let multiplier: u64 = match &arg[digits_end..] {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return Err(ConfigError::NotMemory),
+    };
+    arg[..digits_end]
+        .parse::<u64>()
+        .ok()
+        .and_then(|value| value.checked_mul(multiplier))
+        .ok_or(ConfigError::NotMemory)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(args: &str) -> Vec<String> {
+        args.split(' ').map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn defaults() {
+        assert_eq!(Config::from_args(vec![]), Ok(Config::default()));
+    }
+
+    #[test]
+    fn all_options() {
+        let config = Config::from_args(args(
+            "--port 7000 --bind 0.0.0.0 ::1 --dir /tmp --dbfilename my.rdb \
+             --replicaof localhost 6379 --maxmemory 100mb --maxmemory-policy allkeys-lru --hz 50",
+        ))
+        .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                bind: vec!["0.0.0.0".to_owned(), "::1".to_owned()],
+                port: 7000,
+                dir: PathBuf::from("/tmp"),
+                dbfilename: "my.rdb".to_owned(),
+                replicaof: Some(Replicaof {
+                    host: "localhost".to_owned(),
+                    port: 6379
+                }),
+                maxmemory: 100 * 1024 * 1024,
+                maxmemory_policy: MaxmemoryPolicy::AllkeysLru,
+                hz: 50,
+                set_max_intset_entries: 512,
+                stream_node_max_entries: 100,
+                hll_sparse_max_bytes: 3000,
+                config_file: None,
+            }
+        );
+    }
+
+    #[test]
+    fn quoted_replicaof() {
+        let config =
+            Config::from_args(vec!["--replicaof".to_owned(), "localhost 6380".to_owned()]).unwrap();
+        assert_eq!(
+            config.replicaof,
+            Some(Replicaof {
+                host: "localhost".to_owned(),
+                port: 6380
+            })
+        );
+    }
+
+    #[test]
+    fn memory_units() {
+        assert_eq!(parse_memory("1024"), Ok(1024));
+        assert_eq!(parse_memory("1k"), Ok(1000));
+        assert_eq!(parse_memory("1KB"), Ok(1024));
+        assert_eq!(parse_memory("2gb"), Ok(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_memory("1tb"), Err(ConfigError::NotMemory));
+        assert_eq!(parse_memory("mb"), Err(ConfigError::NotMemory));
+    }
+
+    #[test]
+    fn parameters() {
+        let config = Config::default();
+        let parameters: Vec<_> = config.parameters().collect();
+        assert!(parameters.contains(&("port", "6379".to_owned())));
+        assert!(parameters.contains(&("replicaof", String::new())));
+        assert!(parameters.contains(&("maxmemory-policy", "noeviction".to_owned())));
+    }
+
+    #[test]
+    fn neg_invalid_values() {
+        assert_eq!(
+            Config::from_args(args("--port 70000")),
+            Err(ConfigError::InOption {
+                option: "--port 70000".to_owned(),
+                error: Box::new(ConfigError::OutOfRange { min: 0, max: 65535 }),
+            })
+        );
+        assert_eq!(
+            Config::default().set("port", &args("abc")),
+            Err(ConfigError::NotInteger)
+        );
+        assert_eq!(
+            Config::default().set("hz", &[]),
+            Err(ConfigError::WrongArity)
+        );
+        assert_eq!(
+            Config::default().set("dir", &args("/nonexistent/directory")),
+            Err(ConfigError::Invalid(
+                "Can't chdir to '/nonexistent/directory': No such directory".to_owned()
+            ))
+        );
+        assert_eq!(
+            Config::default().set("replicaof", &args("localhost")),
+            Err(ConfigError::WrongArity)
+        );
+        assert_eq!(
+            Config::default().set("unknown", &args("1")),
+            Err(ConfigError::UnknownParameter("unknown".to_owned()))
+        );
+        assert_eq!(
+            Config::from_args(args("/nonexistent/redis.conf --port 7000")),
+            Err(ConfigError::Invalid(
+                "Fatal error, can't open config file '/nonexistent/redis.conf': \
+                 No such file or directory (os error 2)"
+                    .to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn load_file_content() {
+        let mut config = Config::default();
+        config
+            .load(
+                "# Comment\n\
+                 \n\
+                 port 7000\n\
+                 \x20 maxmemory \"2mb\"\n\
+                 PORT 7001\n",
+            )
+            .unwrap();
+        assert_eq!(config.port, 7001);
+        assert_eq!(config.maxmemory, 2 * 1024 * 1024);
+    }
+
+    #[test]
+    fn neg_load_file_content() {
+        let mut config = Config::default();
+        assert_eq!(
+            config.load("port 7000\nhz 0\n"),
+            Err(ConfigError::InLine {
+                number: 2,
+                line: "hz 0".to_owned(),
+                error: Box::new(ConfigError::OutOfRange { min: 1, max: 500 }),
+            })
+        );
+        assert_eq!(
+            config.load("save 900 1"),
+            Err(ConfigError::InLine {
+                number: 1,
+                line: "save 900 1".to_owned(),
+                error: Box::new(ConfigError::UnknownParameter("save".to_owned())),
+            })
+        );
+    }
+
+    #[test]
+    fn config_file_and_args() {
+        let path = std::env::temp_dir().join("redis-config-file-and-args.conf");
+        std::fs::write(&path, "port 7000\nhz 20\n").unwrap();
+        let config = Config::from_args(vec![
+            path.display().to_string(),
+            "--port".to_owned(),
+            "7001".to_owned(),
+        ])
+        .unwrap();
+        assert_eq!(config.port, 7001);
+        assert_eq!(config.hz, 20);
+        assert_eq!(config.config_file, Some(path.canonicalize().unwrap()));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn neg_stray_argument_after_config_file() {
+        let path = std::env::temp_dir().join("redis-stray-argument.conf");
+        std::fs::write(&path, "port 7000\n").unwrap();
+        for stray in ["x", "b.conf"] {
+            assert_eq!(
+                Config::from_args(vec![path.display().to_string(), stray.to_owned()]),
+                Err(ConfigError::UnexpectedArgument(stray.to_owned()))
+            );
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn matching_parameters() {
+        let config = Config::default();
+        assert_eq!(
+            config.matching_parameters(&[b"MAXMEMORY*", b"hz"]),
+            vec![
+                ("maxmemory", "0".to_owned()),
+                ("maxmemory-policy", "noeviction".to_owned()),
+                ("hz", "10".to_owned()),
+            ]
+        );
+        assert!(config.matching_parameters(&[b"unknown"]).is_empty());
+    }
+
+    #[test]
+    fn rewrite_file() {
+        let mut config = Config::default();
+        config.set("port", &args("7000")).unwrap();
+        config.set("hz", &args("20")).unwrap();
+        config
+            .set("dbfilename", &["my dump.rdb".to_owned()])
+            .unwrap();
+        let old_content = "# Server port\n\
+                           port 6379\n\
+                           \n\
+                           # Timers\n\
+                           port 6380\n\
+                           unknown-directive yes\n\
+                           maxmemory 0\n";
+        assert_eq!(
+            config.rewrite_file(old_content),
+            "# Server port\n\
+             port 7000\n\
+             \n\
+             # Timers\n\
+             unknown-directive yes\n\
+             maxmemory 0\n\
+             # Generated by CONFIG REWRITE\n\
+             dbfilename \"my dump.rdb\"\n\
+             hz 20\n"
+        );
+
+        // Rewriting again keeps the file stable.
+        let content = config.rewrite_file(old_content);
+        assert_eq!(config.rewrite_file(&content), content);
+        config.set("maxmemory", &args("1mb")).unwrap();
+        config.set("replicaof", &args("localhost 6379")).unwrap();
+        assert!(config.rewrite_file(&content).ends_with(
+            "maxmemory 1048576\n\
+             # Generated by CONFIG REWRITE\n\
+             dbfilename \"my dump.rdb\"\n\
+             hz 20\n\
+             replicaof localhost 6379\n"
+        ));
+        let mut reloaded = Config::default();
+        reloaded.load(&config.rewrite_file("")).unwrap();
+        assert_eq!(reloaded, config);
+    }
+
+    #[test]
+    fn set_parameters_at_runtime() {
+        set_at_runtime(&[
+            ("maxmemory".to_owned(), "1mb".to_owned()),
+            ("MAXMEMORY-POLICY".to_owned(), "allkeys-lru".to_owned()),
+        ])
+        .unwrap();
+        assert_eq!(current().maxmemory, 1024 * 1024);
+        assert_eq!(current().maxmemory_policy, MaxmemoryPolicy::AllkeysLru);
+
+        // Nothing is changed if any of the parameters is invalid.
+        assert_eq!(
+            set_at_runtime(&[
+                ("maxmemory".to_owned(), "2mb".to_owned()),
+                ("port".to_owned(), "7000".to_owned()),
+            ]),
+            Err(RedisError::Other(
+                "CONFIG SET failed (possibly related to argument 'port') - \
+                 can't set immutable config"
+                    .to_owned()
+            ))
+        );
+        assert_eq!(current().maxmemory, 1024 * 1024);
+        assert_eq!(
+            set_at_runtime(&[
+                ("maxmemory".to_owned(), "2mb".to_owned()),
+                ("maxmemory".to_owned(), "3mb".to_owned()),
+            ]),
+            Err(RedisError::Other(
+                "CONFIG SET failed (possibly related to argument 'maxmemory') - \
+                 duplicate parameter"
+                    .to_owned()
+            ))
+        );
+        assert_eq!(
+            set_at_runtime(&[("unknown".to_owned(), "1".to_owned())]),
+            Err(RedisError::Other(
+                "Unknown option or number of arguments for CONFIG SET - 'unknown'".to_owned()
+            ))
+        );
+        set_at_runtime(&[
+            ("maxmemory".to_owned(), "0".to_owned()),
+            ("maxmemory-policy".to_owned(), "noeviction".to_owned()),
+        ])
+        .unwrap();
+    }
+}
@@ -1,8 +1,130 @@
-#[derive(thiserror::Error, Debug)]
+/// Errors reported to clients. The message of each error is exactly what is sent to the client
+/// after the `-` sign, so it starts with a Redis error prefix such as `ERR` or `WRONGTYPE`.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
 pub enum RedisError {
-    #[error("Failed to deserialize string '{raw_redis_message}' into Redis message. {details}")]
-    DeserializationError {
-        raw_redis_message: String,
-        details: String,
+    /// Request is not valid RESP. The connection is closed after replying with this error.
+    #[error("ERR Protocol error: {0}")]
+    Protocol(String),
+
+    #[error("ERR unknown command '{command}', with args beginning with: {args}")]
+    UnknownCommand { command: String, args: String },
+
+    #[error("ERR wrong number of arguments for '{0}' command")]
+    WrongArity(String),
+
+    #[error("ERR syntax error")]
+    Syntax,
+
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+
+    #[error("ERR value is not an integer or out of range")]
+    NotInteger,
+
+    #[error("ERR value is not a valid float")]
+    NotFloat,
+
+    #[error("ERR value is out of range, must be positive")]
+    NotPositive,
+
+    #[error("ERR invalid expire time in '{0}' command")]
+    InvalidExpireTime(String),
+
+    #[error("ERR no such key")]
+    NoSuchKey,
+
+    #[error("ERR string exceeds maximum allowed size (proto-max-bulk-len)")]
+    StringTooLong,
+
+    #[error("NOPROTO unsupported protocol version")]
+    NoProto,
+
+    #[error("NOGROUP {0}")]
+    NoGroup(String),
+
+    #[error("BUSYGROUP Consumer Group name already exists")]
+    BusyGroup,
+
+    #[error("WRONGTYPE Key is not a valid HyperLogLog string value.")]
+    InvalidHll,
+
+    #[error("INVALIDOBJ Corrupted HLL object detected")]
+    CorruptedHll,
+
+    /// A blocked client is released because what it waits on is gone.
+    #[error("UNBLOCKED {0}")]
+    Unblocked(String),
+
+    #[error("WRONGPASS invalid username-password pair or user is disabled.")]
+    WrongPass,
+
+    /// Any other error with the generic `ERR` prefix.
+    #[error("ERR {0}")]
+    Other(String),
+}
+
+impl RedisError {
+    /// Builds an error for an unknown command, quoting its first arguments the way Redis does.
+    pub fn unknown_command(command: &[u8], args: &[bytes::Bytes]) -> Self {
+        const MAX_LENGTH: usize = 128;
+        let mut quoted_args = String::new();
+        for arg in args {
+            if quoted_args.len() >= MAX_LENGTH {
+                break;
+            }
+            let arg = String::from_utf8_lossy(arg);
+            let arg: String = arg.chars().take(MAX_LENGTH - quoted_args.len()).collect();
+            quoted_args.push_str(&format!("'{}' ", arg));
+        }
+        RedisError::UnknownCommand {
+            command: String::from_utf8_lossy(command)
+                .chars()
+                .take(MAX_LENGTH)
+                .collect(),
+            args: quoted_args,
+        }
+    }
+}
+
+/// Errors in the server configuration.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    #[error("Bad directive or wrong number of arguments: '{0}'")]
+    UnknownParameter(String),
+
+    #[error("Unexpected argument '{0}'. Options must start with '--'")]
+    UnexpectedArgument(String),
+
+    #[error("wrong number of arguments")]
+    WrongArity,
+
+    #[error("argument couldn't be parsed into an integer")]
+    NotInteger,
+
+    #[error("argument must be a memory value")]
+    NotMemory,
+
+    #[error("argument must be between {min} and {max} inclusive")]
+    OutOfRange { min: i64, max: i64 },
+
+    #[error("argument(s) must be one of the following: {0}")]
+    NotOneOf(String),
+
+    #[error("{0}")]
+    Invalid(String),
+
+    /// Error in a particular line of the configuration file.
+    #[error("Reading the configuration file, at line {number}\n>>> '{line}'\n{error}")]
+    InLine {
+        number: usize,
+        line: String,
+        error: Box<ConfigError>,
+    },
+
+    /// Error in a particular option of the command line.
+    #[error(">>> '{option}'\n{error}")]
+    InOption {
+        option: String,
+        error: Box<ConfigError>,
     },
 }
@@ -0,0 +1,418 @@
+//! Geohash encoding of coordinates, used by the geospatial commands.
+//!
+//! Coordinates are stored as 52-bit geohashes in sorted set scores, the same as Redis does:
+//! 26 bits of longitude and 26 bits of latitude interleaved, longitude in the odd bits. Latitude
+//! is limited to the range of the Web Mercator projection. A search covers the area of the shape
+//! with the geohash box containing the center and its eight neighbors, each of which is a
+//! contiguous range of scores, and then filters the points by the exact distance.
+
+/// Number of bits of each coordinate in a stored geohash.
+pub(crate) const STEPS: u8 = 26;
+pub(crate) const LONGITUDE_MIN: f64 = -180.0;
+pub(crate) const LONGITUDE_MAX: f64 = 180.0;
+pub(crate) const LATITUDE_MIN: f64 = -85.05112878;
+pub(crate) const LATITUDE_MAX: f64 = 85.05112878;
+/// Earth's quadratic mean radius for WGS-84.
+const EARTH_RADIUS_IN_METERS: f64 = 6372797.560856;
+const MERCATOR_MAX: f64 = 20037726.37;
+const ALPHABET: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Range of a coordinate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Range {
+    min: f64,
+    max: f64,
+}
+
+const LONGITUDE: Range = Range {
+    min: LONGITUDE_MIN,
+    max: LONGITUDE_MAX,
+};
+const LATITUDE: Range = Range {
+    min: LATITUDE_MIN,
+    max: LATITUDE_MAX,
+};
+
+/// Geohash of a box. Each coordinate takes `step` bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Hash {
+    bits: u64,
+    step: u8,
+}
+
+/// Box covered by a geohash.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Area {
+    longitude: Range,
+    latitude: Range,
+}
+
+/// Shape of a search around a center, in meters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Shape {
+    Radius(f64),
+    Box { width: f64, height: f64 },
+}
+
+/// Returns true if the coordinates can be stored.
+pub(crate) fn is_valid(longitude: f64, latitude: f64) -> bool {
+    (LONGITUDE_MIN..=LONGITUDE_MAX).contains(&longitude)
+        && (LATITUDE_MIN..=LATITUDE_MAX).contains(&latitude)
+}
+
+/// Spreads the 32 bits over the even bits of the result.
+fn spread(value: u32) -> u64 {
+    let mut x = value as u64;
+    x = (x | (x << 16)) & 0x0000_FFFF_0000_FFFF;
+    x = (x | (x << 8)) & 0x00FF_00FF_00FF_00FF;
+    x = (x | (x << 4)) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | (x << 2)) & 0x3333_3333_3333_3333;
+    (x | (x << 1)) & 0x5555_5555_5555_5555
+}
+
+/// Collects the even bits of the value, the reverse of `spread`.
+fn squash(value: u64) -> u32 {
+    let mut x = value & 0x5555_5555_5555_5555;
+    x = (x | (x >> 1)) & 0x3333_3333_3333_3333;
+    x = (x | (x >> 2)) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | (x >> 4)) & 0x00FF_00FF_00FF_00FF;
+    x = (x | (x >> 8)) & 0x0000_FFFF_0000_FFFF;
+    ((x | (x >> 16)) & 0x0000_0000_FFFF_FFFF) as u32
+}
+
+fn encode_in(
+    longitude_range: Range,
+    latitude_range: Range,
+    longitude: f64,
+    latitude: f64,
+    step: u8,
+) -> Hash {
+    let cells = (1u64 << step) as f64;
+    let offset = |value: f64, range: Range| {
+        // The maximum falls into the last cell.
+        let offset = ((value - range.min) / (range.max - range.min) * cells) as u64;
+        offset.min((1 << step) - 1) as u32
+    };
+    Hash {
+        bits: spread(offset(latitude, latitude_range))
+            | spread(offset(longitude, longitude_range)) << 1,
+        step,
+    }
+}
+
+fn encode(longitude: f64, latitude: f64, step: u8) -> Hash {
+    encode_in(LONGITUDE, LATITUDE, longitude, latitude, step)
+}
+
+impl Hash {
+    /// Returns the box covered by the hash.
+    fn area(self) -> Area {
+        let cells = (1u64 << self.step) as f64;
+        let latitude = squash(self.bits) as f64;
+        let longitude = squash(self.bits >> 1) as f64;
+        let scale =
+            |offset: f64, range: Range| range.min + offset / cells * (range.max - range.min);
+        Area {
+            longitude: Range {
+                min: scale(longitude, LONGITUDE),
+                max: scale(longitude + 1.0, LONGITUDE),
+            },
+            latitude: Range {
+                min: scale(latitude, LATITUDE),
+                max: scale(latitude + 1.0, LATITUDE),
+            },
+        }
+    }
+
+    /// Moves the hash by `dx` boxes east and `dy` boxes north, wrapping around.
+    fn moved(self, dx: i8, dy: i8) -> Hash {
+        let shift = 64 - self.step as u32 * 2;
+        let odd = 0xAAAA_AAAA_AAAA_AAAAu64;
+        let even = 0x5555_5555_5555_5555u64;
+        // Adding to one coordinate sets the bits of the other one first so the carry passes
+        // through them.
+        let step = |bits: u64, own: u64, other: u64, d: i8| {
+            let zz = other >> shift;
+            let moved = match d {
+                0 => return bits,
+                d if d > 0 => bits.wrapping_add(zz + 1),
+                _ => (bits | zz).wrapping_sub(zz + 1),
+            };
+            moved & (own >> shift)
+        };
+        let x = step(self.bits & odd, odd, even, dx);
+        let y = step(self.bits & even, even, odd, dy);
+        Hash {
+            bits: x | y,
+            step: self.step,
+        }
+    }
+
+    /// Returns the range of stored geohashes in the box: from the first to the one after the
+    /// last.
+    pub(crate) fn scores(self) -> (u64, u64) {
+        let shift = (STEPS - self.step) as u32 * 2;
+        (self.bits << shift, (self.bits + 1) << shift)
+    }
+}
+
+/// Returns the geohash stored for the coordinates.
+pub(crate) fn score(longitude: f64, latitude: f64) -> u64 {
+    encode(longitude, latitude, STEPS).bits
+}
+
+/// Returns the coordinates of the center of the box of a stored geohash.
+pub(crate) fn position(score: u64) -> (f64, f64) {
+    let area = Hash {
+        bits: score,
+        step: STEPS,
+    }
+    .area();
+    let longitude = (area.longitude.min + area.longitude.max) / 2.0;
+    let latitude = (area.latitude.min + area.latitude.max) / 2.0;
+    (
+        longitude.clamp(LONGITUDE_MIN, LONGITUDE_MAX),
+        latitude.clamp(LATITUDE_MIN, LATITUDE_MAX),
+    )
+}
+
+/// Returns the standard 11 character geohash string of a stored geohash. Standard geohashes
+/// cover latitudes from -90 to 90, so the position is encoded again.
+pub(crate) fn to_string(score: u64) -> String {
+    let (longitude, latitude) = position(score);
+    let latitude_range = Range {
+        min: -90.0,
+        max: 90.0,
+    };
+    let bits = encode_in(LONGITUDE, latitude_range, longitude, latitude, STEPS).bits;
+    (0..11)
+        .map(|i| {
+            // There are just 52 bits, the last character is always the first of the alphabet.
+            let index = match i {
+                10 => 0,
+                _ => (bits >> (52 - (i + 1) * 5)) & 0x1F,
+            };
+            ALPHABET[index as usize] as char
+        })
+        .collect()
+}
+
+fn to_radians(degrees: f64) -> f64 {
+    degrees * std::f64::consts::PI / 180.0
+}
+
+fn to_degrees(radians: f64) -> f64 {
+    radians * 180.0 / std::f64::consts::PI
+}
+
+fn latitude_distance(latitude1: f64, latitude2: f64) -> f64 {
+    EARTH_RADIUS_IN_METERS * (to_radians(latitude2) - to_radians(latitude1)).abs()
+}
+
+/// Returns the distance in meters between two points using the haversine formula.
+pub(crate) fn distance(longitude1: f64, latitude1: f64, longitude2: f64, latitude2: f64) -> f64 {
+    let v = ((to_radians(longitude2) - to_radians(longitude1)) / 2.0).sin();
+    // Along a meridian the latitude difference is enough.
+    if v == 0.0 {
+        return latitude_distance(latitude1, latitude2);
+    }
+    let (latitude1, latitude2) = (to_radians(latitude1), to_radians(latitude2));
+    let u = ((latitude2 - latitude1) / 2.0).sin();
+    let a = u * u + latitude1.cos() * latitude2.cos() * v * v;
+    2.0 * EARTH_RADIUS_IN_METERS * a.sqrt().asin()
+}
+
+impl Shape {
+    /// Returns the distance in meters from the center to the point if the point is within the
+    /// shape.
+    pub(crate) fn distance_within(
+        self,
+        center: (f64, f64),
+        longitude: f64,
+        latitude: f64,
+    ) -> Option<f64> {
+        if let Shape::Box { width, height } = self {
+            // The latitude distance is cheaper, so it is checked first.
+            if latitude_distance(latitude, center.1) > height / 2.0
+                || distance(longitude, latitude, center.0, latitude) > width / 2.0
+            {
+                return None;
+            }
+        }
+        let distance = distance(center.0, center.1, longitude, latitude);
+        match self {
+            Shape::Radius(radius) if distance > radius => None,
+            _ => Some(distance),
+        }
+    }
+
+    /// Returns the minimum longitude, minimum latitude, maximum longitude and maximum latitude
+    /// of the box bounding the shape.
+    fn bounds(self, longitude: f64, latitude: f64) -> [f64; 4] {
+        let (width, height) = match self {
+            Shape::Radius(radius) => (radius, radius),
+            Shape::Box { width, height } => (width / 2.0, height / 2.0),
+        };
+        let latitude_delta = to_degrees(height / EARTH_RADIUS_IN_METERS);
+        let longitude_delta =
+            |latitude: f64| to_degrees(width / EARTH_RADIUS_IN_METERS / to_radians(latitude).cos());
+        // The edge farther from the equator is the wider one.
+        let longitude_delta = match latitude < 0.0 {
+            true => longitude_delta(latitude - latitude_delta),
+            false => longitude_delta(latitude + latitude_delta),
+        };
+        [
+            longitude - longitude_delta,
+            latitude - latitude_delta,
+            longitude + longitude_delta,
+            latitude + latitude_delta,
+        ]
+    }
+
+    /// Returns the distance in meters from the center to the farthest point of the shape.
+    fn radius(self) -> f64 {
+        match self {
+            Shape::Radius(radius) => radius,
+            Shape::Box { width, height } => (width / 2.0).hypot(height / 2.0),
+        }
+    }
+}
+
+/// Estimates the number of steps of boxes big enough for the range around the latitude.
+fn steps_for_radius(mut range: f64, latitude: f64) -> u8 {
+    if range == 0.0 {
+        return STEPS;
+    }
+    let mut step: i32 = 1;
+    while range < MERCATOR_MAX {
+        range *= 2.0;
+        step += 1;
+    }
+    // Make sure the range is included in most of the base cases.
+    step -= 2;
+    // Meridians get closer towards the poles.
+    if !(-66.0..=66.0).contains(&latitude) {
+        step -= 1;
+        if !(-80.0..=80.0).contains(&latitude) {
+            step -= 1;
+        }
+    }
+    step.clamp(1, STEPS as i32) as u8
+}
+
+/// Returns the boxes to search for points within the shape around the center: the box
+/// containing the center followed by its neighbors which the shape reaches, in the order Redis
+/// searches them in.
+pub(crate) fn search_areas(shape: Shape, longitude: f64, latitude: f64) -> Vec<Hash> {
+    let [min_longitude, min_latitude, max_longitude, max_latitude] =
+        shape.bounds(longitude, latitude);
+    let mut steps = steps_for_radius(shape.radius(), latitude);
+    let mut hash = encode(longitude, latitude, steps);
+    // Near the edges of the box the neighbors may be too small to cover the shape.
+    let too_small = |hash: Hash| {
+        hash.moved(0, 1).area().latitude.max < max_latitude
+            || hash.moved(0, -1).area().latitude.min > min_latitude
+            || hash.moved(1, 0).area().longitude.max < max_longitude
+            || hash.moved(-1, 0).area().longitude.min > min_longitude
+    };
+    if steps > 1 && too_small(hash) {
+        steps -= 1;
+        hash = encode(longitude, latitude, steps);
+    }
+    let area = hash.area();
+    let (mut north, mut south, mut east, mut west) = (true, true, true, true);
+    if steps >= 2 {
+        south = area.latitude.min >= min_latitude;
+        north = area.latitude.max <= max_latitude;
+        west = area.longitude.min >= min_longitude;
+        east = area.longitude.max <= max_longitude;
+    }
+    let neighbors = [
+        (0, 0, true),
+        (0, 1, north),
+        (0, -1, south),
+        (1, 0, east),
+        (-1, 0, west),
+        (1, 1, north && east),
+        (-1, 1, north && west),
+        (1, -1, south && east),
+        (-1, -1, south && west),
+    ];
+    let mut areas: Vec<Hash> = vec![];
+    for (dx, dy, reached) in neighbors {
+        let neighbor = hash.moved(dx, dy);
+        // Around the poles neighbors may repeat.
+        if reached && areas.last() != Some(&neighbor) {
+            areas.push(neighbor);
+        }
+    }
+    areas
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encoding() {
+        // Palermo, as stored by Redis.
+        assert_eq!(score(13.361389, 38.115556), 3479099956230698);
+        let (longitude, latitude) = position(3479099956230698);
+        assert!((longitude - 13.361389).abs() < 1e-5);
+        assert!((latitude - 38.115556).abs() < 1e-5);
+        assert_eq!(to_string(3479099956230698), "sqc8b49rny0");
+        assert_eq!(to_string(score(15.087269, 37.502669)), "sqdtr74hyu0");
+        assert!(is_valid(180.0, LATITUDE_MAX));
+        assert!(!is_valid(0.0, 86.0));
+    }
+
+    #[test]
+    fn distances() {
+        // Distances are between the stored positions.
+        let palermo = position(score(13.361389, 38.115556));
+        let catania = position(score(15.087269, 37.502669));
+        let meters = distance(palermo.0, palermo.1, catania.0, catania.1);
+        assert_eq!(format!("{:.4}", meters), "166274.1516");
+        assert_eq!(
+            Shape::Radius(100_000.0).distance_within(palermo, catania.0, catania.1),
+            None
+        );
+        assert!(Shape::Box {
+            width: 400_000.0,
+            height: 400_000.0
+        }
+        .distance_within(palermo, catania.0, catania.1)
+        .is_some());
+    }
+
+    #[test]
+    fn neighbors() {
+        let hash = encode(13.361389, 38.115556, 10);
+        let moved = hash.moved(1, 0).moved(-1, 0).moved(0, 1).moved(0, -1);
+        assert_eq!(moved, hash);
+        let east = hash.moved(1, 0).area();
+        assert_eq!(east.longitude.min, hash.area().longitude.max);
+        assert_eq!(east.latitude, hash.area().latitude);
+    }
+
+    #[test]
+    fn areas_cover_shape() {
+        let (longitude, latitude) = (15.0, 37.0);
+        let areas = search_areas(Shape::Radius(200_000.0), longitude, latitude);
+        assert!(!areas.is_empty() && areas.len() <= 9);
+        // Points on the circle fall into one of the boxes.
+        for degrees in (0..360).step_by(15) {
+            let angle = to_radians(degrees as f64);
+            let point_latitude =
+                latitude + to_degrees(199_000.0 / EARTH_RADIUS_IN_METERS) * angle.sin();
+            let point_longitude = longitude
+                + to_degrees(199_000.0 / EARTH_RADIUS_IN_METERS / to_radians(latitude).cos())
+                    * angle.cos();
+            let point = score(point_longitude, point_latitude);
+            assert!(areas.iter().any(|area| {
+                let (min, max) = area.scores();
+                (min..max).contains(&point)
+            }));
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Glob-style pattern matching used by commands that filter names, e.g. CONFIG GET.
+//!
+//! Supported syntax is the same as in Redis:
+//! - `*` matches any sequence of characters, `?` matches any single character;
+//! - `[abc]` matches one of the characters, `[^abc]` any but them, `[a-z]` a range;
+//! - `\` escapes the next character.
+
+/// Returns true if the whole string matches the pattern.
+pub(crate) fn glob_match(pattern: &[u8], string: &[u8], nocase: bool) -> bool {
+    let eq = |a: u8, b: u8| {
+        if nocase {
+            a.eq_ignore_ascii_case(&b)
+        } else {
+            a == b
+        }
+    };
+    let mut p = 0;
+    let mut s = 0;
+    while p < pattern.len() {
+        match pattern[p] {
+            b'*' => {
+                // Collapse consecutive stars.
+                while pattern.get(p + 1) == Some(&b'*') {
+                    p += 1;
+                }
+                if p + 1 == pattern.len() {
+                    return true;
+                }
+                return (s..=string.len())
+                    .any(|start| glob_match(&pattern[p + 1..], &string[start..], nocase));
+            }
+            b'?' => {
+                if s == string.len() {
+                    return false;
+                }
+                s += 1;
+            }
+            b'[' => {
+                let c = match string.get(s) {
+                    Some(&c) => c,
+                    None => return false,
+                };
+                p += 1;
+                let negate = pattern.get(p) == Some(&b'^');
+                if negate {
+                    p += 1;
+                }
+                let mut matched = false;
+                while p < pattern.len() && pattern[p] != b']' {
+                    if pattern[p] == b'\\' && p + 1 < pattern.len() {
+                        p += 1;
+                        matched |= eq(pattern[p], c);
+                    } else if pattern.get(p + 1) == Some(&b'-') && p + 2 < pattern.len() {
+                        let (mut start, mut end) = (pattern[p], pattern[p + 2]);
+                        if start > end {
+                            std::mem::swap(&mut start, &mut end);
+                        }
+                        let c = if nocase { c.to_ascii_lowercase() } else { c };
+                        let (start, end) = if nocase {
+                            (start.to_ascii_lowercase(), end.to_ascii_lowercase())
+                        } else {
+                            (start, end)
+                        };
+                        matched |= (start..=end).contains(&c);
+                        p += 2;
+                    } else {
+                        matched |= eq(pattern[p], c);
+                    }
+                    p += 1;
+                }
+                if matched == negate {
+                    return false;
+                }
+                s += 1;
+            }
+            b'\\' if p + 1 < pattern.len() => {
+                p += 1;
+                if s == string.len() || !eq(pattern[p], string[s]) {
+                    return false;
+                }
+                s += 1;
+            }
+            c => {
+                if s == string.len() || !eq(c, string[s]) {
+                    return false;
+                }
+                s += 1;
+            }
+        }
+        p += 1;
+    }
+    s == string.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::glob_match;
+
+    fn matches(pattern: &str, string: &str) -> bool {
+        glob_match(pattern.as_bytes(), string.as_bytes(), false)
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*", ""));
+        assert!(matches("*", "anything"));
+        assert!(matches("max*", "maxmemory-policy"));
+        assert!(matches("*memory*", "maxmemory"));
+        assert!(matches("h?", "hz"));
+        assert!(!matches("h?", "h"));
+        assert!(!matches("max*", "dbfilename"));
+        assert!(matches("a**b", "axxb"));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(matches("h[aeiou]llo", "hello"));
+        assert!(!matches("h[^e]llo", "hello"));
+        assert!(matches("h[a-f]llo", "hello"));
+        assert!(matches("h[f-a]llo", "hello"));
+        assert!(!matches("h[a-d]llo", "hello"));
+    }
+
+    #[test]
+    fn escapes() {
+        assert!(matches("a\\*b", "a*b"));
+        assert!(!matches("a\\*b", "axb"));
+        assert!(matches("[\\]]", "]"));
+    }
+
+    #[test]
+    fn nocase() {
+        assert!(glob_match(b"PORT", b"port", true));
+        assert!(glob_match(b"[A-Z]ort", b"port", true));
+        assert!(!glob_match(b"PORT", b"port", false));
+    }
+}
@@ -0,0 +1,124 @@
+//! Longest common subsequence of two strings, used by the LCS command.
+//!
+//! The subsequence is found with the classic dynamic programming table, so the memory needed is
+//! proportional to the product of the string lengths.
+use std::ops::RangeInclusive;
+
+/// Maximum size of the table in bytes, the same as the maximum length of a string.
+const MAX_TABLE_SIZE: usize = 512 * 1024 * 1024;
+
+/// A run of consecutive bytes of the subsequence found at the same place in both strings.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct LcsMatch {
+    pub(crate) a: RangeInclusive<usize>,
+    pub(crate) b: RangeInclusive<usize>,
+}
+
+impl LcsMatch {
+    pub(crate) fn len(&self) -> usize {
+        self.a.end() - self.a.start() + 1
+    }
+}
+
+/// Common subsequence along with the runs it consists of. The runs go from the end of the
+/// strings to the start, the same order as Redis reports them in.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Subsequence {
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) matches: Vec<LcsMatch>,
+}
+
+/// Returns false if the table needed to find the subsequence of strings of these lengths takes
+/// more memory than a string may.
+pub(crate) fn table_fits(a_len: usize, b_len: usize) -> bool {
+    a_len
+        .checked_add(1)
+        .zip(b_len.checked_add(1))
+        .and_then(|(rows, columns)| rows.checked_mul(columns))
+        .and_then(|cells| cells.checked_mul(std::mem::size_of::<u32>()))
+        .is_some_and(|size| size <= MAX_TABLE_SIZE)
+}
+
+/// Finds the longest common subsequence of the strings.
+pub(crate) fn lcs(a: &[u8], b: &[u8]) -> Subsequence {
+    // lengths[i * (b.len() + 1) + j] is the length of the subsequence of a[..i] and b[..j].
+    let width = b.len() + 1;
+    let mut lengths = vec![0u32; (a.len() + 1) * width];
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            lengths[i * width + j] = if a[i - 1] == b[j - 1] {
+                lengths[(i - 1) * width + j - 1] + 1
+            } else {
+                lengths[(i - 1) * width + j].max(lengths[i * width + j - 1])
+            };
+        }
+    }
+
+    // Walk back from the end of both strings collecting the matching bytes.
+    let mut bytes = Vec::with_capacity(lengths[lengths.len() - 1] as usize);
+    let mut matches = Vec::new();
+    let mut current: Option<LcsMatch> = None;
+    let (mut i, mut j) = (a.len(), b.len());
+    while i > 0 && j > 0 {
+        if a[i - 1] == b[j - 1] {
+            bytes.push(a[i - 1]);
+            current = Some(match current {
+                Some(run) => LcsMatch {
+                    a: i - 1..=*run.a.end(),
+                    b: j - 1..=*run.b.end(),
+                },
+                None => LcsMatch {
+                    a: i - 1..=i - 1,
+                    b: j - 1..=j - 1,
+                },
+            });
+            i -= 1;
+            j -= 1;
+        } else {
+            if lengths[(i - 1) * width + j] > lengths[i * width + j - 1] {
+                i -= 1;
+            } else {
+                j -= 1;
+            }
+            matches.extend(current.take());
+        }
+    }
+    matches.extend(current);
+    bytes.reverse();
+
+    Subsequence { bytes, matches }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn subsequence() {
+        assert_eq!(lcs(b"ohmytext", b"mynewtext").bytes, b"mytext");
+        assert_eq!(lcs(b"", b"abc").bytes, b"");
+        assert_eq!(lcs(b"abc", b"xyz").bytes, b"");
+        assert_eq!(lcs(b"same", b"same").bytes, b"same");
+    }
+
+    #[test]
+    fn matches() {
+        let result = lcs(b"ohmytext", b"mynewtext");
+        assert_eq!(
+            result.matches,
+            vec![
+                LcsMatch { a: 4..=7, b: 5..=8 },
+                LcsMatch { a: 2..=3, b: 0..=1 },
+            ]
+        );
+        assert_eq!(result.matches[0].len(), 4);
+        assert!(lcs(b"abc", b"xyz").matches.is_empty());
+    }
+
+    #[test]
+    fn table_limit() {
+        assert!(table_fits(1000, 1000));
+        assert!(!table_fits(100_000, 100_000));
+        assert!(!table_fits(usize::MAX, 1));
+    }
+}
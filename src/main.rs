@@ -1,10 +1,17 @@
 //! Implementation of Redis server made for educational purposes.
 //! [Link to course](https://app.codecrafters.io/courses/redis)
 use std::{error::Error, net::SocketAddr};
+mod config;
 mod error;
+mod geohash;
+mod glob;
+mod lcs;
 pub(crate) mod protocol;
 mod request_processor;
 mod storage;
+use bytes::BytesMut;
+use error::RedisError;
+use protocol::Response;
 use request_processor::RequestProcessor;
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
@@ -12,35 +19,52 @@ use tokio::{
     signal,
 };
 
-const LISTEN_ADDR: &str = "127.0.0.1:6379";
+const READ_BUFFER_CAPACITY: usize = 16 * 1024;
 
 async fn handle_connection(
     mut connection: TcpStream,
     sender: SocketAddr,
 ) -> Result<(), Box<dyn Error>> {
     println!("Accepted connection from {}", sender);
-    let mut byte_buf = [0; 1024];
-    let mut str_buf = String::new();
-    let processor = RequestProcessor::new();
+    let mut buffer = BytesMut::with_capacity(READ_BUFFER_CAPACITY);
+    let mut processor = RequestProcessor::new();
+    // Requests received while a request was blocked are processed without waiting for more.
+    let mut received_while_blocked = false;
     loop {
-        let n = connection.read(&mut byte_buf).await?;
-        if n == 0 {
-            break;
-        }
-        println!("received {n} bytes");
-
-        str_buf.push_str(String::from_utf8_lossy(&byte_buf[..n]).as_ref());
-        match protocol::Request::deserialize(&mut str_buf) {
-            Ok(request) => {
-                dbg!(&request);
-                let response = processor.process_request(request).await?;
-                connection
-                    .write_all(response.serialize().as_bytes())
-                    .await?;
+        if !received_while_blocked {
+            let n = connection.read_buf(&mut buffer).await?;
+            if n == 0 {
+                break;
             }
-            Err(e) => {
-                println!("Failed to deserialize request. Error: {:?}", e);
+        }
+
+        let mut responses = Vec::new();
+        let mut incoming = BytesMut::new();
+        // A blocking request parks the connection here until it is served. Meanwhile the
+        // connection is watched, so a client which disconnects stops waiting.
+        let result = {
+            let pipeline = process_pipeline(&mut processor, &mut buffer, &mut responses);
+            tokio::pin!(pipeline);
+            loop {
+                tokio::select! {
+                    result = &mut pipeline => break result,
+                    n = connection.read_buf(&mut incoming) => {
+                        if n? == 0 {
+                            println!("Finished serving {} while it was blocked", sender);
+                            return Ok(());
+                        }
+                    }
+                }
             }
+        };
+        received_while_blocked = !incoming.is_empty();
+        buffer.extend_from_slice(&incoming);
+        if !responses.is_empty() {
+            connection.write_all(&responses).await?;
+        }
+        if let Err(e) = result {
+            println!("Closing connection after protocol error: {}", e);
+            break;
         }
     }
 
@@ -48,25 +72,163 @@ async fn handle_connection(
     Ok(())
 }
 
+/// Processes every complete request in the buffer and appends their serialized responses
+/// to `responses` in the order of requests. An incomplete trailing request stays in the buffer.
+/// Returns an error if the buffer doesn't contain valid RESP. The error reply is already in
+/// `responses` then and the connection must be closed.
+async fn process_pipeline(
+    processor: &mut RequestProcessor,
+    buffer: &mut BytesMut,
+    responses: &mut Vec<u8>,
+) -> Result<(), RedisError> {
+    loop {
+        match protocol::Request::deserialize(buffer) {
+            Ok(Some(request)) => {
+                let response = processor.process_request(request).await;
+                response.serialize_into(responses, processor.protocol_version());
+            }
+            Ok(None) => return Ok(()),
+            Err(e @ RedisError::Protocol(_)) => {
+                Response::from(e.clone()).serialize_into(responses, processor.protocol_version());
+                return Err(e);
+            }
+            Err(e) => Response::from(e).serialize_into(responses, processor.protocol_version()),
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
+    let config = match config::Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("\n*** FATAL CONFIG ERROR ***\n{}", e);
+            std::process::exit(1);
+        }
+    };
+    std::env::set_current_dir(&config.dir)?;
+    for (name, value) in config.parameters() {
+        println!("Config: {} {}", name, value);
+    }
+
     // You can use print statements as follows for debugging, they'll be visible when running tests.
-    let listener = TcpListener::bind(LISTEN_ADDR).await?;
-    println!("Starting accepting connections on {}", LISTEN_ADDR);
+    for address in &config.bind {
+        // `*` means all IPv4 interfaces.
+        let address = if address == "*" { "0.0.0.0" } else { address };
+        let listener = TcpListener::bind((address, config.port)).await?;
+        println!(
+            "Starting accepting connections on {}",
+            listener.local_addr()?
+        );
+        tokio::spawn(accept_connections(listener));
+    }
+    config::init(config);
+    tokio::spawn(storage::Storage::instance().run_active_expiration());
+
+    signal::ctrl_c().await?;
+    println!("Got keyboard signal. Shutting down the server...");
+    Ok(())
+}
+
+/// Accepts connections on the listener and serves each of them in a separate task.
+async fn accept_connections(listener: TcpListener) {
     loop {
-        tokio::select! {
-            result = listener.accept() => {
-                // let a = result;
-                let (connection, addr) = result?;
+        match listener.accept().await {
+            Ok((connection, addr)) => {
                 tokio::spawn(async move {
                     let _ = handle_connection(connection, addr).await;
                 });
             }
-            _ = signal::ctrl_c() => {
-                println!("Got keyboard signal. Shutting down the server...");
-                break;
-            }
+            Err(e) => println!("Failed to accept connection: {}", e),
         }
     }
-    Ok(())
+}
+
+#[cfg(test)]
+mod pipeline {
+    use super::*;
+
+    #[tokio::test]
+    async fn all_complete_requests_are_processed() {
+        let mut processor = RequestProcessor::new();
+        let mut buffer = BytesMut::from(
+            "*1\r\n$4\r\nping\r\n\
+             *3\r\n$3\r\nset\r\n$9\r\npipelined\r\n$1\r\nv\r\n\
+             *2\r\n$3\r\nget\r\n$9\r\npipelined\r\n",
+        );
+        let mut responses = Vec::new();
+        process_pipeline(&mut processor, &mut buffer, &mut responses)
+            .await
+            .unwrap();
+        assert_eq!(responses, b"+PONG\r\n+OK\r\n$1\r\nv\r\n");
+        assert!(buffer.is_empty());
+    }
+
+    #[tokio::test]
+    async fn partial_request_is_kept() {
+        let mut processor = RequestProcessor::new();
+        let mut buffer = BytesMut::from("*1\r\n$4\r\nping\r\n*2\r\n$4\r\necho\r\n$2\r\nh");
+        let mut responses = Vec::new();
+        process_pipeline(&mut processor, &mut buffer, &mut responses)
+            .await
+            .unwrap();
+        assert_eq!(responses, b"+PONG\r\n");
+        assert_eq!(&buffer[..], b"*2\r\n$4\r\necho\r\n$2\r\nh");
+
+        buffer.extend_from_slice(b"i\r\n");
+        let mut responses = Vec::new();
+        process_pipeline(&mut processor, &mut buffer, &mut responses)
+            .await
+            .unwrap();
+        assert_eq!(responses, b"$2\r\nhi\r\n");
+        assert!(buffer.is_empty());
+    }
+
+    #[tokio::test]
+    async fn command_errors_are_replied() {
+        let mut processor = RequestProcessor::new();
+        let mut buffer = BytesMut::from(
+            "*1\r\n$3\r\nget\r\n*2\r\n$3\r\nfoo\r\n$3\r\nbar\r\n*1\r\n$4\r\nping\r\n",
+        );
+        let mut responses = Vec::new();
+        process_pipeline(&mut processor, &mut buffer, &mut responses)
+            .await
+            .unwrap();
+        assert_eq!(
+            responses,
+            &b"-ERR wrong number of arguments for 'get' command\r\n\
+               -ERR unknown command 'foo', with args beginning with: 'bar' \r\n\
+               +PONG\r\n"[..]
+        );
+    }
+
+    #[tokio::test]
+    async fn protocol_error_is_replied() {
+        let mut processor = RequestProcessor::new();
+        let mut buffer = BytesMut::from("*1\r\n$4\r\nping\r\n*1\r\n$x\r\n");
+        let mut responses = Vec::new();
+        let result = process_pipeline(&mut processor, &mut buffer, &mut responses).await;
+        assert_eq!(
+            result,
+            Err(RedisError::Protocol("invalid length".to_string()))
+        );
+        assert_eq!(
+            responses,
+            &b"+PONG\r\n-ERR Protocol error: invalid length\r\n"[..]
+        );
+    }
+
+    #[tokio::test]
+    async fn hello_switches_protocol_of_following_responses() {
+        let mut processor = RequestProcessor::new();
+        let mut buffer = BytesMut::from(
+            "*2\r\n$5\r\nhello\r\n$1\r\n3\r\n*2\r\n$3\r\nget\r\n$10\r\nabsent_key\r\n",
+        );
+        let mut responses = Vec::new();
+        process_pipeline(&mut processor, &mut buffer, &mut responses)
+            .await
+            .unwrap();
+        assert!(responses.starts_with(b"%7\r\n"));
+        assert!(responses.ends_with(b"$7\r\nmodules\r\n*0\r\n_\r\n"));
+    }
 }
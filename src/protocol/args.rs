@@ -0,0 +1,182 @@
+//! Cursor over the arguments of a request and parsers for typed arguments.
+use crate::{error::RedisError, protocol::request::Array};
+use bytes::Bytes;
+
+/// Arguments of a request following the command name.
+pub(crate) struct Args {
+    /// Command name as sent by the client.
+    name: Bytes,
+    command: String,
+    args: std::vec::IntoIter<Bytes>,
+}
+
+impl Args {
+    /// Splits the array into a lowercase command name and its arguments.
+    pub(crate) fn new(array: Array) -> Self {
+        let mut args = array.args.into_iter();
+        let name = args.next().unwrap_or_default();
+        let command = String::from_utf8_lossy(&name).to_lowercase();
+        Self {
+            name,
+            command,
+            args,
+        }
+    }
+
+    /// Lowercase name of the command.
+    pub(crate) fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Arguments not consumed yet.
+    pub(crate) fn as_slice(&self) -> &[Bytes] {
+        self.args.as_slice()
+    }
+
+    /// Returns the next argument or a wrong arity error if there are no arguments left.
+    pub(crate) fn required(&mut self) -> Result<Bytes, RedisError> {
+        self.args
+            .next()
+            .ok_or_else(|| RedisError::WrongArity(self.command.clone()))
+    }
+
+    /// Returns all remaining arguments or a wrong arity error if there are none.
+    pub(crate) fn required_rest(&mut self) -> Result<Vec<Bytes>, RedisError> {
+        let rest: Vec<Bytes> = self.args.by_ref().collect();
+        if rest.is_empty() {
+            return Err(RedisError::WrongArity(self.command.clone()));
+        }
+        Ok(rest)
+    }
+
+    /// Returns all remaining arguments as pairs or a wrong arity error if there are none or one
+    /// is left without a pair.
+    pub(crate) fn required_pairs(&mut self) -> Result<Vec<(Bytes, Bytes)>, RedisError> {
+        if self.args.len() == 0 || self.args.len() & 1 == 1 {
+            return Err(RedisError::WrongArity(self.command.clone()));
+        }
+        let mut pairs = Vec::with_capacity(self.args.len() / 2);
+        while let (Some(first), Some(second)) = (self.args.next(), self.args.next()) {
+            pairs.push((first, second));
+        }
+        Ok(pairs)
+    }
+
+    /// Builds an unknown command error from the command name and the remaining arguments.
+    pub(crate) fn unknown_command(&self) -> RedisError {
+        RedisError::unknown_command(&self.name, self.as_slice())
+    }
+
+    /// Returns a wrong arity error if some arguments were not consumed.
+    pub(crate) fn finish(self) -> Result<(), RedisError> {
+        if self.args.len() == 0 {
+            Ok(())
+        } else {
+            Err(RedisError::WrongArity(self.command))
+        }
+    }
+}
+
+/// Builds arguments of a request from the command name and its arguments.
+#[cfg(test)]
+pub(crate) fn args(args: &[&'static str]) -> Args {
+    Args::new(Array::new(
+        args.iter().map(|arg| Bytes::from(*arg)).collect(),
+    ))
+}
+
+impl Iterator for Args {
+    type Item = Bytes;
+
+    fn next(&mut self) -> Option<Bytes> {
+        self.args.next()
+    }
+}
+
+/// Parses a signed 64-bit integer with the same strictness as Redis: no sign other than a
+/// leading minus, no leading zeros and no whitespace.
+pub(crate) fn parse_i64(arg: &[u8]) -> Result<i64, RedisError> {
+    let digits = arg.strip_prefix(b"-").unwrap_or(arg);
+    let valid = match digits {
+        [] => false,
+        [b'0'] => arg.len() == 1,
+        [first, ..] => *first != b'0' && digits.iter().all(u8::is_ascii_digit),
+    };
+    if !valid {
+        return Err(RedisError::NotInteger);
+    }
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|arg| arg.parse().ok())
+        .ok_or(RedisError::NotInteger)
+}
+
+/// Parses a double. Infinities are accepted, but NaN and whitespace are not.
+pub(crate) fn parse_f64(arg: &[u8]) -> Result<f64, RedisError> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|arg| arg.parse::<f64>().ok())
+        .filter(|value| !value.is_nan())
+        .ok_or(RedisError::NotFloat)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn integers() {
+        assert_eq!(parse_i64(b"0"), Ok(0));
+        assert_eq!(parse_i64(b"-12"), Ok(-12));
+        assert_eq!(parse_i64(b"9223372036854775807"), Ok(i64::MAX));
+        assert_eq!(parse_i64(b"-9223372036854775808"), Ok(i64::MIN));
+        for invalid in [
+            &b""[..],
+            b"-",
+            b"-0",
+            b"01",
+            b"+1",
+            b" 1",
+            b"1.5",
+            b"9223372036854775808",
+        ] {
+            assert_eq!(parse_i64(invalid), Err(RedisError::NotInteger));
+        }
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(parse_f64(b"1.5"), Ok(1.5));
+        assert_eq!(parse_f64(b"-3"), Ok(-3.0));
+        assert_eq!(parse_f64(b"5.0e3"), Ok(5000.0));
+        assert_eq!(parse_f64(b"inf"), Ok(f64::INFINITY));
+        for invalid in [&b""[..], b"nan", b" 1", b"1 ", b"one"] {
+            assert_eq!(parse_f64(invalid), Err(RedisError::NotFloat));
+        }
+    }
+
+    #[test]
+    fn arity() {
+        let array = Array::new(vec![Bytes::from("GET"), Bytes::from("a")]);
+        let mut args = Args::new(array);
+        assert_eq!(args.command(), "get");
+        assert_eq!(args.required(), Ok(Bytes::from("a")));
+        assert_eq!(
+            args.required(),
+            Err(RedisError::WrongArity("get".to_string()))
+        );
+        args.finish().unwrap();
+
+        let array = Array::new(vec![Bytes::from("MSET"), Bytes::from("a")]);
+        assert_eq!(
+            Args::new(array).required_pairs(),
+            Err(RedisError::WrongArity("mset".to_string()))
+        );
+
+        let array = Array::new(vec![Bytes::from("DEL")]);
+        assert_eq!(
+            Args::new(array).required_rest(),
+            Err(RedisError::WrongArity("del".to_string()))
+        );
+    }
+}
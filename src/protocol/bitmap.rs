@@ -0,0 +1,426 @@
+//! Bitmap requests.
+use crate::{
+    error::RedisError,
+    protocol::args::{parse_i64, Args},
+};
+use bytes::Bytes;
+
+/// Bit offsets address strings of up to 512MB.
+const MAX_BIT_OFFSET: i64 = 512 * 1024 * 1024 * 8;
+
+fn invalid_offset() -> RedisError {
+    RedisError::Other("bit offset is not an integer or out of range".to_owned())
+}
+
+/// Parses a bit offset of SETBIT, GETBIT or BITFIELD. With `field_bits` the offset may be given
+/// as `#N`, the N-th field of that width.
+pub(super) fn parse_bit_offset(arg: &[u8], field_bits: Option<u32>) -> Result<usize, RedisError> {
+    let (arg, multiplier) = match (arg.strip_prefix(b"#"), field_bits) {
+        (Some(index), Some(bits)) => (index, bits as i64),
+        _ => (arg, 1),
+    };
+    let offset = parse_i64(arg)
+        .ok()
+        .and_then(|offset| offset.checked_mul(multiplier))
+        .filter(|offset| (0..MAX_BIT_OFFSET).contains(offset))
+        .ok_or_else(invalid_offset)?;
+    Ok(offset as usize)
+}
+
+/// Unit of the indexes of a range.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Default)]
+pub enum BitUnit {
+    #[default]
+    Byte,
+    Bit,
+}
+
+/// Range of a string to count or search bits in. Negative indexes count from the end.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct BitRange {
+    pub start: i64,
+    /// Without an end the range runs to the end of the string.
+    pub end: Option<i64>,
+    pub unit: BitUnit,
+}
+
+impl BitRange {
+    /// Parses the remaining `[start [end [BYTE|BIT]]]` arguments. The end is required if the
+    /// start is given and `end_required` is set.
+    fn parse(args: &mut Args, end_required: bool) -> Result<Option<Self>, RedisError> {
+        let start = match args.next() {
+            Some(start) => parse_i64(&start)?,
+            None => return Ok(None),
+        };
+        let end = match args.next() {
+            Some(end) => Some(parse_i64(&end)?),
+            None if end_required => return Err(RedisError::Syntax),
+            None => None,
+        };
+        let unit = match args.next() {
+            Some(unit) => match unit.to_ascii_lowercase().as_slice() {
+                b"byte" => BitUnit::Byte,
+                b"bit" => BitUnit::Bit,
+                _ => return Err(RedisError::Syntax),
+            },
+            None => BitUnit::Byte,
+        };
+        if args.next().is_some() {
+            return Err(RedisError::Syntax);
+        }
+        Ok(Some(BitRange { start, end, unit }))
+    }
+}
+
+/// Counts set bits of the string, within the range if given.
+#[derive(Eq, PartialEq, Debug)]
+pub struct BitCount {
+    pub key: Bytes,
+    pub range: Option<BitRange>,
+}
+
+impl TryFrom<Args> for BitCount {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        Ok(BitCount {
+            key: args.required()?,
+            range: BitRange::parse(&mut args, true)?,
+        })
+    }
+}
+
+/// Finds the first bit set to the value, within the range if given.
+#[derive(Eq, PartialEq, Debug)]
+pub struct BitPos {
+    pub key: Bytes,
+    pub bit: bool,
+    pub range: Option<BitRange>,
+}
+
+impl TryFrom<Args> for BitPos {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let key = args.required()?;
+        let bit = match args.required()?.as_ref() {
+            b"0" => false,
+            b"1" => true,
+            _ => {
+                return Err(RedisError::Other(
+                    "The bit argument must be 1 or 0.".to_owned(),
+                ))
+            }
+        };
+        Ok(BitPos {
+            key,
+            bit,
+            range: BitRange::parse(&mut args, false)?,
+        })
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum BitOperation {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+/// Stores the bitwise operation of the strings at the destination. Shorter strings are padded
+/// with zero bytes.
+#[derive(Eq, PartialEq, Debug)]
+pub struct BitOp {
+    pub operation: BitOperation,
+    pub destination: Bytes,
+    pub keys: Vec<Bytes>,
+}
+
+impl TryFrom<Args> for BitOp {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let operation = match args.required()?.to_ascii_lowercase().as_slice() {
+            b"and" => BitOperation::And,
+            b"or" => BitOperation::Or,
+            b"xor" => BitOperation::Xor,
+            b"not" => BitOperation::Not,
+            _ => return Err(RedisError::Syntax),
+        };
+        let destination = args.required()?;
+        let keys = args.required_rest()?;
+        if operation == BitOperation::Not && keys.len() != 1 {
+            return Err(RedisError::Other(
+                "BITOP NOT must be called with a single source key.".to_owned(),
+            ));
+        }
+        Ok(BitOp {
+            operation,
+            destination,
+            keys,
+        })
+    }
+}
+
+/// Integer type of a bit field.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct BitFieldType {
+    pub signed: bool,
+    /// Width of the field, up to 64 bits for signed and 63 bits for unsigned integers.
+    pub bits: u32,
+}
+
+impl BitFieldType {
+    fn parse(arg: &[u8]) -> Result<Self, RedisError> {
+        let invalid = || {
+            RedisError::Other(
+                "Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported \
+                 but i64 is."
+                    .to_owned(),
+            )
+        };
+        let (signed, max) = match arg.first() {
+            Some(b'i' | b'I') => (true, 64),
+            Some(b'u' | b'U') => (false, 63),
+            _ => return Err(invalid()),
+        };
+        let bits = parse_i64(&arg[1..])
+            .ok()
+            .filter(|bits| (1..=max).contains(bits))
+            .ok_or_else(invalid)?;
+        Ok(BitFieldType {
+            signed,
+            bits: bits as u32,
+        })
+    }
+}
+
+/// Behavior of SET and INCRBY when the value doesn't fit the field.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Default)]
+pub enum Overflow {
+    /// Keep the low bits of the value.
+    #[default]
+    Wrap,
+    /// Saturate to the minimum or the maximum of the field.
+    Sat,
+    /// Don't change the field and reply with a null.
+    Fail,
+}
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum BitFieldAction {
+    Get,
+    Set(i64),
+    IncrBy(i64),
+}
+
+/// An operation of BITFIELD, with the overflow behavior in effect for it.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct BitFieldOperation {
+    pub action: BitFieldAction,
+    pub field: BitFieldType,
+    /// Offset of the first bit of the field.
+    pub offset: usize,
+    pub overflow: Overflow,
+}
+
+/// Reads and writes integer fields of arbitrary width and offset. Serves BITFIELD and
+/// BITFIELD_RO, which only allows GET.
+#[derive(Eq, PartialEq, Debug)]
+pub struct BitField {
+    pub key: Bytes,
+    pub operations: Vec<BitFieldOperation>,
+}
+
+impl TryFrom<Args> for BitField {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let read_only = args.command() == "bitfield_ro";
+        let key = args.required()?;
+        let mut operations = vec![];
+        let mut overflow = Overflow::default();
+        while let Some(subcommand) = args.next() {
+            let subcommand = subcommand.to_ascii_lowercase();
+            if subcommand == b"overflow" {
+                overflow = match args
+                    .next()
+                    .ok_or(RedisError::Syntax)?
+                    .to_ascii_lowercase()
+                    .as_slice()
+                {
+                    b"wrap" => Overflow::Wrap,
+                    b"sat" => Overflow::Sat,
+                    b"fail" => Overflow::Fail,
+                    _ => {
+                        return Err(RedisError::Other(
+                            "Invalid OVERFLOW type specified".to_owned(),
+                        ))
+                    }
+                };
+                continue;
+            }
+            if !matches!(subcommand.as_slice(), b"get" | b"set" | b"incrby") {
+                return Err(RedisError::Syntax);
+            }
+            let (field, offset) = match (args.next(), args.next()) {
+                (Some(field), Some(offset)) => (field, offset),
+                _ => return Err(RedisError::Syntax),
+            };
+            let field = BitFieldType::parse(&field)?;
+            let offset = parse_bit_offset(&offset, Some(field.bits))?;
+            let action = match subcommand.as_slice() {
+                b"get" => BitFieldAction::Get,
+                _ if read_only => {
+                    return Err(RedisError::Other(
+                        "BITFIELD_RO only supports the GET subcommand".to_owned(),
+                    ))
+                }
+                _ => {
+                    let value = parse_i64(&args.next().ok_or(RedisError::Syntax)?)?;
+                    match subcommand.as_slice() {
+                        b"set" => BitFieldAction::Set(value),
+                        _ => BitFieldAction::IncrBy(value),
+                    }
+                }
+            };
+            operations.push(BitFieldOperation {
+                action,
+                field,
+                offset,
+                overflow,
+            });
+        }
+        Ok(BitField { key, operations })
+    }
+}
+
+#[cfg(test)]
+mod try_from_args {
+    use super::*;
+    use crate::protocol::args::args;
+
+    #[test]
+    fn ranges() {
+        assert_eq!(
+            BitCount::try_from(args(&["bitcount", "k", "1", "-1", "bit"])),
+            Ok(BitCount {
+                key: Bytes::from("k"),
+                range: Some(BitRange {
+                    start: 1,
+                    end: Some(-1),
+                    unit: BitUnit::Bit,
+                }),
+            })
+        );
+        assert_eq!(
+            BitCount::try_from(args(&["bitcount", "k", "1"])),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            BitPos::try_from(args(&["bitpos", "k", "0", "2"])),
+            Ok(BitPos {
+                key: Bytes::from("k"),
+                bit: false,
+                range: Some(BitRange {
+                    start: 2,
+                    end: None,
+                    unit: BitUnit::Byte,
+                }),
+            })
+        );
+        assert_eq!(
+            BitPos::try_from(args(&["bitpos", "k", "1", "0", "1", "word"])),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            BitPos::try_from(args(&["bitpos", "k", "2"])),
+            Err(RedisError::Other(
+                "The bit argument must be 1 or 0.".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn bitop() {
+        assert_eq!(
+            BitOp::try_from(args(&["bitop", "XOR", "dest", "a", "b"])),
+            Ok(BitOp {
+                operation: BitOperation::Xor,
+                destination: Bytes::from("dest"),
+                keys: vec![Bytes::from("a"), Bytes::from("b")],
+            })
+        );
+        assert_eq!(
+            BitOp::try_from(args(&["bitop", "not", "dest", "a", "b"])),
+            Err(RedisError::Other(
+                "BITOP NOT must be called with a single source key.".to_owned()
+            ))
+        );
+        assert_eq!(
+            BitOp::try_from(args(&["bitop", "nand", "dest", "a"])),
+            Err(RedisError::Syntax)
+        );
+    }
+
+    #[test]
+    fn bitfield() {
+        assert_eq!(
+            BitField::try_from(args(&[
+                "bitfield", "k", "GET", "u8", "#2", "OVERFLOW", "SAT", "INCRBY", "i5", "100", "-3"
+            ])),
+            Ok(BitField {
+                key: Bytes::from("k"),
+                operations: vec![
+                    BitFieldOperation {
+                        action: BitFieldAction::Get,
+                        field: BitFieldType {
+                            signed: false,
+                            bits: 8,
+                        },
+                        offset: 16,
+                        overflow: Overflow::Wrap,
+                    },
+                    BitFieldOperation {
+                        action: BitFieldAction::IncrBy(-3),
+                        field: BitFieldType {
+                            signed: true,
+                            bits: 5,
+                        },
+                        offset: 100,
+                        overflow: Overflow::Sat,
+                    },
+                ],
+            })
+        );
+        assert_eq!(
+            BitField::try_from(args(&["bitfield", "k", "get", "u64", "0"])),
+            Err(RedisError::Other(
+                "Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported \
+                 but i64 is."
+                    .to_owned()
+            ))
+        );
+        assert_eq!(
+            BitField::try_from(args(&["bitfield", "k", "get", "u8", "-1"])),
+            Err(invalid_offset())
+        );
+        assert_eq!(
+            BitField::try_from(args(&["bitfield", "k", "overflow", "none"])),
+            Err(RedisError::Other(
+                "Invalid OVERFLOW type specified".to_owned()
+            ))
+        );
+        assert_eq!(
+            BitField::try_from(args(&["bitfield_ro", "k", "set", "u8", "0", "1"])),
+            Err(RedisError::Other(
+                "BITFIELD_RO only supports the GET subcommand".to_owned()
+            ))
+        );
+        assert_eq!(
+            BitField::try_from(args(&["bitfield", "k", "get", "u8"])),
+            Err(RedisError::Syntax)
+        );
+    }
+}
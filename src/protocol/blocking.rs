@@ -0,0 +1,89 @@
+//! Timeouts of blocking requests.
+use crate::{
+    error::RedisError,
+    protocol::args::{parse_f64, parse_i64},
+};
+use std::time::Duration;
+
+/// How long a blocked client waits for a key to become ready.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Timeout {
+    Forever,
+    After(Duration),
+}
+
+impl Timeout {
+    /// Parses a timeout in seconds, which may be fractional. 0 blocks forever.
+    pub(crate) fn parse_seconds(arg: &[u8]) -> Result<Self, RedisError> {
+        let seconds = parse_f64(arg)
+            .map_err(|_| RedisError::Other("timeout is not a float or out of range".to_owned()))?;
+        if seconds < 0.0 {
+            return Err(RedisError::Other("timeout is negative".to_owned()));
+        }
+        if seconds == 0.0 {
+            return Ok(Timeout::Forever);
+        }
+        Duration::try_from_secs_f64(seconds)
+            .map(Timeout::After)
+            .map_err(|_| RedisError::Other("timeout is out of range".to_owned()))
+    }
+
+    /// Parses a timeout in milliseconds. 0 blocks forever.
+    pub(crate) fn parse_millis(arg: &[u8]) -> Result<Self, RedisError> {
+        let milliseconds = parse_i64(arg).map_err(|_| {
+            RedisError::Other("timeout is not an integer or out of range".to_owned())
+        })?;
+        match u64::try_from(milliseconds) {
+            Ok(0) => Ok(Timeout::Forever),
+            Ok(milliseconds) => Ok(Timeout::After(Duration::from_millis(milliseconds))),
+            Err(_) => Err(RedisError::Other("timeout is negative".to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn seconds() {
+        assert_eq!(Timeout::parse_seconds(b"0"), Ok(Timeout::Forever));
+        assert_eq!(
+            Timeout::parse_seconds(b"1.5"),
+            Ok(Timeout::After(Duration::from_millis(1500)))
+        );
+        assert_eq!(
+            Timeout::parse_seconds(b"-1"),
+            Err(RedisError::Other("timeout is negative".to_owned()))
+        );
+        assert_eq!(
+            Timeout::parse_seconds(b"soon"),
+            Err(RedisError::Other(
+                "timeout is not a float or out of range".to_owned()
+            ))
+        );
+        assert_eq!(
+            Timeout::parse_seconds(b"inf"),
+            Err(RedisError::Other("timeout is out of range".to_owned()))
+        );
+    }
+
+    #[test]
+    fn milliseconds() {
+        assert_eq!(Timeout::parse_millis(b"0"), Ok(Timeout::Forever));
+        assert_eq!(
+            Timeout::parse_millis(b"250"),
+            Ok(Timeout::After(Duration::from_millis(250)))
+        );
+        assert_eq!(
+            Timeout::parse_millis(b"-1"),
+            Err(RedisError::Other("timeout is negative".to_owned()))
+        );
+        assert_eq!(
+            Timeout::parse_millis(b"1.5"),
+            Err(RedisError::Other(
+                "timeout is not an integer or out of range".to_owned()
+            ))
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Copy request.
+use crate::{
+    error::RedisError,
+    protocol::args::{parse_i64, Args},
+};
+use bytes::Bytes;
+
+/// Copies the value of the source key to the destination key.
+#[derive(Eq, PartialEq, Debug)]
+pub struct Copy {
+    pub source: Bytes,
+    pub destination: Bytes,
+    /// Overwrite the destination key if it exists.
+    pub replace: bool,
+}
+
+impl TryFrom<Args> for Copy {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let source = args.required()?;
+        let destination = args.required()?;
+        let mut replace = false;
+        while let Some(arg) = args.next() {
+            if arg.eq_ignore_ascii_case(b"REPLACE") {
+                replace = true;
+            } else if arg.eq_ignore_ascii_case(b"DB") {
+                // There is a single database, so only its index is accepted.
+                let db = parse_i64(&args.next().ok_or(RedisError::Syntax)?)?;
+                if db != 0 {
+                    return Err(RedisError::Other("DB index is out of range".to_owned()));
+                }
+            } else {
+                return Err(RedisError::Syntax);
+            }
+        }
+        if source == destination {
+            return Err(RedisError::Other(
+                "source and destination objects are the same".to_owned(),
+            ));
+        }
+
+        Ok(Copy {
+            source,
+            destination,
+            replace,
+        })
+    }
+}
+
+#[cfg(test)]
+mod try_from_args {
+    use super::*;
+    use crate::protocol::args::args;
+
+    #[test]
+    fn options() {
+        assert_eq!(
+            Copy::try_from(args(&["copy", "a", "b", "db", "0", "REPLACE"])).unwrap(),
+            Copy {
+                source: Bytes::from("a"),
+                destination: Bytes::from("b"),
+                replace: true,
+            }
+        );
+        assert!(!Copy::try_from(args(&["copy", "a", "b"])).unwrap().replace);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Copy::try_from(args(&["copy", "a"])),
+            Err(RedisError::WrongArity("copy".to_owned()))
+        );
+        assert_eq!(
+            Copy::try_from(args(&["copy", "a", "b", "db"])),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            Copy::try_from(args(&["copy", "a", "b", "db", "x"])),
+            Err(RedisError::NotInteger)
+        );
+        assert_eq!(
+            Copy::try_from(args(&["copy", "a", "b", "db", "1"])),
+            Err(RedisError::Other("DB index is out of range".to_owned()))
+        );
+        assert_eq!(
+            Copy::try_from(args(&["copy", "a", "b", "nx"])),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            Copy::try_from(args(&["copy", "a", "a"])),
+            Err(RedisError::Other(
+                "source and destination objects are the same".to_owned()
+            ))
+        );
+    }
+}
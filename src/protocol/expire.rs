@@ -0,0 +1,204 @@
+//! Requests that set a deadline of a key: EXPIRE, PEXPIRE, EXPIREAT and PEXPIREAT.
+use crate::{
+    error::RedisError,
+    protocol::args::{parse_i64, Args},
+};
+use bytes::Bytes;
+
+/// Deadline of a key as given by a client.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Expiration {
+    /// Milliseconds from now.
+    After(i64),
+    /// Unix time in milliseconds.
+    At(i64),
+}
+
+impl Expiration {
+    /// Parses the time following one of the EX, PX, EXAT and PXAT options of a command, given
+    /// in lowercase. The time must be positive.
+    pub(crate) fn parse(option: &[u8], time: &[u8], command: &str) -> Result<Self, RedisError> {
+        let time = parse_i64(time)?;
+        // Seconds are converted to milliseconds, which must not overflow.
+        if time <= 0 || time > i64::MAX / 1000 && option.starts_with(b"ex") {
+            return Err(RedisError::InvalidExpireTime(command.to_owned()));
+        }
+        Ok(match option {
+            b"ex" => Expiration::After(time * 1000),
+            b"px" => Expiration::After(time),
+            b"exat" => Expiration::At(time * 1000),
+            _ => Expiration::At(time),
+        })
+    }
+
+    /// Unix time in milliseconds when the key expires. It is in the past for negative values.
+    pub fn deadline(self, now: u64) -> i64 {
+        match self {
+            Expiration::After(timeout) => (now as i64).saturating_add(timeout),
+            Expiration::At(deadline) => deadline,
+        }
+    }
+}
+
+/// Condition on the current deadline of a key that must hold to set the new one.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum ExpireCondition {
+    /// The key has no deadline.
+    Nx,
+    /// The key has a deadline.
+    Xx,
+    /// The new deadline is later than the current one. A key without a deadline never expires,
+    /// so the condition doesn't hold for it.
+    Gt,
+    /// The new deadline is earlier than the current one.
+    Lt,
+}
+
+impl ExpireCondition {
+    /// Checks the condition against the current deadline of a key.
+    pub fn holds(self, current: Option<u64>, deadline: i64) -> bool {
+        match (self, current) {
+            (ExpireCondition::Nx, current) => current.is_none(),
+            (ExpireCondition::Xx, current) => current.is_some(),
+            (ExpireCondition::Gt, None) => false,
+            (ExpireCondition::Gt, Some(current)) => deadline > current as i64,
+            (ExpireCondition::Lt, None) => true,
+            (ExpireCondition::Lt, Some(current)) => deadline < current as i64,
+        }
+    }
+}
+
+// Expire request
+#[derive(Eq, PartialEq, Debug)]
+pub struct Expire {
+    pub key: Bytes,
+    pub expiration: Expiration,
+    /// Conditions that all must hold to set the deadline.
+    pub conditions: Vec<ExpireCondition>,
+}
+
+impl TryFrom<Args> for Expire {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let (multiplier, absolute) = match args.command() {
+            "expire" => (1000, false),
+            "pexpire" => (1, false),
+            "expireat" => (1000, true),
+            "pexpireat" => (1, true),
+            _ => return Err(args.unknown_command()),
+        };
+        let key = args.required()?;
+        let time = parse_i64(&args.required()?)?
+            .checked_mul(multiplier)
+            .ok_or_else(|| RedisError::InvalidExpireTime(args.command().to_owned()))?;
+        let expiration = if absolute {
+            Expiration::At(time)
+        } else {
+            Expiration::After(time)
+        };
+
+        let (mut nx, mut xx, mut gt, mut lt) = (false, false, false, false);
+        for arg in args.by_ref() {
+            if arg.eq_ignore_ascii_case(b"NX") {
+                nx = true;
+            } else if arg.eq_ignore_ascii_case(b"XX") {
+                xx = true;
+            } else if arg.eq_ignore_ascii_case(b"GT") {
+                gt = true;
+            } else if arg.eq_ignore_ascii_case(b"LT") {
+                lt = true;
+            } else {
+                return Err(RedisError::Other(format!(
+                    "Unsupported option {}",
+                    String::from_utf8_lossy(&arg)
+                )));
+            }
+        }
+        if nx && (xx || gt || lt) {
+            return Err(RedisError::Other(
+                "NX and XX, GT or LT options at the same time are not compatible".to_owned(),
+            ));
+        }
+        if gt && lt {
+            return Err(RedisError::Other(
+                "GT and LT options at the same time are not compatible".to_owned(),
+            ));
+        }
+        let conditions = [
+            (nx, ExpireCondition::Nx),
+            (xx, ExpireCondition::Xx),
+            (gt, ExpireCondition::Gt),
+            (lt, ExpireCondition::Lt),
+        ]
+        .into_iter()
+        .filter_map(|(given, condition)| given.then_some(condition))
+        .collect();
+
+        Ok(Expire {
+            key,
+            expiration,
+            conditions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod try_from_args {
+    use super::*;
+    use crate::protocol::args::args;
+
+    #[test]
+    fn units() {
+        let expire = Expire::try_from(args(&["expire", "a", "10"])).unwrap();
+        assert_eq!(expire.expiration, Expiration::After(10_000));
+        assert!(expire.conditions.is_empty());
+        let expire = Expire::try_from(args(&["PEXPIREAT", "a", "-5"])).unwrap();
+        assert_eq!(expire.expiration, Expiration::At(-5));
+        assert_eq!(
+            Expire::try_from(args(&["expireat", "a", "9223372036854775807"])),
+            Err(RedisError::InvalidExpireTime("expireat".to_owned()))
+        );
+        assert_eq!(
+            Expire::try_from(args(&["expire", "a", "1.5"])),
+            Err(RedisError::NotInteger)
+        );
+    }
+
+    #[test]
+    fn conditions() {
+        let expire = Expire::try_from(args(&["expire", "a", "1", "xx", "LT"])).unwrap();
+        assert_eq!(
+            expire.conditions,
+            vec![ExpireCondition::Xx, ExpireCondition::Lt]
+        );
+        assert_eq!(
+            Expire::try_from(args(&["expire", "a", "1", "nx", "gt"])),
+            Err(RedisError::Other(
+                "NX and XX, GT or LT options at the same time are not compatible".to_owned()
+            ))
+        );
+        assert_eq!(
+            Expire::try_from(args(&["expire", "a", "1", "gt", "lt"])),
+            Err(RedisError::Other(
+                "GT and LT options at the same time are not compatible".to_owned()
+            ))
+        );
+        assert_eq!(
+            Expire::try_from(args(&["expire", "a", "1", "ex"])),
+            Err(RedisError::Other("Unsupported option ex".to_owned()))
+        );
+    }
+
+    #[test]
+    fn condition_holds() {
+        assert!(ExpireCondition::Nx.holds(None, 10));
+        assert!(!ExpireCondition::Nx.holds(Some(5), 10));
+        assert!(ExpireCondition::Xx.holds(Some(5), 10));
+        assert!(!ExpireCondition::Gt.holds(None, 10));
+        assert!(ExpireCondition::Gt.holds(Some(5), 10));
+        assert!(!ExpireCondition::Gt.holds(Some(10), 10));
+        assert!(ExpireCondition::Lt.holds(None, 10));
+        assert!(!ExpireCondition::Lt.holds(Some(5), 10));
+    }
+}
@@ -0,0 +1,485 @@
+//! Geospatial requests: GEOADD, GEOSEARCH and GEOSEARCHSTORE, and distance units.
+use crate::{
+    error::RedisError,
+    geohash::{self, Shape},
+    protocol::args::{parse_f64, parse_i64, Args},
+};
+use bytes::Bytes;
+
+/// Unit of distances in requests and replies.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Unit {
+    Meters,
+    Kilometers,
+    Feet,
+    Miles,
+}
+
+impl Unit {
+    pub(crate) fn parse(arg: &[u8]) -> Result<Self, RedisError> {
+        match arg.to_ascii_lowercase().as_slice() {
+            b"m" => Ok(Unit::Meters),
+            b"km" => Ok(Unit::Kilometers),
+            b"ft" => Ok(Unit::Feet),
+            b"mi" => Ok(Unit::Miles),
+            _ => Err(RedisError::Other(
+                "unsupported unit provided. please use M, KM, FT, MI".to_owned(),
+            )),
+        }
+    }
+
+    /// Number of meters in the unit.
+    pub(crate) fn meters(self) -> f64 {
+        match self {
+            Unit::Meters => 1.0,
+            Unit::Kilometers => 1000.0,
+            Unit::Feet => 0.3048,
+            Unit::Miles => 1609.34,
+        }
+    }
+}
+
+/// Parses longitude and latitude, which must be within the range geohashes cover.
+fn parse_position(longitude: &[u8], latitude: &[u8]) -> Result<(f64, f64), RedisError> {
+    let (longitude, latitude) = (parse_f64(longitude)?, parse_f64(latitude)?);
+    if !geohash::is_valid(longitude, latitude) {
+        return Err(RedisError::Other(format!(
+            "invalid longitude,latitude pair {:.6},{:.6}",
+            longitude, latitude
+        )));
+    }
+    Ok((longitude, latitude))
+}
+
+/// Adds members at positions to a sorted set, scored by their geohashes.
+#[derive(PartialEq, Debug)]
+pub struct GeoAdd {
+    pub key: Bytes,
+    /// Only add new members.
+    pub nx: bool,
+    /// Only update existing members.
+    pub xx: bool,
+    /// Count updated members along with the added ones.
+    pub ch: bool,
+    /// Longitudes, latitudes and members.
+    pub points: Vec<(f64, f64, Bytes)>,
+}
+
+impl TryFrom<Args> for GeoAdd {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let key = args.required()?;
+        // At least a longitude, a latitude and a member, or options in their place.
+        if args.as_slice().len() < 3 {
+            return Err(RedisError::WrongArity(args.command().to_owned()));
+        }
+        let (mut nx, mut xx, mut ch) = (false, false, false);
+        while let Some(arg) = args.as_slice().first() {
+            let flag = match arg.to_ascii_lowercase().as_slice() {
+                b"nx" => &mut nx,
+                b"xx" => &mut xx,
+                b"ch" => &mut ch,
+                _ => break,
+            };
+            *flag = true;
+            args.next();
+        }
+        let rest = args.as_slice();
+        if rest.is_empty() || !rest.chunks_exact(3).remainder().is_empty() || nx && xx {
+            return Err(RedisError::Syntax);
+        }
+        let mut points = Vec::with_capacity(rest.len() / 3);
+        while let (Some(longitude), Some(latitude), Some(member)) =
+            (args.next(), args.next(), args.next())
+        {
+            let (longitude, latitude) = parse_position(&longitude, &latitude)?;
+            points.push((longitude, latitude, member));
+        }
+        Ok(GeoAdd {
+            key,
+            nx,
+            xx,
+            ch,
+            points,
+        })
+    }
+}
+
+/// Center of a search.
+#[derive(PartialEq, Debug)]
+pub enum GeoOrigin {
+    /// Position of a member of the sorted set.
+    Member(Bytes),
+    /// Longitude and latitude.
+    Position(f64, f64),
+}
+
+/// Destination of GEOSEARCHSTORE.
+#[derive(Eq, PartialEq, Debug)]
+pub struct GeoStore {
+    pub destination: Bytes,
+    /// Score stored members by their distance instead of their geohash.
+    pub store_dist: bool,
+}
+
+/// Searches members of a sorted set within a shape. Serves GEOSEARCH and GEOSEARCHSTORE.
+#[derive(PartialEq, Debug)]
+pub struct GeoSearch {
+    pub key: Bytes,
+    pub origin: GeoOrigin,
+    /// Shape in meters.
+    pub shape: Shape,
+    /// Unit of the shape, in which distances are replied.
+    pub unit: Unit,
+    /// Sort by distance, from the farthest if `Some(true)`.
+    pub descending: Option<bool>,
+    pub count: Option<usize>,
+    /// Stop at any `count` members found instead of the nearest ones.
+    pub any: bool,
+    pub with_coord: bool,
+    pub with_dist: bool,
+    pub with_hash: bool,
+    pub store: Option<GeoStore>,
+}
+
+impl TryFrom<Args> for GeoSearch {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let command = args.command().to_owned();
+        let storing = command == "geosearchstore";
+        let destination = match storing {
+            true => Some(args.required()?),
+            false => None,
+        };
+        let key = args.required()?;
+        let mut origin = None;
+        let mut shape = None;
+        let mut descending = None;
+        let mut count = None;
+        let mut any = false;
+        let (mut with_coord, mut with_dist, mut with_hash) = (false, false, false);
+        let mut store_dist = false;
+        let one_origin = || {
+            RedisError::Other(format!(
+                "exactly one of FROMMEMBER or FROMLONLAT can be specified for {}",
+                command
+            ))
+        };
+        let one_shape = || {
+            RedisError::Other(format!(
+                "exactly one of BYRADIUS and BYBOX can be specified for {}",
+                command
+            ))
+        };
+        while let Some(arg) = args.next() {
+            let remaining = args.as_slice().len();
+            match arg.to_ascii_lowercase().as_slice() {
+                b"withdist" => with_dist = true,
+                b"withhash" => with_hash = true,
+                b"withcoord" => with_coord = true,
+                b"any" => any = true,
+                b"asc" => descending = Some(false),
+                b"desc" => descending = Some(true),
+                b"count" if remaining >= 1 => {
+                    let value = parse_i64(&args.required()?)?;
+                    if value <= 0 {
+                        return Err(RedisError::Other("COUNT must be > 0".to_owned()));
+                    }
+                    count = Some(usize::try_from(value).unwrap_or(usize::MAX));
+                }
+                b"storedist" if storing => store_dist = true,
+                b"frommember" if remaining >= 1 => {
+                    if origin.is_some() {
+                        return Err(one_origin());
+                    }
+                    origin = Some(GeoOrigin::Member(args.required()?));
+                }
+                b"fromlonlat" if remaining >= 2 => {
+                    if origin.is_some() {
+                        return Err(one_origin());
+                    }
+                    let (longitude, latitude) =
+                        parse_position(&args.required()?, &args.required()?)?;
+                    origin = Some(GeoOrigin::Position(longitude, latitude));
+                }
+                b"byradius" if remaining >= 2 => {
+                    if shape.is_some() {
+                        return Err(one_shape());
+                    }
+                    let radius = parse_f64(&args.required()?)
+                        .map_err(|_| RedisError::Other("need numeric radius".to_owned()))?;
+                    let unit = Unit::parse(&args.required()?)?;
+                    if radius < 0.0 {
+                        return Err(RedisError::Other("radius cannot be negative".to_owned()));
+                    }
+                    shape = Some((Shape::Radius(radius * unit.meters()), unit));
+                }
+                b"bybox" if remaining >= 3 => {
+                    if shape.is_some() {
+                        return Err(one_shape());
+                    }
+                    let width = parse_f64(&args.required()?)?;
+                    let height = parse_f64(&args.required()?)?;
+                    let unit = Unit::parse(&args.required()?)?;
+                    if width < 0.0 || height < 0.0 {
+                        return Err(RedisError::Other(
+                            "height or width cannot be negative".to_owned(),
+                        ));
+                    }
+                    let shape_in_meters = Shape::Box {
+                        width: width * unit.meters(),
+                        height: height * unit.meters(),
+                    };
+                    shape = Some((shape_in_meters, unit));
+                }
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+        if storing && (with_dist || with_hash || with_coord) {
+            return Err(RedisError::Other(
+                "GEOSEARCHSTORE is not compatible with WITHDIST, WITHHASH and WITHCOORD options"
+                    .to_owned(),
+            ));
+        }
+        let origin = origin.ok_or_else(one_origin)?;
+        let (shape, unit) = shape.ok_or_else(one_shape)?;
+        if any && count.is_none() {
+            return Err(RedisError::Other(
+                "the ANY argument requires COUNT argument".to_owned(),
+            ));
+        }
+        Ok(GeoSearch {
+            key,
+            origin,
+            shape,
+            unit,
+            descending,
+            count,
+            any,
+            with_coord,
+            with_dist,
+            with_hash,
+            store: destination.map(|destination| GeoStore {
+                destination,
+                store_dist,
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod try_from_args {
+    use super::*;
+    use crate::protocol::args::args;
+
+    fn search(key: &'static str, origin: GeoOrigin, shape: Shape, unit: Unit) -> GeoSearch {
+        GeoSearch {
+            key: Bytes::from(key),
+            origin,
+            shape,
+            unit,
+            descending: None,
+            count: None,
+            any: false,
+            with_coord: false,
+            with_dist: false,
+            with_hash: false,
+            store: None,
+        }
+    }
+
+    #[test]
+    fn units() {
+        assert_eq!(Unit::parse(b"KM"), Ok(Unit::Kilometers));
+        assert_eq!(
+            Unit::parse(b"yd"),
+            Err(RedisError::Other(
+                "unsupported unit provided. please use M, KM, FT, MI".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn geoadd() {
+        assert_eq!(
+            GeoAdd::try_from(args(&["geoadd", "g", "CH", "13.5", "38.1", "a"])),
+            Ok(GeoAdd {
+                key: Bytes::from("g"),
+                nx: false,
+                xx: false,
+                ch: true,
+                points: vec![(13.5, 38.1, Bytes::from("a"))],
+            })
+        );
+        assert_eq!(
+            GeoAdd::try_from(args(&["geoadd", "g", "13.5", "38.1", "a", "1"])),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            GeoAdd::try_from(args(&["geoadd", "g", "NX", "XX", "13.5", "38.1", "a"])),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            GeoAdd::try_from(args(&["geoadd", "g", "181", "0", "a"])),
+            Err(RedisError::Other(
+                "invalid longitude,latitude pair 181.000000,0.000000".to_owned()
+            ))
+        );
+        assert_eq!(
+            GeoAdd::try_from(args(&["geoadd", "g", "13.5", "38.1"])),
+            Err(RedisError::WrongArity("geoadd".to_owned()))
+        );
+    }
+
+    #[test]
+    fn geosearch() {
+        assert_eq!(
+            GeoSearch::try_from(args(&[
+                "geosearch",
+                "g",
+                "FROMMEMBER",
+                "a",
+                "BYRADIUS",
+                "2",
+                "km"
+            ])),
+            Ok(search(
+                "g",
+                GeoOrigin::Member(Bytes::from("a")),
+                Shape::Radius(2000.0),
+                Unit::Kilometers
+            ))
+        );
+        let mut expected = search(
+            "g",
+            GeoOrigin::Position(15.0, 37.0),
+            Shape::Box {
+                width: 400.0,
+                height: 200.0,
+            },
+            Unit::Meters,
+        );
+        expected.descending = Some(true);
+        expected.count = Some(3);
+        expected.any = true;
+        expected.with_dist = true;
+        expected.with_coord = true;
+        assert_eq!(
+            GeoSearch::try_from(args(&[
+                "geosearch",
+                "g",
+                "FROMLONLAT",
+                "15",
+                "37",
+                "BYBOX",
+                "400",
+                "200",
+                "m",
+                "DESC",
+                "COUNT",
+                "3",
+                "ANY",
+                "WITHDIST",
+                "WITHCOORD"
+            ])),
+            Ok(expected)
+        );
+        assert_eq!(
+            GeoSearch::try_from(args(&["geosearch", "g", "BYRADIUS", "2", "km"])),
+            Err(RedisError::Other(
+                "exactly one of FROMMEMBER or FROMLONLAT can be specified for geosearch".to_owned()
+            ))
+        );
+        assert_eq!(
+            GeoSearch::try_from(args(&[
+                "geosearch",
+                "g",
+                "FROMMEMBER",
+                "a",
+                "BYRADIUS",
+                "-1",
+                "km"
+            ])),
+            Err(RedisError::Other("radius cannot be negative".to_owned()))
+        );
+        assert_eq!(
+            GeoSearch::try_from(args(&[
+                "geosearch",
+                "g",
+                "FROMMEMBER",
+                "a",
+                "BYRADIUS",
+                "1",
+                "km",
+                "ANY"
+            ])),
+            Err(RedisError::Other(
+                "the ANY argument requires COUNT argument".to_owned()
+            ))
+        );
+        assert_eq!(
+            GeoSearch::try_from(args(&["geosearch", "g", "FROMMEMBER", "a", "COUNT"])),
+            Err(RedisError::Syntax)
+        );
+    }
+
+    #[test]
+    fn geosearchstore() {
+        let mut expected = search(
+            "g",
+            GeoOrigin::Member(Bytes::from("a")),
+            Shape::Radius(1.0),
+            Unit::Meters,
+        );
+        expected.store = Some(GeoStore {
+            destination: Bytes::from("d"),
+            store_dist: true,
+        });
+        assert_eq!(
+            GeoSearch::try_from(args(&[
+                "geosearchstore",
+                "d",
+                "g",
+                "FROMMEMBER",
+                "a",
+                "BYRADIUS",
+                "1",
+                "m",
+                "STOREDIST"
+            ])),
+            Ok(expected)
+        );
+        assert_eq!(
+            GeoSearch::try_from(args(&[
+                "geosearchstore",
+                "d",
+                "g",
+                "FROMMEMBER",
+                "a",
+                "BYRADIUS",
+                "1",
+                "m",
+                "WITHDIST"
+            ])),
+            Err(RedisError::Other(
+                "GEOSEARCHSTORE is not compatible with WITHDIST, WITHHASH and WITHCOORD options"
+                    .to_owned()
+            ))
+        );
+        assert_eq!(
+            GeoSearch::try_from(args(&[
+                "geosearch",
+                "g",
+                "FROMMEMBER",
+                "a",
+                "BYRADIUS",
+                "1",
+                "m",
+                "STOREDIST"
+            ])),
+            Err(RedisError::Syntax)
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! GetEx request.
+use crate::{
+    error::RedisError,
+    protocol::{args::Args, Expiration},
+};
+use bytes::Bytes;
+
+/// Gets the value of a key and optionally changes its deadline.
+#[derive(Eq, PartialEq, Debug, Default)]
+pub struct GetEx {
+    pub key: Bytes,
+    /// New deadline of the key.
+    pub expiration: Option<Expiration>,
+    /// Remove the deadline of the key.
+    pub persist: bool,
+}
+
+impl TryFrom<Args> for GetEx {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let mut request = GetEx {
+            key: args.required()?,
+            ..Default::default()
+        };
+        while let Some(arg) = args.next() {
+            let option = arg.to_ascii_lowercase();
+            match option.as_slice() {
+                b"persist" if request.expiration.is_none() => request.persist = true,
+                // Expiration time cannot be set twice or along with PERSIST.
+                b"ex" | b"px" | b"exat" | b"pxat"
+                    if request.expiration.is_none() && !request.persist =>
+                {
+                    let time = args.next().ok_or(RedisError::Syntax)?;
+                    request.expiration = Some(Expiration::parse(&option, &time, "getex")?);
+                }
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod try_from_args {
+    use super::*;
+    use crate::protocol::args::args;
+
+    #[test]
+    fn options() {
+        assert_eq!(
+            GetEx::try_from(args(&["getex", "a", "ex", "5"])).unwrap(),
+            GetEx {
+                key: Bytes::from("a"),
+                expiration: Some(Expiration::After(5000)),
+                persist: false,
+            }
+        );
+        assert!(
+            GetEx::try_from(args(&["getex", "a", "PERSIST"]))
+                .unwrap()
+                .persist
+        );
+        assert_eq!(
+            GetEx::try_from(args(&["getex", "a"])).unwrap(),
+            GetEx {
+                key: Bytes::from("a"),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            GetEx::try_from(args(&["getex", "a", "persist", "px", "5"])),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            GetEx::try_from(args(&["getex", "a", "exat", "0"])),
+            Err(RedisError::InvalidExpireTime("getex".to_owned()))
+        );
+        assert_eq!(
+            GetEx::try_from(args(&["getex", "a", "keepttl"])),
+            Err(RedisError::Syntax)
+        );
+    }
+}
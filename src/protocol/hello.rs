@@ -0,0 +1,122 @@
+//! Hello request.
+use crate::{
+    error::RedisError,
+    protocol::{args::Args, ProtocolVersion},
+};
+use bytes::Bytes;
+
+/// Hello request: switches the protocol version, authenticates and names the connection.
+#[derive(Eq, PartialEq, Debug, Default)]
+pub struct Hello {
+    pub protocol_version: Option<ProtocolVersion>,
+    /// User name and password.
+    pub auth: Option<(Bytes, Bytes)>,
+    pub client_name: Option<Bytes>,
+}
+
+impl TryFrom<Args> for Hello {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let mut hello = Hello::default();
+        let version = match args.next() {
+            Some(version) => version,
+            None => return Ok(hello),
+        };
+        hello.protocol_version = Some(
+            match std::str::from_utf8(&version)
+                .ok()
+                .and_then(|version| version.parse::<i64>().ok())
+            {
+                Some(2) => ProtocolVersion::Resp2,
+                Some(3) => ProtocolVersion::Resp3,
+                Some(_) => return Err(RedisError::NoProto),
+                None => {
+                    return Err(RedisError::Other(
+                        "Protocol version is not an integer or out of range".to_owned(),
+                    ))
+                }
+            },
+        );
+
+        while let Some(option) = args.next() {
+            if option.eq_ignore_ascii_case(b"AUTH") && args.as_slice().len() >= 2 {
+                hello.auth = Some((args.required()?, args.required()?));
+            } else if option.eq_ignore_ascii_case(b"SETNAME") && !args.as_slice().is_empty() {
+                hello.client_name = Some(parse_client_name(args.required()?)?);
+            } else {
+                return Err(RedisError::Other(format!(
+                    "Syntax error in HELLO option '{}'",
+                    String::from_utf8_lossy(&option)
+                )));
+            }
+        }
+        Ok(hello)
+    }
+}
+
+/// Validates a client name. Names can't contain spaces, newlines or special characters.
+fn parse_client_name(name: Bytes) -> Result<Bytes, RedisError> {
+    if name.iter().all(|&c| (b'!'..=b'~').contains(&c)) {
+        Ok(name)
+    } else {
+        Err(RedisError::Other(
+            "Client names cannot contain spaces, newlines or special characters.".to_owned(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod try_from_args {
+    use super::*;
+    use crate::protocol::args::args;
+
+    #[test]
+    fn no_args() {
+        assert_eq!(Hello::try_from(args(&["hello"])), Ok(Hello::default()));
+    }
+
+    #[test]
+    fn all_options() {
+        assert_eq!(
+            Hello::try_from(args(&[
+                "hello", "3", "auth", "default", "secret", "setname", "worker"
+            ])),
+            Ok(Hello {
+                protocol_version: Some(ProtocolVersion::Resp3),
+                auth: Some((Bytes::from("default"), Bytes::from("secret"))),
+                client_name: Some(Bytes::from("worker")),
+            })
+        );
+    }
+
+    #[test]
+    fn neg_unsupported_version() {
+        assert_eq!(
+            Hello::try_from(args(&["hello", "4"])),
+            Err(RedisError::NoProto)
+        );
+        assert_eq!(
+            Hello::try_from(args(&["hello", "three"])),
+            Err(RedisError::Other(
+                "Protocol version is not an integer or out of range".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn neg_bad_option() {
+        assert_eq!(
+            Hello::try_from(args(&["hello", "2", "auth", "default"])),
+            Err(RedisError::Other(
+                "Syntax error in HELLO option 'auth'".to_string()
+            ))
+        );
+        assert_eq!(
+            Hello::try_from(args(&["hello", "2", "setname", "with space"])),
+            Err(RedisError::Other(
+                "Client names cannot contain spaces, newlines or special characters.".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,282 @@
+//! Requests on deadlines of hash fields: HEXPIRE, HPEXPIRE, HEXPIREAT, HPEXPIREAT and HGETEX.
+use crate::{
+    error::RedisError,
+    protocol::{
+        args::{parse_i64, Args},
+        expire::ExpireCondition,
+        Expiration,
+    },
+};
+use bytes::Bytes;
+
+/// Latest deadline of a field, unix time in milliseconds.
+pub(crate) const MAX_FIELD_DEADLINE: i64 = (1 << 48) - 1;
+
+/// Returns a wrong arity error unless there are at least `count` arguments left.
+pub(crate) fn require_args(args: &Args, count: usize) -> Result<(), RedisError> {
+    if args.as_slice().len() < count {
+        return Err(RedisError::WrongArity(args.command().to_owned()));
+    }
+    Ok(())
+}
+
+/// Parses `FIELDS numfields field [field ...]`, which ends the arguments of hash field
+/// expiration commands.
+pub(crate) fn parse_fields(args: &mut Args) -> Result<Vec<Bytes>, RedisError> {
+    match args.next() {
+        Some(arg) if arg.eq_ignore_ascii_case(b"FIELDS") => {}
+        _ => {
+            return Err(RedisError::Other(
+                "Mandatory argument FIELDS is missing or not at the right position".to_owned(),
+            ))
+        }
+    }
+    let count = parse_i64(&args.required()?)
+        .ok()
+        .filter(|count| *count > 0)
+        .ok_or_else(|| {
+            RedisError::Other("Parameter `numFields` should be greater than 0".to_owned())
+        })?;
+    let fields: Vec<Bytes> = args.collect();
+    if fields.len() as i64 != count {
+        return Err(RedisError::Other(
+            "The `numfields` parameter must match the number of arguments".to_owned(),
+        ));
+    }
+    Ok(fields)
+}
+
+/// Sets deadlines of hash fields.
+#[derive(Eq, PartialEq, Debug)]
+pub struct HExpire {
+    pub key: Bytes,
+    pub expiration: Expiration,
+    pub condition: Option<ExpireCondition>,
+    pub fields: Vec<Bytes>,
+    /// Name of the command, which is reported if the deadline is too far in the future.
+    pub command: String,
+}
+
+impl TryFrom<Args> for HExpire {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let (multiplier, absolute) = match args.command() {
+            "hexpire" => (1000, false),
+            "hpexpire" => (1, false),
+            "hexpireat" => (1000, true),
+            "hpexpireat" => (1, true),
+            _ => return Err(args.unknown_command()),
+        };
+        // Key, time, FIELDS, number of fields and a field.
+        require_args(&args, 5)?;
+        let command = args.command().to_owned();
+        let key = args.required()?;
+        let time = parse_i64(&args.required()?)?;
+        if time < 0 {
+            return Err(RedisError::Other(
+                "invalid expire time, must be >= 0".to_owned(),
+            ));
+        }
+        let time = time
+            .checked_mul(multiplier)
+            .filter(|time| *time <= MAX_FIELD_DEADLINE)
+            .ok_or_else(|| RedisError::InvalidExpireTime(command.clone()))?;
+        let expiration = if absolute {
+            Expiration::At(time)
+        } else {
+            Expiration::After(time)
+        };
+
+        let condition = match args.as_slice().first() {
+            Some(arg) if !arg.eq_ignore_ascii_case(b"FIELDS") => {
+                let condition = match arg.to_ascii_lowercase().as_slice() {
+                    b"nx" => ExpireCondition::Nx,
+                    b"xx" => ExpireCondition::Xx,
+                    b"gt" => ExpireCondition::Gt,
+                    b"lt" => ExpireCondition::Lt,
+                    _ => {
+                        return Err(RedisError::Other(format!(
+                            "Unsupported option {}",
+                            String::from_utf8_lossy(arg)
+                        )))
+                    }
+                };
+                args.next();
+                Some(condition)
+            }
+            _ => None,
+        };
+        let fields = parse_fields(&mut args)?;
+
+        Ok(HExpire {
+            key,
+            expiration,
+            condition,
+            fields,
+            command,
+        })
+    }
+}
+
+/// Gets values of hash fields and optionally changes their deadlines.
+#[derive(Eq, PartialEq, Debug)]
+pub struct HGetEx {
+    pub key: Bytes,
+    /// New deadline of the fields.
+    pub expiration: Option<Expiration>,
+    /// Remove the deadlines of the fields.
+    pub persist: bool,
+    pub fields: Vec<Bytes>,
+}
+
+impl TryFrom<Args> for HGetEx {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        // Key, FIELDS, number of fields and a field.
+        require_args(&args, 4)?;
+        let key = args.required()?;
+        let mut expiration = None;
+        let mut persist = false;
+        if let Some(arg) = args.as_slice().first() {
+            let option = arg.to_ascii_lowercase();
+            match option.as_slice() {
+                b"persist" => {
+                    args.next();
+                    persist = true;
+                }
+                b"ex" | b"px" | b"exat" | b"pxat" => {
+                    args.next();
+                    let time = args.next().ok_or(RedisError::Syntax)?;
+                    let parsed = Expiration::parse(&option, &time, "hgetex")?;
+                    let (Expiration::At(time) | Expiration::After(time)) = parsed;
+                    if time > MAX_FIELD_DEADLINE {
+                        return Err(RedisError::InvalidExpireTime("hgetex".to_owned()));
+                    }
+                    expiration = Some(parsed);
+                }
+                _ => {}
+            }
+        }
+        let fields = parse_fields(&mut args)?;
+
+        Ok(HGetEx {
+            key,
+            expiration,
+            persist,
+            fields,
+        })
+    }
+}
+
+#[cfg(test)]
+mod try_from_args {
+    use super::*;
+    use crate::protocol::args::args;
+
+    #[test]
+    fn hexpire() {
+        assert_eq!(
+            HExpire::try_from(args(&["hexpire", "h", "10", "GT", "fields", "2", "a", "b"])),
+            Ok(HExpire {
+                key: Bytes::from("h"),
+                expiration: Expiration::After(10_000),
+                condition: Some(ExpireCondition::Gt),
+                fields: vec![Bytes::from("a"), Bytes::from("b")],
+                command: "hexpire".to_owned(),
+            })
+        );
+        assert_eq!(
+            HExpire::try_from(args(&["hpexpireat", "h", "0", "FIELDS", "1", "a"])),
+            Ok(HExpire {
+                key: Bytes::from("h"),
+                expiration: Expiration::At(0),
+                condition: None,
+                fields: vec![Bytes::from("a")],
+                command: "hpexpireat".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn hexpire_errors() {
+        assert_eq!(
+            HExpire::try_from(args(&["hexpire", "h", "1", "FIELDS", "1"])),
+            Err(RedisError::WrongArity("hexpire".to_owned()))
+        );
+        assert_eq!(
+            HExpire::try_from(args(&["hexpire", "h", "-1", "FIELDS", "1", "a"])),
+            Err(RedisError::Other(
+                "invalid expire time, must be >= 0".to_owned()
+            ))
+        );
+        assert_eq!(
+            HExpire::try_from(args(&[
+                "hexpire",
+                "h",
+                "281474976710656",
+                "FIELDS",
+                "1",
+                "a"
+            ])),
+            Err(RedisError::InvalidExpireTime("hexpire".to_owned()))
+        );
+        assert_eq!(
+            HExpire::try_from(args(&["hexpire", "h", "1", "ZZ", "FIELDS", "1", "a"])),
+            Err(RedisError::Other("Unsupported option ZZ".to_owned()))
+        );
+        assert_eq!(
+            HExpire::try_from(args(&["hexpire", "h", "1", "NX", "XX", "FIELDS", "1", "a"])),
+            Err(RedisError::Other(
+                "Mandatory argument FIELDS is missing or not at the right position".to_owned()
+            ))
+        );
+        assert_eq!(
+            HExpire::try_from(args(&["hexpire", "h", "1", "FIELDS", "0", "a"])),
+            Err(RedisError::Other(
+                "Parameter `numFields` should be greater than 0".to_owned()
+            ))
+        );
+        assert_eq!(
+            HExpire::try_from(args(&["hexpire", "h", "1", "FIELDS", "2", "a"])),
+            Err(RedisError::Other(
+                "The `numfields` parameter must match the number of arguments".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn hgetex() {
+        assert_eq!(
+            HGetEx::try_from(args(&["hgetex", "h", "PX", "100", "FIELDS", "1", "a"])),
+            Ok(HGetEx {
+                key: Bytes::from("h"),
+                expiration: Some(Expiration::After(100)),
+                persist: false,
+                fields: vec![Bytes::from("a")],
+            })
+        );
+        assert_eq!(
+            HGetEx::try_from(args(&["hgetex", "h", "persist", "FIELDS", "1", "a"])),
+            Ok(HGetEx {
+                key: Bytes::from("h"),
+                expiration: None,
+                persist: true,
+                fields: vec![Bytes::from("a")],
+            })
+        );
+        assert_eq!(
+            HGetEx::try_from(args(&["hgetex", "h", "EX", "0", "FIELDS", "1", "a"])),
+            Err(RedisError::InvalidExpireTime("hgetex".to_owned()))
+        );
+        assert_eq!(
+            HGetEx::try_from(args(&[
+                "hgetex", "h", "EX", "1", "PERSIST", "FIELDS", "1", "a"
+            ])),
+            Err(RedisError::Other(
+                "Mandatory argument FIELDS is missing or not at the right position".to_owned()
+            ))
+        );
+    }
+}
@@ -0,0 +1,106 @@
+//! Lcs request.
+use crate::{
+    error::RedisError,
+    protocol::args::{parse_i64, Args},
+};
+use bytes::Bytes;
+
+/// Finds the longest common subsequence of the strings stored at two keys.
+#[derive(Eq, PartialEq, Debug, Default)]
+pub struct Lcs {
+    pub key1: Bytes,
+    pub key2: Bytes,
+    /// Reply with the length of the subsequence only.
+    pub len: bool,
+    /// Reply with the positions of the matching runs.
+    pub idx: bool,
+    /// Runs shorter than this are not reported.
+    pub min_match_len: usize,
+    /// Report the length of each run.
+    pub with_match_len: bool,
+}
+
+impl TryFrom<Args> for Lcs {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let mut request = Lcs {
+            key1: args.required()?,
+            key2: args.required()?,
+            ..Default::default()
+        };
+        while let Some(arg) = args.next() {
+            match arg.to_ascii_lowercase().as_slice() {
+                b"len" => request.len = true,
+                b"idx" => request.idx = true,
+                b"withmatchlen" => request.with_match_len = true,
+                b"minmatchlen" => {
+                    let length = parse_i64(&args.next().ok_or(RedisError::Syntax)?)?;
+                    request.min_match_len = length.max(0) as usize;
+                }
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+        if request.len && request.idx {
+            return Err(RedisError::Other(
+                "If you want both the length and indexes, please just use IDX.".to_owned(),
+            ));
+        }
+
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod try_from_args {
+    use super::*;
+    use crate::protocol::args::args;
+
+    #[test]
+    fn options() {
+        assert_eq!(
+            Lcs::try_from(args(&[
+                "lcs",
+                "a",
+                "b",
+                "IDX",
+                "minmatchlen",
+                "4",
+                "WITHMATCHLEN"
+            ]))
+            .unwrap(),
+            Lcs {
+                key1: Bytes::from("a"),
+                key2: Bytes::from("b"),
+                len: false,
+                idx: true,
+                min_match_len: 4,
+                with_match_len: true,
+            }
+        );
+        assert_eq!(
+            Lcs::try_from(args(&["lcs", "a", "b", "minmatchlen", "-1"]))
+                .unwrap()
+                .min_match_len,
+            0
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Lcs::try_from(args(&["lcs", "a", "b", "len", "idx"])),
+            Err(RedisError::Other(
+                "If you want both the length and indexes, please just use IDX.".to_owned()
+            ))
+        );
+        assert_eq!(
+            Lcs::try_from(args(&["lcs", "a", "b", "minmatchlen"])),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            Lcs::try_from(args(&["lcs", "a", "b", "all"])),
+            Err(RedisError::Syntax)
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! List requests.
+use crate::{
+    error::RedisError,
+    protocol::{
+        args::{parse_i64, Args},
+        Timeout,
+    },
+};
+use bytes::Bytes;
+
+/// End of a list.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    /// Parses LEFT or RIGHT.
+    pub(crate) fn parse(arg: &[u8]) -> Result<Self, RedisError> {
+        if arg.eq_ignore_ascii_case(b"LEFT") {
+            Ok(Side::Left)
+        } else if arg.eq_ignore_ascii_case(b"RIGHT") {
+            Ok(Side::Right)
+        } else {
+            Err(RedisError::Syntax)
+        }
+    }
+}
+
+/// Parses a count of elements, which must not be negative.
+pub(crate) fn parse_count(arg: &[u8]) -> Result<usize, RedisError> {
+    usize::try_from(parse_i64(arg)?).map_err(|_| RedisError::NotPositive)
+}
+
+/// Finds positions of an element in a list.
+#[derive(Eq, PartialEq, Debug)]
+pub struct LPos {
+    pub key: Bytes,
+    pub element: Bytes,
+    /// Skip the first `rank - 1` matches. A negative rank searches from the tail.
+    pub rank: i64,
+    /// Reply with up to this many positions, all of them if 0. Without it the reply is a
+    /// single position.
+    pub count: Option<usize>,
+    /// Compare at most this many elements, all of them if 0.
+    pub max_len: usize,
+}
+
+impl TryFrom<Args> for LPos {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let mut request = LPos {
+            key: args.required()?,
+            element: args.required()?,
+            rank: 1,
+            count: None,
+            max_len: 0,
+        };
+        while let Some(arg) = args.next() {
+            let option = arg.to_ascii_lowercase();
+            let value = parse_i64(&args.next().ok_or(RedisError::Syntax)?)?;
+            match option.as_slice() {
+                b"rank" if value == 0 => {
+                    return Err(RedisError::Other(
+                        "RANK can't be zero: use 1 to start from the first match, 2 from the \
+                         second ... or use negative to start from the end of the list"
+                            .to_owned(),
+                    ))
+                }
+                b"rank" => request.rank = value,
+                b"count" => {
+                    request.count = Some(
+                        usize::try_from(value)
+                            .map_err(|_| RedisError::Other("COUNT can't be negative".to_owned()))?,
+                    )
+                }
+                b"maxlen" => {
+                    request.max_len = usize::try_from(value)
+                        .map_err(|_| RedisError::Other("MAXLEN can't be negative".to_owned()))?
+                }
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+/// Pops elements from the first non-empty list of several. Serves LMPOP and BLMPOP, the latter
+/// blocks until a list is not empty.
+#[derive(Eq, PartialEq, Debug)]
+pub struct MPop {
+    pub keys: Vec<Bytes>,
+    pub side: Side,
+    pub count: usize,
+    pub timeout: Option<Timeout>,
+}
+
+impl TryFrom<Args> for MPop {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let blocking = args.command() == "blmpop";
+        // Number of keys, the keys themselves and the side.
+        let min_args = if blocking { 4 } else { 3 };
+        if args.as_slice().len() < min_args {
+            return Err(RedisError::WrongArity(args.command().to_owned()));
+        }
+        let timeout = match blocking {
+            true => Some(Timeout::parse_seconds(&args.required()?)?),
+            false => None,
+        };
+        let num_keys = parse_positive(&args.required()?, "numkeys should be greater than 0")?;
+        if num_keys >= args.as_slice().len() {
+            return Err(RedisError::Syntax);
+        }
+        let keys = (0..num_keys).filter_map(|_| args.next()).collect();
+        let side = Side::parse(&args.required()?)?;
+        let mut count = None;
+        while let Some(arg) = args.next() {
+            match (arg.to_ascii_lowercase().as_slice(), count) {
+                (b"count", None) => {
+                    let value = args.next().ok_or(RedisError::Syntax)?;
+                    count = Some(parse_positive(&value, "count should be greater than 0")?);
+                }
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+
+        Ok(MPop {
+            keys,
+            side,
+            count: count.unwrap_or(1),
+            timeout,
+        })
+    }
+}
+
+/// Parses a number which must be greater than 0, failing with the message otherwise.
+fn parse_positive(arg: &[u8], message: &str) -> Result<usize, RedisError> {
+    parse_i64(arg)
+        .ok()
+        .filter(|value| *value > 0)
+        .and_then(|value| usize::try_from(value).ok())
+        .ok_or_else(|| RedisError::Other(message.to_owned()))
+}
+
+#[cfg(test)]
+mod try_from_args {
+    use super::*;
+    use crate::protocol::args::args;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn options() {
+        assert_eq!(
+            LPos::try_from(args(&[
+                "lpos", "l", "a", "RANK", "-2", "count", "0", "MAXLEN", "5"
+            ]))
+            .unwrap(),
+            LPos {
+                key: Bytes::from("l"),
+                element: Bytes::from("a"),
+                rank: -2,
+                count: Some(0),
+                max_len: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_matches!(
+            LPos::try_from(args(&["lpos", "l", "a", "rank", "0"])),
+            Err(RedisError::Other(message)) if message.starts_with("RANK can't be zero")
+        );
+        assert_eq!(
+            LPos::try_from(args(&["lpos", "l", "a", "count", "-1"])),
+            Err(RedisError::Other("COUNT can't be negative".to_owned()))
+        );
+        assert_eq!(
+            LPos::try_from(args(&["lpos", "l", "a", "maxlen", "-1"])),
+            Err(RedisError::Other("MAXLEN can't be negative".to_owned()))
+        );
+        assert_eq!(
+            LPos::try_from(args(&["lpos", "l", "a", "count"])),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            LPos::try_from(args(&["lpos", "l", "a", "first", "1"])),
+            Err(RedisError::Syntax)
+        );
+    }
+
+    #[test]
+    fn mpop() {
+        assert_eq!(
+            MPop::try_from(args(&["lmpop", "2", "a", "b", "right", "COUNT", "3"])).unwrap(),
+            MPop {
+                keys: vec![Bytes::from("a"), Bytes::from("b")],
+                side: Side::Right,
+                count: 3,
+                timeout: None,
+            }
+        );
+        assert_eq!(
+            MPop::try_from(args(&["blmpop", "0.5", "1", "a", "LEFT"])).unwrap(),
+            MPop {
+                keys: vec![Bytes::from("a")],
+                side: Side::Left,
+                count: 1,
+                timeout: Some(Timeout::After(std::time::Duration::from_millis(500))),
+            }
+        );
+        assert_eq!(
+            MPop::try_from(args(&["lmpop", "1", "a"])),
+            Err(RedisError::WrongArity("lmpop".to_owned()))
+        );
+        assert_eq!(
+            MPop::try_from(args(&["lmpop", "0", "a", "left"])),
+            Err(RedisError::Other(
+                "numkeys should be greater than 0".to_owned()
+            ))
+        );
+        assert_eq!(
+            MPop::try_from(args(&["lmpop", "2", "a", "left"])),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            MPop::try_from(args(&["lmpop", "1", "a", "left", "count", "0"])),
+            Err(RedisError::Other(
+                "count should be greater than 0".to_owned()
+            ))
+        );
+        assert_eq!(
+            MPop::try_from(args(&[
+                "lmpop", "1", "a", "left", "count", "1", "count", "2"
+            ])),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            MPop::try_from(args(&["lmpop", "1", "a", "up"])),
+            Err(RedisError::Syntax)
+        );
+    }
+
+    #[test]
+    fn sides_and_counts() {
+        assert_eq!(Side::parse(b"left"), Ok(Side::Left));
+        assert_eq!(Side::parse(b"RIGHT"), Ok(Side::Right));
+        assert_eq!(Side::parse(b"up"), Err(RedisError::Syntax));
+        assert_eq!(parse_count(b"3"), Ok(3));
+        assert_eq!(parse_count(b"-3"), Err(RedisError::NotPositive));
+    }
+}
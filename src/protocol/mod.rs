@@ -1,7 +1,46 @@
 //! This module contains the protocol implementation for the Redis protocol.
+mod args;
+mod bitmap;
+mod blocking;
+mod copy;
+mod expire;
+mod geo;
+mod getex;
+mod hello;
+mod hexpire;
+mod lcs;
+mod list;
 mod request;
 mod response;
+mod scan;
 mod set;
-pub(crate) use request::Request;
-pub(crate) use response::Response;
-pub(crate) use set::Set;
+mod set_ops;
+mod sorted_set;
+mod stream;
+mod stream_group;
+pub(crate) use args::{parse_f64, parse_i64};
+pub(crate) use bitmap::{
+    BitCount, BitField, BitFieldAction, BitFieldOperation, BitFieldType, BitOp, BitOperation,
+    BitPos, BitRange, BitUnit, Overflow,
+};
+pub(crate) use blocking::Timeout;
+pub(crate) use copy::Copy;
+pub(crate) use expire::{Expiration, Expire};
+pub(crate) use geo::{GeoAdd, GeoOrigin, GeoSearch, Unit};
+pub(crate) use getex::GetEx;
+pub(crate) use hello::Hello;
+pub(crate) use hexpire::{HExpire, HGetEx, MAX_FIELD_DEADLINE};
+pub(crate) use lcs::Lcs;
+pub(crate) use list::{LPos, MPop, Side};
+pub(crate) use request::{split_args, Request};
+pub(crate) use response::{format_stored_double, ProtocolVersion, Response};
+pub(crate) use scan::Scan;
+pub(crate) use set::{Set, SetCondition};
+pub(crate) use set_ops::{SInterCard, SetOperation};
+pub(crate) use sorted_set::{LexBound, ScoreBound, ZAdd, ZRange, ZRangeBy};
+pub(crate) use stream::{
+    ReadAfter, ReadGroup, StreamId, Trim, TrimStrategy, XAdd, XAddId, XRange, XRead, XTrim,
+};
+pub(crate) use stream_group::{
+    ClaimTime, PendingRange, XAutoClaim, XClaim, XGroup, XPending, AUTOCLAIM_ATTEMPTS_FACTOR,
+};
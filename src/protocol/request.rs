@@ -1,21 +1,412 @@
 //! Contains the Redis request types and their serialization and deserialization.
 use crate::error::RedisError;
-use crate::protocol::Set;
+use crate::protocol::{
+    args::{parse_f64, parse_i64, Args},
+    bitmap::parse_bit_offset,
+    hexpire::{parse_fields, require_args},
+    list::parse_count,
+    BitCount, BitField, BitOp, BitPos, Copy, Expire, GeoAdd, GeoSearch, GetEx, HExpire, HGetEx,
+    Hello, LPos, Lcs, LexBound, MPop, SInterCard, Scan, ScoreBound, Set, SetOperation, Side,
+    StreamId, Timeout, Unit, XAdd, XAutoClaim, XClaim, XGroup, XPending, XRange, XRead, XTrim,
+    ZAdd, ZRange, ZRangeBy,
+};
+use bytes::{Bytes, BytesMut};
 use core::fmt;
+use std::ops::Range;
+
+/// Maximum number of elements in a request array.
+const MAX_MULTIBULK_LENGTH: i64 = 1024 * 1024;
+/// Maximum length of a single bulk string in a request.
+const MAX_BULK_LENGTH: i64 = 512 * 1024 * 1024;
+/// Maximum length of an inline request.
+const MAX_INLINE_LENGTH: usize = 64 * 1024;
 
 /// Contains Redis requests. All requests are arrays.
-#[derive(Eq, PartialEq, Debug)]
+#[derive(PartialEq, Debug)]
 pub enum Request {
-    Ping,
-    Echo(String),
+    /// Replies with the message if there is one, PONG otherwise.
+    Ping(Option<Bytes>),
+    Echo(Bytes),
     Set(Set),
-    Get(String),
+    Get(Bytes),
+    /// Sets the keys to the values at once.
+    MSet(Vec<(Bytes, Bytes)>),
+    /// Sets the keys to the values at once only if none of the keys exist.
+    MSetNx(Vec<(Bytes, Bytes)>),
+    MGet(Vec<Bytes>),
+    /// Removes the keys.
+    Del(Vec<Bytes>),
+    /// Removes the keys. Large values are freed in the background.
+    Unlink(Vec<Bytes>),
+    /// Counts existing keys. A key repeated in the list is counted multiple times.
+    Exists(Vec<Bytes>),
+    Type(Bytes),
+    Rename {
+        key: Bytes,
+        new_key: Bytes,
+    },
+    /// Renames the key only if the new key doesn't exist.
+    RenameNx {
+        key: Bytes,
+        new_key: Bytes,
+    },
+    Copy(Copy),
+    /// Counts existing keys and reclaims expired ones.
+    Touch(Vec<Bytes>),
+    Expire(Expire),
+    /// Remaining time to live of the key in seconds.
+    Ttl(Bytes),
+    /// Remaining time to live of the key in milliseconds.
+    Pttl(Bytes),
+    /// Deadline of the key as unix time in seconds.
+    ExpireTime(Bytes),
+    /// Deadline of the key as unix time in milliseconds.
+    PexpireTime(Bytes),
+    /// Removes the deadline of the key.
+    Persist(Bytes),
+    /// Adds the increment to the integer stored at the key. Serves INCR, DECR, INCRBY and DECRBY.
+    IncrBy {
+        key: Bytes,
+        increment: i64,
+    },
+    IncrByFloat {
+        key: Bytes,
+        increment: f64,
+    },
+    Append {
+        key: Bytes,
+        value: Bytes,
+    },
+    Strlen(Bytes),
+    /// Returns a substring. Negative indexes count from the end of the string.
+    GetRange {
+        key: Bytes,
+        start: i64,
+        end: i64,
+    },
+    /// Overwrites part of the string starting at the offset, padding the string with zero bytes
+    /// if it is shorter than the offset.
+    SetRange {
+        key: Bytes,
+        offset: usize,
+        value: Bytes,
+    },
+    GetDel(Bytes),
+    GetEx(GetEx),
+    Lcs(Lcs),
+    /// Sets the bit at the offset, growing the string with zero bytes if needed. Replies with
+    /// the previous bit.
+    SetBit {
+        key: Bytes,
+        offset: usize,
+        bit: bool,
+    },
+    GetBit {
+        key: Bytes,
+        offset: usize,
+    },
+    BitCount(BitCount),
+    BitPos(BitPos),
+    BitOp(BitOp),
+    BitField(BitField),
+    /// Pushes the elements one by one to a side of the list. Serves LPUSH, RPUSH, LPUSHX and
+    /// RPUSHX, the latter two push only if the list exists.
+    Push {
+        key: Bytes,
+        elements: Vec<Bytes>,
+        side: Side,
+        only_existing: bool,
+    },
+    /// Removes elements from a side of the list. Without a count a single element is removed and
+    /// the reply is the element rather than an array.
+    Pop {
+        key: Bytes,
+        side: Side,
+        count: Option<usize>,
+    },
+    LLen(Bytes),
+    /// Returns elements between the inclusive indexes. Negative indexes count from the tail.
+    LRange {
+        key: Bytes,
+        start: i64,
+        end: i64,
+    },
+    LIndex {
+        key: Bytes,
+        index: i64,
+    },
+    LSet {
+        key: Bytes,
+        index: i64,
+        element: Bytes,
+    },
+    /// Inserts the element before or after the first occurrence of the pivot.
+    LInsert {
+        key: Bytes,
+        before: bool,
+        pivot: Bytes,
+        element: Bytes,
+    },
+    /// Removes occurrences of the element: the first `count` ones, the last `-count` ones for a
+    /// negative count or all of them for 0.
+    LRem {
+        key: Bytes,
+        count: i64,
+        element: Bytes,
+    },
+    /// Keeps only elements between the inclusive indexes.
+    LTrim {
+        key: Bytes,
+        start: i64,
+        end: i64,
+    },
+    LPos(LPos),
+    /// Pops an element from a side of the source list and pushes it to a side of the destination
+    /// list. Serves LMOVE and RPOPLPUSH, and with a timeout BLMOVE and BRPOPLPUSH, which block
+    /// until the source list is not empty.
+    LMove {
+        source: Bytes,
+        destination: Bytes,
+        from: Side,
+        to: Side,
+        timeout: Option<Timeout>,
+    },
+    /// Pops an element from a side of the first non-empty list, blocking until there is one.
+    /// Serves BLPOP and BRPOP.
+    BPop {
+        keys: Vec<Bytes>,
+        side: Side,
+        timeout: Timeout,
+    },
+    MPop(MPop),
+    /// Sets fields of the hash. Serves HSET and HMSET, which differ in the reply only.
+    HSet {
+        key: Bytes,
+        pairs: Vec<(Bytes, Bytes)>,
+        reply_ok: bool,
+    },
+    HSetNx {
+        key: Bytes,
+        field: Bytes,
+        value: Bytes,
+    },
+    HGet {
+        key: Bytes,
+        field: Bytes,
+    },
+    HMGet {
+        key: Bytes,
+        fields: Vec<Bytes>,
+    },
+    HDel {
+        key: Bytes,
+        fields: Vec<Bytes>,
+    },
+    HGetAll(Bytes),
+    HKeys(Bytes),
+    HVals(Bytes),
+    HLen(Bytes),
+    HExists {
+        key: Bytes,
+        field: Bytes,
+    },
+    HStrlen {
+        key: Bytes,
+        field: Bytes,
+    },
+    HIncrBy {
+        key: Bytes,
+        field: Bytes,
+        increment: i64,
+    },
+    HIncrByFloat {
+        key: Bytes,
+        field: Bytes,
+        increment: f64,
+    },
+    /// Returns random fields. Without a count a single field is returned rather than an array.
+    /// A negative count allows the same field to be returned several times.
+    HRandField {
+        key: Bytes,
+        count: Option<i64>,
+        with_values: bool,
+    },
+    HScan(Scan),
+    HExpire(HExpire),
+    /// Returns deadlines of hash fields. Serves HTTL, HPTTL, HEXPIRETIME and HPEXPIRETIME, which
+    /// differ in the unit and whether the deadline is relative to now.
+    HTtl {
+        key: Bytes,
+        fields: Vec<Bytes>,
+        milliseconds: bool,
+        absolute: bool,
+    },
+    HPersist {
+        key: Bytes,
+        fields: Vec<Bytes>,
+    },
+    HGetEx(HGetEx),
+    SAdd {
+        key: Bytes,
+        members: Vec<Bytes>,
+    },
+    SRem {
+        key: Bytes,
+        members: Vec<Bytes>,
+    },
+    SMembers(Bytes),
+    /// Checks whether the members belong to the set. Serves SISMEMBER and SMISMEMBER, which
+    /// replies with an array.
+    SIsMember {
+        key: Bytes,
+        members: Vec<Bytes>,
+        multiple: bool,
+    },
+    SCard(Bytes),
+    /// Removes random members. Without a count the reply is a single member.
+    SPop {
+        key: Bytes,
+        count: Option<usize>,
+    },
+    /// Returns random members. A negative count allows repeated members. Without a count the
+    /// reply is a single member.
+    SRandMember {
+        key: Bytes,
+        count: Option<i64>,
+    },
+    SMove {
+        source: Bytes,
+        destination: Bytes,
+        member: Bytes,
+    },
+    /// Combines sets. Serves SINTER, SUNION and SDIFF.
+    SetAlgebra {
+        operation: SetOperation,
+        keys: Vec<Bytes>,
+    },
+    /// Combines sets and stores the result. Serves SINTERSTORE, SUNIONSTORE and SDIFFSTORE.
+    SetAlgebraStore {
+        operation: SetOperation,
+        destination: Bytes,
+        keys: Vec<Bytes>,
+    },
+    SInterCard(SInterCard),
+    /// Adds members to a sorted set or updates their scores. Serves ZADD and ZINCRBY.
+    ZAdd(ZAdd),
+    ZRange(ZRange),
+    /// Returns the rank of the member, counting from the highest score if `reverse` is set.
+    /// Serves ZRANK and ZREVRANK.
+    ZRank {
+        key: Bytes,
+        member: Bytes,
+        reverse: bool,
+        with_score: bool,
+    },
+    /// Returns scores of the members. Serves ZSCORE and ZMSCORE, which replies with an array.
+    ZScore {
+        key: Bytes,
+        members: Vec<Bytes>,
+        multiple: bool,
+    },
+    ZRem {
+        key: Bytes,
+        members: Vec<Bytes>,
+    },
+    ZCard(Bytes),
+    /// Counts members in a score or lexicographical range. Serves ZCOUNT and ZLEXCOUNT.
+    ZCount {
+        key: Bytes,
+        range: ZRangeBy,
+    },
+    /// Removes members in a range. Serves ZREMRANGEBYRANK, ZREMRANGEBYSCORE and
+    /// ZREMRANGEBYLEX.
+    ZRemRange {
+        key: Bytes,
+        range: ZRangeBy,
+    },
+    /// Removes members with the lowest scores, or the highest if `max` is set. Serves ZPOPMIN
+    /// and ZPOPMAX. Without a count the reply is a flat array of a single member and its score.
+    ZPop {
+        key: Bytes,
+        count: Option<usize>,
+        max: bool,
+    },
+    /// Returns random members. A negative count allows repeated members. Without a count the
+    /// reply is a single member.
+    ZRandMember {
+        key: Bytes,
+        count: Option<i64>,
+        with_scores: bool,
+    },
+    GeoAdd(GeoAdd),
+    /// Returns the distance between two members in the unit.
+    GeoDist {
+        key: Bytes,
+        first: Bytes,
+        second: Bytes,
+        unit: Unit,
+    },
+    GeoPos {
+        key: Bytes,
+        members: Vec<Bytes>,
+    },
+    GeoHash {
+        key: Bytes,
+        members: Vec<Bytes>,
+    },
+    GeoSearch(GeoSearch),
+    PfAdd {
+        key: Bytes,
+        elements: Vec<Bytes>,
+    },
+    /// Returns the estimated cardinality of the union of the HyperLogLogs.
+    PfCount(Vec<Bytes>),
+    PfMerge {
+        destination: Bytes,
+        keys: Vec<Bytes>,
+    },
+    XAdd(XAdd),
+    XTrim(XTrim),
+    XRange(XRange),
+    XLen(Bytes),
+    XDel {
+        key: Bytes,
+        ids: Vec<StreamId>,
+    },
+    /// Serves XREAD and XREADGROUP.
+    XRead(XRead),
+    XGroup(XGroup),
+    /// Acknowledges pending entries of the group.
+    XAck {
+        key: Bytes,
+        group: Bytes,
+        ids: Vec<StreamId>,
+    },
+    XPending(XPending),
+    XClaim(XClaim),
+    XAutoClaim(XAutoClaim),
+    XInfoStream(Bytes),
+    XInfoGroups(Bytes),
+    XInfoConsumers {
+        key: Bytes,
+        group: Bytes,
+    },
+    Hello(Hello),
+    /// Returns configuration parameters matching any of the glob patterns.
+    ConfigGet(Vec<Bytes>),
+    /// Sets configuration parameters: pairs of names and values.
+    ConfigSet(Vec<(Bytes, Bytes)>),
+    ConfigRewrite,
 }
 
 impl Request {
-    /// Converts a string to a request. Consumes the buffer up to the end of the first request.
-    pub fn deserialize(buffer: &mut String) -> Result<Self, RedisError> {
-        Request::try_from(Array::deserialize(buffer)?)
+    /// Parses the first request from the buffer and consumes its bytes.
+    /// Returns `Ok(None)` if the buffer doesn't contain a complete request yet.
+    pub fn deserialize(buffer: &mut BytesMut) -> Result<Option<Self>, RedisError> {
+        match Array::deserialize(buffer)? {
+            Some(array) => Request::try_from(array).map(Some),
+            None => Ok(None),
+        }
     }
 }
 
@@ -23,117 +414,818 @@ impl TryFrom<Array> for Request {
     type Error = RedisError;
 
     fn try_from(array: Array) -> Result<Self, RedisError> {
-        match array.args[0].to_lowercase().as_str() {
-            "ping" => Ok(Request::Ping),
-            "echo" => Ok(Request::Echo(array.args[1].clone())),
-            "set" => Ok(Request::Set(Set::try_from(array)?)),
-            "get" => Ok(Request::Get(array.args[1].clone())),
-            cmd => Err(RedisError::DeserializationError {
-                raw_redis_message: array.serialize(),
-                details: format!("Unknown command: {}", cmd),
+        let mut args = Args::new(array);
+        let request = match args.command() {
+            "ping" => Request::Ping(args.next()),
+            "echo" => Request::Echo(args.required()?),
+            "set" => return Ok(Request::Set(Set::try_from(args)?)),
+            "get" => Request::Get(args.required()?),
+            "mset" => Request::MSet(args.required_pairs()?),
+            "msetnx" => Request::MSetNx(args.required_pairs()?),
+            "mget" => Request::MGet(args.required_rest()?),
+            "del" => Request::Del(args.required_rest()?),
+            "unlink" => Request::Unlink(args.required_rest()?),
+            "exists" => Request::Exists(args.required_rest()?),
+            "type" => Request::Type(args.required()?),
+            "rename" => Request::Rename {
+                key: args.required()?,
+                new_key: args.required()?,
+            },
+            "renamenx" => Request::RenameNx {
+                key: args.required()?,
+                new_key: args.required()?,
+            },
+            "copy" => return Ok(Request::Copy(Copy::try_from(args)?)),
+            "touch" => Request::Touch(args.required_rest()?),
+            "expire" | "pexpire" | "expireat" | "pexpireat" => {
+                return Ok(Request::Expire(Expire::try_from(args)?))
+            }
+            "ttl" => Request::Ttl(args.required()?),
+            "pttl" => Request::Pttl(args.required()?),
+            "expiretime" => Request::ExpireTime(args.required()?),
+            "pexpiretime" => Request::PexpireTime(args.required()?),
+            "persist" => Request::Persist(args.required()?),
+            "incr" => Request::IncrBy {
+                key: args.required()?,
+                increment: 1,
+            },
+            "decr" => Request::IncrBy {
+                key: args.required()?,
+                increment: -1,
+            },
+            "incrby" => Request::IncrBy {
+                key: args.required()?,
+                increment: parse_i64(&args.required()?)?,
+            },
+            "decrby" => Request::IncrBy {
+                key: args.required()?,
+                increment: parse_i64(&args.required()?)?
+                    .checked_neg()
+                    .ok_or(RedisError::NotInteger)?,
+            },
+            "incrbyfloat" => Request::IncrByFloat {
+                key: args.required()?,
+                increment: parse_f64(&args.required()?)?,
+            },
+            "append" => Request::Append {
+                key: args.required()?,
+                value: args.required()?,
+            },
+            "strlen" => Request::Strlen(args.required()?),
+            "getrange" | "substr" => Request::GetRange {
+                key: args.required()?,
+                start: parse_i64(&args.required()?)?,
+                end: parse_i64(&args.required()?)?,
+            },
+            "setrange" => {
+                let key = args.required()?;
+                let offset = parse_i64(&args.required()?)?;
+                Request::SetRange {
+                    key,
+                    offset: usize::try_from(offset)
+                        .map_err(|_| RedisError::Other("offset is out of range".to_owned()))?,
+                    value: args.required()?,
+                }
+            }
+            "getdel" => Request::GetDel(args.required()?),
+            "getex" => return Ok(Request::GetEx(GetEx::try_from(args)?)),
+            // GETSET is a shorthand for SET with the GET option.
+            "getset" => Request::Set(Set {
+                key: args.required()?,
+                value: args.required()?,
+                get: true,
+                ..Default::default()
             }),
-        }
+            "lcs" => return Ok(Request::Lcs(Lcs::try_from(args)?)),
+            "setbit" => {
+                let key = args.required()?;
+                let offset = parse_bit_offset(&args.required()?, None)?;
+                let bit = match args.required()?.as_ref() {
+                    b"0" => false,
+                    b"1" => true,
+                    _ => {
+                        return Err(RedisError::Other(
+                            "bit is not an integer or out of range".to_owned(),
+                        ))
+                    }
+                };
+                Request::SetBit { key, offset, bit }
+            }
+            "getbit" => Request::GetBit {
+                key: args.required()?,
+                offset: parse_bit_offset(&args.required()?, None)?,
+            },
+            "bitcount" => return Ok(Request::BitCount(BitCount::try_from(args)?)),
+            "bitpos" => return Ok(Request::BitPos(BitPos::try_from(args)?)),
+            "bitop" => return Ok(Request::BitOp(BitOp::try_from(args)?)),
+            "bitfield" | "bitfield_ro" => return Ok(Request::BitField(BitField::try_from(args)?)),
+            "lpush" | "rpush" | "lpushx" | "rpushx" => {
+                let side = if args.command().starts_with('l') {
+                    Side::Left
+                } else {
+                    Side::Right
+                };
+                let only_existing = args.command().ends_with('x');
+                Request::Push {
+                    key: args.required()?,
+                    elements: args.required_rest()?,
+                    side,
+                    only_existing,
+                }
+            }
+            "lpop" | "rpop" => Request::Pop {
+                side: if args.command() == "lpop" {
+                    Side::Left
+                } else {
+                    Side::Right
+                },
+                key: args.required()?,
+                count: args.next().map(|count| parse_count(&count)).transpose()?,
+            },
+            "llen" => Request::LLen(args.required()?),
+            "lrange" => Request::LRange {
+                key: args.required()?,
+                start: parse_i64(&args.required()?)?,
+                end: parse_i64(&args.required()?)?,
+            },
+            "lindex" => Request::LIndex {
+                key: args.required()?,
+                index: parse_i64(&args.required()?)?,
+            },
+            "lset" => Request::LSet {
+                key: args.required()?,
+                index: parse_i64(&args.required()?)?,
+                element: args.required()?,
+            },
+            "linsert" => {
+                let key = args.required()?;
+                let position = args.required()?;
+                let pivot = args.required()?;
+                let element = args.required()?;
+                let before = if position.eq_ignore_ascii_case(b"BEFORE") {
+                    true
+                } else if position.eq_ignore_ascii_case(b"AFTER") {
+                    false
+                } else {
+                    return Err(RedisError::Syntax);
+                };
+                Request::LInsert {
+                    key,
+                    before,
+                    pivot,
+                    element,
+                }
+            }
+            "lrem" => Request::LRem {
+                key: args.required()?,
+                count: parse_i64(&args.required()?)?,
+                element: args.required()?,
+            },
+            "ltrim" => Request::LTrim {
+                key: args.required()?,
+                start: parse_i64(&args.required()?)?,
+                end: parse_i64(&args.required()?)?,
+            },
+            "lpos" => return Ok(Request::LPos(LPos::try_from(args)?)),
+            "lmove" | "blmove" => {
+                let source = args.required()?;
+                let destination = args.required()?;
+                let from = args.required()?;
+                let to = args.required()?;
+                let timeout = match args.command() {
+                    "blmove" => Some(args.required()?),
+                    _ => None,
+                };
+                Request::LMove {
+                    source,
+                    destination,
+                    from: Side::parse(&from)?,
+                    to: Side::parse(&to)?,
+                    timeout: timeout
+                        .map(|timeout| Timeout::parse_seconds(&timeout))
+                        .transpose()?,
+                }
+            }
+            "rpoplpush" | "brpoplpush" => {
+                let source = args.required()?;
+                let destination = args.required()?;
+                let timeout = match args.command() {
+                    "brpoplpush" => Some(Timeout::parse_seconds(&args.required()?)?),
+                    _ => None,
+                };
+                Request::LMove {
+                    source,
+                    destination,
+                    from: Side::Right,
+                    to: Side::Left,
+                    timeout,
+                }
+            }
+            "blpop" | "brpop" => {
+                let side = if args.command() == "blpop" {
+                    Side::Left
+                } else {
+                    Side::Right
+                };
+                let mut keys = args.required_rest()?;
+                let timeout = match keys.pop() {
+                    Some(timeout) if !keys.is_empty() => Timeout::parse_seconds(&timeout)?,
+                    _ => return Err(RedisError::WrongArity(args.command().to_owned())),
+                };
+                Request::BPop {
+                    keys,
+                    side,
+                    timeout,
+                }
+            }
+            "lmpop" | "blmpop" => return Ok(Request::MPop(MPop::try_from(args)?)),
+            "hset" | "hmset" => Request::HSet {
+                reply_ok: args.command() == "hmset",
+                key: args.required()?,
+                pairs: args.required_pairs()?,
+            },
+            "hsetnx" => Request::HSetNx {
+                key: args.required()?,
+                field: args.required()?,
+                value: args.required()?,
+            },
+            "hget" => Request::HGet {
+                key: args.required()?,
+                field: args.required()?,
+            },
+            "hmget" => Request::HMGet {
+                key: args.required()?,
+                fields: args.required_rest()?,
+            },
+            "hdel" => Request::HDel {
+                key: args.required()?,
+                fields: args.required_rest()?,
+            },
+            "hgetall" => Request::HGetAll(args.required()?),
+            "hkeys" => Request::HKeys(args.required()?),
+            "hvals" => Request::HVals(args.required()?),
+            "hlen" => Request::HLen(args.required()?),
+            "hexists" => Request::HExists {
+                key: args.required()?,
+                field: args.required()?,
+            },
+            "hstrlen" => Request::HStrlen {
+                key: args.required()?,
+                field: args.required()?,
+            },
+            "hincrby" => Request::HIncrBy {
+                key: args.required()?,
+                field: args.required()?,
+                increment: parse_i64(&args.required()?)?,
+            },
+            "hincrbyfloat" => Request::HIncrByFloat {
+                key: args.required()?,
+                field: args.required()?,
+                increment: parse_f64(&args.required()?)?,
+            },
+            "hrandfield" => {
+                let key = args.required()?;
+                let count = args.next().map(|count| parse_i64(&count)).transpose()?;
+                let with_values = match args.next() {
+                    Some(arg) if count.is_some() && arg.eq_ignore_ascii_case(b"WITHVALUES") => true,
+                    Some(_) => return Err(RedisError::Syntax),
+                    None => false,
+                };
+                Request::HRandField {
+                    key,
+                    count: check_random_count(count, with_values)?,
+                    with_values,
+                }
+            }
+            "hscan" => return Ok(Request::HScan(Scan::try_from(args)?)),
+            "hexpire" | "hpexpire" | "hexpireat" | "hpexpireat" => {
+                return Ok(Request::HExpire(HExpire::try_from(args)?))
+            }
+            "httl" | "hpttl" | "hexpiretime" | "hpexpiretime" => {
+                require_args(&args, 4)?;
+                let milliseconds = args.command().starts_with("hp");
+                let absolute = args.command().ends_with("time");
+                return Ok(Request::HTtl {
+                    key: args.required()?,
+                    fields: parse_fields(&mut args)?,
+                    milliseconds,
+                    absolute,
+                });
+            }
+            "hpersist" => {
+                require_args(&args, 4)?;
+                return Ok(Request::HPersist {
+                    key: args.required()?,
+                    fields: parse_fields(&mut args)?,
+                });
+            }
+            "hgetex" => return Ok(Request::HGetEx(HGetEx::try_from(args)?)),
+            "sadd" => Request::SAdd {
+                key: args.required()?,
+                members: args.required_rest()?,
+            },
+            "srem" => Request::SRem {
+                key: args.required()?,
+                members: args.required_rest()?,
+            },
+            "smembers" => Request::SMembers(args.required()?),
+            "sismember" => Request::SIsMember {
+                key: args.required()?,
+                members: vec![args.required()?],
+                multiple: false,
+            },
+            "smismember" => Request::SIsMember {
+                key: args.required()?,
+                members: args.required_rest()?,
+                multiple: true,
+            },
+            "scard" => Request::SCard(args.required()?),
+            "spop" => Request::SPop {
+                key: args.required()?,
+                count: args.next().map(|count| parse_count(&count)).transpose()?,
+            },
+            "srandmember" => Request::SRandMember {
+                key: args.required()?,
+                count: check_random_count(
+                    args.next().map(|count| parse_i64(&count)).transpose()?,
+                    false,
+                )?,
+            },
+            "smove" => Request::SMove {
+                source: args.required()?,
+                destination: args.required()?,
+                member: args.required()?,
+            },
+            "sinter" | "sunion" | "sdiff" => Request::SetAlgebra {
+                operation: SetOperation::of_command(args.command())
+                    .ok_or_else(|| args.unknown_command())?,
+                keys: args.required_rest()?,
+            },
+            "sinterstore" | "sunionstore" | "sdiffstore" => Request::SetAlgebraStore {
+                operation: SetOperation::of_command(args.command())
+                    .ok_or_else(|| args.unknown_command())?,
+                destination: args.required()?,
+                keys: args.required_rest()?,
+            },
+            "sintercard" => return Ok(Request::SInterCard(SInterCard::try_from(args)?)),
+            "zadd" => return Ok(Request::ZAdd(ZAdd::try_from(args)?)),
+            "zincrby" => Request::ZAdd(ZAdd {
+                key: args.required()?,
+                incr: true,
+                pairs: vec![(parse_f64(&args.required()?)?, args.required()?)],
+                ..Default::default()
+            }),
+            "zrange" | "zrevrange" | "zrangebyscore" | "zrevrangebyscore" | "zrangebylex"
+            | "zrevrangebylex" => return Ok(Request::ZRange(ZRange::try_from(args)?)),
+            "zrank" | "zrevrank" => {
+                let reverse = args.command() == "zrevrank";
+                let key = args.required()?;
+                let member = args.required()?;
+                let with_score = match args.next() {
+                    Some(arg) if arg.eq_ignore_ascii_case(b"WITHSCORE") => true,
+                    Some(_) => return Err(RedisError::Syntax),
+                    None => false,
+                };
+                Request::ZRank {
+                    key,
+                    member,
+                    reverse,
+                    with_score,
+                }
+            }
+            "zscore" => Request::ZScore {
+                key: args.required()?,
+                members: vec![args.required()?],
+                multiple: false,
+            },
+            "zmscore" => Request::ZScore {
+                key: args.required()?,
+                members: args.required_rest()?,
+                multiple: true,
+            },
+            "zrem" => Request::ZRem {
+                key: args.required()?,
+                members: args.required_rest()?,
+            },
+            "zcard" => Request::ZCard(args.required()?),
+            "zcount" => Request::ZCount {
+                key: args.required()?,
+                range: ZRangeBy::Score(
+                    ScoreBound::parse(&args.required()?)?,
+                    ScoreBound::parse(&args.required()?)?,
+                ),
+            },
+            "zlexcount" => Request::ZCount {
+                key: args.required()?,
+                range: ZRangeBy::Lex(
+                    LexBound::parse(args.required()?)?,
+                    LexBound::parse(args.required()?)?,
+                ),
+            },
+            "zremrangebyrank" => Request::ZRemRange {
+                key: args.required()?,
+                range: ZRangeBy::Rank(parse_i64(&args.required()?)?, parse_i64(&args.required()?)?),
+            },
+            "zremrangebyscore" => Request::ZRemRange {
+                key: args.required()?,
+                range: ZRangeBy::Score(
+                    ScoreBound::parse(&args.required()?)?,
+                    ScoreBound::parse(&args.required()?)?,
+                ),
+            },
+            "zremrangebylex" => Request::ZRemRange {
+                key: args.required()?,
+                range: ZRangeBy::Lex(
+                    LexBound::parse(args.required()?)?,
+                    LexBound::parse(args.required()?)?,
+                ),
+            },
+            "zpopmin" | "zpopmax" => Request::ZPop {
+                max: args.command() == "zpopmax",
+                key: args.required()?,
+                count: args.next().map(|count| parse_count(&count)).transpose()?,
+            },
+            "zrandmember" => {
+                let key = args.required()?;
+                let count = args.next().map(|count| parse_i64(&count)).transpose()?;
+                let with_scores = match args.next() {
+                    Some(arg) if arg.eq_ignore_ascii_case(b"WITHSCORES") => true,
+                    Some(_) => return Err(RedisError::Syntax),
+                    None => false,
+                };
+                Request::ZRandMember {
+                    key,
+                    count: check_random_count(count, with_scores)?,
+                    with_scores,
+                }
+            }
+            "geoadd" => return Ok(Request::GeoAdd(GeoAdd::try_from(args)?)),
+            "geodist" => {
+                let key = args.required()?;
+                let (first, second) = (args.required()?, args.required()?);
+                let unit = match args.next() {
+                    Some(unit) => Unit::parse(&unit)?,
+                    None => Unit::Meters,
+                };
+                if args.next().is_some() {
+                    return Err(RedisError::Syntax);
+                }
+                Request::GeoDist {
+                    key,
+                    first,
+                    second,
+                    unit,
+                }
+            }
+            "geopos" => Request::GeoPos {
+                key: args.required()?,
+                members: args.by_ref().collect(),
+            },
+            "geohash" => Request::GeoHash {
+                key: args.required()?,
+                members: args.by_ref().collect(),
+            },
+            "geosearch" | "geosearchstore" => {
+                return Ok(Request::GeoSearch(GeoSearch::try_from(args)?))
+            }
+            "pfadd" => Request::PfAdd {
+                key: args.required()?,
+                elements: args.by_ref().collect(),
+            },
+            "pfcount" => Request::PfCount(args.required_rest()?),
+            "pfmerge" => Request::PfMerge {
+                destination: args.required()?,
+                keys: args.by_ref().collect(),
+            },
+            "xadd" => return Ok(Request::XAdd(XAdd::try_from(args)?)),
+            "xtrim" => return Ok(Request::XTrim(XTrim::try_from(args)?)),
+            "xrange" | "xrevrange" => return Ok(Request::XRange(XRange::try_from(args)?)),
+            "xlen" => Request::XLen(args.required()?),
+            "xdel" => Request::XDel {
+                key: args.required()?,
+                ids: args
+                    .required_rest()?
+                    .iter()
+                    .map(|id| StreamId::parse(id, 0))
+                    .collect::<Result<_, _>>()?,
+            },
+            "xread" | "xreadgroup" => return Ok(Request::XRead(XRead::try_from(args)?)),
+            "xgroup" => return Ok(Request::XGroup(XGroup::try_from(args)?)),
+            "xack" => Request::XAck {
+                key: args.required()?,
+                group: args.required()?,
+                ids: args
+                    .required_rest()?
+                    .iter()
+                    .map(|id| StreamId::parse(id, 0))
+                    .collect::<Result<_, _>>()?,
+            },
+            "xpending" => return Ok(Request::XPending(XPending::try_from(args)?)),
+            "xclaim" => return Ok(Request::XClaim(XClaim::try_from(args)?)),
+            "xautoclaim" => return Ok(Request::XAutoClaim(XAutoClaim::try_from(args)?)),
+            "xinfo" => {
+                let subcommand = args.required()?;
+                match subcommand.to_ascii_lowercase().as_slice() {
+                    b"stream" => Request::XInfoStream(args.required()?),
+                    b"groups" => Request::XInfoGroups(args.required()?),
+                    b"consumers" => Request::XInfoConsumers {
+                        key: args.required()?,
+                        group: args.required()?,
+                    },
+                    _ => return Err(unknown_subcommand(&subcommand, "XINFO")),
+                }
+            }
+            "hello" => return Ok(Request::Hello(Hello::try_from(args)?)),
+            "config" => {
+                let subcommand = args.required()?;
+                match subcommand.to_ascii_lowercase().as_slice() {
+                    b"get" => {
+                        let patterns: Vec<Bytes> = args.by_ref().collect();
+                        if patterns.is_empty() {
+                            return Err(RedisError::WrongArity("config|get".to_owned()));
+                        }
+                        Request::ConfigGet(patterns)
+                    }
+                    b"set" => {
+                        let mut pairs = Vec::new();
+                        while let Some(name) = args.next() {
+                            let value = args
+                                .next()
+                                .ok_or_else(|| RedisError::WrongArity("config|set".to_owned()))?;
+                            pairs.push((name, value));
+                        }
+                        if pairs.is_empty() {
+                            return Err(RedisError::WrongArity("config|set".to_owned()));
+                        }
+                        Request::ConfigSet(pairs)
+                    }
+                    b"rewrite" => Request::ConfigRewrite,
+                    _ => return Err(unknown_subcommand(&subcommand, "CONFIG")),
+                }
+            }
+            _ => return Err(args.unknown_command()),
+        };
+        args.finish()?;
+        Ok(request)
     }
 }
 
+pub(super) fn unknown_subcommand(subcommand: &[u8], command: &str) -> RedisError {
+    RedisError::Other(format!(
+        "unknown subcommand '{}'. Try {} HELP.",
+        String::from_utf8_lossy(subcommand),
+        command
+    ))
+}
+
 #[derive(Eq, PartialEq, Debug)]
 pub(crate) struct Array {
-    pub(crate) args: Vec<String>,
+    pub(crate) args: Vec<Bytes>,
 }
 
 impl Array {
-    pub(crate) fn new(args: Vec<String>) -> Self {
+    pub(crate) fn new(args: Vec<Bytes>) -> Self {
         Array { args }
     }
 
-    pub(crate) fn serialize(self) -> String {
-        assert!(
-            self.args_count() > 0,
-            "Array size must be greater than 0 for serialization"
-        );
-        let mut result = std::format!("*{}\r\n", self.args_count());
-        self.args.into_iter().for_each(|arg| {
-            result.push_str(format!("${}\r\n{}\r\n", arg.chars().count(), arg).as_str());
-        });
-        result
-    }
-
     /// Returns number of array elements, including a command name.
     pub(crate) fn args_count(&self) -> usize {
         self.args.len()
     }
 
     /// Consumes the part of the buffer that contains the array and returns the array.
-    fn deserialize(buffer: &mut String) -> Result<Self, RedisError> {
-        let original_message = buffer.clone();
-
-        // Find the beginning of an array or clear the buffer and return an error
-        if let Some(asterisk_position) = buffer.find('*') {
-            buffer.drain(0..asterisk_position);
-        } else {
-            buffer.clear();
-            return Err(RedisError::DeserializationError {
-                raw_redis_message: original_message,
-                details: "Couldn't find asterisk in a buffer".to_owned(),
-            });
+    /// Besides RESP arrays, accepts inline commands: a line of arguments separated with spaces.
+    /// Returns `Ok(None)` and leaves the array bytes intact if the array is incomplete.
+    fn deserialize(buffer: &mut BytesMut) -> Result<Option<Self>, RedisError> {
+        loop {
+            if buffer.is_empty() {
+                return Ok(None);
+            }
+
+            if buffer[0] != b'*' {
+                match Self::deserialize_inline(buffer)? {
+                    // Empty lines carry no command, so they are skipped.
+                    Some(args) if args.is_empty() => continue,
+                    Some(args) => return Ok(Some(Array::new(args))),
+                    None => return Ok(None),
+                }
+            }
+
+            // The rest of the buffer can't be interpreted after a protocol error, so it is dropped.
+            let layout = match Self::parse(buffer) {
+                Ok(Some(layout)) => layout,
+                Ok(None) => return Ok(None),
+                Err(err) => {
+                    buffer.clear();
+                    return Err(err);
+                }
+            };
+            let frame = buffer.split_to(layout.length).freeze();
+            // Empty arrays are valid but carry no command, so they are skipped.
+            if layout.args.is_empty() {
+                continue;
+            }
+            let args = layout
+                .args
+                .into_iter()
+                .map(|range| frame.slice(range))
+                .collect();
+            return Ok(Some(Array::new(args)));
+        }
+    }
+
+    /// Consumes an inline command terminated by a newline and splits it into arguments.
+    /// Returns `Ok(None)` if the line is incomplete.
+    fn deserialize_inline(buffer: &mut BytesMut) -> Result<Option<Vec<Bytes>>, RedisError> {
+        let line_length = match buffer.iter().position(|&b| b == b'\n') {
+            Some(newline_position) => newline_position + 1,
+            None if buffer.len() > MAX_INLINE_LENGTH => {
+                buffer.clear();
+                return Err(protocol_error("too big inline request"));
+            }
+            None => return Ok(None),
+        };
+        let line = buffer.split_to(line_length);
+        let line = &line[..line_length - 1];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        match split_args(line) {
+            Some(args) => Ok(Some(args)),
+            None => {
+                buffer.clear();
+                Err(protocol_error("unbalanced quotes in request"))
+            }
         }
+    }
 
-        let mut lines = buffer.lines();
-
-        // Find out the number of arguments
-        let number_of_args = {
-            lines
-                .next()
-                .ok_or(RedisError::DeserializationError {
-                    raw_redis_message: original_message,
-                    details: "First line is absent".to_owned(),
-                })?
-                .chars()
-                .skip(1)
-                .take_while(|c| c.is_ascii_digit())
-                .collect::<String>()
-                .parse::<usize>()
-                .map_err(|err| RedisError::DeserializationError {
-                    raw_redis_message: buffer.clone(),
-                    details: err.to_string(),
-                })?
+    /// Parses an array at the beginning of the buffer without consuming it.
+    fn parse(buffer: &[u8]) -> Result<Option<ArrayLayout>, RedisError> {
+        let (header, mut position) = match read_header(buffer, 0, "too big mbulk count string")? {
+            Some(line) => line,
+            None => return Ok(None),
         };
+        let number_of_args = parse_length(&header[1..])?;
+        if number_of_args > MAX_MULTIBULK_LENGTH {
+            return Err(protocol_error("invalid multibulk length"));
+        }
 
-        let mut args = Vec::with_capacity(number_of_args);
+        let mut args = Vec::with_capacity(number_of_args.max(0) as usize);
+        for _ in 0..number_of_args {
+            let (header, data_start) =
+                match read_header(buffer, position, "too big bulk count string")? {
+                    Some(line) => line,
+                    None => return Ok(None),
+                };
+            if header.first() != Some(&b'$') {
+                return Err(protocol_error(&format!(
+                    "expected '$', got '{}'",
+                    header.first().map(|&b| b as char).unwrap_or(' ')
+                )));
+            }
+            let length = parse_length(&header[1..])?;
+            if !(0..=MAX_BULK_LENGTH).contains(&length) {
+                return Err(protocol_error("invalid bulk length"));
+            }
+            let data_end = data_start + length as usize;
+            if buffer.len() < data_end + 2 {
+                return Ok(None);
+            }
+            if &buffer[data_end..data_end + 2] != b"\r\n" {
+                return Err(protocol_error("expected CRLF after bulk string"));
+            }
+            args.push(data_start..data_end);
+            position = data_end + 2;
+        }
+        Ok(Some(ArrayLayout {
+            length: position,
+            args,
+        }))
+    }
+}
 
-        for _i in 0..number_of_args * 2 {
-            if let Some(line) = lines.next() {
-                if line.chars().nth(0) == Some('$') {
-                    // This is a length line
-                    continue;
+/// Splits a line into arguments separated with whitespace. Arguments can be quoted.
+/// Double quoted arguments support escape sequences `\n`, `\r`, `\t`, `\b`, `\a`, `\xHH` and
+/// escaped characters, single quoted ones support only `\'`. Returns `None` if quotes are
+/// unbalanced or a closing quote is not followed by whitespace.
+pub(crate) fn split_args(line: &[u8]) -> Option<Vec<Bytes>> {
+    let mut args = Vec::new();
+    let mut position = 0;
+    loop {
+        while line.get(position).is_some_and(u8::is_ascii_whitespace) {
+            position += 1;
+        }
+        if position == line.len() {
+            return Some(args);
+        }
+
+        let mut arg = Vec::new();
+        let mut quote = None;
+        loop {
+            let c = line.get(position).copied();
+            match (quote, c) {
+                (None, None) => break,
+                (None, Some(c)) if c.is_ascii_whitespace() => break,
+                (None, Some(q @ (b'"' | b'\''))) => quote = Some(q),
+                (None, Some(c)) => arg.push(c),
+                // Closing quote is missing.
+                (Some(_), None) => return None,
+                (Some(b'"'), Some(b'\\'))
+                    if line.len() > position + 3
+                        && line[position + 1] == b'x'
+                        && line[position + 2].is_ascii_hexdigit()
+                        && line[position + 3].is_ascii_hexdigit() =>
+                {
+                    let hex = std::str::from_utf8(&line[position + 2..position + 4]).ok()?;
+                    arg.push(u8::from_str_radix(hex, 16).ok()?);
+                    position += 3;
                 }
-                args.push(line.to_string());
-            } else {
-                return Err(RedisError::DeserializationError {
-                    raw_redis_message: buffer.clone(),
-                    details: format!("Not enough args for array. Message: '{}'", buffer),
-                });
+                (Some(b'"'), Some(b'\\')) if position + 1 < line.len() => {
+                    position += 1;
+                    arg.push(match line[position] {
+                        b'n' => b'\n',
+                        b'r' => b'\r',
+                        b't' => b'\t',
+                        b'b' => 0x08,
+                        b'a' => 0x07,
+                        c => c,
+                    });
+                }
+                (Some(b'\''), Some(b'\\')) if line.get(position + 1) == Some(&b'\'') => {
+                    position += 1;
+                    arg.push(b'\'');
+                }
+                (Some(q), Some(c)) if c == q => {
+                    // Closing quote must be followed by whitespace or the end of the line.
+                    if line
+                        .get(position + 1)
+                        .is_some_and(|next| !next.is_ascii_whitespace())
+                    {
+                        return None;
+                    }
+                    position += 1;
+                    break;
+                }
+                (Some(_), Some(c)) => arg.push(c),
             }
+            position += 1;
         }
+        args.push(Bytes::from(arg));
+    }
+}
+
+/// Positions of a parsed array within the buffer.
+struct ArrayLayout {
+    /// Length of the whole array in bytes.
+    length: usize,
+    /// Positions of the array elements.
+    args: Vec<Range<usize>>,
+}
 
-        let array = Array::new(args);
+/// Returns the line starting at `start` without CRLF and the position right after it.
+fn read_line(buffer: &[u8], start: usize) -> Option<(&[u8], usize)> {
+    buffer[start..]
+        .windows(2)
+        .position(|window| window == b"\r\n")
+        .map(|length| (&buffer[start..start + length], start + length + 2))
+}
 
-        // Collect reminder of buffer. It may contain more messages.
-        *buffer = lines
-            .map(|line| line.to_string() + "\r\n")
-            .collect::<String>();
+/// Reads the header line of an array or a bulk string starting at `start`. A header without
+/// CRLF is as limited as an inline request, so garbage can't grow the buffer without bound.
+fn read_header<'a>(
+    buffer: &'a [u8],
+    start: usize,
+    too_big: &str,
+) -> Result<Option<(&'a [u8], usize)>, RedisError> {
+    match read_line(buffer, start) {
+        Some(line) => Ok(Some(line)),
+        None if buffer.len() - start > MAX_INLINE_LENGTH => Err(protocol_error(too_big)),
+        None => Ok(None),
+    }
+}
 
-        if array.args_count() == number_of_args {
-            Ok(array)
-        } else {
-            Err(RedisError::DeserializationError {
-                raw_redis_message: buffer.clone(),
-                details: format!("Array is incomplete {}", array),
-            })
+/// Parses a length of an array or a bulk string.
+fn parse_length(digits: &[u8]) -> Result<i64, RedisError> {
+    std::str::from_utf8(digits)
+        .ok()
+        .and_then(|digits| digits.parse::<i64>().ok())
+        .ok_or_else(|| protocol_error("invalid length"))
+}
+
+/// Rejects a count of random picks whose reply length would not fit, as Redis does. Replies
+/// with values hold two items per pick.
+fn check_random_count(count: Option<i64>, with_values: bool) -> Result<Option<i64>, RedisError> {
+    let limit = if with_values { i64::MAX / 2 } else { i64::MAX };
+    match count {
+        Some(count) if !(-limit..=limit).contains(&count) => {
+            Err(RedisError::Other("value is out of range".to_owned()))
         }
+        _ => Ok(count),
     }
 }
 
+fn protocol_error(details: &str) -> RedisError {
+    RedisError::Protocol(details.to_owned())
+}
+
 impl fmt::Display for Array {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -155,20 +1247,18 @@ mod deserialize {
 
     #[test]
     fn empty_buffer() {
-        let mut buffer = "".to_string();
-        assert_matches!(
-            Request::deserialize(&mut buffer).unwrap_err(),
-            RedisError::DeserializationError { .. }
-        );
+        let mut buffer = BytesMut::new();
+        assert_eq!(Request::deserialize(&mut buffer).unwrap(), None);
         assert!(buffer.is_empty());
     }
 
     #[test]
     fn junk() {
-        let mut buffer = "junk".to_string();
-        assert_matches!(
+        // Anything that is not an array is an inline command.
+        let mut buffer = BytesMut::from("junk\r\n");
+        assert_eq!(
             Request::deserialize(&mut buffer).unwrap_err(),
-            RedisError::DeserializationError { .. }
+            RedisError::unknown_command(b"junk", &[])
         );
         assert!(buffer.is_empty());
     }
@@ -176,77 +1266,218 @@ mod deserialize {
     #[test]
     fn incomplete_request() {
         // If message is incomplete, the buffer must remain intact.
-        let mut buffer = "*1\r\n$4\r\n".to_string();
+        let mut buffer = BytesMut::from("*1\r\n$4\r\n");
         let old_buf_len = buffer.len();
+        assert_eq!(Request::deserialize(&mut buffer).unwrap(), None);
+        assert_eq!(buffer.len(), old_buf_len);
+    }
+
+    #[test]
+    fn incomplete_bulk_string() {
+        // The bulk string length says there are more bytes to come.
+        let mut buffer = BytesMut::from("*2\r\n$4\r\necho\r\n$13\r\nHello");
+        assert_eq!(Request::deserialize(&mut buffer).unwrap(), None);
+        buffer.extend_from_slice(b", world!\r\n");
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::Echo(Bytes::from(ECHO_ARG)))
+        );
+    }
+
+    #[test]
+    fn invalid_bulk_length() {
+        let mut buffer = BytesMut::from("*1\r\n$abc\r\nping\r\n");
         assert_matches!(
             Request::deserialize(&mut buffer).unwrap_err(),
-            RedisError::DeserializationError { .. }
+            RedisError::Protocol(_)
         );
-        assert_eq!(buffer.len(), old_buf_len);
+        assert!(buffer.is_empty());
+        let mut buffer = BytesMut::from("*1\r\n$-5\r\nping\r\n");
+        assert_matches!(
+            Request::deserialize(&mut buffer).unwrap_err(),
+            RedisError::Protocol(_)
+        );
+        assert!(buffer.is_empty());
     }
 
     #[test]
-    fn incomplete_request_with_prefix() {
-        // If message is incomplete, buffer must remain intact starting from the asterisk.
-        static PREFIX: &str = "\r\n$4s";
-        let mut buffer = format!("{}*1\r\n$4\r\n", PREFIX);
-        let old_buf_len = buffer.len();
+    fn bulk_string_is_not_terminated() {
+        // Bulk string is longer than its length header says.
+        let mut buffer = BytesMut::from("*1\r\n$2\r\nping\r\n");
         assert_matches!(
             Request::deserialize(&mut buffer).unwrap_err(),
-            RedisError::DeserializationError { .. }
+            RedisError::Protocol(_)
         );
-        assert_eq!(buffer.len(), old_buf_len - PREFIX.len());
-        assert_eq!(&buffer, "*1\r\n$4\r\n");
+    }
+
+    #[test]
+    fn empty_array_is_skipped() {
+        let mut buffer = BytesMut::from(format!("*0\r\n{}", PING_RAW).as_str());
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::Ping(None))
+        );
+        assert!(buffer.is_empty());
     }
 
     #[test]
     fn ping() {
-        let mut buffer = PING_RAW.to_string();
-        assert_eq!(Request::deserialize(&mut buffer).unwrap(), Request::Ping);
+        let mut buffer = BytesMut::from(PING_RAW);
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::Ping(None))
+        );
         assert!(buffer.is_empty());
     }
 
+    #[test]
+    fn ping_with_message() {
+        let mut buffer = BytesMut::from("*2\r\n$4\r\nPING\r\n$5\r\nhello\r\n");
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::Ping(Some(Bytes::from("hello"))))
+        );
+        let mut buffer = BytesMut::from("*3\r\n$4\r\nPING\r\n$1\r\na\r\n$1\r\nb\r\n");
+        assert_matches!(
+            Request::deserialize(&mut buffer).unwrap_err(),
+            RedisError::WrongArity(_)
+        );
+    }
+
     #[test]
     fn ping_various_case() {
-        let mut buffer = "*1\r\n$4\r\nPING\r\n".to_string();
-        assert_eq!(Request::deserialize(&mut buffer).unwrap(), Request::Ping);
-        let mut buffer = "*1\r\n$4\r\npiNG\r\n".to_string();
-        assert_eq!(Request::deserialize(&mut buffer).unwrap(), Request::Ping);
+        let mut buffer = BytesMut::from("*1\r\n$4\r\nPING\r\n");
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::Ping(None))
+        );
+        let mut buffer = BytesMut::from("*1\r\n$4\r\npiNG\r\n");
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::Ping(None))
+        );
     }
 
     #[test]
-    fn ping_with_prefix() {
-        // The deserialize function should skip unrelated characters
-        let mut buffer = format!("\r\n$4s{}", PING_RAW);
-        assert_eq!(Request::deserialize(&mut buffer).unwrap(), Request::Ping);
+    fn ping_and_then_echo() {
+        let mut buffer = BytesMut::from(format!("{}{}", PING_RAW, ECHO_RAW).as_str());
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::Ping(None))
+        );
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::Echo(Bytes::from(ECHO_ARG)))
+        );
         assert!(buffer.is_empty());
     }
 
     #[test]
-    fn ping_with_prefix_and_postfix() {
-        // The deserialize function should skip unrelated characters and leave the part
-        // of the message that wasn't parsed
-        let mut buffer = format!("$4\r\n{}$4\r\n", PING_RAW);
-        assert_eq!(Request::deserialize(&mut buffer).unwrap(), Request::Ping);
-        assert_eq!(buffer, "$4\r\n");
+    fn inline_ping() {
+        let mut buffer = BytesMut::from("PING\r\n");
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::Ping(None))
+        );
+        assert!(buffer.is_empty());
+        // Netcat may send lines terminated with a bare newline.
+        let mut buffer = BytesMut::from("ping\n");
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::Ping(None))
+        );
     }
 
     #[test]
-    fn ping_and_then_echo() {
-        let mut buffer = format!("\r\n$4s{}\r\n$4s{}", PING_RAW, ECHO_RAW);
-        assert_eq!(Request::deserialize(&mut buffer).unwrap(), Request::Ping);
+    fn inline_incomplete() {
+        let mut buffer = BytesMut::from("echo hel");
+        assert_eq!(Request::deserialize(&mut buffer).unwrap(), None);
+        buffer.extend_from_slice(b"lo\r\n");
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::Echo(Bytes::from("hello")))
+        );
+    }
+
+    #[test]
+    fn inline_empty_lines_are_skipped() {
+        let mut buffer = BytesMut::from("\r\n  \r\n\n*1\r\n$4\r\nping\r\n");
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::Ping(None))
+        );
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn inline_set_quoted() {
+        let mut buffer = BytesMut::from("set \"my key\" 'it\\'s'  \r\n");
         assert_eq!(
             Request::deserialize(&mut buffer).unwrap(),
-            Request::Echo("Hello, world!".to_string())
+            Some(Request::Set(Set {
+                key: Bytes::from("my key"),
+                value: Bytes::from("it's"),
+                ..Default::default()
+            }))
+        );
+    }
+
+    #[test]
+    fn inline_unbalanced_quotes() {
+        let mut buffer = BytesMut::from("echo \"hello\r\n");
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap_err(),
+            RedisError::Protocol("unbalanced quotes in request".to_string())
+        );
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn inline_too_big() {
+        let mut buffer = BytesMut::from("a".repeat(MAX_INLINE_LENGTH + 1).as_str());
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap_err(),
+            RedisError::Protocol("too big inline request".to_string())
+        );
+    }
+
+    #[test]
+    fn header_too_big() {
+        let mut buffer = BytesMut::from(format!("*{}", "1".repeat(MAX_INLINE_LENGTH - 1)).as_str());
+        assert_eq!(Request::deserialize(&mut buffer), Ok(None));
+        buffer.extend_from_slice(b"1");
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap_err(),
+            RedisError::Protocol("too big mbulk count string".to_string())
+        );
+        assert!(buffer.is_empty());
+
+        let header = format!("*1\r\n${}", "1".repeat(MAX_INLINE_LENGTH + 1));
+        let mut buffer = BytesMut::from(header.as_str());
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap_err(),
+            RedisError::Protocol("too big bulk count string".to_string())
         );
     }
 
     #[test]
     fn echo() {
-        let mut buffer = ECHO_RAW.to_string();
+        let mut buffer = BytesMut::from(ECHO_RAW);
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::Echo(Bytes::from(ECHO_ARG)))
+        );
+        assert!(buffer.is_empty());
+    }
+
+    /// Validates that bulk strings may contain CRLF and arbitrary bytes.
+    #[test]
+    fn echo_binary() {
+        let mut buffer = BytesMut::from("*2\r\n$4\r\necho\r\n$6\r\n");
+        buffer.extend_from_slice(b"a\r\n\xff\x00b\r\n");
         assert_eq!(
             Request::deserialize(&mut buffer).unwrap(),
-            Request::Echo(ECHO_ARG.to_string())
+            Some(Request::Echo(Bytes::from_static(b"a\r\n\xff\x00b")))
         );
         assert!(buffer.is_empty());
     }
@@ -254,14 +1485,14 @@ mod deserialize {
     #[test]
     fn set() {
         // set a b
-        let mut buffer: String = "*3\r\n$3\r\nset\r\n$1\r\na\r\n$1\r\nb\r\n".to_owned();
+        let mut buffer = BytesMut::from("*3\r\n$3\r\nset\r\n$1\r\na\r\n$1\r\nb\r\n");
         assert_eq!(
             Request::deserialize(&mut buffer).unwrap(),
-            Request::Set(Set {
-                key: "a".to_string(),
-                value: "b".to_string(),
-                expiration_timeout_ms: None
-            })
+            Some(Request::Set(Set {
+                key: Bytes::from("a"),
+                value: Bytes::from("b"),
+                ..Default::default()
+            }))
         );
         assert!(buffer.is_empty());
     }
@@ -270,14 +1501,14 @@ mod deserialize {
     #[test]
     fn set_case_sensitive_args() {
         // set a b
-        let mut buffer: String = "*3\r\n$3\r\nset\r\n$1\r\nA\r\n$1\r\nb\r\n".to_owned();
+        let mut buffer = BytesMut::from("*3\r\n$3\r\nset\r\n$1\r\nA\r\n$1\r\nb\r\n");
         assert_eq!(
             Request::deserialize(&mut buffer).unwrap(),
-            Request::Set(Set {
-                key: "A".to_string(),
-                value: "b".to_string(),
-                expiration_timeout_ms: None
-            })
+            Some(Request::Set(Set {
+                key: Bytes::from("A"),
+                value: Bytes::from("b"),
+                ..Default::default()
+            }))
         );
         assert!(buffer.is_empty());
     }
@@ -285,11 +1516,213 @@ mod deserialize {
     #[test]
     fn get() {
         // get a
-        let mut buffer: String = "*2\r\n$3\r\nget\r\n$1\r\na\r\n".to_owned();
+        let mut buffer = BytesMut::from("*2\r\n$3\r\nget\r\n$1\r\na\r\n");
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::Get(Bytes::from("a")))
+        );
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn counters() {
+        let mut buffer = BytesMut::from("DECRBY a -9223372036854775808\r\nINCRBYFLOAT a 1e2\r\n");
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap_err(),
+            RedisError::NotInteger
+        );
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::IncrByFloat {
+                key: Bytes::from("a"),
+                increment: 100.0
+            })
+        );
+        let mut buffer = BytesMut::from("DECR a\r\n");
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::IncrBy {
+                key: Bytes::from("a"),
+                increment: -1
+            })
+        );
+    }
+
+    #[test]
+    fn string_commands() {
+        let mut buffer = BytesMut::from("GETSET a b\r\nSETRANGE a -1 x\r\nGETRANGE a 0\r\n");
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::Set(Set {
+                key: Bytes::from("a"),
+                value: Bytes::from("b"),
+                get: true,
+                ..Default::default()
+            }))
+        );
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap_err(),
+            RedisError::Other("offset is out of range".to_string())
+        );
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap_err(),
+            RedisError::WrongArity("getrange".to_string())
+        );
+    }
+
+    #[test]
+    fn list_commands() {
+        let mut buffer = BytesMut::from(
+            "RPUSHX l a b\r\nLPOP l -1\r\nLINSERT l AROUND a b\r\nRPOPLPUSH a b\r\n\
+             BLPOP a b 0\r\nBRPOP a\r\nBLMOVE a b LEFT RIGHT -1\r\n",
+        );
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::Push {
+                key: Bytes::from("l"),
+                elements: vec![Bytes::from("a"), Bytes::from("b")],
+                side: Side::Right,
+                only_existing: true,
+            })
+        );
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap_err(),
+            RedisError::NotPositive
+        );
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap_err(),
+            RedisError::Syntax
+        );
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::LMove {
+                source: Bytes::from("a"),
+                destination: Bytes::from("b"),
+                from: Side::Right,
+                to: Side::Left,
+                timeout: None,
+            })
+        );
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::BPop {
+                keys: vec![Bytes::from("a"), Bytes::from("b")],
+                side: Side::Left,
+                timeout: Timeout::Forever,
+            })
+        );
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap_err(),
+            RedisError::WrongArity("brpop".to_owned())
+        );
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap_err(),
+            RedisError::Other("timeout is negative".to_owned())
+        );
+    }
+
+    #[test]
+    fn sorted_set_commands() {
+        let mut buffer = BytesMut::from(
+            "ZINCRBY z 1.5 a\r\nZREVRANK z a WITHSCORE\r\nZRANK z a WITHSCORES\r\n\
+             ZLEXCOUNT z - [b\r\nZPOPMAX z -1\r\n",
+        );
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::ZAdd(ZAdd {
+                key: Bytes::from("z"),
+                incr: true,
+                pairs: vec![(1.5, Bytes::from("a"))],
+                ..Default::default()
+            }))
+        );
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::ZRank {
+                key: Bytes::from("z"),
+                member: Bytes::from("a"),
+                reverse: true,
+                with_score: true,
+            })
+        );
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap_err(),
+            RedisError::Syntax
+        );
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap(),
+            Some(Request::ZCount {
+                key: Bytes::from("z"),
+                range: ZRangeBy::Lex(LexBound::Lowest, LexBound::Inclusive(Bytes::from("b"))),
+            })
+        );
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap_err(),
+            RedisError::NotPositive
+        );
+    }
+
+    #[test]
+    fn multi_key() {
+        let mut buffer = BytesMut::from("DEL a b a\r\nEXISTS\r\nRENAME a\r\n");
         assert_eq!(
             Request::deserialize(&mut buffer).unwrap(),
-            Request::Get("a".to_string())
+            Some(Request::Del(vec![
+                Bytes::from("a"),
+                Bytes::from("b"),
+                Bytes::from("a")
+            ]))
+        );
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap_err(),
+            RedisError::WrongArity("exists".to_string())
+        );
+        assert_eq!(
+            Request::deserialize(&mut buffer).unwrap_err(),
+            RedisError::WrongArity("rename".to_string())
         );
         assert!(buffer.is_empty());
     }
 }
+
+#[cfg(test)]
+mod split_args {
+    use super::split_args;
+    use bytes::Bytes;
+
+    fn split(line: &str) -> Option<Vec<Bytes>> {
+        split_args(line.as_bytes())
+    }
+
+    #[test]
+    fn plain() {
+        assert_eq!(
+            split("  set a\tb  "),
+            Some(vec![Bytes::from("set"), Bytes::from("a"), Bytes::from("b")])
+        );
+        assert_eq!(split(""), Some(vec![]));
+    }
+
+    #[test]
+    fn double_quotes() {
+        assert_eq!(
+            split(r#""a b" "\x41\n\"" """#),
+            Some(vec![Bytes::from("a b"), Bytes::from("A\n\""), Bytes::new()])
+        );
+    }
+
+    #[test]
+    fn single_quotes() {
+        assert_eq!(
+            split(r#"'a\'b' 'c\n'"#),
+            Some(vec![Bytes::from("a'b"), Bytes::from("c\\n")])
+        );
+    }
+
+    #[test]
+    fn unbalanced() {
+        assert_eq!(split(r#""abc"#), None);
+        assert_eq!(split("'abc"), None);
+        assert_eq!(split(r#""abc"def"#), None);
+    }
+}
@@ -1,40 +1,268 @@
 //! Responses to Redis requests.
+use crate::error::RedisError;
+use bytes::Bytes;
 
+/// Version of RESP spoken on a connection. Negotiated with the HELLO command.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum ProtocolVersion {
+    #[default]
+    Resp2,
+    Resp3,
+}
+
+/// Response to a request. Besides the command specific responses it contains the generic RESP3
+/// value model. RESP3 types are downgraded to their RESP2 counterparts when serialized for a
+/// RESP2 client.
+#[derive(Debug, PartialEq)]
 pub(crate) enum Response {
     Ok,
     Ping,
-    Echo(String),
-    Get(Option<String>),
+    Echo(Bytes),
+    Get(Option<Bytes>),
+    /// Array of bulk strings, some of which may be absent.
+    BulkArray(Vec<Option<Bytes>>),
+    /// Error message starting with an error prefix, e.g. `ERR` or `WRONGTYPE`.
+    Error(String),
+    SimpleString(String),
+    Integer(i64),
+    BulkString(Bytes),
+    /// Absent value. A null bulk string in RESP2.
+    Null,
+    /// Absent aggregate. A null array in RESP2.
+    NullArray,
+    Array(Vec<Response>),
+    /// Key-value pairs. A flat array of keys and values in RESP2.
+    Map(Vec<(Response, Response)>),
+    /// Unordered collection of unique values. An array in RESP2.
+    Set(Vec<Response>),
+    /// A bulk string in RESP2.
+    Double(f64),
+}
+
+impl From<RedisError> for Response {
+    fn from(error: RedisError) -> Self {
+        Response::Error(error.to_string())
+    }
 }
 
 impl Response {
-    pub(crate) fn serialize(self) -> String {
+    #[cfg(test)]
+    pub(crate) fn serialize(self, protocol: ProtocolVersion) -> Vec<u8> {
+        let mut result = Vec::new();
+        self.serialize_into(&mut result, protocol);
+        result
+    }
+
+    /// Appends serialized response to the buffer.
+    pub(crate) fn serialize_into(self, buffer: &mut Vec<u8>, protocol: ProtocolVersion) {
+        let resp3 = protocol == ProtocolVersion::Resp3;
         match self {
-            Response::Ok => "+OK\r\n".to_string(),
-            Response::Ping => "+PONG\r\n".to_string(),
-            Response::Echo(arg) => format!("+{}\r\n", arg),
-            Response::Get(Some(arg)) => format!("${}\r\n{}\r\n", arg.chars().count(), arg),
-            Response::Get(None) => "$-1\r\n".to_string(),
+            Response::Ok => buffer.extend_from_slice(b"+OK\r\n"),
+            Response::Ping => buffer.extend_from_slice(b"+PONG\r\n"),
+            Response::Echo(arg) | Response::Get(Some(arg)) | Response::BulkString(arg) => {
+                serialize_bulk_string(&arg, buffer)
+            }
+            Response::Get(None) | Response::Null if resp3 => buffer.extend_from_slice(b"_\r\n"),
+            Response::Get(None) | Response::Null => buffer.extend_from_slice(b"$-1\r\n"),
+            Response::NullArray if resp3 => buffer.extend_from_slice(b"_\r\n"),
+            Response::NullArray => buffer.extend_from_slice(b"*-1\r\n"),
+            Response::Error(message) => serialize_simple(b'-', &message, buffer),
+            Response::SimpleString(string) => serialize_simple(b'+', &string, buffer),
+            Response::Integer(value) => {
+                buffer.extend_from_slice(format!(":{}\r\n", value).as_bytes())
+            }
+            Response::BulkArray(items) => {
+                buffer.extend_from_slice(format!("*{}\r\n", items.len()).as_bytes());
+                for item in items {
+                    Response::Get(item).serialize_into(buffer, protocol);
+                }
+            }
+            Response::Array(items) => serialize_aggregate(b'*', items, buffer, protocol),
+            Response::Map(pairs) => {
+                let (prefix, length) = if resp3 {
+                    (b'%', pairs.len())
+                } else {
+                    (b'*', pairs.len() * 2)
+                };
+                buffer.extend_from_slice(format!("{}{}\r\n", prefix as char, length).as_bytes());
+                for (key, value) in pairs {
+                    key.serialize_into(buffer, protocol);
+                    value.serialize_into(buffer, protocol);
+                }
+            }
+            Response::Set(items) if resp3 => serialize_aggregate(b'~', items, buffer, protocol),
+            Response::Set(items) => serialize_aggregate(b'*', items, buffer, protocol),
+            Response::Double(value) if resp3 => {
+                serialize_simple(b',', &format_double(value), buffer)
+            }
+            Response::Double(value) => {
+                serialize_bulk_string(format_double(value).as_bytes(), buffer)
+            }
         }
     }
 }
 
+/// Formats a double the way Redis does with `%.17g`, but with the shortest digits that read
+/// back as the same value: integral values have no fractional part, the exponent notation is
+/// used for decimal exponents below -4 or from 17 on, and infinities are spelled `inf` and
+/// `-inf`.
+fn format_double(value: f64) -> String {
+    if value.is_nan() {
+        return "nan".to_owned();
+    } else if value.is_infinite() {
+        return if value > 0.0 { "inf" } else { "-inf" }.to_owned();
+    }
+    let scientific = format!("{:e}", value);
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("Exponent notation has an exponent");
+    let exponent: i32 = exponent.parse().expect("Exponent is an integer");
+    if (-4..17).contains(&exponent) {
+        value.to_string()
+    } else {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+    }
+}
+
+/// Formats a double stored by INCRBYFLOAT and HINCRBYFLOAT the way Redis does: in the fixed
+/// notation without trailing zeros, never with an exponent, with the shortest digits that read
+/// back as the same value.
+pub(crate) fn format_stored_double(value: f64) -> String {
+    value.to_string()
+}
+
+fn serialize_bulk_string(arg: &[u8], buffer: &mut Vec<u8>) {
+    buffer.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+    buffer.extend_from_slice(arg);
+    buffer.extend_from_slice(b"\r\n");
+}
+
+/// Serializes a one-line value. Line breaks are replaced with spaces.
+fn serialize_simple(prefix: u8, value: &str, buffer: &mut Vec<u8>) {
+    buffer.push(prefix);
+    buffer.extend(value.bytes().map(|b| match b {
+        b'\r' | b'\n' => b' ',
+        b => b,
+    }));
+    buffer.extend_from_slice(b"\r\n");
+}
+
+fn serialize_aggregate(
+    prefix: u8,
+    items: Vec<Response>,
+    buffer: &mut Vec<u8>,
+    protocol: ProtocolVersion,
+) {
+    buffer.extend_from_slice(format!("{}{}\r\n", prefix as char, items.len()).as_bytes());
+    for item in items {
+        item.serialize_into(buffer, protocol);
+    }
+}
+
 #[cfg(test)]
 mod serialize {
     use super::*;
+    use ProtocolVersion::{Resp2, Resp3};
 
     #[test]
     fn all() {
-        assert_eq!(Response::Ok.serialize(), "+OK\r\n");
-        assert_eq!(Response::Ping.serialize(), "+PONG\r\n");
+        assert_eq!(Response::Ok.serialize(Resp2), b"+OK\r\n");
+        assert_eq!(Response::Ping.serialize(Resp2), b"+PONG\r\n");
         assert_eq!(
-            Response::Echo("hello".to_string()).serialize(),
-            "+hello\r\n"
+            Response::Echo(Bytes::from("hello")).serialize(Resp2),
+            b"$5\r\nhello\r\n"
         );
         assert_eq!(
-            Response::Get(Some("value".to_string())).serialize(),
-            "$5\r\nvalue\r\n"
+            Response::Get(Some(Bytes::from("value"))).serialize(Resp2),
+            b"$5\r\nvalue\r\n"
+        );
+        assert_eq!(Response::Get(None).serialize(Resp2), b"$-1\r\n");
+        assert_eq!(
+            Response::Error("ERR syntax error".to_string()).serialize(Resp2),
+            b"-ERR syntax error\r\n"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Response::from(RedisError::WrongType).serialize(Resp2),
+            &b"-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"[..]
         );
-        assert_eq!(Response::Get(None).serialize(), "$-1\r\n");
+        assert_eq!(
+            Response::from(RedisError::WrongArity("get".to_string())).serialize(Resp2),
+            &b"-ERR wrong number of arguments for 'get' command\r\n"[..]
+        );
+        assert_eq!(
+            Response::from(RedisError::unknown_command(
+                b"foo",
+                &[Bytes::from("a"), Bytes::from("b")]
+            ))
+            .serialize(Resp2),
+            &b"-ERR unknown command 'foo', with args beginning with: 'a' 'b' \r\n"[..]
+        );
+        assert_eq!(
+            Response::Error("ERR multi\r\nline".to_string()).serialize(Resp2),
+            b"-ERR multi  line\r\n"
+        );
+    }
+
+    #[test]
+    fn bulk_array() {
+        let array = || Response::BulkArray(vec![Some(Bytes::from("a")), None]);
+        assert_eq!(array().serialize(Resp2), b"*2\r\n$1\r\na\r\n$-1\r\n");
+        assert_eq!(array().serialize(Resp3), b"*2\r\n$1\r\na\r\n_\r\n");
+        assert_eq!(Response::BulkArray(vec![]).serialize(Resp2), b"*0\r\n");
+    }
+
+    #[test]
+    fn binary() {
+        assert_eq!(
+            Response::Get(Some(Bytes::from_static(b"a\r\n\xff"))).serialize(Resp2),
+            b"$4\r\na\r\n\xff\r\n"
+        );
+    }
+
+    #[test]
+    fn resp3_types() {
+        let map = || {
+            Response::Map(vec![(
+                Response::BulkString(Bytes::from("proto")),
+                Response::Integer(3),
+            )])
+        };
+        assert_eq!(map().serialize(Resp3), b"%1\r\n$5\r\nproto\r\n:3\r\n");
+        assert_eq!(map().serialize(Resp2), b"*2\r\n$5\r\nproto\r\n:3\r\n");
+
+        let set = || Response::Set(vec![Response::Integer(1)]);
+        assert_eq!(set().serialize(Resp3), b"~1\r\n:1\r\n");
+        assert_eq!(set().serialize(Resp2), b"*1\r\n:1\r\n");
+
+        assert_eq!(Response::Double(1.5).serialize(Resp3), b",1.5\r\n");
+        assert_eq!(Response::Double(1.5).serialize(Resp2), b"$3\r\n1.5\r\n");
+        assert_eq!(
+            Response::Double(f64::INFINITY).serialize(Resp3),
+            b",inf\r\n"
+        );
+        assert_eq!(Response::Double(-2.0).serialize(Resp3), b",-2\r\n");
+        assert_eq!(Response::Double(1e20).serialize(Resp3), b",1e+20\r\n");
+        assert_eq!(Response::Double(1e20).serialize(Resp2), b"$5\r\n1e+20\r\n");
+        assert_eq!(Response::Double(1e-5).serialize(Resp3), b",1e-05\r\n");
+        assert_eq!(
+            Response::Double(-1.5e300).serialize(Resp3),
+            b",-1.5e+300\r\n"
+        );
+        assert_eq!(Response::Double(0.0001).serialize(Resp3), b",0.0001\r\n");
+        assert_eq!(
+            Response::Double(12345678901234567.0).serialize(Resp3),
+            b",12345678901234568\r\n"
+        );
+
+        assert_eq!(Response::Null.serialize(Resp3), b"_\r\n");
+        assert_eq!(Response::Null.serialize(Resp2), b"$-1\r\n");
+        assert_eq!(Response::NullArray.serialize(Resp3), b"_\r\n");
+        assert_eq!(Response::NullArray.serialize(Resp2), b"*-1\r\n");
+        assert_eq!(Response::Get(None).serialize(Resp3), b"_\r\n");
     }
 }
@@ -0,0 +1,112 @@
+//! Scan request of a collection.
+use crate::{
+    error::RedisError,
+    protocol::args::{parse_i64, Args},
+};
+use bytes::Bytes;
+
+/// Number of elements a scan returns per call unless asked otherwise.
+const DEFAULT_COUNT: usize = 10;
+
+/// Iterates over elements of a collection a few at a time. Serves HSCAN.
+#[derive(Eq, PartialEq, Debug)]
+pub struct Scan {
+    pub key: Bytes,
+    /// 0 starts an iteration, other values are returned by the previous call.
+    pub cursor: u64,
+    /// Only elements matching the glob-style pattern are returned.
+    pub pattern: Option<Bytes>,
+    /// Approximate number of elements to examine.
+    pub count: usize,
+    /// Return hash fields without their values.
+    pub no_values: bool,
+}
+
+impl TryFrom<Args> for Scan {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let hash = args.command() == "hscan";
+        let key = args.required()?;
+        let cursor = std::str::from_utf8(&args.required()?)
+            .ok()
+            .and_then(|cursor| cursor.parse().ok())
+            .ok_or_else(|| RedisError::Other("invalid cursor".to_owned()))?;
+        let mut request = Scan {
+            key,
+            cursor,
+            pattern: None,
+            count: DEFAULT_COUNT,
+            no_values: false,
+        };
+        while let Some(arg) = args.next() {
+            let option = arg.to_ascii_lowercase();
+            match option.as_slice() {
+                b"match" => request.pattern = Some(args.next().ok_or(RedisError::Syntax)?),
+                b"count" => {
+                    let count = parse_i64(&args.next().ok_or(RedisError::Syntax)?)?;
+                    request.count = usize::try_from(count)
+                        .ok()
+                        .filter(|count| *count > 0)
+                        .ok_or(RedisError::Syntax)?;
+                }
+                b"novalues" if hash => request.no_values = true,
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod try_from_args {
+    use super::*;
+    use crate::protocol::args::args;
+
+    #[test]
+    fn options() {
+        assert_eq!(
+            Scan::try_from(args(&[
+                "hscan", "h", "42", "MATCH", "f*", "count", "100", "NOVALUES"
+            ])),
+            Ok(Scan {
+                key: Bytes::from("h"),
+                cursor: 42,
+                pattern: Some(Bytes::from("f*")),
+                count: 100,
+                no_values: true,
+            })
+        );
+        assert_eq!(
+            Scan::try_from(args(&["hscan", "h", "0"])),
+            Ok(Scan {
+                key: Bytes::from("h"),
+                cursor: 0,
+                pattern: None,
+                count: 10,
+                no_values: false,
+            })
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Scan::try_from(args(&["hscan", "h", "-1"])),
+            Err(RedisError::Other("invalid cursor".to_owned()))
+        );
+        assert_eq!(
+            Scan::try_from(args(&["hscan", "h", "0", "count", "0"])),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            Scan::try_from(args(&["hscan", "h", "0", "match"])),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            Scan::try_from(args(&["hscan", "h"])),
+            Err(RedisError::WrongArity("hscan".to_owned()))
+        );
+    }
+}
@@ -1,96 +1,88 @@
 //! Set request.
-use crate::{error::RedisError, protocol::request::Array};
+use crate::{
+    error::RedisError,
+    protocol::{args::Args, Expiration},
+};
+use bytes::Bytes;
+
+/// Condition on the existence of the key that must hold to set it.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum SetCondition {
+    /// The key doesn't exist.
+    Nx,
+    /// The key exists.
+    Xx,
+}
 
 // Set request
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Eq, PartialEq, Debug, Default)]
 pub struct Set {
-    pub key: String,
-    pub value: String,
-    pub expiration_timeout_ms: Option<u64>,
+    pub key: Bytes,
+    pub value: Bytes,
+    /// New deadline of the key. Without it the key loses its deadline unless `keep_ttl` is set.
+    pub expiration: Option<Expiration>,
+    /// Retain the deadline of the existing key.
+    pub keep_ttl: bool,
+    pub condition: Option<SetCondition>,
+    /// Reply with the old value of the key.
+    pub get: bool,
 }
 
-impl TryFrom<Array> for Set {
+impl TryFrom<Args> for Set {
     type Error = RedisError;
 
-    fn try_from(array: Array) -> Result<Self, Self::Error> {
-        if array.args_count() < 3 {
-            return Err(RedisError::DeserializationError {
-                raw_redis_message: array.serialize(),
-                details: "SET command requires at least 2 arguments".to_owned(),
-            });
-        }
-        // Skip array.args[0]: it is a command name.
-        let mut args = array.args.into_iter().skip(1);
-        let key = args.next().unwrap();
-        let value = args.next().unwrap();
-        let mut expiration_timeout_ms = None;
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let mut request = Set {
+            key: args.required()?,
+            value: args.required()?,
+            ..Default::default()
+        };
         while let Some(arg) = args.next() {
-            if arg.to_ascii_uppercase() == "EX" {
-                if expiration_timeout_ms.is_some() {
-                    return Err(RedisError::DeserializationError {
-                        raw_redis_message: "N/A".to_string(),
-                        details: "Expiration time cannot be set twice".to_owned(),
-                    });
+            let option = arg.to_ascii_lowercase();
+            match option.as_slice() {
+                b"nx" | b"xx" => {
+                    let condition = if option == b"nx" {
+                        SetCondition::Nx
+                    } else {
+                        SetCondition::Xx
+                    };
+                    if request.condition.is_some_and(|other| other != condition) {
+                        return Err(RedisError::Syntax);
+                    }
+                    request.condition = Some(condition);
                 }
-                expiration_timeout_ms = Some(
-                    args.next()
-                        .ok_or(RedisError::DeserializationError {
-                            raw_redis_message: "N/A".to_string(),
-                            details: "EX argument is missing".to_owned(),
-                        })?
-                        .parse::<u64>()
-                        .map_err(|err| RedisError::DeserializationError {
-                            raw_redis_message: "N/A".to_string(),
-                            details: err.to_string(),
-                        })?
-                        * 1000,
-                );
-            }
-            if arg.to_ascii_uppercase() == "PX" {
-                if expiration_timeout_ms.is_some() {
-                    return Err(RedisError::DeserializationError {
-                        raw_redis_message: "N/A".to_string(),
-                        details: "Expiration time cannot be set twice".to_owned(),
-                    });
+                b"get" => request.get = true,
+                b"keepttl" if request.expiration.is_none() => request.keep_ttl = true,
+                // Expiration time cannot be set twice or along with KEEPTTL.
+                b"ex" | b"px" | b"exat" | b"pxat"
+                    if request.expiration.is_none() && !request.keep_ttl =>
+                {
+                    let time = args.next().ok_or(RedisError::Syntax)?;
+                    request.expiration = Some(Expiration::parse(&option, &time, "set")?);
                 }
-                expiration_timeout_ms = Some(
-                    args.next()
-                        .ok_or(RedisError::DeserializationError {
-                            raw_redis_message: "N/A".to_string(),
-                            details: "PX argument is missing".to_owned(),
-                        })?
-                        .parse::<u64>()
-                        .map_err(|err| RedisError::DeserializationError {
-                            raw_redis_message: "N/A".to_string(),
-                            details: err.to_string(),
-                        })?,
-                );
+                _ => return Err(RedisError::Syntax),
             }
         }
 
-        Ok(Set {
-            key,
-            value,
-            expiration_timeout_ms,
-        })
+        Ok(request)
     }
 }
 
 #[cfg(test)]
-mod try_from_array {
+mod try_from_args {
     use super::*;
-    use assert_matches::assert_matches;
+    use crate::protocol::args::args;
 
     #[test]
     fn simple() {
         // Set a b
-        let array = Array::new(vec!["set".to_string(), "a".to_string(), "b".to_string()]);
+        let args = args(&["set", "a", "b"]);
         assert_eq!(
-            Set::try_from(array).unwrap(),
+            Set::try_from(args).unwrap(),
             Set {
-                key: "a".to_string(),
-                value: "b".to_string(),
-                expiration_timeout_ms: None
+                key: Bytes::from("a"),
+                value: Bytes::from("b"),
+                ..Default::default()
             }
         );
     }
@@ -98,19 +90,14 @@ mod try_from_array {
     #[test]
     fn px_arg() {
         // Set expiration timeout in milliseconds
-        let array = Array::new(vec![
-            "set".to_string(),
-            "a".to_string(),
-            "b".to_string(),
-            "px".to_string(),
-            "1000".to_string(),
-        ]);
+        let args = args(&["set", "a", "b", "px", "1000"]);
         assert_eq!(
-            Set::try_from(array).unwrap(),
+            Set::try_from(args).unwrap(),
             Set {
-                key: "a".to_string(),
-                value: "b".to_string(),
-                expiration_timeout_ms: Some(1000),
+                key: Bytes::from("a"),
+                value: Bytes::from("b"),
+                expiration: Some(Expiration::After(1000)),
+                ..Default::default()
             }
         );
     }
@@ -118,19 +105,14 @@ mod try_from_array {
     #[test]
     fn ex_arg() {
         // Set expiration timeout in seconds
-        let array = Array::new(vec![
-            "set".to_string(),
-            "a".to_string(),
-            "b".to_string(),
-            "ex".to_string(),
-            "2".to_string(),
-        ]);
+        let args = args(&["set", "a", "b", "ex", "2"]);
         assert_eq!(
-            Set::try_from(array).unwrap(),
+            Set::try_from(args).unwrap(),
             Set {
-                key: "a".to_string(),
-                value: "b".to_string(),
-                expiration_timeout_ms: Some(2000),
+                key: Bytes::from("a"),
+                value: Bytes::from("b"),
+                expiration: Some(Expiration::After(2000)),
+                ..Default::default()
             }
         );
     }
@@ -139,19 +121,14 @@ mod try_from_array {
     fn variadic_case() {
         // Case of key and value should preserve.
         // Case of the command name and other args can be ignored.
-        let array = Array::new(vec![
-            "SeT".to_string(),
-            "A".to_string(),
-            "b".to_string(),
-            "Ex".to_string(),
-            "3".to_string(),
-        ]);
+        let args = args(&["SeT", "A", "b", "Ex", "3"]);
         assert_eq!(
-            Set::try_from(array).unwrap(),
+            Set::try_from(args).unwrap(),
             Set {
-                key: "A".to_string(),
-                value: "b".to_string(),
-                expiration_timeout_ms: Some(3000)
+                key: Bytes::from("A"),
+                value: Bytes::from("b"),
+                expiration: Some(Expiration::After(3000)),
+                ..Default::default()
             }
         );
     }
@@ -159,64 +136,92 @@ mod try_from_array {
     #[test]
     fn neg_value_is_missing() {
         // Value is missing
-        let array = Array::new(vec!["SET".to_string(), "a".to_string()]);
-        assert_matches!(
-            Set::try_from(array),
-            Err(RedisError::DeserializationError { .. })
+        let args = args(&["SET", "a"]);
+        assert_eq!(
+            Set::try_from(args),
+            Err(RedisError::WrongArity("set".to_string()))
         );
     }
 
     #[test]
     fn neg_px_is_set_twice() {
         // PX is set twice
-        let array = Array::new(vec![
-            "SET".to_string(),
-            "a".to_string(),
-            "b".to_string(),
-            "PX".to_string(),
-            "1000".to_string(),
-            "PX".to_string(),
-            "1000".to_string(),
-        ]);
-        assert_matches!(
-            Set::try_from(array),
-            Err(RedisError::DeserializationError { .. })
-        );
+        let args = args(&["SET", "a", "b", "PX", "1000", "PX", "1000"]);
+        assert_eq!(Set::try_from(args), Err(RedisError::Syntax));
     }
 
     #[test]
     fn neg_ex_is_set_twice() {
         // PX is set twice
-        let array = Array::new(vec![
-            "SET".to_string(),
-            "a".to_string(),
-            "b".to_string(),
-            "EX".to_string(),
-            "1000".to_string(),
-            "EX".to_string(),
-            "1000".to_string(),
-        ]);
-        assert_matches!(
-            Set::try_from(array),
-            Err(RedisError::DeserializationError { .. })
-        );
+        let args = args(&["SET", "a", "b", "EX", "1000", "EX", "1000"]);
+        assert_eq!(Set::try_from(args), Err(RedisError::Syntax));
     }
 
     #[test]
     fn neg_ex_and_px() {
         // PX is set twice
-        let array = Array::new(vec![
-            "SET".to_string(),
-            "a".to_string(),
-            "b".to_string(),
-            "EX".to_string(),
-            "1000".to_string(),
-            "PX".to_string(),
-            "1000".to_string(),
-        ]);
-        assert_matches!(
-            Set::try_from(array),
-            Err(RedisError::DeserializationError { .. })
+        let args = args(&["SET", "a", "b", "EX", "1000", "PX", "1000"]);
+        assert_eq!(Set::try_from(args), Err(RedisError::Syntax));
+    }
+
+    #[test]
+    fn neg_ex_is_not_positive() {
+        let args = args(&["SET", "a", "b", "EX", "0"]);
+        assert_eq!(
+            Set::try_from(args),
+            Err(RedisError::InvalidExpireTime("set".to_string()))
+        );
+    }
+
+    #[test]
+    fn neg_px_is_not_integer() {
+        let args = args(&["SET", "a", "b", "PX", "soon"]);
+        assert_eq!(Set::try_from(args), Err(RedisError::NotInteger));
+    }
+
+    #[test]
+    fn absolute_time() {
+        let request = Set::try_from(args(&["SET", "a", "b", "exat", "1700000000"])).unwrap();
+        assert_eq!(request.expiration, Some(Expiration::At(1_700_000_000_000)));
+        let request = Set::try_from(args(&["SET", "a", "b", "PXAT", "1700000000000"])).unwrap();
+        assert_eq!(request.expiration, Some(Expiration::At(1_700_000_000_000)));
+        assert_eq!(
+            Set::try_from(args(&["SET", "a", "b", "EX", "9223372036854775807"])),
+            Err(RedisError::InvalidExpireTime("set".to_string()))
         );
     }
+
+    #[test]
+    fn conditions_and_flags() {
+        let args = args(&["SET", "a", "b", "nx", "GET", "KEEPTTL", "NX"]);
+        assert_eq!(
+            Set::try_from(args).unwrap(),
+            Set {
+                key: Bytes::from("a"),
+                value: Bytes::from("b"),
+                expiration: None,
+                keep_ttl: true,
+                condition: Some(SetCondition::Nx),
+                get: true,
+            }
+        );
+    }
+
+    #[test]
+    fn neg_conflicting_options() {
+        for conflicting in [
+            &["SET", "a", "b", "NX", "XX"][..],
+            &["SET", "a", "b", "KEEPTTL", "EX", "1"],
+            &["SET", "a", "b", "PXAT", "1", "KEEPTTL"],
+            &["SET", "a", "b", "EX", "1", "EXAT", "1"],
+            &["SET", "a", "b", "EX"],
+            &["SET", "a", "b", "IF"],
+        ] {
+            assert_eq!(
+                Set::try_from(args(conflicting)),
+                Err(RedisError::Syntax),
+                "{conflicting:?}"
+            );
+        }
+    }
 }
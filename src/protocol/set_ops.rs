@@ -0,0 +1,132 @@
+//! Set algebra requests: SINTER, SUNION, SDIFF, their STORE variants and SINTERCARD.
+use crate::{
+    error::RedisError,
+    protocol::args::{parse_i64, Args},
+};
+use bytes::Bytes;
+
+/// Operation combining sets.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum SetOperation {
+    /// Members of all sets.
+    Inter,
+    /// Members of any set.
+    Union,
+    /// Members of the first set which are not in the others.
+    Diff,
+}
+
+impl SetOperation {
+    /// Returns the operation of a command, e.g. `sinter` or `sdiffstore`.
+    pub(crate) fn of_command(command: &str) -> Option<Self> {
+        let name = command.strip_suffix("store").unwrap_or(command);
+        match name {
+            "sinter" => Some(SetOperation::Inter),
+            "sunion" => Some(SetOperation::Union),
+            "sdiff" => Some(SetOperation::Diff),
+            _ => None,
+        }
+    }
+}
+
+/// Counts members of the intersection of sets.
+#[derive(Eq, PartialEq, Debug)]
+pub struct SInterCard {
+    pub keys: Vec<Bytes>,
+    /// Stop counting once the count reaches the limit. 0 means no limit.
+    pub limit: usize,
+}
+
+impl TryFrom<Args> for SInterCard {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        // Number of keys and a key.
+        if args.as_slice().len() < 2 {
+            return Err(RedisError::WrongArity(args.command().to_owned()));
+        }
+        let num_keys = parse_i64(&args.required()?)
+            .ok()
+            .filter(|num_keys| *num_keys > 0)
+            .and_then(|num_keys| usize::try_from(num_keys).ok())
+            .ok_or_else(|| RedisError::Other("numkeys should be greater than 0".to_owned()))?;
+        if num_keys > args.as_slice().len() {
+            return Err(RedisError::Other(
+                "Number of keys can't be greater than number of args".to_owned(),
+            ));
+        }
+        let keys = (0..num_keys).filter_map(|_| args.next()).collect();
+        let mut limit = 0;
+        while let Some(arg) = args.next() {
+            if !arg.eq_ignore_ascii_case(b"LIMIT") {
+                return Err(RedisError::Syntax);
+            }
+            let value = args.next().ok_or(RedisError::Syntax)?;
+            limit = parse_i64(&value)
+                .ok()
+                .and_then(|limit| usize::try_from(limit).ok())
+                .ok_or_else(|| RedisError::Other("LIMIT can't be negative".to_owned()))?;
+        }
+
+        Ok(SInterCard { keys, limit })
+    }
+}
+
+#[cfg(test)]
+mod try_from_args {
+    use super::*;
+    use crate::protocol::args::args;
+
+    #[test]
+    fn operations() {
+        assert_eq!(
+            SetOperation::of_command("sinter"),
+            Some(SetOperation::Inter)
+        );
+        assert_eq!(
+            SetOperation::of_command("sunionstore"),
+            Some(SetOperation::Union)
+        );
+        assert_eq!(SetOperation::of_command("sdiff"), Some(SetOperation::Diff));
+        assert_eq!(SetOperation::of_command("store"), None);
+    }
+
+    #[test]
+    fn sintercard() {
+        assert_eq!(
+            SInterCard::try_from(args(&["sintercard", "2", "a", "b", "limit", "5"])),
+            Ok(SInterCard {
+                keys: vec![Bytes::from("a"), Bytes::from("b")],
+                limit: 5,
+            })
+        );
+        assert_eq!(
+            SInterCard::try_from(args(&["sintercard", "1"])),
+            Err(RedisError::WrongArity("sintercard".to_owned()))
+        );
+        assert_eq!(
+            SInterCard::try_from(args(&["sintercard", "0", "a"])),
+            Err(RedisError::Other(
+                "numkeys should be greater than 0".to_owned()
+            ))
+        );
+        assert_eq!(
+            SInterCard::try_from(args(&["sintercard", "3", "a", "b"])),
+            Err(RedisError::Other(
+                "Number of keys can't be greater than number of args".to_owned()
+            ))
+        );
+        assert_eq!(
+            SInterCard::try_from(args(&["sintercard", "1", "a", "LIMIT", "-1"])),
+            Err(RedisError::Other("LIMIT can't be negative".to_owned()))
+        );
+        assert_eq!(
+            SInterCard::try_from(args(&["sintercard", "1", "a", "LIMIT"])),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            SInterCard::try_from(args(&["sintercard", "1", "a", "b"])),
+            Err(RedisError::Syntax)
+        );
+    }
+}
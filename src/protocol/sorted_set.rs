@@ -0,0 +1,367 @@
+//! Sorted set requests with options of their own: ZADD and the ZRANGE family, along with the
+//! score and lexicographical ranges they take.
+use crate::{
+    error::RedisError,
+    protocol::args::{parse_f64, parse_i64, Args},
+};
+use bytes::Bytes;
+
+/// Bound of a score range. `(` before a score makes it exclusive.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ScoreBound {
+    Inclusive(f64),
+    Exclusive(f64),
+}
+
+impl ScoreBound {
+    pub(crate) fn parse(arg: &[u8]) -> Result<Self, RedisError> {
+        let invalid = || RedisError::Other("min or max is not a float".to_owned());
+        match arg.strip_prefix(b"(") {
+            Some(score) => Ok(ScoreBound::Exclusive(
+                parse_f64(score).map_err(|_| invalid())?,
+            )),
+            None => Ok(ScoreBound::Inclusive(
+                parse_f64(arg).map_err(|_| invalid())?,
+            )),
+        }
+    }
+
+    /// Returns true if the score is below the range this bound is the minimum of.
+    pub(crate) fn is_below(self, score: f64) -> bool {
+        match self {
+            ScoreBound::Inclusive(min) => score < min,
+            ScoreBound::Exclusive(min) => score <= min,
+        }
+    }
+
+    /// Returns true if the score is above the range this bound is the maximum of.
+    pub(crate) fn is_above(self, score: f64) -> bool {
+        match self {
+            ScoreBound::Inclusive(max) => score > max,
+            ScoreBound::Exclusive(max) => score >= max,
+        }
+    }
+}
+
+/// Bound of a lexicographical range: `-` and `+` for the lowest and the highest strings, or a
+/// string prefixed with `[` if inclusive and with `(` if exclusive.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum LexBound {
+    Lowest,
+    Highest,
+    Inclusive(Bytes),
+    Exclusive(Bytes),
+}
+
+impl LexBound {
+    pub(crate) fn parse(arg: Bytes) -> Result<Self, RedisError> {
+        match arg.first() {
+            Some(b'-') if arg.len() == 1 => Ok(LexBound::Lowest),
+            Some(b'+') if arg.len() == 1 => Ok(LexBound::Highest),
+            Some(b'[') => Ok(LexBound::Inclusive(arg.slice(1..))),
+            Some(b'(') => Ok(LexBound::Exclusive(arg.slice(1..))),
+            _ => Err(RedisError::Other(
+                "min or max not valid string range item".to_owned(),
+            )),
+        }
+    }
+
+    /// Returns true if the member is below the range this bound is the minimum of.
+    pub(crate) fn is_below(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::Lowest => false,
+            LexBound::Highest => true,
+            LexBound::Inclusive(min) => member < &min[..],
+            LexBound::Exclusive(min) => member <= &min[..],
+        }
+    }
+
+    /// Returns true if the member is above the range this bound is the maximum of.
+    pub(crate) fn is_above(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::Lowest => true,
+            LexBound::Highest => false,
+            LexBound::Inclusive(max) => member > &max[..],
+            LexBound::Exclusive(max) => member >= &max[..],
+        }
+    }
+}
+
+/// Range of members of a sorted set.
+#[derive(PartialEq, Debug, Clone)]
+pub enum ZRangeBy {
+    /// Ranks, which count from the end if negative. Both ends are inclusive.
+    Rank(i64, i64),
+    Score(ScoreBound, ScoreBound),
+    /// Members between the bounds. Only meaningful when all members have the same score.
+    Lex(LexBound, LexBound),
+}
+
+/// Adds members to a sorted set or updates their scores. Serves ZADD and ZINCRBY.
+#[derive(PartialEq, Debug, Default)]
+pub struct ZAdd {
+    pub key: Bytes,
+    /// Only add new members.
+    pub nx: bool,
+    /// Only update existing members.
+    pub xx: bool,
+    /// Only update scores to greater ones. New members are added anyway.
+    pub gt: bool,
+    /// Only update scores to lower ones. New members are added anyway.
+    pub lt: bool,
+    /// Count updated members along with the added ones.
+    pub ch: bool,
+    /// Add the score to the current score of the single member.
+    pub incr: bool,
+    pub pairs: Vec<(f64, Bytes)>,
+}
+
+impl TryFrom<Args> for ZAdd {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let mut request = ZAdd {
+            key: args.required()?,
+            ..Default::default()
+        };
+        while let Some(arg) = args.as_slice().first() {
+            let flag = match arg.to_ascii_lowercase().as_slice() {
+                b"nx" => &mut request.nx,
+                b"xx" => &mut request.xx,
+                b"gt" => &mut request.gt,
+                b"lt" => &mut request.lt,
+                b"ch" => &mut request.ch,
+                b"incr" => &mut request.incr,
+                _ => break,
+            };
+            *flag = true;
+            args.next();
+        }
+        let rest = args.as_slice();
+        if rest.is_empty() || rest.len() & 1 == 1 {
+            return Err(RedisError::Syntax);
+        }
+        if request.nx && request.xx {
+            return Err(RedisError::Other(
+                "XX and NX options at the same time are not compatible".to_owned(),
+            ));
+        }
+        if request.nx && (request.gt || request.lt) || request.gt && request.lt {
+            return Err(RedisError::Other(
+                "GT, LT, and/or NX options at the same time are not compatible".to_owned(),
+            ));
+        }
+        if request.incr && rest.len() > 2 {
+            return Err(RedisError::Other(
+                "INCR option supports a single increment-element pair".to_owned(),
+            ));
+        }
+        while let (Some(score), Some(member)) = (args.next(), args.next()) {
+            request.pairs.push((parse_f64(&score)?, member));
+        }
+
+        Ok(request)
+    }
+}
+
+/// Returns members of a sorted set in a range. Serves ZRANGE and the older ZREVRANGE,
+/// ZRANGEBYSCORE, ZREVRANGEBYSCORE, ZRANGEBYLEX and ZREVRANGEBYLEX.
+#[derive(PartialEq, Debug)]
+pub struct ZRange {
+    pub key: Bytes,
+    /// The range. Score and lexicographical ranges are stored as minimum and maximum, even
+    /// though reversed requests give them the other way round.
+    pub range: ZRangeBy,
+    /// Order from the highest to the lowest. Ranks count from the highest then.
+    pub reverse: bool,
+    /// Number of matching members to skip and the maximum number of members to return, all of
+    /// them if negative.
+    pub limit: Option<(i64, i64)>,
+    pub with_scores: bool,
+}
+
+impl TryFrom<Args> for ZRange {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let command = args.command().to_owned();
+        let key = args.required()?;
+        let start = args.required()?;
+        let stop = args.required()?;
+        let (mut by_score, mut by_lex, mut reverse) = match command.as_str() {
+            "zrange" => (false, false, false),
+            "zrevrange" => (false, false, true),
+            "zrangebyscore" => (true, false, false),
+            "zrevrangebyscore" => (true, false, true),
+            "zrangebylex" => (false, true, false),
+            "zrevrangebylex" => (false, true, true),
+            _ => return Err(args.unknown_command()),
+        };
+        let mut limit = None;
+        let mut with_scores = false;
+        while let Some(arg) = args.next() {
+            match arg.to_ascii_lowercase().as_slice() {
+                b"withscores" => with_scores = true,
+                b"limit" => {
+                    let offset = args.next().ok_or(RedisError::Syntax)?;
+                    let count = args.next().ok_or(RedisError::Syntax)?;
+                    limit = Some((parse_i64(&offset)?, parse_i64(&count)?));
+                }
+                b"byscore" if command == "zrange" => by_score = true,
+                b"bylex" if command == "zrange" => by_lex = true,
+                b"rev" if command == "zrange" => reverse = true,
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+        if by_score && by_lex {
+            return Err(RedisError::Syntax);
+        }
+        if limit.is_some() && !by_score && !by_lex {
+            return Err(RedisError::Other(
+                "syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"
+                    .to_owned(),
+            ));
+        }
+        if with_scores && by_lex {
+            return Err(RedisError::Other(
+                "syntax error, WITHSCORES not supported in combination with BYLEX".to_owned(),
+            ));
+        }
+        // Reversed score and lexicographical ranges start with the maximum.
+        let (min, max) = if reverse && (by_score || by_lex) {
+            (stop, start)
+        } else {
+            (start, stop)
+        };
+        let range = if by_score {
+            ZRangeBy::Score(ScoreBound::parse(&min)?, ScoreBound::parse(&max)?)
+        } else if by_lex {
+            ZRangeBy::Lex(LexBound::parse(min)?, LexBound::parse(max)?)
+        } else {
+            ZRangeBy::Rank(parse_i64(&min)?, parse_i64(&max)?)
+        };
+
+        Ok(ZRange {
+            key,
+            range,
+            reverse,
+            limit,
+            with_scores,
+        })
+    }
+}
+
+#[cfg(test)]
+mod try_from_args {
+    use super::*;
+    use crate::protocol::args::args;
+
+    #[test]
+    fn zadd() {
+        assert_eq!(
+            ZAdd::try_from(args(&["zadd", "z", "GT", "ch", "1", "a", "-inf", "b"])),
+            Ok(ZAdd {
+                key: Bytes::from("z"),
+                gt: true,
+                ch: true,
+                pairs: vec![
+                    (1.0, Bytes::from("a")),
+                    (f64::NEG_INFINITY, Bytes::from("b"))
+                ],
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            ZAdd::try_from(args(&["zadd", "z", "nx", "xx", "1", "a"])),
+            Err(RedisError::Other(
+                "XX and NX options at the same time are not compatible".to_owned()
+            ))
+        );
+        assert_eq!(
+            ZAdd::try_from(args(&["zadd", "z", "gt", "lt", "1", "a"])),
+            Err(RedisError::Other(
+                "GT, LT, and/or NX options at the same time are not compatible".to_owned()
+            ))
+        );
+        assert_eq!(
+            ZAdd::try_from(args(&["zadd", "z", "incr", "1", "a", "2", "b"])),
+            Err(RedisError::Other(
+                "INCR option supports a single increment-element pair".to_owned()
+            ))
+        );
+        assert_eq!(
+            ZAdd::try_from(args(&["zadd", "z", "1", "a", "2"])),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            ZAdd::try_from(args(&["zadd", "z", "one", "a"])),
+            Err(RedisError::NotFloat)
+        );
+    }
+
+    #[test]
+    fn zrange() {
+        assert_eq!(
+            ZRange::try_from(args(&[
+                "zrange",
+                "z",
+                "(5",
+                "1",
+                "BYSCORE",
+                "REV",
+                "LIMIT",
+                "1",
+                "2",
+                "WITHSCORES"
+            ])),
+            Ok(ZRange {
+                key: Bytes::from("z"),
+                range: ZRangeBy::Score(ScoreBound::Inclusive(1.0), ScoreBound::Exclusive(5.0)),
+                reverse: true,
+                limit: Some((1, 2)),
+                with_scores: true,
+            })
+        );
+        assert_eq!(
+            ZRange::try_from(args(&["zrevrangebylex", "z", "+", "[a"])),
+            Ok(ZRange {
+                key: Bytes::from("z"),
+                range: ZRangeBy::Lex(LexBound::Inclusive(Bytes::from("a")), LexBound::Highest),
+                reverse: true,
+                limit: None,
+                with_scores: false,
+            })
+        );
+        assert_eq!(
+            ZRange::try_from(args(&["zrange", "z", "0", "-1", "LIMIT", "0", "1"])),
+            Err(RedisError::Other(
+                "syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"
+                    .to_owned()
+            ))
+        );
+        assert_eq!(
+            ZRange::try_from(args(&["zrangebyscore", "z", "a", "1"])),
+            Err(RedisError::Other("min or max is not a float".to_owned()))
+        );
+        assert_eq!(
+            ZRange::try_from(args(&["zrangebylex", "z", "a", "+"])),
+            Err(RedisError::Other(
+                "min or max not valid string range item".to_owned()
+            ))
+        );
+        assert_eq!(
+            ZRange::try_from(args(&["zrangebyscore", "z", "0", "1", "REV"])),
+            Err(RedisError::Syntax)
+        );
+    }
+
+    #[test]
+    fn bounds() {
+        assert!(ScoreBound::Exclusive(1.0).is_below(1.0));
+        assert!(!ScoreBound::Inclusive(1.0).is_below(1.0));
+        assert!(ScoreBound::Exclusive(1.0).is_above(1.0));
+        assert!(LexBound::Highest.is_below(b"z"));
+        assert!(!LexBound::Inclusive(Bytes::from("b")).is_above(b"b"));
+        assert!(LexBound::Exclusive(Bytes::from("b")).is_above(b"b"));
+    }
+}
@@ -0,0 +1,677 @@
+//! Stream requests: XADD, XTRIM, XRANGE, XREVRANGE, XREAD and XREADGROUP, along with the
+//! stream IDs they take.
+use crate::{
+    error::RedisError,
+    protocol::{
+        args::{parse_i64, Args},
+        Timeout,
+    },
+};
+use bytes::Bytes;
+use std::fmt;
+
+/// ID of a stream entry: unix time in milliseconds and a sequence number of entries added in the
+/// same millisecond.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy, Default, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+pub(super) fn invalid_id() -> RedisError {
+    RedisError::Other("Invalid stream ID specified as stream command argument".to_owned())
+}
+
+/// Parses an unsigned 64-bit integer of a stream ID.
+fn parse_u64(arg: &[u8]) -> Option<u64> {
+    if arg.is_empty() || !arg.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    std::str::from_utf8(arg).ok()?.parse().ok()
+}
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    /// Parses `<ms>-<seq>`. A missing sequence number is `missing_seq`.
+    pub(crate) fn parse(arg: &[u8], missing_seq: u64) -> Result<Self, RedisError> {
+        let (ms, seq) = match arg.iter().position(|byte| *byte == b'-') {
+            Some(dash) => (&arg[..dash], parse_u64(&arg[dash + 1..])),
+            None => (arg, Some(missing_seq)),
+        };
+        match (parse_u64(ms), seq) {
+            (Some(ms), Some(seq)) => Ok(StreamId { ms, seq }),
+            _ => Err(invalid_id()),
+        }
+    }
+
+    /// Returns the smallest ID greater than this one.
+    pub(crate) fn next(self) -> Option<Self> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(StreamId { ms: self.ms, seq }),
+            None => Some(StreamId {
+                ms: self.ms.checked_add(1)?,
+                seq: 0,
+            }),
+        }
+    }
+
+    /// Returns the greatest ID smaller than this one.
+    pub(crate) fn previous(self) -> Option<Self> {
+        match self.seq.checked_sub(1) {
+            Some(seq) => Some(StreamId { ms: self.ms, seq }),
+            None => Some(StreamId {
+                ms: self.ms.checked_sub(1)?,
+                seq: u64::MAX,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+/// ID of an entry to add.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum XAddId {
+    /// `*`: generated from the current time.
+    Auto,
+    /// `<ms>-*`: the sequence number is generated.
+    AutoSeq(u64),
+    Explicit(StreamId),
+}
+
+impl XAddId {
+    fn parse(arg: &[u8]) -> Result<Self, RedisError> {
+        if arg == b"*" {
+            return Ok(XAddId::Auto);
+        }
+        match arg.strip_suffix(b"-*") {
+            Some(ms) => parse_u64(ms).map(XAddId::AutoSeq).ok_or_else(invalid_id),
+            None => StreamId::parse(arg, 0).map(XAddId::Explicit),
+        }
+    }
+}
+
+/// Which entries trimming removes.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum TrimStrategy {
+    /// Oldest entries beyond the length.
+    MaxLen(usize),
+    /// Entries with IDs lower than the ID.
+    MinId(StreamId),
+}
+
+/// Trimming of a stream. Serves XTRIM and the trimming options of XADD.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct Trim {
+    pub strategy: TrimStrategy,
+    /// `~`: trimming may leave more entries than requested if it is cheaper.
+    pub approximate: bool,
+    /// Maximum number of entries approximate trimming removes. `None` for the default.
+    pub limit: Option<usize>,
+}
+
+/// Parses trimming options: the strategy given as `option`, followed by an optional `=` or `~`
+/// and the threshold, and LIMIT. Returns `Ok(false)` if the option is not a trimming one.
+fn parse_trim_option(
+    option: &[u8],
+    args: &mut Args,
+    trim: &mut Option<(TrimStrategy, bool)>,
+    limit: &mut Option<usize>,
+) -> Result<bool, RedisError> {
+    let option = option.to_ascii_lowercase();
+    if option == b"limit" {
+        let value = parse_i64(&args.next().ok_or(RedisError::Syntax)?)?;
+        *limit = Some(
+            usize::try_from(value)
+                .map_err(|_| RedisError::Other("The LIMIT argument must be >= 0.".to_owned()))?,
+        );
+        return Ok(true);
+    }
+    if option != b"maxlen" && option != b"minid" {
+        return Ok(false);
+    }
+    let mut threshold = args.next().ok_or(RedisError::Syntax)?;
+    let approximate = threshold[..] == b"~"[..];
+    if approximate || threshold[..] == b"="[..] {
+        threshold = args.next().ok_or(RedisError::Syntax)?;
+    }
+    let strategy = if option == b"maxlen" {
+        let max_len = parse_i64(&threshold)?;
+        TrimStrategy::MaxLen(
+            usize::try_from(max_len)
+                .map_err(|_| RedisError::Other("The MAXLEN argument must be >= 0.".to_owned()))?,
+        )
+    } else {
+        TrimStrategy::MinId(StreamId::parse(&threshold, 0)?)
+    };
+    *trim = Some((strategy, approximate));
+    Ok(true)
+}
+
+impl Trim {
+    fn new(
+        trim: Option<(TrimStrategy, bool)>,
+        limit: Option<usize>,
+    ) -> Result<Option<Self>, RedisError> {
+        let (strategy, approximate) = match trim {
+            Some(trim) => trim,
+            None if limit.is_some() => return Err(RedisError::Syntax),
+            None => return Ok(None),
+        };
+        if limit.is_some() && !approximate {
+            return Err(RedisError::Other(
+                "syntax error, LIMIT cannot be used without the special ~ option".to_owned(),
+            ));
+        }
+        Ok(Some(Trim {
+            strategy,
+            approximate,
+            limit,
+        }))
+    }
+}
+
+/// Adds an entry to a stream.
+#[derive(Eq, PartialEq, Debug)]
+pub struct XAdd {
+    pub key: Bytes,
+    /// Don't create the stream if it doesn't exist.
+    pub no_mkstream: bool,
+    /// Trimming applied after the entry is added.
+    pub trim: Option<Trim>,
+    pub id: XAddId,
+    /// Field-value pairs, flattened.
+    pub fields: Vec<Bytes>,
+}
+
+impl TryFrom<Args> for XAdd {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let key = args.required()?;
+        let mut no_mkstream = false;
+        let mut trim = None;
+        let mut limit = None;
+        let id = loop {
+            let arg = args.required()?;
+            if arg.eq_ignore_ascii_case(b"NOMKSTREAM") {
+                no_mkstream = true;
+            } else if !parse_trim_option(&arg, &mut args, &mut trim, &mut limit)? {
+                break XAddId::parse(&arg)?;
+            }
+        };
+        let trim = Trim::new(trim, limit)?;
+        let fields: Vec<Bytes> = args.collect();
+        if fields.is_empty() || fields.len() & 1 == 1 {
+            return Err(RedisError::WrongArity("xadd".to_owned()));
+        }
+
+        Ok(XAdd {
+            key,
+            no_mkstream,
+            trim,
+            id,
+            fields,
+        })
+    }
+}
+
+/// Trims a stream.
+#[derive(Eq, PartialEq, Debug)]
+pub struct XTrim {
+    pub key: Bytes,
+    pub trim: Trim,
+}
+
+impl TryFrom<Args> for XTrim {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let key = args.required()?;
+        // A strategy and a threshold.
+        if args.as_slice().len() < 2 {
+            return Err(RedisError::WrongArity("xtrim".to_owned()));
+        }
+        let mut trim = None;
+        let mut limit = None;
+        while let Some(arg) = args.next() {
+            if !parse_trim_option(&arg, &mut args, &mut trim, &mut limit)? {
+                return Err(RedisError::Syntax);
+            }
+        }
+        let trim = Trim::new(trim, limit)?.ok_or(RedisError::Syntax)?;
+
+        Ok(XTrim { key, trim })
+    }
+}
+
+/// Returns entries of a stream with IDs in an inclusive range. Serves XRANGE and XREVRANGE.
+#[derive(Eq, PartialEq, Debug)]
+pub struct XRange {
+    pub key: Bytes,
+    pub start: StreamId,
+    pub end: StreamId,
+    /// Maximum number of entries to return.
+    pub count: Option<usize>,
+    /// Order from the highest ID to the lowest.
+    pub reverse: bool,
+}
+
+/// Parses a bound of a range: `-`, `+`, or an ID which may be prefixed with `(` to make it
+/// exclusive. A missing sequence number is 0 for the start and the maximum for the end.
+pub(super) fn parse_range_bound(arg: &[u8], is_start: bool) -> Result<StreamId, RedisError> {
+    match arg {
+        b"-" => return Ok(StreamId::MIN),
+        b"+" => return Ok(StreamId::MAX),
+        _ => {}
+    }
+    let missing_seq = if is_start { 0 } else { u64::MAX };
+    match (arg.strip_prefix(b"("), is_start) {
+        (Some(id), true) => StreamId::parse(id, missing_seq)?
+            .next()
+            .ok_or_else(|| RedisError::Other("invalid start ID for the interval".to_owned())),
+        (Some(id), false) => StreamId::parse(id, missing_seq)?
+            .previous()
+            .ok_or_else(|| RedisError::Other("invalid end ID for the interval".to_owned())),
+        (None, _) => StreamId::parse(arg, missing_seq),
+    }
+}
+
+impl TryFrom<Args> for XRange {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let reverse = args.command() == "xrevrange";
+        let key = args.required()?;
+        let (first, second) = (args.required()?, args.required()?);
+        let (start, end) = match reverse {
+            true => (second, first),
+            false => (first, second),
+        };
+        let start = parse_range_bound(&start, true)?;
+        let end = parse_range_bound(&end, false)?;
+        let mut count = None;
+        while let Some(arg) = args.next() {
+            if !arg.eq_ignore_ascii_case(b"COUNT") {
+                return Err(RedisError::Syntax);
+            }
+            let value = parse_i64(&args.next().ok_or(RedisError::Syntax)?)?;
+            count = Some(usize::try_from(value).unwrap_or(0));
+        }
+
+        Ok(XRange {
+            key,
+            start,
+            end,
+            count,
+            reverse,
+        })
+    }
+}
+
+/// Position in a stream to read entries after.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum ReadAfter {
+    Id(StreamId),
+    /// `$` of XREAD: the last entry when the request is received, so only new entries are read.
+    /// `>` of XREADGROUP: the last entry delivered to the group, so only entries never
+    /// delivered to it are read.
+    Last,
+}
+
+/// Consumer reading with XREADGROUP.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct ReadGroup {
+    pub group: Bytes,
+    pub consumer: Bytes,
+    /// Don't add read entries to the pending entries list, as if they were acknowledged.
+    pub no_ack: bool,
+}
+
+/// Reads entries of streams, optionally blocking until there are some. Serves XREAD and
+/// XREADGROUP.
+#[derive(Eq, PartialEq, Debug)]
+pub struct XRead {
+    /// The consumer of XREADGROUP.
+    pub group: Option<ReadGroup>,
+    /// Maximum number of entries to read from each stream.
+    pub count: Option<usize>,
+    /// How long to block if there are no entries. Doesn't block if `None`.
+    pub timeout: Option<Timeout>,
+    pub streams: Vec<(Bytes, ReadAfter)>,
+}
+
+impl TryFrom<Args> for XRead {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let read_group = args.command() == "xreadgroup";
+        let mut group = None;
+        let mut no_ack = false;
+        let mut count = None;
+        let mut timeout = None;
+        loop {
+            let arg = args.next().ok_or(RedisError::Syntax)?;
+            match arg.to_ascii_lowercase().as_slice() {
+                b"count" => {
+                    let value = parse_i64(&args.next().ok_or(RedisError::Syntax)?)?;
+                    // A count which is not positive means no limit.
+                    count = usize::try_from(value).ok().filter(|count| *count > 0);
+                }
+                b"block" => {
+                    let value = args.next().ok_or(RedisError::Syntax)?;
+                    timeout = Some(Timeout::parse_millis(&value)?);
+                }
+                b"group" if read_group => {
+                    let name = args.next().ok_or(RedisError::Syntax)?;
+                    let consumer = args.next().ok_or(RedisError::Syntax)?;
+                    group = Some((name, consumer));
+                }
+                b"noack" if read_group => no_ack = true,
+                b"streams" => break,
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+        let group = match (group, read_group) {
+            (Some((group, consumer)), _) => Some(ReadGroup {
+                group,
+                consumer,
+                no_ack,
+            }),
+            (None, true) => {
+                return Err(RedisError::Other(
+                    "Missing GROUP option for XREADGROUP".to_owned(),
+                ))
+            }
+            (None, false) => None,
+        };
+        let rest: Vec<Bytes> = args.collect();
+        if rest.is_empty() || rest.len() & 1 == 1 {
+            let (command, last) = match read_group {
+                true => ("xreadgroup", '>'),
+                false => ("xread", '$'),
+            };
+            return Err(RedisError::Other(format!(
+                "Unbalanced '{command}' list of streams: for each stream key an ID or '{last}' \
+                 must be specified."
+            )));
+        }
+        let (keys, ids) = rest.split_at(rest.len() / 2);
+        let streams =
+            keys.iter()
+                .zip(ids)
+                .map(|(key, id)| {
+                    let after = match (&id[..], read_group) {
+                        (b"$", false) | (b">", true) => ReadAfter::Last,
+                        (b"$", true) => return Err(RedisError::Other(
+                            "The $ ID is meaningless in the context of XREADGROUP: you want to \
+                             read the history of this consumer by specifying a proper ID, or \
+                             use the > ID to get new messages. The $ ID would just return an \
+                             empty result set."
+                                .to_owned(),
+                        )),
+                        (b">", false) => {
+                            return Err(RedisError::Other(
+                                "The > ID can be specified only when calling XREADGROUP using the \
+                             GROUP <group> <consumer> option."
+                                    .to_owned(),
+                            ))
+                        }
+                        (id, _) => ReadAfter::Id(StreamId::parse(id, 0)?),
+                    };
+                    Ok((key.clone(), after))
+                })
+                .collect::<Result<_, RedisError>>()?;
+
+        Ok(XRead {
+            group,
+            count,
+            timeout,
+            streams,
+        })
+    }
+}
+
+#[cfg(test)]
+mod try_from_args {
+    use super::*;
+    use crate::protocol::args::args;
+    use std::time::Duration;
+
+    fn id(ms: u64, seq: u64) -> StreamId {
+        StreamId { ms, seq }
+    }
+
+    #[test]
+    fn ids() {
+        assert_eq!(StreamId::parse(b"5-3", 0), Ok(id(5, 3)));
+        assert_eq!(StreamId::parse(b"5", u64::MAX), Ok(id(5, u64::MAX)));
+        for invalid in [&b""[..], b"-1", b"5-", b"a-1", b"1-2-3", b"+5"] {
+            assert_eq!(StreamId::parse(invalid, 0), Err(invalid_id()));
+        }
+        assert_eq!(id(1, u64::MAX).next(), Some(id(2, 0)));
+        assert_eq!(StreamId::MAX.next(), None);
+        assert_eq!(id(2, 0).previous(), Some(id(1, u64::MAX)));
+        assert_eq!(StreamId::MIN.previous(), None);
+        assert_eq!(id(5, 3).to_string(), "5-3");
+        assert!(id(1, 5) < id(2, 0));
+    }
+
+    #[test]
+    fn xadd() {
+        assert_eq!(
+            XAdd::try_from(args(&["xadd", "s", "*", "f", "v"])),
+            Ok(XAdd {
+                key: Bytes::from("s"),
+                no_mkstream: false,
+                trim: None,
+                id: XAddId::Auto,
+                fields: vec![Bytes::from("f"), Bytes::from("v")],
+            })
+        );
+        assert_eq!(
+            XAdd::try_from(args(&[
+                "xadd",
+                "s",
+                "NOMKSTREAM",
+                "MINID",
+                "~",
+                "5",
+                "LIMIT",
+                "10",
+                "7-*",
+                "f",
+                "v"
+            ])),
+            Ok(XAdd {
+                key: Bytes::from("s"),
+                no_mkstream: true,
+                trim: Some(Trim {
+                    strategy: TrimStrategy::MinId(id(5, 0)),
+                    approximate: true,
+                    limit: Some(10),
+                }),
+                id: XAddId::AutoSeq(7),
+                fields: vec![Bytes::from("f"), Bytes::from("v")],
+            })
+        );
+        assert_eq!(
+            XAdd::try_from(args(&["xadd", "s", "1-1", "f"])),
+            Err(RedisError::WrongArity("xadd".to_owned()))
+        );
+        assert_eq!(
+            XAdd::try_from(args(&["xadd", "s", "x-*", "f", "v"])),
+            Err(invalid_id())
+        );
+        assert_eq!(
+            XAdd::try_from(args(&["xadd", "s", "MAXLEN", "-1", "*", "f", "v"])),
+            Err(RedisError::Other(
+                "The MAXLEN argument must be >= 0.".to_owned()
+            ))
+        );
+        assert_eq!(
+            XAdd::try_from(args(&[
+                "xadd", "s", "MAXLEN", "1", "LIMIT", "1", "*", "f", "v"
+            ])),
+            Err(RedisError::Other(
+                "syntax error, LIMIT cannot be used without the special ~ option".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn xtrim() {
+        assert_eq!(
+            XTrim::try_from(args(&["xtrim", "s", "MAXLEN", "=", "5"])),
+            Ok(XTrim {
+                key: Bytes::from("s"),
+                trim: Trim {
+                    strategy: TrimStrategy::MaxLen(5),
+                    approximate: false,
+                    limit: None,
+                },
+            })
+        );
+        assert_eq!(
+            XTrim::try_from(args(&["xtrim", "s", "LIMIT", "5"])),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            XTrim::try_from(args(&["xtrim", "s", "MAXLEN"])),
+            Err(RedisError::WrongArity("xtrim".to_owned()))
+        );
+    }
+
+    #[test]
+    fn xrange() {
+        assert_eq!(
+            XRange::try_from(args(&["xrange", "s", "-", "5", "COUNT", "2"])),
+            Ok(XRange {
+                key: Bytes::from("s"),
+                start: StreamId::MIN,
+                end: id(5, u64::MAX),
+                count: Some(2),
+                reverse: false,
+            })
+        );
+        assert_eq!(
+            XRange::try_from(args(&["xrevrange", "s", "(5-0", "(3"])),
+            Ok(XRange {
+                key: Bytes::from("s"),
+                start: id(3, 1),
+                end: id(4, u64::MAX),
+                count: None,
+                reverse: true,
+            })
+        );
+        assert_eq!(
+            XRange::try_from(args(&[
+                "xrange",
+                "s",
+                "(18446744073709551615-18446744073709551615",
+                "+"
+            ])),
+            Err(RedisError::Other(
+                "invalid start ID for the interval".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn xread() {
+        assert_eq!(
+            XRead::try_from(args(&[
+                "xread", "COUNT", "2", "BLOCK", "100", "STREAMS", "a", "b", "$", "1"
+            ])),
+            Ok(XRead {
+                group: None,
+                count: Some(2),
+                timeout: Some(Timeout::After(Duration::from_millis(100))),
+                streams: vec![
+                    (Bytes::from("a"), ReadAfter::Last),
+                    (Bytes::from("b"), ReadAfter::Id(id(1, 0))),
+                ],
+            })
+        );
+        assert_eq!(
+            XRead::try_from(args(&["xread", "STREAMS", "a", "b", "0"])),
+            Err(RedisError::Other(
+                "Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be \
+                 specified."
+                    .to_owned()
+            ))
+        );
+        assert_eq!(
+            XRead::try_from(args(&["xread", "COUNT", "2", "a", "0"])),
+            Err(RedisError::Syntax)
+        );
+    }
+
+    #[test]
+    fn xreadgroup() {
+        assert_eq!(
+            XRead::try_from(args(&[
+                "xreadgroup",
+                "GROUP",
+                "g",
+                "c",
+                "NOACK",
+                "STREAMS",
+                "a",
+                "b",
+                ">",
+                "0"
+            ])),
+            Ok(XRead {
+                group: Some(ReadGroup {
+                    group: Bytes::from("g"),
+                    consumer: Bytes::from("c"),
+                    no_ack: true,
+                }),
+                count: None,
+                timeout: None,
+                streams: vec![
+                    (Bytes::from("a"), ReadAfter::Last),
+                    (Bytes::from("b"), ReadAfter::Id(id(0, 0))),
+                ],
+            })
+        );
+        assert_eq!(
+            XRead::try_from(args(&["xreadgroup", "STREAMS", "a", ">"])),
+            Err(RedisError::Other(
+                "Missing GROUP option for XREADGROUP".to_owned()
+            ))
+        );
+        assert_eq!(
+            XRead::try_from(args(&["xread", "NOACK", "STREAMS", "a", "0"])),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            XRead::try_from(args(&["xread", "STREAMS", "a", ">"])),
+            Err(RedisError::Other(
+                "The > ID can be specified only when calling XREADGROUP using the GROUP <group> \
+                 <consumer> option."
+                    .to_owned()
+            ))
+        );
+        assert!(XRead::try_from(args(&[
+            "xreadgroup",
+            "GROUP",
+            "g",
+            "c",
+            "STREAMS",
+            "a",
+            "$"
+        ]))
+        .is_err());
+    }
+}
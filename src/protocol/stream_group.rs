@@ -0,0 +1,573 @@
+//! Consumer group requests: XGROUP, XPENDING, XCLAIM and XAUTOCLAIM.
+use crate::{
+    error::RedisError,
+    protocol::{
+        args::{parse_i64, Args},
+        request::unknown_subcommand,
+        stream::{invalid_id, parse_range_bound},
+        ReadAfter, StreamId,
+    },
+};
+use bytes::Bytes;
+
+/// Manages consumer groups of a stream.
+#[derive(Eq, PartialEq, Debug)]
+pub enum XGroup {
+    /// Creates a group which delivers entries after the ID. `$` is the last entry of the stream.
+    Create {
+        key: Bytes,
+        group: Bytes,
+        last_id: ReadAfter,
+        /// Create an empty stream if the key doesn't exist.
+        mkstream: bool,
+        /// Number of entries the group has read, if known. Used to compute the lag.
+        entries_read: Option<u64>,
+    },
+    /// Sets the last delivered ID of a group.
+    SetId {
+        key: Bytes,
+        group: Bytes,
+        last_id: ReadAfter,
+        entries_read: Option<u64>,
+    },
+    Destroy {
+        key: Bytes,
+        group: Bytes,
+    },
+    CreateConsumer {
+        key: Bytes,
+        group: Bytes,
+        consumer: Bytes,
+    },
+    /// Deletes a consumer along with its pending entries.
+    DelConsumer {
+        key: Bytes,
+        group: Bytes,
+        consumer: Bytes,
+    },
+}
+
+/// Parses the last delivered ID of a group: `$` or an ID.
+fn parse_last_id(arg: &[u8]) -> Result<ReadAfter, RedisError> {
+    match arg {
+        b"$" => Ok(ReadAfter::Last),
+        id => StreamId::parse(id, 0).map(ReadAfter::Id),
+    }
+}
+
+/// Parses ENTRIESREAD, where -1 stands for an unknown number.
+fn parse_entries_read(arg: &[u8]) -> Result<Option<u64>, RedisError> {
+    match parse_i64(arg)? {
+        -1 => Ok(None),
+        entries_read => u64::try_from(entries_read).map(Some).map_err(|_| {
+            RedisError::Other("value for ENTRIESREAD must be positive or -1".to_owned())
+        }),
+    }
+}
+
+impl TryFrom<Args> for XGroup {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let subcommand = args.required()?;
+        let name = subcommand.to_ascii_lowercase();
+        let (min_args, max_args) = match name.as_slice() {
+            b"create" => (3, 6),
+            b"setid" => (3, 5),
+            b"destroy" => (2, 2),
+            b"createconsumer" | b"delconsumer" => (3, 3),
+            _ => return Err(unknown_subcommand(&subcommand, "XGROUP")),
+        };
+        let count = args.as_slice().len();
+        if count < min_args || count > max_args {
+            return Err(RedisError::WrongArity(format!(
+                "xgroup|{}",
+                String::from_utf8_lossy(&name)
+            )));
+        }
+        let key = args.required()?;
+        let group = args.required()?;
+        let request = match name.as_slice() {
+            b"create" | b"setid" => {
+                let last_id = parse_last_id(&args.required()?)?;
+                let create = name == b"create";
+                let mut mkstream = false;
+                let mut entries_read = None;
+                while let Some(arg) = args.next() {
+                    match arg.to_ascii_lowercase().as_slice() {
+                        b"mkstream" if create => mkstream = true,
+                        b"entriesread" => {
+                            entries_read =
+                                parse_entries_read(&args.next().ok_or(RedisError::Syntax)?)?;
+                        }
+                        _ => return Err(RedisError::Syntax),
+                    }
+                }
+                match create {
+                    true => XGroup::Create {
+                        key,
+                        group,
+                        last_id,
+                        mkstream,
+                        entries_read,
+                    },
+                    false => XGroup::SetId {
+                        key,
+                        group,
+                        last_id,
+                        entries_read,
+                    },
+                }
+            }
+            b"destroy" => XGroup::Destroy { key, group },
+            b"createconsumer" => XGroup::CreateConsumer {
+                key,
+                group,
+                consumer: args.required()?,
+            },
+            _ => XGroup::DelConsumer {
+                key,
+                group,
+                consumer: args.required()?,
+            },
+        };
+
+        Ok(request)
+    }
+}
+
+/// Returns pending entries of a group: a summary, or entries in a range if one is given.
+#[derive(Eq, PartialEq, Debug)]
+pub struct XPending {
+    pub key: Bytes,
+    pub group: Bytes,
+    pub range: Option<PendingRange>,
+}
+
+/// Pending entries with IDs in an inclusive range.
+#[derive(Eq, PartialEq, Debug)]
+pub struct PendingRange {
+    /// Only entries not delivered for this many milliseconds.
+    pub min_idle: u64,
+    pub start: StreamId,
+    pub end: StreamId,
+    pub count: usize,
+    /// Only entries of the consumer.
+    pub consumer: Option<Bytes>,
+}
+
+impl TryFrom<Args> for XPending {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let key = args.required()?;
+        let group = args.required()?;
+        if args.as_slice().is_empty() {
+            return Ok(XPending {
+                key,
+                group,
+                range: None,
+            });
+        }
+        let mut arg = args.required()?;
+        let mut min_idle = 0;
+        if arg.eq_ignore_ascii_case(b"IDLE") {
+            let value = parse_i64(&args.next().ok_or(RedisError::Syntax)?)?;
+            min_idle = u64::try_from(value).unwrap_or(0);
+            arg = args.next().ok_or(RedisError::Syntax)?;
+        }
+        let start = parse_range_bound(&arg, true)?;
+        let end = parse_range_bound(&args.next().ok_or(RedisError::Syntax)?, false)?;
+        let count = parse_i64(&args.next().ok_or(RedisError::Syntax)?)?;
+        let consumer = args.next();
+        if args.next().is_some() {
+            return Err(RedisError::Syntax);
+        }
+
+        Ok(XPending {
+            key,
+            group,
+            range: Some(PendingRange {
+                min_idle,
+                start,
+                end,
+                count: usize::try_from(count).unwrap_or(0),
+                consumer,
+            }),
+        })
+    }
+}
+
+/// Time an entry is delivered at when it is claimed.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum ClaimTime {
+    /// Milliseconds before now.
+    Idle(i64),
+    /// Unix time in milliseconds.
+    At(i64),
+}
+
+/// Changes the owner of pending entries.
+#[derive(Eq, PartialEq, Debug)]
+pub struct XClaim {
+    pub key: Bytes,
+    pub group: Bytes,
+    pub consumer: Bytes,
+    /// Only entries not delivered for this many milliseconds are claimed.
+    pub min_idle: u64,
+    pub ids: Vec<StreamId>,
+    /// Delivery time of claimed entries. Now if `None`.
+    pub time: Option<ClaimTime>,
+    /// Delivery count of claimed entries. Otherwise it is incremented.
+    pub retry_count: Option<u64>,
+    /// Claim entries which are not pending, as long as they are in the stream.
+    pub force: bool,
+    /// Reply with IDs only. The delivery count is not incremented then.
+    pub just_id: bool,
+    /// Raises the last delivered ID of the group.
+    pub last_id: Option<StreamId>,
+}
+
+/// Parses the minimum idle time of claiming commands. Negative times are 0.
+fn parse_min_idle(arg: &[u8], command: &str) -> Result<u64, RedisError> {
+    let min_idle = parse_i64(arg)
+        .map_err(|_| RedisError::Other(format!("Invalid min-idle-time argument for {command}")))?;
+    Ok(u64::try_from(min_idle).unwrap_or(0))
+}
+
+impl TryFrom<Args> for XClaim {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        // A key, a group, a consumer, the minimum idle time and an ID.
+        if args.as_slice().len() < 5 {
+            return Err(RedisError::WrongArity("xclaim".to_owned()));
+        }
+        let key = args.required()?;
+        let group = args.required()?;
+        let consumer = args.required()?;
+        let min_idle = parse_min_idle(&args.required()?, "XCLAIM")?;
+        let mut request = XClaim {
+            key,
+            group,
+            consumer,
+            min_idle,
+            ids: vec![],
+            time: None,
+            retry_count: None,
+            force: false,
+            just_id: false,
+            last_id: None,
+        };
+        // IDs go until the first argument which is not an ID.
+        let mut option = None;
+        for arg in args.by_ref() {
+            match StreamId::parse(&arg, 0) {
+                Ok(id) => request.ids.push(id),
+                Err(_) => {
+                    option = Some(arg);
+                    break;
+                }
+            }
+        }
+        if request.ids.is_empty() {
+            return Err(invalid_id());
+        }
+        while let Some(arg) = option.take().or_else(|| args.next()) {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or(RedisError::Syntax)
+                    .and_then(|value| parse_i64(&value))
+                    .map_err(|_| {
+                        RedisError::Other(format!("Invalid {name} option argument for XCLAIM"))
+                    })
+            };
+            match arg.to_ascii_lowercase().as_slice() {
+                b"idle" => request.time = Some(ClaimTime::Idle(value("IDLE")?)),
+                b"time" => request.time = Some(ClaimTime::At(value("TIME")?)),
+                b"retrycount" => {
+                    request.retry_count =
+                        Some(u64::try_from(value("RETRYCOUNT")?).map_err(|_| {
+                            RedisError::Other(
+                                "Invalid RETRYCOUNT option argument for XCLAIM".to_owned(),
+                            )
+                        })?)
+                }
+                b"force" => request.force = true,
+                b"justid" => request.just_id = true,
+                b"lastid" => {
+                    let id = args.next().ok_or(RedisError::Syntax)?;
+                    request.last_id = Some(StreamId::parse(&id, 0)?);
+                }
+                _ => {
+                    return Err(RedisError::Other(format!(
+                        "Unrecognized XCLAIM option '{}'",
+                        String::from_utf8_lossy(&arg)
+                    )))
+                }
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+/// Claims pending entries which are idle for long enough, scanning the pending entries list
+/// from a cursor.
+#[derive(Eq, PartialEq, Debug)]
+pub struct XAutoClaim {
+    pub key: Bytes,
+    pub group: Bytes,
+    pub consumer: Bytes,
+    pub min_idle: u64,
+    /// ID to start scanning at.
+    pub start: StreamId,
+    /// Maximum number of entries to claim.
+    pub count: usize,
+    /// Reply with IDs only. The delivery count is not incremented then.
+    pub just_id: bool,
+}
+
+/// Number of entries XAUTOCLAIM claims by default.
+const DEFAULT_AUTOCLAIM_COUNT: usize = 100;
+/// XAUTOCLAIM scans at most this many pending entries per entry to claim.
+pub(crate) const AUTOCLAIM_ATTEMPTS_FACTOR: usize = 10;
+
+impl TryFrom<Args> for XAutoClaim {
+    type Error = RedisError;
+
+    fn try_from(mut args: Args) -> Result<Self, Self::Error> {
+        let key = args.required()?;
+        let group = args.required()?;
+        let consumer = args.required()?;
+        let min_idle = parse_min_idle(&args.required()?, "XAUTOCLAIM")?;
+        let start = parse_range_bound(&args.required()?, true)?;
+        let mut count = DEFAULT_AUTOCLAIM_COUNT;
+        let mut just_id = false;
+        while let Some(arg) = args.next() {
+            match arg.to_ascii_lowercase().as_slice() {
+                b"count" => {
+                    let value = parse_i64(&args.next().ok_or(RedisError::Syntax)?)?;
+                    count = usize::try_from(value)
+                        .ok()
+                        .filter(|count| {
+                            *count > 0 && count.checked_mul(AUTOCLAIM_ATTEMPTS_FACTOR).is_some()
+                        })
+                        .ok_or_else(|| RedisError::Other("COUNT must be > 0".to_owned()))?;
+                }
+                b"justid" => just_id = true,
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+
+        Ok(XAutoClaim {
+            key,
+            group,
+            consumer,
+            min_idle,
+            start,
+            count,
+            just_id,
+        })
+    }
+}
+
+#[cfg(test)]
+mod try_from_args {
+    use super::*;
+    use crate::protocol::args::args;
+
+    fn id(ms: u64, seq: u64) -> StreamId {
+        StreamId { ms, seq }
+    }
+
+    #[test]
+    fn xgroup() {
+        assert_eq!(
+            XGroup::try_from(args(&[
+                "xgroup",
+                "CREATE",
+                "s",
+                "g",
+                "$",
+                "MKSTREAM",
+                "ENTRIESREAD",
+                "3"
+            ])),
+            Ok(XGroup::Create {
+                key: Bytes::from("s"),
+                group: Bytes::from("g"),
+                last_id: ReadAfter::Last,
+                mkstream: true,
+                entries_read: Some(3),
+            })
+        );
+        assert_eq!(
+            XGroup::try_from(args(&[
+                "xgroup",
+                "setid",
+                "s",
+                "g",
+                "5",
+                "ENTRIESREAD",
+                "-1"
+            ])),
+            Ok(XGroup::SetId {
+                key: Bytes::from("s"),
+                group: Bytes::from("g"),
+                last_id: ReadAfter::Id(id(5, 0)),
+                entries_read: None,
+            })
+        );
+        assert_eq!(
+            XGroup::try_from(args(&["xgroup", "SETID", "s", "g", "5", "MKSTREAM"])),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            XGroup::try_from(args(&["xgroup", "destroy", "s"])),
+            Err(RedisError::WrongArity("xgroup|destroy".to_owned()))
+        );
+        assert_eq!(
+            XGroup::try_from(args(&[
+                "xgroup",
+                "create",
+                "s",
+                "g",
+                "0",
+                "ENTRIESREAD",
+                "-2"
+            ])),
+            Err(RedisError::Other(
+                "value for ENTRIESREAD must be positive or -1".to_owned()
+            ))
+        );
+        assert_eq!(
+            XGroup::try_from(args(&["xgroup", "drop", "s", "g"])),
+            Err(RedisError::Other(
+                "unknown subcommand 'drop'. Try XGROUP HELP.".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn xpending() {
+        assert_eq!(
+            XPending::try_from(args(&["xpending", "s", "g"])),
+            Ok(XPending {
+                key: Bytes::from("s"),
+                group: Bytes::from("g"),
+                range: None,
+            })
+        );
+        assert_eq!(
+            XPending::try_from(args(&[
+                "xpending", "s", "g", "IDLE", "10", "-", "(5", "2", "c"
+            ])),
+            Ok(XPending {
+                key: Bytes::from("s"),
+                group: Bytes::from("g"),
+                range: Some(PendingRange {
+                    min_idle: 10,
+                    start: StreamId::MIN,
+                    end: id(5, u64::MAX - 1),
+                    count: 2,
+                    consumer: Some(Bytes::from("c")),
+                }),
+            })
+        );
+        assert_eq!(
+            XPending::try_from(args(&["xpending", "s", "g", "-", "+"])),
+            Err(RedisError::Syntax)
+        );
+    }
+
+    #[test]
+    fn xclaim() {
+        assert_eq!(
+            XClaim::try_from(args(&[
+                "xclaim",
+                "s",
+                "g",
+                "c",
+                "10",
+                "1-1",
+                "2",
+                "IDLE",
+                "5",
+                "RETRYCOUNT",
+                "3",
+                "JUSTID",
+                "LASTID",
+                "3-0"
+            ])),
+            Ok(XClaim {
+                key: Bytes::from("s"),
+                group: Bytes::from("g"),
+                consumer: Bytes::from("c"),
+                min_idle: 10,
+                ids: vec![id(1, 1), id(2, 0)],
+                time: Some(ClaimTime::Idle(5)),
+                retry_count: Some(3),
+                force: false,
+                just_id: true,
+                last_id: Some(id(3, 0)),
+            })
+        );
+        assert_eq!(
+            XClaim::try_from(args(&["xclaim", "s", "g", "c", "x", "1-1"])),
+            Err(RedisError::Other(
+                "Invalid min-idle-time argument for XCLAIM".to_owned()
+            ))
+        );
+        assert_eq!(
+            XClaim::try_from(args(&["xclaim", "s", "g", "c", "0", "1-1", "SOON"])),
+            Err(RedisError::Other(
+                "Unrecognized XCLAIM option 'SOON'".to_owned()
+            ))
+        );
+        assert_eq!(
+            XClaim::try_from(args(&["xclaim", "s", "g", "c", "0", "1-1", "TIME", "x"])),
+            Err(RedisError::Other(
+                "Invalid TIME option argument for XCLAIM".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn xautoclaim() {
+        assert_eq!(
+            XAutoClaim::try_from(args(&[
+                "xautoclaim",
+                "s",
+                "g",
+                "c",
+                "10",
+                "(1-1",
+                "COUNT",
+                "5"
+            ])),
+            Ok(XAutoClaim {
+                key: Bytes::from("s"),
+                group: Bytes::from("g"),
+                consumer: Bytes::from("c"),
+                min_idle: 10,
+                start: id(1, 2),
+                count: 5,
+                just_id: false,
+            })
+        );
+        assert_eq!(
+            XAutoClaim::try_from(args(&[
+                "xautoclaim",
+                "s",
+                "g",
+                "c",
+                "10",
+                "0",
+                "COUNT",
+                "0"
+            ])),
+            Err(RedisError::Other("COUNT must be > 0".to_owned()))
+        );
+    }
+}
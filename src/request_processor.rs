@@ -1,43 +1,2267 @@
 //! Handles client requests.
+use std::{
+    ops::RangeInclusive,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use bytes::Bytes;
+
 use crate::{
+    config,
     error::RedisError,
-    protocol::{self, Request, Response},
+    lcs,
+    protocol::{self, ProtocolVersion, Request, Response},
     storage,
 };
 
-/// Processes client requests.
+/// Source of unique connection ids.
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Redis version the server is compatible with. Reported to clients by HELLO.
+const REDIS_VERSION: &str = "7.4.0";
+
+/// Name of the only user. It has no password, so any password is accepted for it.
+const DEFAULT_USER: &[u8] = b"default";
+
+/// Processes client requests. Created per connection and holds the connection state.
 pub(crate) struct RequestProcessor {
     storage: storage::Storage,
+    id: u64,
+    protocol_version: ProtocolVersion,
+    client_name: Option<Bytes>,
 }
 
 impl RequestProcessor {
     pub(crate) fn new() -> Self {
         Self {
             storage: storage::Storage::instance(),
+            id: NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed),
+            protocol_version: ProtocolVersion::default(),
+            client_name: None,
         }
     }
 
-    /// Executes handler for client's request. Generates response.
-    pub(crate) async fn process_request(&self, request: Request) -> Result<Response, RedisError> {
+    /// Protocol version responses must be serialized with.
+    pub(crate) fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
+    /// Executes handler for client's request. Generates response. Errors are turned into
+    /// error responses.
+    pub(crate) async fn process_request(&mut self, request: Request) -> Response {
+        self.execute_request(request)
+            .await
+            .unwrap_or_else(Response::from)
+    }
+
+    async fn execute_request(&mut self, request: Request) -> Result<Response, RedisError> {
         match request {
-            Request::Ping => Ok(Response::Ping),
+            Request::Ping(None) => Ok(Response::Ping),
+            Request::Ping(Some(message)) => Ok(Response::Echo(message)),
             Request::Echo(arg) => Ok(Response::Echo(arg)),
             Request::Set(request) => self.process_request_set(request).await,
             Request::Get(key) => self.process_request_get(key).await,
+            Request::MSet(pairs) => {
+                self.storage.mset(pairs).await;
+                Ok(Response::Ok)
+            }
+            Request::MSetNx(pairs) => {
+                Ok(Response::Integer(self.storage.msetnx(pairs).await as i64))
+            }
+            Request::MGet(keys) => Ok(Response::BulkArray(self.storage.get_many(&keys).await)),
+            Request::Del(keys) => Ok(Response::Integer(self.storage.del(&keys).await as i64)),
+            Request::Unlink(keys) => Ok(Response::Integer(self.storage.unlink(&keys).await as i64)),
+            Request::Exists(keys) => Ok(Response::Integer(self.storage.exists(&keys).await as i64)),
+            Request::Touch(keys) => Ok(Response::Integer(self.storage.touch(&keys).await as i64)),
+            Request::Type(key) => Ok(Response::SimpleString(
+                self.storage.key_type(&key).await.to_owned(),
+            )),
+            Request::Rename { key, new_key } => self
+                .storage
+                .rename(&key, new_key, false)
+                .await
+                .map(|_| Response::Ok),
+            Request::RenameNx { key, new_key } => self
+                .storage
+                .rename(&key, new_key, true)
+                .await
+                .map(|renamed| Response::Integer(renamed as i64)),
+            Request::Copy(request) => {
+                Ok(Response::Integer(self.storage.copy(request).await as i64))
+            }
+            Request::Expire(request) => {
+                Ok(Response::Integer(self.storage.expire(request).await as i64))
+            }
+            Request::Ttl(key) => Ok(self
+                .process_request_ttl(key, |deadline| {
+                    to_seconds(deadline.saturating_sub(storage::now_ms()))
+                })
+                .await),
+            Request::Pttl(key) => Ok(self
+                .process_request_ttl(key, |deadline| deadline.saturating_sub(storage::now_ms()))
+                .await),
+            Request::ExpireTime(key) => Ok(self.process_request_ttl(key, to_seconds).await),
+            Request::PexpireTime(key) => {
+                Ok(self.process_request_ttl(key, |deadline| deadline).await)
+            }
+            Request::Persist(key) => Ok(Response::Integer(self.storage.persist(&key).await as i64)),
+            Request::IncrBy { key, increment } => self
+                .storage
+                .incr_by(key, increment)
+                .await
+                .map(Response::Integer),
+            Request::IncrByFloat { key, increment } => self
+                .storage
+                .incr_by_float(key, increment)
+                .await
+                .map(Response::BulkString),
+            Request::Append { key, value } => self
+                .storage
+                .append(key, &value)
+                .await
+                .map(|length| Response::Integer(length as i64)),
+            Request::Strlen(key) => self
+                .storage
+                .strlen(&key)
+                .await
+                .map(|length| Response::Integer(length as i64)),
+            Request::GetRange { key, start, end } => self
+                .storage
+                .get_range(&key, start, end)
+                .await
+                .map(Response::BulkString),
+            Request::SetRange { key, offset, value } => self
+                .storage
+                .set_range(key, offset, &value)
+                .await
+                .map(|length| Response::Integer(length as i64)),
+            Request::GetDel(key) => self.storage.get_del(&key).await.map(Response::Get),
+            Request::GetEx(request) => self.storage.get_ex(request).await.map(Response::Get),
+            Request::Lcs(request) => self.process_request_lcs(request).await,
+            Request::SetBit { key, offset, bit } => self
+                .storage
+                .setbit(key, offset, bit)
+                .await
+                .map(|previous| Response::Integer(previous as i64)),
+            Request::GetBit { key, offset } => self
+                .storage
+                .getbit(&key, offset)
+                .await
+                .map(|bit| Response::Integer(bit as i64)),
+            Request::BitCount(request) => self
+                .storage
+                .bitcount(&request.key, request.range)
+                .await
+                .map(|count| Response::Integer(count as i64)),
+            Request::BitPos(request) => self.storage.bitpos(&request).await.map(Response::Integer),
+            Request::BitOp(request) => self
+                .storage
+                .bitop(request)
+                .await
+                .map(|length| Response::Integer(length as i64)),
+            Request::BitField(request) => self.storage.bitfield(request).await.map(|replies| {
+                Response::Array(
+                    replies
+                        .into_iter()
+                        .map(|reply| reply.map_or(Response::Null, Response::Integer))
+                        .collect(),
+                )
+            }),
+            Request::Push {
+                key,
+                elements,
+                side,
+                only_existing,
+            } => self
+                .storage
+                .push(key, elements, side, only_existing)
+                .await
+                .map(|length| Response::Integer(length as i64)),
+            Request::Pop { key, side, count } => self.process_request_pop(key, side, count).await,
+            Request::LLen(key) => self
+                .storage
+                .llen(&key)
+                .await
+                .map(|length| Response::Integer(length as i64)),
+            Request::LRange { key, start, end } => self
+                .storage
+                .lrange(&key, start, end)
+                .await
+                .map(bulk_strings),
+            Request::LIndex { key, index } => {
+                self.storage.lindex(&key, index).await.map(Response::Get)
+            }
+            Request::LSet {
+                key,
+                index,
+                element,
+            } => self
+                .storage
+                .lset(&key, index, element)
+                .await
+                .map(|_| Response::Ok),
+            Request::LInsert {
+                key,
+                before,
+                pivot,
+                element,
+            } => self
+                .storage
+                .linsert(&key, before, &pivot, element)
+                .await
+                .map(Response::Integer),
+            Request::LRem {
+                key,
+                count,
+                element,
+            } => self
+                .storage
+                .lrem(&key, count, &element)
+                .await
+                .map(|removed| Response::Integer(removed as i64)),
+            Request::LTrim { key, start, end } => self
+                .storage
+                .ltrim(&key, start, end)
+                .await
+                .map(|_| Response::Ok),
+            Request::LPos(request) => {
+                let with_count = request.count.is_some();
+                let positions = self.storage.lpos(request).await?;
+                let mut positions = positions
+                    .into_iter()
+                    .map(|position| Response::Integer(position as i64));
+                Ok(match with_count {
+                    true => Response::Array(positions.collect()),
+                    false => positions.next().unwrap_or(Response::Null),
+                })
+            }
+            Request::LMove {
+                source,
+                destination,
+                from,
+                to,
+                timeout: None,
+            } => self
+                .storage
+                .lmove(&source, destination, from, to)
+                .await
+                .map(Response::Get),
+            Request::LMove {
+                source,
+                destination,
+                from,
+                to,
+                timeout: Some(timeout),
+            } => self
+                .storage
+                .blocking_move(source, destination, from, to, timeout)
+                .await
+                .map(Response::Get),
+            Request::BPop {
+                keys,
+                side,
+                timeout,
+            } => Ok(
+                match self.storage.blocking_pop(keys, side, 1, timeout).await? {
+                    Some(storage::Popped { key, mut elements }) => Response::Array(vec![
+                        Response::BulkString(key),
+                        Response::BulkString(elements.remove(0)),
+                    ]),
+                    None => Response::NullArray,
+                },
+            ),
+            Request::MPop(request) => self.process_request_mpop(request).await,
+            Request::HSet {
+                key,
+                pairs,
+                reply_ok,
+            } => {
+                let added = self.storage.hset(key, pairs).await?;
+                Ok(match reply_ok {
+                    true => Response::Ok,
+                    false => Response::Integer(added as i64),
+                })
+            }
+            Request::HSetNx { key, field, value } => self
+                .storage
+                .hsetnx(key, field, value)
+                .await
+                .map(|set| Response::Integer(set as i64)),
+            Request::HGet { key, field } => {
+                let mut values = self.storage.hmget(&key, &[field]).await?;
+                Ok(Response::Get(values.pop().flatten()))
+            }
+            Request::HMGet { key, fields } => self
+                .storage
+                .hmget(&key, &fields)
+                .await
+                .map(Response::BulkArray),
+            Request::HDel { key, fields } => self
+                .storage
+                .hdel(&key, &fields)
+                .await
+                .map(|removed| Response::Integer(removed as i64)),
+            Request::HGetAll(key) => Ok(Response::Map(
+                self.storage
+                    .hgetall(&key)
+                    .await?
+                    .into_iter()
+                    .map(|(field, value)| {
+                        (Response::BulkString(field), Response::BulkString(value))
+                    })
+                    .collect(),
+            )),
+            Request::HKeys(key) => {
+                let pairs = self.storage.hgetall(&key).await?;
+                Ok(bulk_strings(pairs.into_iter().map(|pair| pair.0).collect()))
+            }
+            Request::HVals(key) => {
+                let pairs = self.storage.hgetall(&key).await?;
+                Ok(bulk_strings(pairs.into_iter().map(|pair| pair.1).collect()))
+            }
+            Request::HLen(key) => self
+                .storage
+                .hlen(&key)
+                .await
+                .map(|length| Response::Integer(length as i64)),
+            Request::HExists { key, field } => {
+                let values = self.storage.hmget(&key, &[field]).await?;
+                Ok(Response::Integer(values[0].is_some() as i64))
+            }
+            Request::HStrlen { key, field } => {
+                let values = self.storage.hmget(&key, &[field]).await?;
+                Ok(Response::Integer(
+                    values[0].as_ref().map_or(0, |value| value.len()) as i64,
+                ))
+            }
+            Request::HIncrBy {
+                key,
+                field,
+                increment,
+            } => self
+                .storage
+                .hincrby(key, field, increment)
+                .await
+                .map(Response::Integer),
+            Request::HIncrByFloat {
+                key,
+                field,
+                increment,
+            } => self
+                .storage
+                .hincrbyfloat(key, field, increment)
+                .await
+                .map(Response::BulkString),
+            Request::HRandField {
+                key,
+                count,
+                with_values,
+            } => {
+                self.process_request_hrandfield(key, count, with_values)
+                    .await
+            }
+            Request::HScan(request) => {
+                let (cursor, pairs) = self.storage.hscan(&request).await?;
+                let mut items = vec![];
+                for (field, value) in pairs {
+                    items.push(Response::BulkString(field));
+                    if !request.no_values {
+                        items.push(Response::BulkString(value));
+                    }
+                }
+                Ok(Response::Array(vec![
+                    Response::BulkString(Bytes::from(cursor.to_string())),
+                    Response::Array(items),
+                ]))
+            }
+            Request::HExpire(request) => Ok(integers(self.storage.hexpire(request).await?)),
+            Request::HTtl {
+                key,
+                fields,
+                milliseconds,
+                absolute,
+            } => {
+                let deadlines = self.storage.hexpires_at(&key, &fields).await?;
+                let now = storage::now_ms();
+                Ok(Response::Array(
+                    deadlines
+                        .into_iter()
+                        .map(|deadline| match deadline {
+                            None => Response::Integer(-2),
+                            Some(None) => Response::Integer(-1),
+                            Some(Some(deadline)) => {
+                                let time = if absolute {
+                                    deadline
+                                } else {
+                                    deadline.saturating_sub(now)
+                                };
+                                let time = if milliseconds {
+                                    time
+                                } else {
+                                    to_seconds_rounded_up(time)
+                                };
+                                Response::Integer(time as i64)
+                            }
+                        })
+                        .collect(),
+                ))
+            }
+            Request::HPersist { key, fields } => {
+                Ok(integers(self.storage.hpersist(&key, &fields).await?))
+            }
+            Request::HGetEx(request) => self.storage.hgetex(request).await.map(Response::BulkArray),
+            Request::SAdd { key, members } => self
+                .storage
+                .sadd(key, members)
+                .await
+                .map(|added| Response::Integer(added as i64)),
+            Request::SRem { key, members } => self
+                .storage
+                .srem(&key, &members)
+                .await
+                .map(|removed| Response::Integer(removed as i64)),
+            Request::SMembers(key) => self.storage.smembers(&key).await.map(bulk_string_set),
+            Request::SIsMember {
+                key,
+                members,
+                multiple,
+            } => {
+                let found: Vec<i64> = self
+                    .storage
+                    .smismember(&key, &members)
+                    .await?
+                    .into_iter()
+                    .map(i64::from)
+                    .collect();
+                Ok(match multiple {
+                    true => integers(found),
+                    false => Response::Integer(found[0]),
+                })
+            }
+            Request::SCard(key) => self
+                .storage
+                .scard(&key)
+                .await
+                .map(|length| Response::Integer(length as i64)),
+            Request::SPop { key, count } => {
+                let popped = self.storage.spop(&key, count.unwrap_or(1)).await?;
+                Ok(match count {
+                    Some(_) => bulk_string_set(popped),
+                    None => Response::Get(popped.into_iter().next()),
+                })
+            }
+            Request::SRandMember { key, count } => {
+                let members = self.storage.srandmember(&key, count.unwrap_or(1)).await?;
+                Ok(match count {
+                    Some(_) => bulk_strings(members),
+                    None => Response::Get(members.into_iter().next()),
+                })
+            }
+            Request::SMove {
+                source,
+                destination,
+                member,
+            } => self
+                .storage
+                .smove(&source, destination, member)
+                .await
+                .map(|moved| Response::Integer(moved as i64)),
+            Request::SetAlgebra { operation, keys } => self
+                .storage
+                .set_algebra(operation, &keys)
+                .await
+                .map(bulk_string_set),
+            Request::SetAlgebraStore {
+                operation,
+                destination,
+                keys,
+            } => self
+                .storage
+                .set_algebra_store(operation, destination, &keys)
+                .await
+                .map(|length| Response::Integer(length as i64)),
+            Request::SInterCard(request) => self
+                .storage
+                .sintercard(&request)
+                .await
+                .map(|count| Response::Integer(count as i64)),
+            Request::ZAdd(request) => {
+                let incr = request.incr;
+                let (count, score) = self.storage.zadd(request).await?;
+                Ok(match incr {
+                    true => score.map_or(Response::Null, Response::Double),
+                    false => Response::Integer(count as i64),
+                })
+            }
+            Request::ZRange(request) => {
+                let entries = self.storage.zrange(&request).await?;
+                Ok(self.scored_members(entries, request.with_scores))
+            }
+            Request::ZRank {
+                key,
+                member,
+                reverse,
+                with_score,
+            } => {
+                let rank = self.storage.zrank(&key, &member, reverse).await?;
+                Ok(match (rank, with_score) {
+                    (Some((rank, score)), true) => Response::Array(vec![
+                        Response::Integer(rank as i64),
+                        Response::Double(score),
+                    ]),
+                    (Some((rank, _)), false) => Response::Integer(rank as i64),
+                    (None, true) => Response::NullArray,
+                    (None, false) => Response::Null,
+                })
+            }
+            Request::ZScore {
+                key,
+                members,
+                multiple,
+            } => {
+                let mut scores: Vec<Response> = self
+                    .storage
+                    .zmscore(&key, &members)
+                    .await?
+                    .into_iter()
+                    .map(|score| score.map_or(Response::Null, Response::Double))
+                    .collect();
+                Ok(match multiple {
+                    true => Response::Array(scores),
+                    false => scores.swap_remove(0),
+                })
+            }
+            Request::ZRem { key, members } => self
+                .storage
+                .zrem(&key, &members)
+                .await
+                .map(|removed| Response::Integer(removed as i64)),
+            Request::ZCard(key) => self
+                .storage
+                .zcard(&key)
+                .await
+                .map(|length| Response::Integer(length as i64)),
+            Request::ZCount { key, range } => self
+                .storage
+                .zcount(&key, &range)
+                .await
+                .map(|count| Response::Integer(count as i64)),
+            Request::ZRemRange { key, range } => self
+                .storage
+                .zremrange(&key, &range)
+                .await
+                .map(|removed| Response::Integer(removed as i64)),
+            Request::ZPop { key, count, max } => {
+                let popped = self.storage.zpop(&key, count.unwrap_or(1), max).await?;
+                Ok(match count {
+                    Some(_) => self.scored_members(popped, true),
+                    None => Response::Array(
+                        popped
+                            .into_iter()
+                            .flat_map(|(member, score)| {
+                                [Response::BulkString(member), Response::Double(score)]
+                            })
+                            .collect(),
+                    ),
+                })
+            }
+            Request::ZRandMember {
+                key,
+                count,
+                with_scores,
+            } => {
+                let entries = self.storage.zrandmember(&key, count.unwrap_or(1)).await?;
+                Ok(match count {
+                    Some(_) => self.scored_members(entries, with_scores),
+                    None => Response::Get(entries.into_iter().next().map(|entry| entry.0)),
+                })
+            }
+            Request::GeoAdd(request) => self
+                .storage
+                .geoadd(request)
+                .await
+                .map(|added| Response::Integer(added as i64)),
+            Request::GeoDist {
+                key,
+                first,
+                second,
+                unit,
+            } => {
+                let distance = self.storage.geodist(&key, first, second).await?;
+                Ok(distance.map_or(Response::Null, |distance| {
+                    format_distance(distance / unit.meters())
+                }))
+            }
+            Request::GeoPos { key, members } => {
+                let positions = self.storage.geopos(&key, &members).await?;
+                Ok(Response::Array(
+                    positions
+                        .into_iter()
+                        .map(|position| position.map_or(Response::NullArray, coordinates))
+                        .collect(),
+                ))
+            }
+            Request::GeoHash { key, members } => {
+                let hashes = self.storage.geohash(&key, &members).await?;
+                Ok(Response::Array(
+                    hashes
+                        .into_iter()
+                        .map(|hash| {
+                            hash.map_or(Response::Null, |hash| {
+                                Response::BulkString(Bytes::from(hash))
+                            })
+                        })
+                        .collect(),
+                ))
+            }
+            Request::GeoSearch(request) => self.process_request_geosearch(request).await,
+            Request::PfAdd { key, elements } => self
+                .storage
+                .pfadd(key, &elements)
+                .await
+                .map(|updated| Response::Integer(updated as i64)),
+            Request::PfCount(keys) => self
+                .storage
+                .pfcount(&keys)
+                .await
+                .map(|cardinality| Response::Integer(cardinality as i64)),
+            Request::PfMerge { destination, keys } => self
+                .storage
+                .pfmerge(destination, &keys)
+                .await
+                .map(|_| Response::Ok),
+            Request::XAdd(request) => Ok(match self.storage.xadd(request).await? {
+                Some(id) => Response::BulkString(Bytes::from(id.to_string())),
+                None => Response::Null,
+            }),
+            Request::XTrim(request) => self
+                .storage
+                .xtrim(&request.key, &request.trim)
+                .await
+                .map(|removed| Response::Integer(removed as i64)),
+            Request::XRange(request) => self.storage.xrange(&request).await.map(stream_entries),
+            Request::XLen(key) => self
+                .storage
+                .xlen(&key)
+                .await
+                .map(|length| Response::Integer(length as i64)),
+            Request::XDel { key, ids } => self
+                .storage
+                .xdel(&key, &ids)
+                .await
+                .map(|deleted| Response::Integer(deleted as i64)),
+            Request::XRead(request) => {
+                let read = match request.group {
+                    Some(group) => {
+                        self.storage
+                            .xreadgroup(group, request.streams, request.count, request.timeout)
+                            .await?
+                    }
+                    None => {
+                        self.storage
+                            .xread(request.streams, request.count, request.timeout)
+                            .await?
+                    }
+                };
+                Ok(read.map_or(Response::NullArray, |read| self.streams_read(read)))
+            }
+            Request::XGroup(request) => self.process_request_xgroup(request).await,
+            Request::XAck { key, group, ids } => self
+                .storage
+                .xack(&key, &group, &ids)
+                .await
+                .map(|acknowledged| Response::Integer(acknowledged as i64)),
+            Request::XPending(request) => self.process_request_xpending(request).await,
+            Request::XClaim(request) => {
+                let claimed = self.storage.xclaim(&request).await?;
+                Ok(match request.just_id {
+                    true => stream_ids(claimed.into_iter().map(|entry| entry.id).collect()),
+                    false => stream_entries(claimed),
+                })
+            }
+            Request::XAutoClaim(request) => {
+                let claimed = self.storage.xautoclaim(&request).await?;
+                Ok(Response::Array(vec![
+                    stream_id(claimed.cursor),
+                    match request.just_id {
+                        true => {
+                            stream_ids(claimed.claimed.into_iter().map(|entry| entry.id).collect())
+                        }
+                        false => stream_entries(claimed.claimed),
+                    },
+                    stream_ids(claimed.deleted),
+                ]))
+            }
+            Request::XInfoStream(key) => self.process_request_xinfo_stream(key).await,
+            Request::XInfoGroups(key) => self.process_request_xinfo_groups(key).await,
+            Request::XInfoConsumers { key, group } => {
+                self.process_request_xinfo_consumers(key, group).await
+            }
+            Request::Hello(request) => self.process_request_hello(request),
+            Request::ConfigGet(patterns) => Ok(self.process_request_config_get(patterns)),
+            Request::ConfigSet(pairs) => self.process_request_config_set(pairs),
+            Request::ConfigRewrite => config::rewrite().map(|_| Response::Ok),
         }
     }
 
     /// Get value from the storage by a key (returns Nil response if key is absent).
-    async fn process_request_get(&self, key: String) -> Result<Response, RedisError> {
-        match self.storage.get(&key).await {
+    async fn process_request_get(&self, key: Bytes) -> Result<Response, RedisError> {
+        match self.storage.get(&key).await? {
             Some(value) => Ok(Response::Get(Some(value))),
             None => Ok(Response::Get(None)),
         }
     }
 
-    /// Store key-value pair in the storage.
+    /// Store key-value pair in the storage. Replies with the old value if requested, otherwise
+    /// with OK or, if the condition of the request doesn't hold, with nil.
     async fn process_request_set(&self, request: protocol::Set) -> Result<Response, RedisError> {
-        self.storage.set(request).await;
+        let get = request.get;
+        Ok(match self.storage.set(request).await? {
+            (_, old_value) if get => Response::Get(old_value),
+            (true, _) => Response::Ok,
+            (false, _) => Response::Null,
+        })
+    }
+
+    /// Find the longest common subsequence of the strings stored at two keys. Replies with the
+    /// subsequence, its length or the runs it consists of. Absent keys are empty strings.
+    async fn process_request_lcs(&self, request: protocol::Lcs) -> Result<Response, RedisError> {
+        let (a, b) = match self.storage.get_pair(&request.key1, &request.key2).await {
+            Ok((a, b)) => (a.unwrap_or_default(), b.unwrap_or_default()),
+            Err(RedisError::WrongType) => {
+                return Err(RedisError::Other(
+                    "The specified keys must contain string values".to_owned(),
+                ))
+            }
+            Err(err) => return Err(err),
+        };
+        if !lcs::table_fits(a.len(), b.len()) {
+            return Err(RedisError::Other(
+                "Insufficient memory, transient memory for LCS exceeds proto-max-bulk-len"
+                    .to_owned(),
+            ));
+        }
+        let subsequence = lcs::lcs(&a, &b);
+        if request.len {
+            return Ok(Response::Integer(subsequence.bytes.len() as i64));
+        }
+        if !request.idx {
+            return Ok(Response::BulkString(Bytes::from(subsequence.bytes)));
+        }
+
+        let range = |range: RangeInclusive<usize>| {
+            Response::Array(vec![
+                Response::Integer(*range.start() as i64),
+                Response::Integer(*range.end() as i64),
+            ])
+        };
+        let matches = subsequence
+            .matches
+            .into_iter()
+            .filter(|run| run.len() >= request.min_match_len)
+            .map(|run| {
+                let length = run.len();
+                let mut fields = vec![range(run.a), range(run.b)];
+                if request.with_match_len {
+                    fields.push(Response::Integer(length as i64));
+                }
+                Response::Array(fields)
+            })
+            .collect();
+        let field = |name: &'static str| Response::BulkString(Bytes::from_static(name.as_bytes()));
+        Ok(Response::Map(vec![
+            (field("matches"), Response::Array(matches)),
+            (
+                field("len"),
+                Response::Integer(subsequence.bytes.len() as i64),
+            ),
+        ]))
+    }
+
+    /// Remove elements from a side of the list. Without a count replies with the element or nil,
+    /// with a count replies with an array of elements or, if the list doesn't exist, a null array.
+    async fn process_request_pop(
+        &self,
+        key: Bytes,
+        side: protocol::Side,
+        count: Option<usize>,
+    ) -> Result<Response, RedisError> {
+        let elements = self.storage.pop(&key, side, count.unwrap_or(1)).await?;
+        Ok(match (elements, count) {
+            (None, Some(_)) => Response::NullArray,
+            (Some(elements), Some(_)) => bulk_strings(elements),
+            (elements, None) => {
+                Response::Get(elements.and_then(|elements| elements.into_iter().next()))
+            }
+        })
+    }
+
+    /// Pop elements from the first non-empty list, blocking for BLMPOP. Replies with the key and
+    /// the elements or with a null array if all the lists are empty.
+    async fn process_request_mpop(&self, request: protocol::MPop) -> Result<Response, RedisError> {
+        let protocol::MPop {
+            keys,
+            side,
+            count,
+            timeout,
+        } = request;
+        let popped = match timeout {
+            Some(timeout) => {
+                self.storage
+                    .blocking_pop(keys, side, count, timeout)
+                    .await?
+            }
+            None => self.storage.mpop(&keys, side, count).await?,
+        };
+        Ok(match popped {
+            Some(storage::Popped { key, elements }) => {
+                Response::Array(vec![Response::BulkString(key), bulk_strings(elements)])
+            }
+            None => Response::NullArray,
+        })
+    }
+
+    /// Get random fields of the hash. Without a count replies with a field or nil. With values
+    /// replies with field-value pairs, which are nested arrays in RESP3 and a flat array in RESP2.
+    async fn process_request_hrandfield(
+        &self,
+        key: Bytes,
+        count: Option<i64>,
+        with_values: bool,
+    ) -> Result<Response, RedisError> {
+        let pairs = self.storage.hrandfield(&key, count.unwrap_or(1)).await?;
+        if count.is_none() {
+            return Ok(Response::Get(pairs.into_iter().next().map(|pair| pair.0)));
+        }
+        if !with_values {
+            return Ok(bulk_strings(pairs.into_iter().map(|pair| pair.0).collect()));
+        }
+        Ok(Response::Array(match self.protocol_version {
+            ProtocolVersion::Resp3 => pairs
+                .into_iter()
+                .map(|(field, value)| {
+                    Response::Array(vec![
+                        Response::BulkString(field),
+                        Response::BulkString(value),
+                    ])
+                })
+                .collect(),
+            ProtocolVersion::Resp2 => pairs
+                .into_iter()
+                .flat_map(|(field, value)| {
+                    [Response::BulkString(field), Response::BulkString(value)]
+                })
+                .collect(),
+        }))
+    }
+
+    /// Array reply of sorted set members. With scores the members are paired with their scores,
+    /// which are nested arrays in RESP3 and a flat array in RESP2.
+    fn scored_members(&self, entries: Vec<(Bytes, f64)>, with_scores: bool) -> Response {
+        if !with_scores {
+            return bulk_strings(entries.into_iter().map(|entry| entry.0).collect());
+        }
+        Response::Array(match self.protocol_version {
+            ProtocolVersion::Resp3 => entries
+                .into_iter()
+                .map(|(member, score)| {
+                    Response::Array(vec![Response::BulkString(member), Response::Double(score)])
+                })
+                .collect(),
+            ProtocolVersion::Resp2 => entries
+                .into_iter()
+                .flat_map(|(member, score)| [Response::BulkString(member), Response::Double(score)])
+                .collect(),
+        })
+    }
+
+    /// Reply of entries read from streams: a map from keys to entries in RESP3, and an array of
+    /// key-entries pairs in RESP2.
+    fn streams_read(&self, read: Vec<storage::StreamRead>) -> Response {
+        let read = read
+            .into_iter()
+            .map(|(key, entries)| (Response::BulkString(key), stream_entries(entries)));
+        match self.protocol_version {
+            ProtocolVersion::Resp3 => Response::Map(read.collect()),
+            ProtocolVersion::Resp2 => Response::Array(
+                read.map(|(key, entries)| Response::Array(vec![key, entries]))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Replies with members, or with arrays of a member followed by the requested distance,
+    /// geohash and coordinates. GEOSEARCHSTORE replies with the number of stored members.
+    async fn process_request_geosearch(
+        &self,
+        request: protocol::GeoSearch,
+    ) -> Result<Response, RedisError> {
+        if let Some(store) = &request.store {
+            let stored = self
+                .storage
+                .geosearchstore(&request, store.destination.clone(), store.store_dist)
+                .await?;
+            return Ok(Response::Integer(stored as i64));
+        }
+        let found = self.storage.geosearch(&request).await?;
+        let plain = !(request.with_dist || request.with_hash || request.with_coord);
+        Ok(Response::Array(
+            found
+                .into_iter()
+                .map(|found| {
+                    let member = Response::BulkString(found.member);
+                    if plain {
+                        return member;
+                    }
+                    let mut item = vec![member];
+                    if request.with_dist {
+                        item.push(format_distance(found.distance));
+                    }
+                    if request.with_hash {
+                        item.push(Response::Integer(found.score as i64));
+                    }
+                    if request.with_coord {
+                        item.push(coordinates((found.longitude, found.latitude)));
+                    }
+                    Response::Array(item)
+                })
+                .collect(),
+        ))
+    }
+
+    async fn process_request_xgroup(
+        &self,
+        request: protocol::XGroup,
+    ) -> Result<Response, RedisError> {
+        let flag = |flag: bool| Response::Integer(flag as i64);
+        match request {
+            protocol::XGroup::Create {
+                key,
+                group,
+                last_id,
+                mkstream,
+                entries_read,
+            } => self
+                .storage
+                .xgroup_create(key, group, last_id, mkstream, entries_read)
+                .await
+                .map(|_| Response::Ok),
+            protocol::XGroup::SetId {
+                key,
+                group,
+                last_id,
+                entries_read,
+            } => self
+                .storage
+                .xgroup_setid(&key, &group, last_id, entries_read)
+                .await
+                .map(|_| Response::Ok),
+            protocol::XGroup::Destroy { key, group } => {
+                self.storage.xgroup_destroy(&key, &group).await.map(flag)
+            }
+            protocol::XGroup::CreateConsumer {
+                key,
+                group,
+                consumer,
+            } => self
+                .storage
+                .xgroup_createconsumer(&key, &group, consumer)
+                .await
+                .map(flag),
+            protocol::XGroup::DelConsumer {
+                key,
+                group,
+                consumer,
+            } => self
+                .storage
+                .xgroup_delconsumer(&key, &group, &consumer)
+                .await
+                .map(|deleted| Response::Integer(deleted as i64)),
+        }
+    }
+
+    /// Without a range replies with the number of pending entries, the lowest and highest IDs
+    /// and the number of entries of each consumer. With a range replies with the entries.
+    async fn process_request_xpending(
+        &self,
+        request: protocol::XPending,
+    ) -> Result<Response, RedisError> {
+        let range = match request.range {
+            Some(range) => range,
+            None => {
+                let summary = self
+                    .storage
+                    .xpending_summary(&request.key, &request.group)
+                    .await?;
+                let (first, last) = match summary.bounds {
+                    Some((first, last)) => (stream_id(first), stream_id(last)),
+                    None => (Response::Null, Response::Null),
+                };
+                let consumers = match summary.consumers.is_empty() {
+                    true => Response::NullArray,
+                    false => Response::Array(
+                        summary
+                            .consumers
+                            .into_iter()
+                            .map(|(name, count)| {
+                                Response::Array(vec![
+                                    Response::BulkString(name),
+                                    Response::BulkString(Bytes::from(count.to_string())),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                };
+                return Ok(Response::Array(vec![
+                    Response::Integer(summary.count as i64),
+                    first,
+                    last,
+                    consumers,
+                ]));
+            }
+        };
+        let pending = self
+            .storage
+            .xpending_range(&request.key, &request.group, &range)
+            .await?;
+        Ok(Response::Array(
+            pending
+                .into_iter()
+                .map(|entry| {
+                    Response::Array(vec![
+                        stream_id(entry.id),
+                        Response::BulkString(entry.consumer),
+                        Response::Integer(entry.idle as i64),
+                        Response::Integer(entry.delivery_count as i64),
+                    ])
+                })
+                .collect(),
+        ))
+    }
+
+    async fn process_request_xinfo_stream(&self, key: Bytes) -> Result<Response, RedisError> {
+        let info = self.storage.xinfo_stream(&key).await?;
+        let field = |name: &'static str| Response::BulkString(Bytes::from_static(name.as_bytes()));
+        let entry = |entry: Option<storage::StreamEntry>| match entry {
+            Some(entry) => stream_entry(entry),
+            None => Response::Null,
+        };
+        Ok(Response::Map(vec![
+            (field("length"), Response::Integer(info.length as i64)),
+            (
+                field("last-generated-id"),
+                stream_id(info.last_generated_id),
+            ),
+            (
+                field("max-deleted-entry-id"),
+                stream_id(info.max_deleted_id),
+            ),
+            (
+                field("entries-added"),
+                Response::Integer(info.entries_added as i64),
+            ),
+            (field("recorded-first-entry-id"), stream_id(info.first_id)),
+            (field("groups"), Response::Integer(info.groups as i64)),
+            (field("first-entry"), entry(info.first_entry)),
+            (field("last-entry"), entry(info.last_entry)),
+        ]))
+    }
+
+    async fn process_request_xinfo_groups(&self, key: Bytes) -> Result<Response, RedisError> {
+        let groups = self.storage.xinfo_groups(&key).await?;
+        let field = |name: &'static str| Response::BulkString(Bytes::from_static(name.as_bytes()));
+        let optional = |value: Option<u64>| {
+            value.map_or(Response::Null, |value| Response::Integer(value as i64))
+        };
+        Ok(Response::Array(
+            groups
+                .into_iter()
+                .map(|group| {
+                    Response::Map(vec![
+                        (field("name"), Response::BulkString(group.name)),
+                        (
+                            field("consumers"),
+                            Response::Integer(group.consumers as i64),
+                        ),
+                        (field("pending"), Response::Integer(group.pending as i64)),
+                        (
+                            field("last-delivered-id"),
+                            stream_id(group.last_delivered_id),
+                        ),
+                        (field("entries-read"), optional(group.entries_read)),
+                        (field("lag"), optional(group.lag)),
+                    ])
+                })
+                .collect(),
+        ))
+    }
+
+    /// Replies with -1 as the inactive time of consumers which never read or claimed entries.
+    async fn process_request_xinfo_consumers(
+        &self,
+        key: Bytes,
+        group: Bytes,
+    ) -> Result<Response, RedisError> {
+        let consumers = self.storage.xinfo_consumers(&key, &group).await?;
+        let field = |name: &'static str| Response::BulkString(Bytes::from_static(name.as_bytes()));
+        Ok(Response::Array(
+            consumers
+                .into_iter()
+                .map(|consumer| {
+                    Response::Map(vec![
+                        (field("name"), Response::BulkString(consumer.name)),
+                        (field("pending"), Response::Integer(consumer.pending as i64)),
+                        (field("idle"), Response::Integer(consumer.idle as i64)),
+                        (
+                            field("inactive"),
+                            Response::Integer(
+                                consumer.inactive.map_or(-1, |inactive| inactive as i64),
+                            ),
+                        ),
+                    ])
+                })
+                .collect(),
+        ))
+    }
+
+    /// Replies with -2 if the key doesn't exist, with -1 if it has no deadline and with the
+    /// converted deadline otherwise.
+    async fn process_request_ttl(&self, key: Bytes, convert: impl FnOnce(u64) -> u64) -> Response {
+        Response::Integer(match self.storage.expires_at(&key).await {
+            None => -2,
+            Some(None) => -1,
+            Some(Some(deadline)) => convert(deadline) as i64,
+        })
+    }
+
+    /// Get configuration parameters whose names match any of the patterns.
+    fn process_request_config_get(&self, patterns: Vec<Bytes>) -> Response {
+        let patterns: Vec<&[u8]> = patterns.iter().map(|pattern| pattern.as_ref()).collect();
+        Response::Map(
+            config::current()
+                .matching_parameters(&patterns)
+                .into_iter()
+                .map(|(name, value)| {
+                    (
+                        Response::BulkString(Bytes::from_static(name.as_bytes())),
+                        Response::BulkString(Bytes::from(value)),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// Set configuration parameters of the running server.
+    fn process_request_config_set(
+        &self,
+        pairs: Vec<(Bytes, Bytes)>,
+    ) -> Result<Response, RedisError> {
+        let pairs: Vec<(String, String)> = pairs
+            .iter()
+            .map(|(name, value)| {
+                (
+                    String::from_utf8_lossy(name).into_owned(),
+                    String::from_utf8_lossy(value).into_owned(),
+                )
+            })
+            .collect();
+        config::set_at_runtime(&pairs)?;
         Ok(Response::Ok)
     }
+
+    /// Switch protocol version, authenticate and set connection name. Replies with information
+    /// about the server and the connection.
+    fn process_request_hello(&mut self, request: protocol::Hello) -> Result<Response, RedisError> {
+        if let Some((user, _password)) = &request.auth {
+            if user.as_ref() != DEFAULT_USER {
+                return Err(RedisError::WrongPass);
+            }
+        }
+        if let Some(version) = request.protocol_version {
+            self.protocol_version = version;
+        }
+        if let Some(name) = request.client_name {
+            self.client_name = Some(name).filter(|name| !name.is_empty());
+        }
+
+        let protocol_version = match self.protocol_version {
+            ProtocolVersion::Resp2 => 2,
+            ProtocolVersion::Resp3 => 3,
+        };
+        let role = match config::current().replicaof {
+            Some(_) => "replica",
+            None => "master",
+        };
+        let field = |name: &'static str| Response::BulkString(Bytes::from_static(name.as_bytes()));
+        Ok(Response::Map(vec![
+            (field("server"), field("redis")),
+            (field("version"), field(REDIS_VERSION)),
+            (field("proto"), Response::Integer(protocol_version)),
+            (field("id"), Response::Integer(self.id as i64)),
+            (field("mode"), field("standalone")),
+            (field("role"), field(role)),
+            (field("modules"), Response::Array(vec![])),
+        ]))
+    }
+}
+
+/// Array reply of bulk strings.
+fn bulk_strings(items: Vec<Bytes>) -> Response {
+    Response::Array(items.into_iter().map(Response::BulkString).collect())
+}
+
+/// Set reply of bulk strings.
+fn bulk_string_set(items: Vec<Bytes>) -> Response {
+    Response::Set(items.into_iter().map(Response::BulkString).collect())
+}
+
+/// Array reply of stream entries, each an array of the ID and the field-value pairs.
+fn stream_entries(entries: Vec<storage::StreamEntry>) -> Response {
+    Response::Array(entries.into_iter().map(stream_entry).collect())
+}
+
+/// A stream entry is an array of its ID and its fields, which are a null array if the entry
+/// was deleted.
+fn stream_entry(entry: storage::StreamEntry) -> Response {
+    Response::Array(vec![
+        stream_id(entry.id),
+        entry.fields.map_or(Response::NullArray, bulk_strings),
+    ])
+}
+
+/// Distances are bulk strings with four decimal places.
+fn format_distance(distance: f64) -> Response {
+    Response::BulkString(Bytes::from(format!("{:.4}", distance)))
+}
+
+/// Longitude and latitude as bulk strings with 17 significant digits, formatted the way
+/// `%.17g` does.
+fn coordinates((longitude, latitude): (f64, f64)) -> Response {
+    let format = |value: f64| {
+        let scientific = format!("{:.16e}", value);
+        let (mantissa, exponent) = scientific
+            .split_once('e')
+            .expect("Exponent notation has an exponent");
+        let exponent: i32 = exponent.parse().expect("Exponent is an integer");
+        let trim = |digits: &str| match digits.contains('.') {
+            true => digits
+                .trim_end_matches('0')
+                .trim_end_matches('.')
+                .to_owned(),
+            false => digits.to_owned(),
+        };
+        let formatted = match exponent {
+            -4..=16 => trim(&format!("{:.*}", (16 - exponent) as usize, value)),
+            _ => {
+                let sign = if exponent < 0 { '-' } else { '+' };
+                format!("{}e{}{:02}", trim(mantissa), sign, exponent.abs())
+            }
+        };
+        Response::BulkString(Bytes::from(formatted))
+    };
+    Response::Array(vec![format(longitude), format(latitude)])
+}
+
+fn stream_id(id: protocol::StreamId) -> Response {
+    Response::BulkString(Bytes::from(id.to_string()))
+}
+
+fn stream_ids(ids: Vec<protocol::StreamId>) -> Response {
+    Response::Array(ids.into_iter().map(stream_id).collect())
+}
+
+/// Array reply of integers.
+fn integers(items: Vec<i64>) -> Response {
+    Response::Array(items.into_iter().map(Response::Integer).collect())
+}
+
+/// Converts milliseconds to seconds, rounding to the nearest second.
+fn to_seconds(milliseconds: u64) -> u64 {
+    (milliseconds + 500) / 1000
+}
+
+/// Converts milliseconds to seconds, rounding up, so a live hash field never reports a TTL of 0
+/// seconds.
+fn to_seconds_rounded_up(milliseconds: u64) -> u64 {
+    milliseconds.div_ceil(1000)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bytes::BytesMut;
+    use ProtocolVersion::{Resp2, Resp3};
+
+    /// Parses an inline command and executes it.
+    async fn execute(processor: &mut RequestProcessor, command: &str) -> Response {
+        let mut buffer = BytesMut::from(format!("{command}\r\n").as_str());
+        match Request::deserialize(&mut buffer) {
+            Ok(Some(request)) => processor.process_request(request).await,
+            Ok(None) => panic!("incomplete command"),
+            Err(err) => Response::from(err),
+        }
+    }
+
+    #[tokio::test]
+    async fn ping() {
+        let client = &mut RequestProcessor::new();
+        assert_eq!(execute(client, "PING").await, Response::Ping);
+        assert_eq!(
+            execute(client, "PING hello").await,
+            Response::Echo(Bytes::from("hello"))
+        );
+        assert_eq!(
+            execute(client, "PING a b").await,
+            Response::from(RedisError::WrongArity("ping".to_owned()))
+        );
+    }
+
+    #[tokio::test]
+    async fn hello_switches_protocol() {
+        let mut processor = RequestProcessor::new();
+        let response = processor
+            .process_request(Request::Hello(protocol::Hello {
+                protocol_version: Some(Resp3),
+                auth: None,
+                client_name: Some(Bytes::from("worker")),
+            }))
+            .await;
+        assert_eq!(processor.protocol_version(), Resp3);
+        let fields = match response {
+            Response::Map(fields) => fields,
+            _ => panic!("HELLO must reply with a map"),
+        };
+        assert!(fields.contains(&(
+            Response::BulkString(Bytes::from("proto")),
+            Response::Integer(3)
+        )));
+        assert_eq!(processor.client_name, Some(Bytes::from("worker")));
+
+        processor
+            .process_request(Request::Hello(protocol::Hello {
+                protocol_version: Some(Resp2),
+                ..Default::default()
+            }))
+            .await;
+        assert_eq!(processor.protocol_version(), Resp2);
+    }
+
+    #[tokio::test]
+    async fn hello_wrong_user() {
+        let mut processor = RequestProcessor::new();
+        let response = processor
+            .process_request(Request::Hello(protocol::Hello {
+                protocol_version: Some(Resp3),
+                auth: Some((Bytes::from("admin"), Bytes::from("secret"))),
+                client_name: None,
+            }))
+            .await;
+        assert_eq!(response, Response::from(RedisError::WrongPass));
+        assert_eq!(processor.protocol_version(), Resp2);
+    }
+
+    #[tokio::test]
+    async fn expire_and_ttl() {
+        let client = &mut RequestProcessor::new();
+        execute(client, "SET ttl:a v").await;
+        assert_eq!(
+            execute(client, "TTL ttl:absent").await,
+            Response::Integer(-2)
+        );
+        assert_eq!(
+            execute(client, "PEXPIRETIME ttl:absent").await,
+            Response::Integer(-2)
+        );
+        assert_eq!(execute(client, "TTL ttl:a").await, Response::Integer(-1));
+        assert_eq!(
+            execute(client, "EXPIRE ttl:absent 10").await,
+            Response::Integer(0)
+        );
+        assert_eq!(
+            execute(client, "EXPIRE ttl:a 10 GT").await,
+            Response::Integer(0)
+        );
+        assert_eq!(
+            execute(client, "EXPIRE ttl:a 100 NX").await,
+            Response::Integer(1)
+        );
+        assert_eq!(execute(client, "TTL ttl:a").await, Response::Integer(100));
+        assert_eq!(
+            execute(client, "EXPIRE ttl:a 10 NX").await,
+            Response::Integer(0)
+        );
+        assert_eq!(
+            execute(client, "EXPIRE ttl:a 200 LT").await,
+            Response::Integer(0)
+        );
+        assert_eq!(
+            execute(client, "EXPIRE ttl:a 200 XX GT").await,
+            Response::Integer(1)
+        );
+        assert_eq!(
+            execute(client, "PEXPIREAT ttl:a 4102444800000").await,
+            Response::Integer(1)
+        );
+        assert_eq!(
+            execute(client, "EXPIRETIME ttl:a").await,
+            Response::Integer(4102444800)
+        );
+        assert_eq!(
+            execute(client, "PEXPIRETIME ttl:a").await,
+            Response::Integer(4102444800000)
+        );
+        match execute(client, "PTTL ttl:a").await {
+            Response::Integer(ttl) => assert!(ttl > 100_000),
+            response => panic!("unexpected response {response:?}"),
+        }
+
+        assert_eq!(execute(client, "PERSIST ttl:a").await, Response::Integer(1));
+        assert_eq!(execute(client, "PERSIST ttl:a").await, Response::Integer(0));
+        assert_eq!(execute(client, "TTL ttl:a").await, Response::Integer(-1));
+
+        // A deadline in the past deletes the key.
+        assert_eq!(
+            execute(client, "EXPIREAT ttl:a 1").await,
+            Response::Integer(1)
+        );
+        assert_eq!(execute(client, "EXISTS ttl:a").await, Response::Integer(0));
+    }
+
+    #[tokio::test]
+    async fn set_options() {
+        let client = &mut RequestProcessor::new();
+        let get = |value: &'static str| Response::Get(Some(Bytes::from(value)));
+        assert_eq!(execute(client, "SET set:a 1 XX").await, Response::Null);
+        assert_eq!(
+            execute(client, "SET set:a 1 XX GET").await,
+            Response::Get(None)
+        );
+        assert_eq!(execute(client, "SET set:a 1 NX EX 100").await, Response::Ok);
+        assert_eq!(execute(client, "SET set:a 2 NX GET").await, get("1"));
+        assert_eq!(
+            execute(client, "SET set:a 2 XX KEEPTTL GET").await,
+            get("1")
+        );
+        assert_eq!(execute(client, "TTL set:a").await, Response::Integer(100));
+        assert_eq!(execute(client, "SET set:a 3").await, Response::Ok);
+        assert_eq!(execute(client, "TTL set:a").await, Response::Integer(-1));
+        assert_eq!(execute(client, "SET set:a 4 PXAT 1 GET").await, get("3"));
+        assert_eq!(execute(client, "EXISTS set:a").await, Response::Integer(0));
+    }
+
+    #[tokio::test]
+    async fn lcs() {
+        let client = &mut RequestProcessor::new();
+        execute(client, "SET lcs:a ohmytext").await;
+        execute(client, "SET lcs:b mynewtext").await;
+        assert_eq!(
+            execute(client, "LCS lcs:a lcs:b").await,
+            Response::BulkString(Bytes::from("mytext"))
+        );
+        assert_eq!(
+            execute(client, "LCS lcs:a lcs:b LEN").await,
+            Response::Integer(6)
+        );
+        assert_eq!(
+            execute(client, "LCS lcs:a lcs:absent").await,
+            Response::BulkString(Bytes::new())
+        );
+        let range =
+            |start, end| Response::Array(vec![Response::Integer(start), Response::Integer(end)]);
+        assert_eq!(
+            execute(client, "LCS lcs:a lcs:b IDX MINMATCHLEN 4 WITHMATCHLEN").await,
+            Response::Map(vec![
+                (
+                    Response::BulkString(Bytes::from("matches")),
+                    Response::Array(vec![Response::Array(vec![
+                        range(4, 7),
+                        range(5, 8),
+                        Response::Integer(4)
+                    ])])
+                ),
+                (
+                    Response::BulkString(Bytes::from("len")),
+                    Response::Integer(6)
+                ),
+            ])
+        );
+        execute(client, "RPUSH lcs:list a").await;
+        assert_eq!(
+            execute(client, "LCS lcs:a lcs:list").await,
+            Response::from(RedisError::Other(
+                "The specified keys must contain string values".to_owned()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn lists() {
+        let client = &mut RequestProcessor::new();
+        let bulk = |value: &'static str| Response::BulkString(Bytes::from(value));
+        assert_eq!(
+            execute(client, "RPUSH lists:a a b c b").await,
+            Response::Integer(4)
+        );
+        assert_eq!(
+            execute(client, "LPOP lists:a").await,
+            Response::Get(Some(Bytes::from("a")))
+        );
+        assert_eq!(
+            execute(client, "RPOP lists:a 2").await,
+            Response::Array(vec![bulk("b"), bulk("c")])
+        );
+        assert_eq!(
+            execute(client, "LPOP lists:absent").await,
+            Response::Get(None)
+        );
+        assert_eq!(
+            execute(client, "LPOP lists:absent 1").await,
+            Response::NullArray
+        );
+        execute(client, "RPUSH lists:a c b").await;
+        assert_eq!(
+            execute(client, "LPOS lists:a b").await,
+            Response::Integer(0)
+        );
+        assert_eq!(
+            execute(client, "LPOS lists:a b COUNT 0").await,
+            Response::Array(vec![Response::Integer(0), Response::Integer(2)])
+        );
+        assert_eq!(execute(client, "LPOS lists:a x").await, Response::Null);
+        assert_eq!(
+            execute(client, "LPOS lists:a x COUNT 1").await,
+            Response::Array(vec![])
+        );
+        assert_eq!(
+            execute(client, "LRANGE lists:a 0 -1").await,
+            Response::Array(vec![bulk("b"), bulk("c"), bulk("b")])
+        );
+        execute(client, "SET lists:string v").await;
+        assert_eq!(
+            execute(client, "LLEN lists:string").await,
+            Response::from(RedisError::WrongType)
+        );
+        assert_eq!(
+            execute(client, "GET lists:a").await,
+            Response::from(RedisError::WrongType)
+        );
+    }
+
+    #[tokio::test]
+    async fn hashes() {
+        let client = &mut RequestProcessor::new();
+        let bulk = |value: &'static str| Response::BulkString(Bytes::from(value));
+        assert_eq!(
+            execute(client, "HSET hashes:a f 1").await,
+            Response::Integer(1)
+        );
+        assert_eq!(execute(client, "HMSET hashes:a f 2").await, Response::Ok);
+        assert_eq!(
+            execute(client, "HGET hashes:a f").await,
+            Response::Get(Some(Bytes::from("2")))
+        );
+        assert_eq!(
+            execute(client, "HGET hashes:a absent").await,
+            Response::Get(None)
+        );
+        assert_eq!(
+            execute(client, "HGETALL hashes:a").await,
+            Response::Map(vec![(bulk("f"), bulk("2"))])
+        );
+        assert_eq!(
+            execute(client, "HSTRLEN hashes:a f").await,
+            Response::Integer(1)
+        );
+        assert_eq!(
+            execute(client, "HEXISTS hashes:a absent").await,
+            Response::Integer(0)
+        );
+        assert_eq!(
+            execute(client, "HRANDFIELD hashes:a").await,
+            Response::Get(Some(Bytes::from("f")))
+        );
+        assert_eq!(
+            execute(client, "HRANDFIELD hashes:a -2 WITHVALUES").await,
+            Response::Array(vec![bulk("f"), bulk("2"), bulk("f"), bulk("2")])
+        );
+        execute(client, "HELLO 3").await;
+        assert_eq!(
+            execute(client, "HRANDFIELD hashes:a 1 WITHVALUES").await,
+            Response::Array(vec![Response::Array(vec![bulk("f"), bulk("2")])])
+        );
+        assert_eq!(
+            execute(client, "HSCAN hashes:a 0 NOVALUES").await,
+            Response::Array(vec![bulk("0"), Response::Array(vec![bulk("f")])])
+        );
+        assert_eq!(
+            execute(client, "HRANDFIELD hashes:absent").await,
+            Response::Get(None)
+        );
+        assert_eq!(
+            execute(client, "HRANDFIELD hashes:a 1 VALUES").await,
+            Response::from(RedisError::Syntax)
+        );
+        assert_eq!(
+            execute(client, "HRANDFIELD hashes:a -9223372036854775808").await,
+            Response::from(RedisError::Other("value is out of range".to_owned()))
+        );
+        assert_eq!(
+            execute(
+                client,
+                "HRANDFIELD hashes:a -9223372036854775807 WITHVALUES"
+            )
+            .await,
+            Response::from(RedisError::Other("value is out of range".to_owned()))
+        );
+    }
+
+    #[tokio::test]
+    async fn hash_field_expiration() {
+        let client = &mut RequestProcessor::new();
+        let integers = |items: &[i64]| {
+            Response::Array(items.iter().map(|item| Response::Integer(*item)).collect())
+        };
+        execute(client, "HSET hfe:a f 1 g 2").await;
+        assert_eq!(
+            execute(client, "HEXPIRE hfe:a 100 FIELDS 2 f absent").await,
+            integers(&[1, -2])
+        );
+        assert_eq!(
+            execute(client, "HEXPIRE hfe:a 200 NX FIELDS 2 f g").await,
+            integers(&[0, 1])
+        );
+        assert_eq!(
+            execute(client, "HTTL hfe:a FIELDS 3 f g absent").await,
+            integers(&[100, 200, -2])
+        );
+        assert_eq!(
+            execute(client, "HTTL hfe:absent FIELDS 1 f").await,
+            integers(&[-2])
+        );
+        execute(client, "HSET hfe:b f 1").await;
+        execute(client, "HPEXPIRE hfe:b 400 FIELDS 1 f").await;
+        assert_eq!(
+            execute(client, "HTTL hfe:b FIELDS 1 f").await,
+            integers(&[1])
+        );
+        assert_eq!(
+            execute(client, "HPERSIST hfe:a FIELDS 1 g").await,
+            integers(&[1])
+        );
+        assert_eq!(
+            execute(client, "HPTTL hfe:a FIELDS 1 g").await,
+            integers(&[-1])
+        );
+        assert_eq!(
+            execute(client, "HGETEX hfe:a PERSIST FIELDS 2 f absent").await,
+            Response::BulkArray(vec![Some(Bytes::from("1")), None])
+        );
+        assert_eq!(
+            execute(client, "HPEXPIREAT hfe:a 1 FIELDS 1 f").await,
+            integers(&[2])
+        );
+        assert_eq!(execute(client, "HLEN hfe:a").await, Response::Integer(1));
+        assert_eq!(
+            execute(client, "HEXPIRE hfe:a 100 FIELDS 2 g").await,
+            Response::from(RedisError::Other(
+                "The `numfields` parameter must match the number of arguments".to_owned()
+            ))
+        );
+        execute(client, "SET hfe:string v").await;
+        assert_eq!(
+            execute(client, "HTTL hfe:string FIELDS 1 f").await,
+            Response::from(RedisError::WrongType)
+        );
+    }
+
+    #[tokio::test]
+    async fn sets() {
+        let client = &mut RequestProcessor::new();
+        let bulk = |value: &'static str| Response::BulkString(Bytes::from(value));
+        assert_eq!(
+            execute(client, "SADD sets:a 1 2 3").await,
+            Response::Integer(3)
+        );
+        assert_eq!(
+            execute(client, "SADD sets:b 3 4").await,
+            Response::Integer(2)
+        );
+        assert_eq!(
+            execute(client, "SISMEMBER sets:a 2").await,
+            Response::Integer(1)
+        );
+        assert_eq!(
+            execute(client, "SMISMEMBER sets:a 2 5").await,
+            Response::Array(vec![Response::Integer(1), Response::Integer(0)])
+        );
+        assert_eq!(
+            execute(client, "SINTER sets:a sets:b").await,
+            Response::Set(vec![bulk("3")])
+        );
+        assert_eq!(
+            execute(client, "SDIFF sets:a sets:b").await,
+            Response::Set(vec![bulk("1"), bulk("2")])
+        );
+        assert_eq!(
+            execute(client, "SUNIONSTORE sets:c sets:a sets:b").await,
+            Response::Integer(4)
+        );
+        assert_eq!(
+            execute(client, "SINTERCARD 2 sets:a sets:c LIMIT 2").await,
+            Response::Integer(2)
+        );
+        assert_eq!(
+            execute(client, "SRANDMEMBER sets:b 0").await,
+            Response::Array(vec![])
+        );
+        assert_eq!(
+            execute(client, "SRANDMEMBER sets:b -9223372036854775808").await,
+            Response::from(RedisError::Other("value is out of range".to_owned()))
+        );
+        assert_eq!(
+            execute(client, "SPOP sets:absent").await,
+            Response::Get(None)
+        );
+        assert_eq!(
+            execute(client, "SPOP sets:absent 2").await,
+            Response::Set(vec![])
+        );
+        assert_eq!(
+            execute(client, "SPOP sets:a -1").await,
+            Response::from(RedisError::NotPositive)
+        );
+        assert_eq!(
+            execute(client, "SMOVE sets:b sets:a 4").await,
+            Response::Integer(1)
+        );
+        assert_eq!(execute(client, "SCARD sets:a").await, Response::Integer(4));
+        execute(client, "SET sets:string v").await;
+        assert_eq!(
+            execute(client, "SMEMBERS sets:string").await,
+            Response::from(RedisError::WrongType)
+        );
+    }
+
+    #[tokio::test]
+    async fn sorted_sets() {
+        let client = &mut RequestProcessor::new();
+        let bulk = |value: &'static str| Response::BulkString(Bytes::from(value));
+        assert_eq!(
+            execute(client, "ZADD zsets:a 1 a 2 b 3 c").await,
+            Response::Integer(3)
+        );
+        assert_eq!(
+            execute(client, "ZADD zsets:a CH XX 5 a 4 d").await,
+            Response::Integer(1)
+        );
+        assert_eq!(
+            execute(client, "ZADD zsets:a NX INCR 1 a").await,
+            Response::Null
+        );
+        assert_eq!(
+            execute(client, "ZINCRBY zsets:a 0.5 b").await,
+            Response::Double(2.5)
+        );
+        assert_eq!(
+            execute(client, "ZRANGE zsets:a 0 -1 WITHSCORES").await,
+            Response::Array(vec![
+                bulk("b"),
+                Response::Double(2.5),
+                bulk("c"),
+                Response::Double(3.0),
+                bulk("a"),
+                Response::Double(5.0),
+            ])
+        );
+        assert_eq!(
+            execute(client, "ZRANGE zsets:a +inf (2.5 BYSCORE REV LIMIT 0 1").await,
+            Response::Array(vec![bulk("a")])
+        );
+        assert_eq!(
+            execute(client, "ZREVRANK zsets:a c WITHSCORE").await,
+            Response::Array(vec![Response::Integer(1), Response::Double(3.0)])
+        );
+        assert_eq!(
+            execute(client, "ZRANK zsets:a x WITHSCORE").await,
+            Response::NullArray
+        );
+        assert_eq!(
+            execute(client, "ZMSCORE zsets:a a x").await,
+            Response::Array(vec![Response::Double(5.0), Response::Null])
+        );
+        assert_eq!(
+            execute(client, "ZCOUNT zsets:a (2.5 +inf").await,
+            Response::Integer(2)
+        );
+        assert_eq!(
+            execute(client, "ZPOPMIN zsets:a").await,
+            Response::Array(vec![bulk("b"), Response::Double(2.5)])
+        );
+        execute(client, "HELLO 3").await;
+        assert_eq!(
+            execute(client, "ZPOPMAX zsets:a 1").await,
+            Response::Array(vec![Response::Array(vec![
+                bulk("a"),
+                Response::Double(5.0)
+            ])])
+        );
+        assert_eq!(
+            execute(client, "ZREMRANGEBYRANK zsets:a 0 -1").await,
+            Response::Integer(1)
+        );
+        assert_eq!(execute(client, "ZCARD zsets:a").await, Response::Integer(0));
+        assert_eq!(
+            execute(client, "ZRANDMEMBER zsets:a").await,
+            Response::Get(None)
+        );
+        assert_eq!(
+            execute(client, "ZRANDMEMBER zsets:a -9223372036854775808").await,
+            Response::from(RedisError::Other("value is out of range".to_owned()))
+        );
+        assert_eq!(
+            execute(
+                client,
+                "ZRANDMEMBER zsets:a -9223372036854775807 WITHSCORES"
+            )
+            .await,
+            Response::from(RedisError::Other("value is out of range".to_owned()))
+        );
+        assert_eq!(
+            execute(client, "ZADD zsets:a GT LT 1 a").await,
+            Response::from(RedisError::Other(
+                "GT, LT, and/or NX options at the same time are not compatible".to_owned()
+            ))
+        );
+        execute(client, "SET zsets:string v").await;
+        assert_eq!(
+            execute(client, "ZSCORE zsets:string a").await,
+            Response::from(RedisError::WrongType)
+        );
+    }
+
+    #[tokio::test]
+    async fn geo() {
+        let client = &mut RequestProcessor::new();
+        let bulk = |value: &'static str| Response::BulkString(Bytes::from(value));
+        assert_eq!(
+            execute(
+                client,
+                "GEOADD geo:sicily 13.361389 38.115556 Palermo 15.087269 37.502669 Catania"
+            )
+            .await,
+            Response::Integer(2)
+        );
+        assert_eq!(
+            execute(client, "GEODIST geo:sicily Palermo Catania km").await,
+            bulk("166.2742")
+        );
+        assert_eq!(
+            execute(client, "GEODIST geo:sicily Palermo absent").await,
+            Response::Null
+        );
+        assert_eq!(
+            execute(client, "GEOPOS geo:sicily Palermo absent").await,
+            Response::Array(vec![
+                Response::Array(vec![bulk("13.361389338970184"), bulk("38.115556395496299")]),
+                Response::NullArray,
+            ])
+        );
+        execute(client, "GEOADD geo:sicily 0.5 -0.25 Equator").await;
+        assert_eq!(
+            execute(client, "GEOPOS geo:sicily Equator").await,
+            Response::Array(vec![Response::Array(vec![
+                bulk("0.49999862909317017"),
+                bulk("-0.2500008153061799")
+            ])])
+        );
+        assert_eq!(
+            execute(client, "GEOHASH geo:sicily Catania").await,
+            Response::Array(vec![bulk("sqdtr74hyu0")])
+        );
+        assert_eq!(
+            execute(
+                client,
+                "GEOSEARCH geo:sicily FROMLONLAT 15 37 BYRADIUS 200 km ASC WITHDIST WITHHASH"
+            )
+            .await,
+            Response::Array(vec![
+                Response::Array(vec![
+                    bulk("Catania"),
+                    bulk("56.4413"),
+                    Response::Integer(3479447370796909),
+                ]),
+                Response::Array(vec![
+                    bulk("Palermo"),
+                    bulk("190.4424"),
+                    Response::Integer(3479099956230698),
+                ]),
+            ])
+        );
+        assert_eq!(
+            execute(
+                client,
+                "GEOSEARCH geo:sicily FROMMEMBER Palermo BYBOX 10 10 km COUNT 1"
+            )
+            .await,
+            Response::Array(vec![bulk("Palermo")])
+        );
+        assert_eq!(
+            execute(
+                client,
+                "GEOSEARCHSTORE geo:near geo:sicily FROMLONLAT 15 37 BYRADIUS 100 km STOREDIST"
+            )
+            .await,
+            Response::Integer(1)
+        );
+        assert_eq!(
+            execute(client, "GEODIST geo:sicily Palermo Catania yd").await,
+            Response::from(RedisError::Other(
+                "unsupported unit provided. please use M, KM, FT, MI".to_owned()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn bitmaps() {
+        let client = &mut RequestProcessor::new();
+        assert_eq!(
+            execute(client, "SETBIT bitmap:cmd:a 7 1").await,
+            Response::Integer(0)
+        );
+        assert_eq!(
+            execute(client, "SETBIT bitmap:cmd:a 7 0").await,
+            Response::Integer(1)
+        );
+        execute(client, "SET bitmap:cmd:b foobar").await;
+        assert_eq!(
+            execute(client, "GETBIT bitmap:cmd:b 1").await,
+            Response::Integer(1)
+        );
+        assert_eq!(
+            execute(client, "BITCOUNT bitmap:cmd:b").await,
+            Response::Integer(26)
+        );
+        assert_eq!(
+            execute(client, "BITCOUNT bitmap:cmd:b 1 1").await,
+            Response::Integer(6)
+        );
+        assert_eq!(
+            execute(client, "BITCOUNT bitmap:cmd:b 5 30 BIT").await,
+            Response::Integer(17)
+        );
+        assert_eq!(
+            execute(client, "BITPOS bitmap:cmd:b 0 1").await,
+            Response::Integer(8)
+        );
+        assert_eq!(
+            execute(client, "BITPOS bitmap:cmd:b 1 7 15 BIT").await,
+            Response::Integer(9)
+        );
+        assert_eq!(
+            execute(client, "BITOP AND bitmap:cmd:c bitmap:cmd:a bitmap:cmd:b").await,
+            Response::Integer(6)
+        );
+        assert_eq!(
+            execute(
+                client,
+                "BITFIELD bitmap:cmd:d SET i8 #0 100 INCRBY i8 #0 100 OVERFLOW FAIL INCRBY u2 100 1 GET u4 0"
+            )
+            .await,
+            Response::Array(vec![
+                Response::Integer(0),
+                Response::Integer(-56),
+                Response::Integer(1),
+                Response::Integer(12),
+            ])
+        );
+        assert_eq!(
+            execute(client, "BITFIELD_RO bitmap:cmd:d GET i8 0").await,
+            Response::Array(vec![Response::Integer(-56)])
+        );
+        assert_eq!(
+            execute(client, "SETBIT bitmap:cmd:a 4294967296 1").await,
+            Response::from(RedisError::Other(
+                "bit offset is not an integer or out of range".to_owned()
+            ))
+        );
+        assert_eq!(
+            execute(client, "SETBIT bitmap:cmd:a 0 2").await,
+            Response::from(RedisError::Other(
+                "bit is not an integer or out of range".to_owned()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn hyperloglog() {
+        let client = &mut RequestProcessor::new();
+        assert_eq!(
+            execute(client, "PFADD hll:cmd:a a b c d").await,
+            Response::Integer(1)
+        );
+        assert_eq!(
+            execute(client, "PFADD hll:cmd:a a b").await,
+            Response::Integer(0)
+        );
+        assert_eq!(
+            execute(client, "PFADD hll:cmd:b c d e").await,
+            Response::Integer(1)
+        );
+        assert_eq!(
+            execute(client, "PFCOUNT hll:cmd:a").await,
+            Response::Integer(4)
+        );
+        assert_eq!(
+            execute(client, "PFCOUNT hll:cmd:a hll:cmd:b hll:cmd:absent").await,
+            Response::Integer(5)
+        );
+        assert_eq!(
+            execute(client, "PFMERGE hll:cmd:c hll:cmd:a hll:cmd:b").await,
+            Response::Ok
+        );
+        assert_eq!(
+            execute(client, "PFCOUNT hll:cmd:c").await,
+            Response::Integer(5)
+        );
+        // The encoding is the one of Redis, so a copy made with GET and SET is still usable.
+        let raw = match execute(client, "GET hll:cmd:c").await {
+            Response::Get(Some(raw)) => raw,
+            response => panic!("{:?}", response),
+        };
+        let request = Request::Set(protocol::Set {
+            key: Bytes::from("hll:cmd:copy"),
+            value: raw,
+            ..Default::default()
+        });
+        assert_eq!(client.process_request(request).await, Response::Ok);
+        assert_eq!(
+            execute(client, "PFCOUNT hll:cmd:copy").await,
+            Response::Integer(5)
+        );
+        execute(client, "SET hll:cmd:string value").await;
+        assert_eq!(
+            execute(client, "PFADD hll:cmd:string a").await,
+            Response::from(RedisError::InvalidHll)
+        );
+    }
+
+    #[tokio::test]
+    async fn streams() {
+        let client = &mut RequestProcessor::new();
+        let bulk = |value: &'static str| Response::BulkString(Bytes::from(value));
+        let entry = |id: &'static str, value: &'static str| {
+            Response::Array(vec![
+                bulk(id),
+                Response::Array(vec![bulk("f"), bulk(value)]),
+            ])
+        };
+        assert_eq!(execute(client, "XADD streams:a 1-1 f a").await, bulk("1-1"));
+        assert_eq!(execute(client, "XADD streams:a 1-* f b").await, bulk("1-2"));
+        assert_eq!(
+            execute(client, "XADD streams:a 1 f c").await,
+            Response::from(RedisError::Other(
+                "The ID specified in XADD is equal or smaller than the target stream top item"
+                    .to_owned()
+            ))
+        );
+        assert_eq!(
+            execute(client, "XADD streams:a MAXLEN 2 2-0 f c").await,
+            bulk("2-0")
+        );
+        assert_eq!(
+            execute(client, "XADD streams:absent NOMKSTREAM * f v").await,
+            Response::Null
+        );
+        assert_eq!(
+            execute(client, "XLEN streams:a").await,
+            Response::Integer(2)
+        );
+        assert_eq!(
+            execute(client, "XRANGE streams:a - +").await,
+            Response::Array(vec![entry("1-2", "b"), entry("2-0", "c")])
+        );
+        assert_eq!(
+            execute(client, "XREVRANGE streams:a + (1-2 COUNT 5").await,
+            Response::Array(vec![entry("2-0", "c")])
+        );
+        assert_eq!(
+            execute(client, "XREAD COUNT 1 STREAMS streams:a streams:absent 0 0").await,
+            Response::Array(vec![Response::Array(vec![
+                bulk("streams:a"),
+                Response::Array(vec![entry("1-2", "b")])
+            ])])
+        );
+        assert_eq!(
+            execute(client, "XREAD BLOCK 10 STREAMS streams:a $").await,
+            Response::NullArray
+        );
+        execute(client, "HELLO 3").await;
+        assert_eq!(
+            execute(client, "XREAD STREAMS streams:a 1-2").await,
+            Response::Map(vec![(
+                bulk("streams:a"),
+                Response::Array(vec![entry("2-0", "c")])
+            )])
+        );
+        assert_eq!(
+            execute(client, "XDEL streams:a 1-2 3-0").await,
+            Response::Integer(1)
+        );
+        assert_eq!(
+            execute(client, "XTRIM streams:a MINID 3").await,
+            Response::Integer(1)
+        );
+        assert_eq!(
+            execute(client, "TYPE streams:a").await,
+            Response::SimpleString("stream".to_owned())
+        );
+        assert_eq!(
+            execute(client, "XDEL streams:a 1-x").await,
+            Response::from(RedisError::Other(
+                "Invalid stream ID specified as stream command argument".to_owned()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_groups() {
+        let client = &mut RequestProcessor::new();
+        let bulk = |value: &'static str| Response::BulkString(Bytes::from(value));
+        let entry = |id: &'static str, value: &'static str| {
+            Response::Array(vec![
+                bulk(id),
+                Response::Array(vec![bulk("f"), bulk(value)]),
+            ])
+        };
+        assert_eq!(
+            execute(client, "XGROUP CREATE groups:a g $ MKSTREAM").await,
+            Response::Ok
+        );
+        assert_eq!(
+            execute(client, "XGROUP CREATE groups:a g 0").await,
+            Response::from(RedisError::BusyGroup)
+        );
+        execute(client, "XADD groups:a 1 f a").await;
+        execute(client, "XADD groups:a 2 f b").await;
+        assert_eq!(
+            execute(client, "XREADGROUP GROUP g c COUNT 1 STREAMS groups:a >").await,
+            Response::Array(vec![Response::Array(vec![
+                bulk("groups:a"),
+                Response::Array(vec![entry("1-0", "a")])
+            ])])
+        );
+        assert_eq!(
+            execute(client, "XREADGROUP GROUP g c STREAMS groups:a 0").await,
+            Response::Array(vec![Response::Array(vec![
+                bulk("groups:a"),
+                Response::Array(vec![entry("1-0", "a")])
+            ])])
+        );
+        assert_eq!(
+            execute(client, "XPENDING groups:a g").await,
+            Response::Array(vec![
+                Response::Integer(1),
+                bulk("1-0"),
+                bulk("1-0"),
+                Response::Array(vec![Response::Array(vec![bulk("c"), bulk("1")])]),
+            ])
+        );
+        assert_eq!(
+            execute(client, "XCLAIM groups:a g d 0 1-0 JUSTID").await,
+            Response::Array(vec![bulk("1-0")])
+        );
+        assert_eq!(
+            execute(client, "XAUTOCLAIM groups:a g c 0 0").await,
+            Response::Array(vec![
+                bulk("0-0"),
+                Response::Array(vec![entry("1-0", "a")]),
+                Response::Array(vec![]),
+            ])
+        );
+        assert_eq!(
+            execute(client, "XACK groups:a g 1-0 2-0").await,
+            Response::Integer(1)
+        );
+        assert_eq!(
+            execute(client, "XPENDING groups:a g").await,
+            Response::Array(vec![
+                Response::Integer(0),
+                Response::Null,
+                Response::Null,
+                Response::NullArray,
+            ])
+        );
+        assert_eq!(
+            execute(client, "XREADGROUP GROUP x c STREAMS groups:a >").await,
+            Response::from(RedisError::NoGroup(
+                "No such key 'groups:a' or consumer group 'x' in XREADGROUP with GROUP option"
+                    .to_owned()
+            ))
+        );
+        execute(client, "HELLO 3").await;
+        assert_eq!(
+            execute(client, "XINFO GROUPS groups:a").await,
+            Response::Array(vec![Response::Map(vec![
+                (bulk("name"), bulk("g")),
+                (bulk("consumers"), Response::Integer(2)),
+                (bulk("pending"), Response::Integer(0)),
+                (bulk("last-delivered-id"), bulk("1-0")),
+                (bulk("entries-read"), Response::Integer(1)),
+                (bulk("lag"), Response::Integer(1)),
+            ])])
+        );
+        assert_eq!(
+            execute(client, "XGROUP DELCONSUMER groups:a g d").await,
+            Response::Integer(0)
+        );
+        assert_eq!(
+            execute(client, "XINFO CONSUMERS groups:a x").await,
+            Response::from(RedisError::NoGroup(
+                "No such consumer group 'x' for key name 'groups:a'".to_owned()
+            ))
+        );
+        assert_eq!(
+            execute(client, "XGROUP DESTROY groups:a g").await,
+            Response::Integer(1)
+        );
+    }
+
+    #[tokio::test]
+    async fn blocking_lists() {
+        let client = &mut RequestProcessor::new();
+        let bulk = |value: &'static str| Response::BulkString(Bytes::from(value));
+        execute(client, "RPUSH blists:b a b c").await;
+        assert_eq!(
+            execute(client, "BLPOP blists:a blists:b 0").await,
+            Response::Array(vec![bulk("blists:b"), bulk("a")])
+        );
+        assert_eq!(
+            execute(client, "LMPOP 2 blists:a blists:b RIGHT COUNT 5").await,
+            Response::Array(vec![
+                bulk("blists:b"),
+                Response::Array(vec![bulk("c"), bulk("b")])
+            ])
+        );
+        assert_eq!(
+            execute(client, "LMPOP 1 blists:a LEFT").await,
+            Response::NullArray
+        );
+        assert_eq!(
+            execute(client, "BRPOP blists:a 0.01").await,
+            Response::NullArray
+        );
+        assert_eq!(
+            execute(client, "BLMPOP 0.01 1 blists:a LEFT").await,
+            Response::NullArray
+        );
+        assert_eq!(
+            execute(client, "BLMOVE blists:a blists:b LEFT LEFT 0.01").await,
+            Response::Get(None)
+        );
+        execute(client, "SET blists:string v").await;
+        assert_eq!(
+            execute(client, "BLPOP blists:a blists:string 0").await,
+            Response::from(RedisError::WrongType)
+        );
+    }
 }
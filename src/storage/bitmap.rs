@@ -0,0 +1,504 @@
+//! Bitmap commands.
+//!
+//! Bitmaps are strings addressed bit by bit, the most significant bit of the first byte being
+//! bit 0. Writes grow the string with zero bytes as needed and reads past its end see zeros.
+use super::{now_ms, Storage, Value};
+use crate::{
+    error::RedisError,
+    protocol::{
+        BitField, BitFieldAction, BitFieldOperation, BitFieldType, BitOp, BitOperation, BitPos,
+        BitRange, BitUnit, Overflow,
+    },
+};
+use bytes::Bytes;
+
+fn get_bit(value: &[u8], offset: usize) -> bool {
+    value
+        .get(offset / 8)
+        .is_some_and(|byte| byte & (0x80 >> (offset & 7)) != 0)
+}
+
+fn set_bit(value: &mut [u8], offset: usize, bit: bool) {
+    let mask = 0x80 >> (offset & 7);
+    match bit {
+        true => value[offset / 8] |= mask,
+        false => value[offset / 8] &= !mask,
+    }
+}
+
+/// Returns the first and the last bit of the range within a string of the length, `None` if
+/// the range is empty. Indexes are clamped the way GETRANGE does.
+fn bit_range(range: Option<BitRange>, length: usize) -> Option<(usize, usize)> {
+    let range = range.unwrap_or(BitRange {
+        start: 0,
+        end: None,
+        unit: BitUnit::Byte,
+    });
+    let length = match range.unit {
+        BitUnit::Byte => length as i64,
+        BitUnit::Bit => length as i64 * 8,
+    };
+    let resolve = |index: i64| match index < 0 {
+        true => (length + index).max(0),
+        false => index,
+    };
+    let start = resolve(range.start);
+    let end = resolve(range.end.unwrap_or(-1)).min(length - 1);
+    if start > end {
+        return None;
+    }
+    Some(match range.unit {
+        BitUnit::Byte => (start as usize * 8, end as usize * 8 + 7),
+        BitUnit::Bit => (start as usize, end as usize),
+    })
+}
+
+/// Returns the bits of the byte at the index that are within the range of bits.
+fn mask(index: usize, first: usize, last: usize) -> u8 {
+    let mut mask = 0xff;
+    if index == first / 8 {
+        mask &= 0xff >> (first & 7);
+    }
+    if index == last / 8 {
+        mask &= !0xffu8.checked_shr((last & 7) as u32 + 1).unwrap_or(0);
+    }
+    mask
+}
+
+/// Reads the field of the width at the offset as an unsigned integer.
+fn get_field(value: &[u8], offset: usize, bits: u32) -> u64 {
+    (offset..offset + bits as usize).fold(0, |field, offset| {
+        field << 1 | get_bit(value, offset) as u64
+    })
+}
+
+fn set_field(value: &mut [u8], offset: usize, bits: u32, field: u64) {
+    for bit in 0..bits as usize {
+        set_bit(
+            value,
+            offset + bit,
+            field >> (bits as usize - 1 - bit) & 1 == 1,
+        );
+    }
+}
+
+/// Returns the value of the field as an integer of its type.
+fn field_value(field: u64, field_type: BitFieldType) -> i64 {
+    let bits = field_type.bits;
+    if field_type.signed && bits < 64 && field >> (bits - 1) & 1 == 1 {
+        // Extend the sign.
+        (field | u64::MAX << bits) as i64
+    } else {
+        field as i64
+    }
+}
+
+/// Fits the value to the field type as the overflow behavior requires, `None` if it doesn't
+/// fit and the behavior is FAIL.
+fn fit(value: i128, field_type: BitFieldType, overflow: Overflow) -> Option<i64> {
+    let bits = field_type.bits;
+    let (min, max) = match field_type.signed {
+        true => (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1),
+        false => (0, (1i128 << bits) - 1),
+    };
+    if (min..=max).contains(&value) {
+        return Some(value as i64);
+    }
+    match overflow {
+        Overflow::Fail => None,
+        Overflow::Sat if value > max => Some(max as i64),
+        Overflow::Sat => Some(min as i64),
+        Overflow::Wrap => {
+            let wrapped = value.rem_euclid(1i128 << bits);
+            Some(match wrapped > max {
+                true => wrapped - (1i128 << bits),
+                false => wrapped,
+            } as i64)
+        }
+    }
+}
+
+/// Returns the length the string needs for the writes, `None` if there are none.
+fn written_length(operations: &[BitFieldOperation]) -> Option<usize> {
+    operations
+        .iter()
+        .filter(|operation| operation.action != BitFieldAction::Get)
+        .map(|operation| (operation.offset + operation.field.bits as usize - 1) / 8 + 1)
+        .max()
+}
+
+impl Storage {
+    /// Sets the bit at the offset, creating the key or growing the string as needed. Returns
+    /// the previous bit.
+    pub async fn setbit(&self, key: Bytes, offset: usize, bit: bool) -> Result<bool, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let entry = keyspace.get_or_insert_with(key, now, || Value::String(Bytes::new()));
+        let mut value = entry.value.take_string()?;
+        let previous = get_bit(&value, offset);
+        if value.len() <= offset / 8 {
+            value.resize(offset / 8 + 1, 0);
+        }
+        set_bit(&mut value, offset, bit);
+        entry.value = Value::String(Bytes::from(value));
+        Ok(previous)
+    }
+
+    /// Returns the bit at the offset. Bits past the end of the string are zeros.
+    pub async fn getbit(&self, key: &[u8], offset: usize) -> Result<bool, RedisError> {
+        let value = self.get(key).await?.unwrap_or_default();
+        Ok(get_bit(&value, offset))
+    }
+
+    /// Counts the set bits of the string within the range.
+    pub async fn bitcount(&self, key: &[u8], range: Option<BitRange>) -> Result<usize, RedisError> {
+        let value = self.get(key).await?.unwrap_or_default();
+        let (first, last) = match bit_range(range, value.len()) {
+            Some(range) => range,
+            None => return Ok(0),
+        };
+        Ok((first / 8..=last / 8)
+            .map(|index| (value[index] & mask(index, first, last)).count_ones() as usize)
+            .sum())
+    }
+
+    /// Returns the position of the first bit set to the value within the range, -1 if there
+    /// is none. Without an end of the range the string is seen as padded with zeros, so a clear
+    /// bit is found right after its end.
+    pub async fn bitpos(&self, request: &BitPos) -> Result<i64, RedisError> {
+        let value = match self.get(&request.key).await? {
+            Some(value) if !value.is_empty() => value,
+            _ => return Ok(if request.bit { -1 } else { 0 }),
+        };
+        let (first, last) = match bit_range(request.range, value.len()) {
+            Some(range) => range,
+            None => return Ok(-1),
+        };
+        for index in first / 8..=last / 8 {
+            let byte = match request.bit {
+                true => value[index],
+                false => !value[index],
+            } & mask(index, first, last);
+            if byte != 0 {
+                return Ok((index * 8 + byte.leading_zeros() as usize) as i64);
+            }
+        }
+        let end_given = request.range.is_some_and(|range| range.end.is_some());
+        Ok(match request.bit || end_given {
+            true => -1,
+            false => value.len() as i64 * 8,
+        })
+    }
+
+    /// Stores the bitwise operation of the strings at the destination, overwriting it. An empty
+    /// result removes the destination. Returns the length of the result.
+    pub async fn bitop(&self, request: BitOp) -> Result<usize, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let values = request
+            .keys
+            .iter()
+            .map(|key| match keyspace.get(key, now) {
+                Some(entry) => entry.value.as_string().cloned(),
+                None => Ok(Bytes::new()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let length = values.iter().map(Bytes::len).max().unwrap_or_default();
+        let result: Vec<u8> = (0..length)
+            .map(|index| {
+                let mut bytes = values
+                    .iter()
+                    .map(|value| value.get(index).copied().unwrap_or_default());
+                let first = bytes.next().unwrap_or_default();
+                match request.operation {
+                    BitOperation::And => bytes.fold(first, |result, byte| result & byte),
+                    BitOperation::Or => bytes.fold(first, |result, byte| result | byte),
+                    BitOperation::Xor => bytes.fold(first, |result, byte| result ^ byte),
+                    BitOperation::Not => !first,
+                }
+            })
+            .collect();
+        if result.is_empty() {
+            keyspace.remove(&request.destination);
+        } else {
+            keyspace.insert(
+                request.destination,
+                Value::String(Bytes::from(result)),
+                None,
+            );
+        }
+        Ok(length)
+    }
+
+    /// Runs the operations on the fields of the string in order. Replies to GET with the
+    /// value, to SET with the previous value and to INCRBY with the new value, `None` if the
+    /// new value overflows with FAIL. Writes create the key or grow the string as needed.
+    pub async fn bitfield(&self, request: BitField) -> Result<Vec<Option<i64>>, RedisError> {
+        let length = written_length(&request.operations);
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        // Only writes create the key.
+        let mut entry = match length {
+            Some(_) => {
+                Some(keyspace.get_or_insert_with(request.key, now, || Value::String(Bytes::new())))
+            }
+            None => keyspace.get_live_mut(&request.key, now),
+        };
+        let mut value = match &mut entry {
+            Some(entry) => entry.value.take_string()?,
+            None => Vec::new(),
+        };
+        if let Some(length) = length {
+            if value.len() < length {
+                value.resize(length, 0);
+            }
+        }
+        let mut replies = Vec::with_capacity(request.operations.len());
+        for operation in &request.operations {
+            let (offset, field_type) = (operation.offset, operation.field);
+            let bits = field_type.bits;
+            let old = field_value(get_field(&value, offset, bits), field_type);
+            let new = match operation.action {
+                BitFieldAction::Get => {
+                    replies.push(Some(old));
+                    continue;
+                }
+                // An unsigned field takes the bits of a negative value, which are too many.
+                BitFieldAction::Set(new) if !field_type.signed => new as u64 as i128,
+                BitFieldAction::Set(new) => new as i128,
+                BitFieldAction::IncrBy(increment) => old as i128 + increment as i128,
+            };
+            let new = fit(new, field_type, operation.overflow);
+            if let Some(new) = new {
+                set_field(&mut value, offset, bits, new as u64);
+            }
+            replies.push(match operation.action {
+                BitFieldAction::Set(_) => new.map(|_| old),
+                _ => new,
+            });
+        }
+        if let Some(entry) = entry {
+            entry.value = Value::String(Bytes::from(value));
+        }
+        Ok(replies)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn range(start: i64, end: Option<i64>, unit: BitUnit) -> Option<BitRange> {
+        Some(BitRange { start, end, unit })
+    }
+
+    async fn bitpos(key: &'static str, bit: bool, range: Option<BitRange>) -> i64 {
+        let request = BitPos {
+            key: Bytes::from(key),
+            bit,
+            range,
+        };
+        Storage::instance().bitpos(&request).await.unwrap()
+    }
+
+    #[test]
+    fn fields() {
+        let mut value = vec![0; 3];
+        set_field(&mut value, 5, 12, 0xabc);
+        assert_eq!(value, [0x05, 0x5e, 0x00]);
+        assert_eq!(get_field(&value, 5, 12), 0xabc);
+        let signed = BitFieldType {
+            signed: true,
+            bits: 4,
+        };
+        assert_eq!(field_value(0b1110, signed), -2);
+        assert_eq!(fit(8, signed, Overflow::Wrap), Some(-8));
+        assert_eq!(fit(-9, signed, Overflow::Wrap), Some(7));
+        assert_eq!(fit(100, signed, Overflow::Sat), Some(7));
+        assert_eq!(fit(-100, signed, Overflow::Sat), Some(-8));
+        assert_eq!(fit(8, signed, Overflow::Fail), None);
+        let unsigned = BitFieldType {
+            signed: false,
+            bits: 63,
+        };
+        assert_eq!(fit(1 << 63, unsigned, Overflow::Wrap), Some(0));
+        assert_eq!(fit(-1, unsigned, Overflow::Sat), Some(0));
+        assert_eq!(
+            fit(i64::MAX as i128, unsigned, Overflow::Fail),
+            Some(i64::MAX)
+        );
+    }
+
+    #[tokio::test]
+    async fn bits() {
+        let storage = Storage::instance();
+        let key = Bytes::from("bitmap:bits");
+        assert_eq!(storage.setbit(key.clone(), 7, true).await, Ok(false));
+        assert_eq!(storage.setbit(key.clone(), 7, true).await, Ok(true));
+        assert_eq!(storage.setbit(key.clone(), 20, true).await, Ok(false));
+        assert_eq!(
+            storage.get(&key).await,
+            Ok(Some(Bytes::from_static(b"\x01\x00\x08")))
+        );
+        assert_eq!(storage.getbit(&key, 20).await, Ok(true));
+        assert_eq!(storage.getbit(&key, 1000).await, Ok(false));
+        assert_eq!(storage.getbit(b"bitmap:absent", 0).await, Ok(false));
+        // A value held elsewhere keeps its contents when the stored string is modified.
+        let held = storage.get(&key).await.unwrap().unwrap();
+        assert_eq!(storage.setbit(key.clone(), 0, true).await, Ok(false));
+        assert_eq!(held, Bytes::from_static(b"\x01\x00\x08"));
+        assert_eq!(storage.setbit(key.clone(), 0, false).await, Ok(true));
+
+        assert_eq!(storage.bitcount(&key, None).await, Ok(2));
+        assert_eq!(
+            storage
+                .bitcount(&key, range(1, Some(-1), BitUnit::Byte))
+                .await,
+            Ok(1)
+        );
+        assert_eq!(
+            storage
+                .bitcount(&key, range(8, Some(20), BitUnit::Bit))
+                .await,
+            Ok(1)
+        );
+        assert_eq!(
+            storage
+                .bitcount(&key, range(0, Some(6), BitUnit::Bit))
+                .await,
+            Ok(0)
+        );
+        assert_eq!(
+            storage
+                .bitcount(&key, range(2, Some(1), BitUnit::Byte))
+                .await,
+            Ok(0)
+        );
+    }
+
+    #[tokio::test]
+    async fn positions() {
+        let storage = Storage::instance();
+        storage
+            .set_range(Bytes::from("bitmap:pos"), 0, b"\xff\xf0\x00")
+            .await
+            .unwrap();
+        assert_eq!(bitpos("bitmap:pos", false, None).await, 12);
+        assert_eq!(bitpos("bitmap:pos", true, None).await, 0);
+        assert_eq!(
+            bitpos("bitmap:pos", true, range(1, None, BitUnit::Byte)).await,
+            8
+        );
+        assert_eq!(
+            bitpos("bitmap:pos", true, range(12, Some(-1), BitUnit::Bit)).await,
+            -1
+        );
+        storage
+            .set_range(Bytes::from("bitmap:ones"), 0, b"\xff\xff")
+            .await
+            .unwrap();
+        assert_eq!(bitpos("bitmap:ones", false, None).await, 16);
+        assert_eq!(
+            bitpos("bitmap:ones", false, range(0, Some(-1), BitUnit::Byte)).await,
+            -1
+        );
+        assert_eq!(bitpos("bitmap:absent", false, None).await, 0);
+        assert_eq!(bitpos("bitmap:absent", true, None).await, -1);
+    }
+
+    #[tokio::test]
+    async fn operations() {
+        let storage = Storage::instance();
+        storage
+            .set_range(Bytes::from("bitmap:op:a"), 0, b"\xf0\x0f")
+            .await
+            .unwrap();
+        storage
+            .set_range(Bytes::from("bitmap:op:b"), 0, b"\x3c")
+            .await
+            .unwrap();
+        let bitop = |operation, keys: &[&'static str]| BitOp {
+            operation,
+            destination: Bytes::from("bitmap:op:dest"),
+            keys: keys.iter().map(|key| Bytes::from(*key)).collect(),
+        };
+        for (operation, expected) in [
+            (BitOperation::And, b"\x30\x00"),
+            (BitOperation::Or, b"\xfc\x0f"),
+            (BitOperation::Xor, b"\xcc\x0f"),
+        ] {
+            assert_eq!(
+                storage
+                    .bitop(bitop(operation, &["bitmap:op:a", "bitmap:op:b"]))
+                    .await,
+                Ok(2)
+            );
+            assert_eq!(
+                storage.get(b"bitmap:op:dest").await,
+                Ok(Some(Bytes::from_static(expected)))
+            );
+        }
+        storage
+            .bitop(bitop(BitOperation::Not, &["bitmap:op:a"]))
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.get(b"bitmap:op:dest").await,
+            Ok(Some(Bytes::from_static(b"\x0f\xf0")))
+        );
+        assert_eq!(
+            storage
+                .bitop(bitop(BitOperation::Or, &["bitmap:op:absent"]))
+                .await,
+            Ok(0)
+        );
+        assert_eq!(storage.key_type(b"bitmap:op:dest").await, "none");
+    }
+
+    #[tokio::test]
+    async fn bit_fields() {
+        let storage = Storage::instance();
+        let operation = |action, signed, bits, offset, overflow| BitFieldOperation {
+            action,
+            field: BitFieldType { signed, bits },
+            offset,
+            overflow,
+        };
+        let request = BitField {
+            key: Bytes::from("bitmap:field"),
+            operations: vec![
+                operation(BitFieldAction::Set(255), false, 8, 0, Overflow::Wrap),
+                operation(BitFieldAction::Get, true, 8, 0, Overflow::Wrap),
+                operation(BitFieldAction::IncrBy(10), false, 8, 0, Overflow::Wrap),
+                operation(BitFieldAction::IncrBy(-20), false, 8, 0, Overflow::Sat),
+                operation(BitFieldAction::IncrBy(127), true, 8, 8, Overflow::Fail),
+                operation(BitFieldAction::IncrBy(1), true, 8, 8, Overflow::Fail),
+                operation(BitFieldAction::Set(-1), false, 4, 16, Overflow::Sat),
+            ],
+        };
+        assert_eq!(
+            storage.bitfield(request).await,
+            Ok(vec![
+                Some(0),
+                Some(-1),
+                Some(9),
+                Some(0),
+                Some(127),
+                None,
+                Some(0)
+            ])
+        );
+        assert_eq!(
+            storage.get(b"bitmap:field").await,
+            Ok(Some(Bytes::from_static(b"\x00\x7f\xf0")))
+        );
+
+        // Reads don't create the key.
+        let request = BitField {
+            key: Bytes::from("bitmap:field:absent"),
+            operations: vec![operation(BitFieldAction::Get, false, 8, 0, Overflow::Wrap)],
+        };
+        assert_eq!(storage.bitfield(request).await, Ok(vec![Some(0)]));
+        assert_eq!(storage.key_type(b"bitmap:field:absent").await, "none");
+    }
+}
@@ -0,0 +1,644 @@
+//! Clients blocked until a key is ready.
+//!
+//! A blocked client registers a waiter on its keys and waits for a reply on a channel. Commands
+//! which may make a key ready signal it, and before releasing the lock they serve waiters of the
+//! ready keys in the order the clients blocked in. So the client that blocked first is served
+//! first and no other client can take the elements in between.
+use super::{list::ListOperation, stream::StreamRead, Keyspace, Storage};
+use crate::{
+    error::RedisError,
+    protocol::{ReadAfter, ReadGroup, StreamId, Timeout},
+};
+use bytes::Bytes;
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::{oneshot, RwLock, RwLockWriteGuard};
+
+/// Elements popped from the list at the key.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Popped {
+    pub key: Bytes,
+    pub elements: Vec<Bytes>,
+}
+
+/// Operation a blocked client waits to perform on one of its keys.
+#[derive(Clone, Debug)]
+pub(super) enum BlockedOperation {
+    List(ListOperation),
+    /// Reads up to `count` entries of a stream added after the ID given for its key.
+    ReadStreams {
+        after: Vec<(Bytes, StreamId)>,
+        count: Option<usize>,
+    },
+    /// Reads up to `count` entries of a stream never delivered to the group for the consumer.
+    ReadGroup {
+        read: ReadGroup,
+        count: Option<usize>,
+    },
+}
+
+/// Result of the operation of a served client.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Served {
+    Popped(Popped),
+    Streams(Vec<StreamRead>),
+}
+
+type Reply = Result<Served, RedisError>;
+
+#[derive(Debug)]
+struct Waiter {
+    keys: Vec<Bytes>,
+    operation: BlockedOperation,
+    sender: oneshot::Sender<Reply>,
+}
+
+/// Waiters of blocked clients.
+#[derive(Default, Debug)]
+pub(super) struct BlockedClients {
+    next_id: u64,
+    waiters: HashMap<u64, Waiter>,
+    /// Ids of waiters by key in the order the clients blocked in.
+    by_key: HashMap<Bytes, VecDeque<u64>>,
+    /// Keys which may be ready to serve their waiters.
+    ready: VecDeque<Bytes>,
+}
+
+impl BlockedClients {
+    fn add(
+        &mut self,
+        keys: Vec<Bytes>,
+        operation: BlockedOperation,
+    ) -> (u64, oneshot::Receiver<Reply>) {
+        let id = self.next_id;
+        self.next_id += 1;
+        for key in &keys {
+            let queue = self.by_key.entry(key.clone()).or_default();
+            // A key given twice must not be waited on twice.
+            if queue.back() != Some(&id) {
+                queue.push_back(id);
+            }
+        }
+        let (sender, receiver) = oneshot::channel();
+        self.waiters.insert(
+            id,
+            Waiter {
+                keys,
+                operation,
+                sender,
+            },
+        );
+        (id, receiver)
+    }
+
+    /// Removes the waiter from all queues it is in. Returns `None` if it is already removed.
+    fn remove(&mut self, id: u64) -> Option<Waiter> {
+        let waiter = self.waiters.remove(&id)?;
+        for key in &waiter.keys {
+            if let Some(queue) = self.by_key.get_mut(key) {
+                queue.retain(|queued| *queued != id);
+                if queue.is_empty() {
+                    self.by_key.remove(key);
+                }
+            }
+        }
+        Some(waiter)
+    }
+
+    /// Marks the key as ready if some client waits on it.
+    pub(super) fn signal(&mut self, key: &[u8]) {
+        if self.by_key.contains_key(key) && !self.ready.iter().any(|ready| ready == key) {
+            self.ready.push_back(Bytes::copy_from_slice(key));
+        }
+    }
+
+    /// Fails clients reading the stream at the key for a group, e.g. because the stream is
+    /// deleted. With a group only the readers of that group are failed.
+    pub(super) fn unblock_group_readers(
+        &mut self,
+        key: &[u8],
+        group: Option<&[u8]>,
+        error: RedisError,
+    ) {
+        let ids: Vec<u64> = match self.by_key.get(key) {
+            Some(queue) => queue.iter().copied().collect(),
+            None => return,
+        };
+        for id in ids {
+            let reads_group = match &self.waiters[&id].operation {
+                BlockedOperation::ReadGroup { read, .. } => {
+                    group.is_none() || group == Some(read.group.as_ref())
+                }
+                _ => false,
+            };
+            if let Some(waiter) = reads_group.then(|| self.remove(id)).flatten() {
+                let _ = waiter.sender.send(Err(error.clone()));
+            }
+        }
+    }
+
+    /// Returns waiters on the key in the order they blocked in. Waiters of disconnected clients
+    /// are dropped on the way.
+    fn waiting_on(&mut self, key: &[u8]) -> Vec<u64> {
+        let ids: Vec<u64> = match self.by_key.get(key) {
+            Some(queue) => queue.iter().copied().collect(),
+            None => return vec![],
+        };
+        ids.into_iter()
+            .filter(|id| {
+                let closed = self.waiters[id].sender.is_closed();
+                if closed {
+                    self.remove(*id);
+                }
+                !closed
+            })
+            .collect()
+    }
+}
+
+impl Keyspace {
+    /// Performs the operation of a blocked client on the key. Returns `None` if the key is not
+    /// ready for it.
+    fn serve(
+        &mut self,
+        key: &Bytes,
+        operation: &BlockedOperation,
+        now: u64,
+    ) -> Result<Option<Served>, RedisError> {
+        match operation {
+            BlockedOperation::List(operation) => {
+                let elements = self.list_operation(key, operation, now)?;
+                Ok(elements.map(|elements| {
+                    Served::Popped(Popped {
+                        key: key.clone(),
+                        elements,
+                    })
+                }))
+            }
+            BlockedOperation::ReadStreams { after, count } => {
+                let after: Vec<(Bytes, StreamId)> = after
+                    .iter()
+                    .filter(|(stream, _)| stream == key)
+                    .cloned()
+                    .collect();
+                let read = self.read_streams(&after, *count, now)?;
+                Ok((!read.is_empty()).then_some(Served::Streams(read)))
+            }
+            BlockedOperation::ReadGroup { read, count } => {
+                let entries = self.read_group(key, read, ReadAfter::Last, *count, now)?;
+                Ok((!entries.is_empty()).then(|| Served::Streams(vec![(key.clone(), entries)])))
+            }
+        }
+    }
+
+    /// Serves waiters of the ready keys in the order they blocked in, while the keys are ready
+    /// for them. Must be called before releasing the lock by every command which signals keys.
+    pub(super) fn serve_blocked(&mut self, now: u64) {
+        while let Some(key) = self.blocked.ready.pop_front() {
+            for id in self.blocked.waiting_on(&key) {
+                let operation = self.blocked.waiters[&id].operation.clone();
+                let reply = match self.serve(&key, &operation, now) {
+                    Ok(Some(served)) => Ok(served),
+                    // Other waiters may still be served, e.g. reading streams after lower IDs.
+                    Ok(None) => continue,
+                    // The key was recreated with another type: the client keeps waiting for
+                    // the type it blocked on, as in Redis.
+                    Err(RedisError::WrongType) => continue,
+                    Err(err) => Err(err),
+                };
+                if let Some(waiter) = self.blocked.remove(id) {
+                    // The client may have disconnected just now, then the reply is lost the
+                    // same as if it disconnected right after receiving it.
+                    let _ = waiter.sender.send(reply);
+                }
+            }
+        }
+    }
+}
+
+/// Waiter registration of a blocked client. Removes the waiter if the client stops waiting
+/// before being served, e.g. because the connection is closed.
+struct Registration {
+    inner: &'static RwLock<Keyspace>,
+    id: u64,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Ok(mut keyspace) = self.inner.try_write() {
+            keyspace.blocked.remove(self.id);
+            return;
+        }
+        // The lock is busy and can't be awaited here, so the waiter is removed by a task once
+        // the lock is free.
+        let (inner, id) = (self.inner, self.id);
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                inner.write().await.blocked.remove(id);
+            });
+        }
+    }
+}
+
+impl Storage {
+    /// Blocks on the keys until the operation is performed on one of them or the timeout
+    /// expires. Takes the lock the caller has already tried to perform the operation under, so
+    /// no change of the keys is missed. Returns `None` on timeout.
+    pub(super) async fn block(
+        &self,
+        mut keyspace: RwLockWriteGuard<'static, Keyspace>,
+        keys: Vec<Bytes>,
+        operation: BlockedOperation,
+        timeout: Timeout,
+    ) -> Result<Option<Served>, RedisError> {
+        let (id, mut receiver) = keyspace.blocked.add(keys, operation);
+        drop(keyspace);
+        let _registration = Registration {
+            inner: self.inner,
+            id,
+        };
+
+        let reply = match timeout {
+            Timeout::After(timeout) => tokio::time::timeout(timeout, &mut receiver).await.ok(),
+            Timeout::Forever => Some((&mut receiver).await),
+        };
+        if let Some(Ok(reply)) = reply {
+            return reply.map(Some);
+        }
+        // The client may have been served after the timeout expired, but before the waiter is
+        // removed here.
+        let waiting = self.inner.write().await.blocked.remove(id).is_some();
+        match receiver.try_recv() {
+            Ok(reply) if !waiting => reply.map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Performs the operation on the first of the keys holding a list. If there is none, blocks
+    /// until a list is pushed to one of the keys or the timeout expires. Returns `None` on
+    /// timeout.
+    pub(super) async fn block_on_lists(
+        &self,
+        keys: Vec<Bytes>,
+        operation: ListOperation,
+        timeout: Timeout,
+    ) -> Result<Option<Popped>, RedisError> {
+        let now = super::now_ms();
+        let mut keyspace = self.inner.write().await;
+        if let Some(popped) = keyspace.pop_first(&keys, &operation, now)? {
+            keyspace.serve_blocked(now);
+            return Ok(Some(popped));
+        }
+        let operation = BlockedOperation::List(operation);
+        match self.block(keyspace, keys, operation, timeout).await? {
+            Some(Served::Popped(popped)) => Ok(Some(popped)),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocol::{ReadAfter, Side};
+    use std::time::Duration;
+
+    /// Waits until the number of clients blocked on the key is `count`.
+    async fn wait_blocked(key: &'static str, count: usize) {
+        loop {
+            let blocked = Storage::instance()
+                .inner
+                .read()
+                .await
+                .blocked
+                .by_key
+                .get(key.as_bytes())
+                .map_or(0, VecDeque::len);
+            if blocked == count {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    fn spawn_pop(
+        keys: &[&'static str],
+    ) -> tokio::task::JoinHandle<Result<Option<Popped>, RedisError>> {
+        let keys = keys.iter().map(|key| Bytes::from(*key)).collect();
+        tokio::spawn(async move {
+            Storage::instance()
+                .blocking_pop(keys, Side::Left, 1, Timeout::Forever)
+                .await
+        })
+    }
+
+    async fn push(key: &'static str, elements: &[&'static str]) {
+        let elements = elements
+            .iter()
+            .map(|element| Bytes::from(*element))
+            .collect();
+        Storage::instance()
+            .push(Bytes::from(key), elements, Side::Right, false)
+            .await
+            .unwrap();
+    }
+
+    fn popped(key: &'static str, element: &'static str) -> Option<Popped> {
+        Some(Popped {
+            key: Bytes::from(key),
+            elements: vec![Bytes::from(element)],
+        })
+    }
+
+    #[tokio::test]
+    async fn served_in_order_of_blocking() {
+        let first = spawn_pop(&["blocking:fifo"]);
+        wait_blocked("blocking:fifo", 1).await;
+        let second = spawn_pop(&["blocking:other", "blocking:fifo"]);
+        wait_blocked("blocking:fifo", 2).await;
+
+        push("blocking:fifo", &["a", "b", "c"]).await;
+        assert_eq!(first.await.unwrap(), Ok(popped("blocking:fifo", "a")));
+        assert_eq!(second.await.unwrap(), Ok(popped("blocking:fifo", "b")));
+        // The rest stays in the list and the served clients don't wait anymore.
+        assert_eq!(Storage::instance().llen(b"blocking:fifo").await, Ok(1));
+        wait_blocked("blocking:fifo", 0).await;
+        wait_blocked("blocking:other", 0).await;
+    }
+
+    #[tokio::test]
+    async fn timeout() {
+        let storage = Storage::instance();
+        let keys = vec![Bytes::from("blocking:timeout")];
+        assert_eq!(
+            storage
+                .blocking_pop(
+                    keys,
+                    Side::Left,
+                    1,
+                    Timeout::After(Duration::from_millis(10))
+                )
+                .await,
+            Ok(None)
+        );
+        wait_blocked("blocking:timeout", 0).await;
+
+        push("blocking:ready", &["a"]).await;
+        let keys = vec![Bytes::from("blocking:ready")];
+        assert_eq!(
+            storage
+                .blocking_pop(keys, Side::Left, 1, Timeout::Forever)
+                .await,
+            Ok(popped("blocking:ready", "a"))
+        );
+    }
+
+    #[tokio::test]
+    async fn abandoned_waiter_is_skipped() {
+        let abandoned = spawn_pop(&["blocking:abandoned"]);
+        wait_blocked("blocking:abandoned", 1).await;
+        let waiting = spawn_pop(&["blocking:abandoned"]);
+        wait_blocked("blocking:abandoned", 2).await;
+        abandoned.abort();
+        assert!(abandoned.await.unwrap_err().is_cancelled());
+
+        push("blocking:abandoned", &["a"]).await;
+        assert_eq!(
+            waiting.await.unwrap(),
+            Ok(popped("blocking:abandoned", "a"))
+        );
+    }
+
+    #[tokio::test]
+    async fn waiter_removed_when_lock_is_busy() {
+        let abandoned = spawn_pop(&["blocking:busy"]);
+        wait_blocked("blocking:busy", 1).await;
+        let keyspace = Storage::instance().inner.write().await;
+        abandoned.abort();
+        assert!(abandoned.await.unwrap_err().is_cancelled());
+        drop(keyspace);
+        wait_blocked("blocking:busy", 0).await;
+    }
+
+    #[tokio::test]
+    async fn list_waiter_keeps_waiting_for_list() {
+        let popping = spawn_pop(&["blocking:retyped"]);
+        wait_blocked("blocking:retyped", 1).await;
+        let add = crate::protocol::XAdd {
+            key: Bytes::from("blocking:retyped"),
+            no_mkstream: false,
+            trim: None,
+            id: crate::protocol::XAddId::Explicit(StreamId { ms: 1, seq: 0 }),
+            fields: vec![Bytes::from("f"), Bytes::from("v")],
+        };
+        Storage::instance().xadd(add).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!popping.is_finished());
+        wait_blocked("blocking:retyped", 1).await;
+
+        Storage::instance()
+            .del(&[Bytes::from("blocking:retyped")])
+            .await;
+        push("blocking:retyped", &["a"]).await;
+        assert_eq!(popping.await.unwrap(), Ok(popped("blocking:retyped", "a")));
+    }
+
+    #[tokio::test]
+    async fn moved_element_serves_destination() {
+        let storage = Storage::instance();
+        let popping = spawn_pop(&["blocking:destination"]);
+        wait_blocked("blocking:destination", 1).await;
+        let moving = tokio::spawn(async {
+            Storage::instance()
+                .blocking_move(
+                    Bytes::from("blocking:source"),
+                    Bytes::from("blocking:destination"),
+                    Side::Left,
+                    Side::Right,
+                    Timeout::Forever,
+                )
+                .await
+        });
+        wait_blocked("blocking:source", 1).await;
+
+        push("blocking:source", &["a"]).await;
+        assert_eq!(moving.await.unwrap(), Ok(Some(Bytes::from("a"))));
+        assert_eq!(
+            popping.await.unwrap(),
+            Ok(popped("blocking:destination", "a"))
+        );
+        assert_eq!(storage.llen(b"blocking:source").await, Ok(0));
+        assert_eq!(storage.llen(b"blocking:destination").await, Ok(0));
+    }
+
+    #[tokio::test]
+    async fn stream_readers_served_by_added_entry() {
+        let storage = Storage::instance();
+        let add = |id: StreamId| crate::protocol::XAdd {
+            key: Bytes::from("blocking:stream"),
+            no_mkstream: false,
+            trim: None,
+            id: crate::protocol::XAddId::Explicit(id),
+            fields: vec![Bytes::from("f"), Bytes::from("v")],
+        };
+        storage.xadd(add(StreamId { ms: 1, seq: 0 })).await.unwrap();
+        let read = |after: ReadAfter| {
+            tokio::spawn(async move {
+                Storage::instance()
+                    .xread(
+                        vec![
+                            (Bytes::from("blocking:no-stream"), ReadAfter::Last),
+                            (Bytes::from("blocking:stream"), after),
+                        ],
+                        None,
+                        Some(Timeout::Forever),
+                    )
+                    .await
+            })
+        };
+        // Waits for an entry after 5-0, so it is not served by the next entry.
+        let later = read(ReadAfter::Id(StreamId { ms: 5, seq: 0 }));
+        wait_blocked("blocking:stream", 1).await;
+        let first = read(ReadAfter::Last);
+        let second = read(ReadAfter::Last);
+        wait_blocked("blocking:stream", 3).await;
+
+        storage.xadd(add(StreamId { ms: 2, seq: 0 })).await.unwrap();
+        for reader in [first, second] {
+            let read = reader.await.unwrap().unwrap().unwrap();
+            assert_eq!(read.len(), 1);
+            assert_eq!(read[0].0, Bytes::from("blocking:stream"));
+            assert_eq!(read[0].1[0].id, StreamId { ms: 2, seq: 0 });
+        }
+        wait_blocked("blocking:stream", 1).await;
+        storage.xadd(add(StreamId { ms: 6, seq: 0 })).await.unwrap();
+        let read = later.await.unwrap().unwrap().unwrap();
+        assert_eq!(read[0].1[0].id, StreamId { ms: 6, seq: 0 });
+        wait_blocked("blocking:no-stream", 0).await;
+    }
+
+    #[tokio::test]
+    async fn stream_reader_keeps_waiting_for_stream() {
+        let reading = tokio::spawn(async {
+            Storage::instance()
+                .xread(
+                    vec![(Bytes::from("blocking:list-or-stream"), ReadAfter::Last)],
+                    None,
+                    Some(Timeout::Forever),
+                )
+                .await
+        });
+        wait_blocked("blocking:list-or-stream", 1).await;
+        push("blocking:list-or-stream", &["a"]).await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!reading.is_finished());
+        wait_blocked("blocking:list-or-stream", 1).await;
+
+        let storage = Storage::instance();
+        storage.del(&[Bytes::from("blocking:list-or-stream")]).await;
+        let add = crate::protocol::XAdd {
+            key: Bytes::from("blocking:list-or-stream"),
+            no_mkstream: false,
+            trim: None,
+            id: crate::protocol::XAddId::Explicit(StreamId { ms: 1, seq: 0 }),
+            fields: vec![Bytes::from("f"), Bytes::from("v")],
+        };
+        storage.xadd(add).await.unwrap();
+        let read = reading.await.unwrap().unwrap().unwrap();
+        assert_eq!(read[0].0, Bytes::from("blocking:list-or-stream"));
+        assert_eq!(read[0].1[0].id, StreamId { ms: 1, seq: 0 });
+    }
+
+    #[tokio::test]
+    async fn group_readers_released_when_stream_or_group_is_gone() {
+        let storage = Storage::instance();
+        let read = |key: &'static str, group: &'static str| {
+            let read = ReadGroup {
+                group: Bytes::from(group),
+                consumer: Bytes::from("c"),
+                no_ack: false,
+            };
+            tokio::spawn(async move {
+                Storage::instance()
+                    .xreadgroup(
+                        read,
+                        vec![(Bytes::from(key), ReadAfter::Last)],
+                        None,
+                        Some(Timeout::Forever),
+                    )
+                    .await
+            })
+        };
+        for group in ["g", "other"] {
+            storage
+                .xgroup_create(
+                    Bytes::from("blocking:group"),
+                    Bytes::from(group),
+                    ReadAfter::Last,
+                    true,
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+        let destroyed = read("blocking:group", "g");
+        let deleted = read("blocking:group", "other");
+        wait_blocked("blocking:group", 2).await;
+
+        assert_eq!(
+            storage.xgroup_destroy(b"blocking:group", b"g").await,
+            Ok(true)
+        );
+        assert_eq!(
+            destroyed.await.unwrap(),
+            Err(RedisError::Unblocked(
+                "the consumer group this client was blocked on no longer exists".to_owned()
+            ))
+        );
+        wait_blocked("blocking:group", 1).await;
+        storage.del(&[Bytes::from("blocking:group")]).await;
+        assert_eq!(
+            deleted.await.unwrap(),
+            Err(RedisError::Unblocked(
+                "the stream key no longer exists".to_owned()
+            ))
+        );
+        wait_blocked("blocking:group", 0).await;
+
+        // Overwriting the stream releases its readers the same.
+        storage
+            .xgroup_create(
+                Bytes::from("blocking:group"),
+                Bytes::from("g"),
+                ReadAfter::Last,
+                true,
+                None,
+            )
+            .await
+            .unwrap();
+        let overwritten = read("blocking:group", "g");
+        wait_blocked("blocking:group", 1).await;
+        push("blocking:group:list", &["a"]).await;
+        storage
+            .rename(b"blocking:group:list", Bytes::from("blocking:group"), false)
+            .await
+            .unwrap();
+        assert_eq!(
+            overwritten.await.unwrap(),
+            Err(RedisError::Unblocked(
+                "the stream key no longer exists".to_owned()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn renamed_list_serves_key() {
+        let popping = spawn_pop(&["blocking:renamed"]);
+        wait_blocked("blocking:renamed", 1).await;
+        push("blocking:original", &["a"]).await;
+        Storage::instance()
+            .rename(b"blocking:original", Bytes::from("blocking:renamed"), false)
+            .await
+            .unwrap();
+        assert_eq!(popping.await.unwrap(), Ok(popped("blocking:renamed", "a")));
+    }
+}
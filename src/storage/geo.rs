@@ -0,0 +1,328 @@
+//! Geospatial commands.
+//!
+//! Positions are members of a sorted set scored by their geohashes, so GEOADD is ZADD with the
+//! scores computed and a search reads the score ranges of the geohash boxes covering the shape.
+use super::{now_ms, sorted_set::SortedSet, Keyspace, Storage, Value};
+use crate::{
+    error::RedisError,
+    geohash,
+    protocol::{GeoAdd, GeoOrigin, GeoSearch, ScoreBound, ZAdd, ZRangeBy},
+};
+use bytes::Bytes;
+
+/// A member found by a search.
+#[derive(PartialEq, Debug)]
+pub struct GeoMatch {
+    pub member: Bytes,
+    /// Distance from the center in the unit of the request.
+    pub distance: f64,
+    /// Geohash stored as the score.
+    pub score: u64,
+    pub longitude: f64,
+    pub latitude: f64,
+}
+
+/// Searches the set for members within the shape of the request, sorted as requested and
+/// limited to the count of the request.
+fn search(set: &SortedSet, request: &GeoSearch) -> Result<Vec<GeoMatch>, RedisError> {
+    let center = match &request.origin {
+        GeoOrigin::Position(longitude, latitude) => (*longitude, *latitude),
+        GeoOrigin::Member(member) => {
+            let score = set.score(member).ok_or_else(|| {
+                RedisError::Other("could not decode requested zset member".to_owned())
+            })?;
+            geohash::position(score as u64)
+        }
+    };
+    // With ANY the search stops at the count, without it the nearest members are needed.
+    let limit = request.count.filter(|_| request.any);
+    let mut found = vec![];
+    'areas: for area in geohash::search_areas(request.shape, center.0, center.1) {
+        let (min, max) = area.scores();
+        let range = ZRangeBy::Score(
+            ScoreBound::Inclusive(min as f64),
+            ScoreBound::Exclusive(max as f64),
+        );
+        for (member, score) in set.entries(set.ranks(&range, false), false) {
+            let score = score as u64;
+            let (longitude, latitude) = geohash::position(score);
+            if let Some(distance) = request.shape.distance_within(center, longitude, latitude) {
+                found.push(GeoMatch {
+                    member,
+                    distance: distance / request.unit.meters(),
+                    score,
+                    longitude,
+                    latitude,
+                });
+                if limit == Some(found.len()) {
+                    break 'areas;
+                }
+            }
+        }
+    }
+    // The nearest members are found by sorting.
+    let descending = match (request.descending, request.count) {
+        (None, Some(_)) if !request.any => Some(false),
+        (descending, _) => descending,
+    };
+    if let Some(descending) = descending {
+        found.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        if descending {
+            found.reverse();
+        }
+    }
+    found.truncate(request.count.unwrap_or(usize::MAX));
+    Ok(found)
+}
+
+impl Keyspace {
+    fn geosearch(&self, request: &GeoSearch, now: u64) -> Result<Vec<GeoMatch>, RedisError> {
+        match self.sorted_set(&request.key, now)? {
+            Some(set) => search(set, request),
+            None => Ok(vec![]),
+        }
+    }
+}
+
+impl Storage {
+    /// Adds the members at their positions or updates them as the flags of the request allow.
+    /// Returns the number of added members, counting updated ones too if requested.
+    pub async fn geoadd(&self, request: GeoAdd) -> Result<usize, RedisError> {
+        let pairs = request
+            .points
+            .into_iter()
+            .map(|(longitude, latitude, member)| {
+                (geohash::score(longitude, latitude) as f64, member)
+            })
+            .collect();
+        let request = ZAdd {
+            key: request.key,
+            nx: request.nx,
+            xx: request.xx,
+            ch: request.ch,
+            pairs,
+            ..Default::default()
+        };
+        self.zadd(request).await.map(|(count, _)| count)
+    }
+
+    /// Returns the geohashes stored for the members, `None` for absent members.
+    async fn geo_scores(
+        &self,
+        key: &[u8],
+        members: &[Bytes],
+    ) -> Result<Vec<Option<u64>>, RedisError> {
+        let scores = self.zmscore(key, members).await?;
+        Ok(scores
+            .into_iter()
+            .map(|score| score.map(|score| score as u64))
+            .collect())
+    }
+
+    /// Returns longitudes and latitudes of the members, `None` for absent members.
+    pub async fn geopos(
+        &self,
+        key: &[u8],
+        members: &[Bytes],
+    ) -> Result<Vec<Option<(f64, f64)>>, RedisError> {
+        let scores = self.geo_scores(key, members).await?;
+        Ok(scores
+            .into_iter()
+            .map(|score| score.map(geohash::position))
+            .collect())
+    }
+
+    /// Returns standard geohash strings of the members, `None` for absent members.
+    pub async fn geohash(
+        &self,
+        key: &[u8],
+        members: &[Bytes],
+    ) -> Result<Vec<Option<String>>, RedisError> {
+        let scores = self.geo_scores(key, members).await?;
+        Ok(scores
+            .into_iter()
+            .map(|score| score.map(geohash::to_string))
+            .collect())
+    }
+
+    /// Returns the distance in meters between the members, `None` if one of them is absent.
+    pub async fn geodist(
+        &self,
+        key: &[u8],
+        first: Bytes,
+        second: Bytes,
+    ) -> Result<Option<f64>, RedisError> {
+        let positions = self.geopos(key, &[first, second]).await?;
+        Ok(match positions[..] {
+            [Some(first), Some(second)] => {
+                Some(geohash::distance(first.0, first.1, second.0, second.1))
+            }
+            _ => None,
+        })
+    }
+
+    /// Returns members within the shape of the request.
+    pub async fn geosearch(&self, request: &GeoSearch) -> Result<Vec<GeoMatch>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        keyspace.geosearch(request, now)
+    }
+
+    /// Stores members within the shape of the request at the destination, overwriting it,
+    /// scored by their geohashes or by their distances if `store_dist` is set. An empty result
+    /// removes the destination. Returns the number of stored members.
+    pub async fn geosearchstore(
+        &self,
+        request: &GeoSearch,
+        destination: Bytes,
+        store_dist: bool,
+    ) -> Result<usize, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let found = keyspace.geosearch(request, now)?;
+        if found.is_empty() {
+            keyspace.remove(&destination);
+            return Ok(0);
+        }
+        let count = found.len();
+        let mut set = SortedSet::default();
+        for found in found {
+            let score = match store_dist {
+                true => found.distance,
+                false => found.score as f64,
+            };
+            set.insert(found.member, score);
+        }
+        keyspace.insert(destination, Value::SortedSet(set), None);
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{geohash::Shape, protocol::Unit};
+
+    async fn add_sicily(key: &'static str) {
+        let points = vec![
+            (13.361389, 38.115556, Bytes::from("Palermo")),
+            (15.087269, 37.502669, Bytes::from("Catania")),
+            (12.758489, 38.788135, Bytes::from("edge1")),
+            (17.241510, 38.788135, Bytes::from("edge2")),
+        ];
+        let request = GeoAdd {
+            key: Bytes::from(key),
+            nx: false,
+            xx: false,
+            ch: false,
+            points,
+        };
+        assert_eq!(Storage::instance().geoadd(request).await, Ok(4));
+    }
+
+    fn search_request(key: &'static str, shape: Shape, unit: Unit) -> GeoSearch {
+        GeoSearch {
+            key: Bytes::from(key),
+            origin: GeoOrigin::Position(15.0, 37.0),
+            shape,
+            unit,
+            descending: None,
+            count: None,
+            any: false,
+            with_coord: false,
+            with_dist: false,
+            with_hash: false,
+            store: None,
+        }
+    }
+
+    fn members(found: &[GeoMatch]) -> Vec<&[u8]> {
+        found.iter().map(|found| found.member.as_ref()).collect()
+    }
+
+    #[tokio::test]
+    async fn positions() {
+        let storage = Storage::instance();
+        add_sicily("geo:pos").await;
+        let positions = storage
+            .geopos(b"geo:pos", &[Bytes::from("Palermo"), Bytes::from("absent")])
+            .await
+            .unwrap();
+        let (longitude, latitude) = positions[0].unwrap();
+        assert!((longitude - 13.361389).abs() < 1e-5);
+        assert!((latitude - 38.115556).abs() < 1e-5);
+        assert_eq!(positions[1], None);
+        let distance = storage
+            .geodist(b"geo:pos", Bytes::from("Palermo"), Bytes::from("Catania"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(format!("{:.4}", distance), "166274.1516");
+        assert_eq!(
+            storage.geohash(b"geo:pos", &[Bytes::from("Palermo")]).await,
+            Ok(vec![Some("sqc8b49rny0".to_owned())])
+        );
+    }
+
+    #[tokio::test]
+    async fn searches() {
+        let storage = Storage::instance();
+        add_sicily("geo:search").await;
+        let mut request = search_request("geo:search", Shape::Radius(200_000.0), Unit::Kilometers);
+        request.descending = Some(false);
+        let found = storage.geosearch(&request).await.unwrap();
+        assert_eq!(
+            members(&found),
+            vec![b"Catania".as_ref(), b"Palermo".as_ref()]
+        );
+        assert_eq!(format!("{:.4}", found[0].distance), "56.4413");
+
+        request.shape = Shape::Box {
+            width: 400_000.0,
+            height: 400_000.0,
+        };
+        request.descending = Some(true);
+        request.count = Some(3);
+        let found = storage.geosearch(&request).await.unwrap();
+        assert_eq!(
+            members(&found),
+            vec![b"edge1".as_ref(), b"edge2".as_ref(), b"Palermo".as_ref()]
+        );
+
+        request.origin = GeoOrigin::Member(Bytes::from("absent"));
+        assert_eq!(
+            storage.geosearch(&request).await,
+            Err(RedisError::Other(
+                "could not decode requested zset member".to_owned()
+            ))
+        );
+        request.key = Bytes::from("geo:absent");
+        assert_eq!(storage.geosearch(&request).await, Ok(vec![]));
+    }
+
+    #[tokio::test]
+    async fn store() {
+        let storage = Storage::instance();
+        add_sicily("geo:store:src").await;
+        let mut request = search_request("geo:store:src", Shape::Radius(100_000.0), Unit::Meters);
+        let destination = Bytes::from("geo:store:dest");
+        assert_eq!(
+            storage
+                .geosearchstore(&request, destination.clone(), true)
+                .await,
+            Ok(1)
+        );
+        let scores = storage
+            .zmscore(b"geo:store:dest", &[Bytes::from("Catania")])
+            .await
+            .unwrap();
+        assert!((scores[0].unwrap() - 56441.3).abs() < 1.0);
+
+        request.shape = Shape::Radius(1.0);
+        assert_eq!(
+            storage.geosearchstore(&request, destination, true).await,
+            Ok(0)
+        );
+        assert_eq!(storage.key_type(b"geo:store:dest").await, "none");
+    }
+}
@@ -0,0 +1,879 @@
+//! Hash commands.
+//!
+//! Fields of a hash may carry deadlines of their own. Expired fields are reclaimed the same two
+//! ways as keys: when the hash is modified, and by the active expiration cycle, which samples
+//! hashes having fields with a deadline. Until then they are treated as absent.
+use super::{
+    now_ms, scan, scan_position, Keyspace, Rng, Storage, Value,
+    ACTIVE_EXPIRE_CYCLE_ACCEPTABLE_STALE_PERCENT, ACTIVE_EXPIRE_CYCLE_KEYS_PER_LOOP,
+};
+use crate::{
+    error::RedisError,
+    glob::glob_match,
+    protocol::{format_stored_double, parse_f64, parse_i64, HExpire, HGetEx, Scan},
+};
+use bytes::Bytes;
+use std::{
+    collections::{BTreeSet, HashMap},
+    time::{Duration, Instant},
+};
+
+/// Status of a field for which a deadline was requested: the field doesn't exist.
+const FIELD_ABSENT: i64 = -2;
+/// The condition of the request doesn't hold.
+const CONDITION_FAILED: i64 = 0;
+/// The deadline is set.
+const DEADLINE_SET: i64 = 1;
+/// The deadline is in the past, so the field is removed.
+const FIELD_REMOVED: i64 = 2;
+/// The deadline is removed by HPERSIST.
+const DEADLINE_REMOVED: i64 = 1;
+/// The field has no deadline to remove.
+const NO_DEADLINE: i64 = -1;
+
+#[derive(Clone, Debug)]
+struct Field {
+    value: Bytes,
+    expires_at: Option<u64>,
+}
+
+impl Field {
+    fn is_live(&self, now: u64) -> bool {
+        match self.expires_at {
+            Some(deadline) => deadline > now,
+            None => true,
+        }
+    }
+}
+
+/// Fields of a hash along with their values and deadlines.
+#[derive(Clone, Debug, Default)]
+pub(super) struct Hash {
+    fields: HashMap<Bytes, Field>,
+    /// Deadlines of the fields which have one, ordered by time.
+    deadlines: BTreeSet<(u64, Bytes)>,
+}
+
+impl Hash {
+    /// Number of fields, including the expired ones which are not removed yet.
+    pub(super) fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Returns true if some field has a deadline.
+    pub(super) fn has_deadlines(&self) -> bool {
+        !self.deadlines.is_empty()
+    }
+
+    /// Returns true if all fields are expired, so the hash is treated as absent.
+    pub(super) fn is_expired(&self, now: u64) -> bool {
+        self.deadlines.len() == self.fields.len()
+            && self
+                .deadlines
+                .last()
+                .is_some_and(|(deadline, _)| *deadline <= now)
+    }
+
+    /// Removes expired fields. Returns the number of removed fields.
+    pub(super) fn remove_expired(&mut self, now: u64) -> usize {
+        let mut removed = 0;
+        while self
+            .deadlines
+            .first()
+            .is_some_and(|(deadline, _)| *deadline <= now)
+        {
+            if let Some((_, field)) = self.deadlines.pop_first() {
+                self.fields.remove(&field);
+                removed += 1;
+            }
+        }
+        removed
+    }
+
+    fn get(&self, field: &[u8], now: u64) -> Option<&Field> {
+        self.fields.get(field).filter(|field| field.is_live(now))
+    }
+
+    fn live_len(&self, now: u64) -> usize {
+        let expired = self
+            .deadlines
+            .iter()
+            .take_while(|(deadline, _)| *deadline <= now)
+            .count();
+        self.fields.len() - expired
+    }
+
+    fn iter(&self, now: u64) -> impl Iterator<Item = (&Bytes, &Bytes)> {
+        self.fields
+            .iter()
+            .filter(move |(_, field)| field.is_live(now))
+            .map(|(name, field)| (name, &field.value))
+    }
+
+    /// Sets the value of the field. The field loses its deadline unless `keep_ttl` is set.
+    /// Returns true if the field is new.
+    fn insert(&mut self, name: Bytes, value: Bytes, keep_ttl: bool) -> bool {
+        match self.fields.get_mut(&name) {
+            Some(field) => {
+                field.value = value;
+                if !keep_ttl {
+                    self.set_expires_at(&name, None);
+                }
+                false
+            }
+            None => {
+                let field = Field {
+                    value,
+                    expires_at: None,
+                };
+                self.fields.insert(name, field);
+                true
+            }
+        }
+    }
+
+    fn remove(&mut self, name: &[u8]) -> Option<Field> {
+        let (name, field) = self.fields.remove_entry(name)?;
+        if let Some(deadline) = field.expires_at {
+            self.deadlines.remove(&(deadline, name));
+        }
+        Some(field)
+    }
+
+    /// Replaces the deadline of an existing field.
+    fn set_expires_at(&mut self, name: &[u8], expires_at: Option<u64>) {
+        let (name, field) = match self.fields.get_key_value(name) {
+            Some((name, field)) => (name.clone(), field.expires_at),
+            None => return,
+        };
+        if let Some(deadline) = field {
+            self.deadlines.remove(&(deadline, name.clone()));
+        }
+        if let Some(deadline) = expires_at {
+            self.deadlines.insert((deadline, name.clone()));
+        }
+        if let Some(field) = self.fields.get_mut(&name) {
+            field.expires_at = expires_at;
+        }
+    }
+}
+
+impl Keyspace {
+    /// Sets the field of the hash, creating the hash if needed. The field keeps its deadline.
+    fn hash_insert(
+        &mut self,
+        key: Bytes,
+        field: Bytes,
+        value: Bytes,
+        now: u64,
+    ) -> Result<(), RedisError> {
+        self.get_or_insert_with(key, now, || Value::Hash(Hash::default()))
+            .value
+            .as_hash_mut()?
+            .insert(field, value, true);
+        Ok(())
+    }
+
+    /// Samples hashes having fields with a deadline and removes expired fields. Repeats while the
+    /// share of sampled hashes with expired fields is high, but for no longer than `time_limit`.
+    /// Returns the number of removed fields.
+    pub(super) fn active_expire_fields_cycle(
+        &mut self,
+        rng: &mut Rng,
+        time_limit: Duration,
+    ) -> usize {
+        let started = Instant::now();
+        let mut removed = 0;
+        loop {
+            let now = now_ms();
+            let samples = ACTIVE_EXPIRE_CYCLE_KEYS_PER_LOOP.min(self.volatile_fields.len());
+            if samples == 0 {
+                break;
+            }
+            let mut expired = 0;
+            for _ in 0..samples {
+                let key = match self.volatile_fields.random(rng) {
+                    Some(key) => key.clone(),
+                    None => break,
+                };
+                let hash = match self.entries.get_mut(&key).map(|entry| &mut entry.value) {
+                    Some(Value::Hash(hash)) => hash,
+                    // The index may lag behind when the fields lose their deadlines.
+                    _ => {
+                        self.volatile_fields.remove(&key);
+                        continue;
+                    }
+                };
+                let count = hash.remove_expired(now);
+                if count > 0 {
+                    expired += 1;
+                    removed += count;
+                }
+                if !hash.has_deadlines() {
+                    self.volatile_fields.remove(&key);
+                }
+                self.remove_if_empty(&key);
+            }
+            if expired * 100 <= samples * ACTIVE_EXPIRE_CYCLE_ACCEPTABLE_STALE_PERCENT
+                || started.elapsed() >= time_limit
+            {
+                break;
+            }
+        }
+        removed
+    }
+}
+
+impl Storage {
+    /// Sets the fields of the hash, creating it if needed. The fields lose their deadlines.
+    /// Returns the number of added fields.
+    pub async fn hset(&self, key: Bytes, pairs: Vec<(Bytes, Bytes)>) -> Result<usize, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let hash = keyspace
+            .get_or_insert_with(key, now, || Value::Hash(Hash::default()))
+            .value
+            .as_hash_mut()?;
+        Ok(pairs
+            .into_iter()
+            .filter(|(field, value)| hash.insert(field.clone(), value.clone(), false))
+            .count())
+    }
+
+    /// Sets the field of the hash unless it exists. Returns false if the field exists.
+    pub async fn hsetnx(&self, key: Bytes, field: Bytes, value: Bytes) -> Result<bool, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let hash = keyspace
+            .get_or_insert_with(key, now, || Value::Hash(Hash::default()))
+            .value
+            .as_hash_mut()?;
+        if hash.get(&field, now).is_some() {
+            return Ok(false);
+        }
+        Ok(hash.insert(field, value, false))
+    }
+
+    /// Returns values of the fields. Absent fields are `None`.
+    pub async fn hmget(
+        &self,
+        key: &[u8],
+        fields: &[Bytes],
+    ) -> Result<Vec<Option<Bytes>>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        let hash = match keyspace.get(key, now) {
+            Some(entry) => Some(entry.value.as_hash()?),
+            None => None,
+        };
+        Ok(fields
+            .iter()
+            .map(|name| {
+                hash.and_then(|hash| hash.get(name, now))
+                    .map(|field| field.value.clone())
+            })
+            .collect())
+    }
+
+    /// Removes the fields. Returns the number of removed fields.
+    pub async fn hdel(&self, key: &[u8], fields: &[Bytes]) -> Result<usize, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let hash = match keyspace.get_live_mut(key, now) {
+            Some(entry) => entry.value.as_hash_mut()?,
+            None => return Ok(0),
+        };
+        let removed = fields
+            .iter()
+            .filter(|field| hash.remove(field).is_some())
+            .count();
+        keyspace.remove_if_empty(key);
+        Ok(removed)
+    }
+
+    /// Returns all fields of the hash along with their values.
+    pub async fn hgetall(&self, key: &[u8]) -> Result<Vec<(Bytes, Bytes)>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        Ok(match keyspace.get(key, now) {
+            Some(entry) => entry
+                .value
+                .as_hash()?
+                .iter(now)
+                .map(|(field, value)| (field.clone(), value.clone()))
+                .collect(),
+            None => vec![],
+        })
+    }
+
+    /// Returns the number of fields in the hash.
+    pub async fn hlen(&self, key: &[u8]) -> Result<usize, RedisError> {
+        let now = now_ms();
+        match self.inner.read().await.get(key, now) {
+            Some(entry) => Ok(entry.value.as_hash()?.live_len(now)),
+            None => Ok(0),
+        }
+    }
+
+    /// Adds the increment to the integer value of the field. An absent field counts as 0. The
+    /// field keeps its deadline.
+    pub async fn hincrby(
+        &self,
+        key: Bytes,
+        field: Bytes,
+        increment: i64,
+    ) -> Result<i64, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let current = match keyspace.get_live(&key, now) {
+            Some(entry) => entry.value.as_hash()?.get(&field, now),
+            None => None,
+        };
+        let current = match current {
+            Some(field) => parse_i64(&field.value)
+                .map_err(|_| RedisError::Other("hash value is not an integer".to_owned()))?,
+            None => 0,
+        };
+        let value = current
+            .checked_add(increment)
+            .ok_or_else(|| RedisError::Other("increment or decrement would overflow".to_owned()))?;
+        keyspace.hash_insert(key, field, Bytes::from(value.to_string()), now)?;
+        Ok(value)
+    }
+
+    /// Adds the increment to the float value of the field and returns the result formatted the
+    /// same way it is stored. An absent field counts as 0. The field keeps its deadline.
+    pub async fn hincrbyfloat(
+        &self,
+        key: Bytes,
+        field: Bytes,
+        increment: f64,
+    ) -> Result<Bytes, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let current = match keyspace.get_live(&key, now) {
+            Some(entry) => entry.value.as_hash()?.get(&field, now),
+            None => None,
+        };
+        let current = match current {
+            Some(field) => parse_f64(&field.value)
+                .map_err(|_| RedisError::Other("hash value is not a float".to_owned()))?,
+            None => 0.0,
+        };
+        let value = current + increment;
+        if !value.is_finite() {
+            return Err(RedisError::Other(
+                "increment would produce NaN or Infinity".to_owned(),
+            ));
+        }
+        let value = Bytes::from(format_stored_double(value));
+        keyspace.hash_insert(key, field, value.clone(), now)?;
+        Ok(value)
+    }
+
+    /// Returns random fields along with their values: up to `count` distinct fields or, if the
+    /// count is negative, `-count` fields which may repeat.
+    pub async fn hrandfield(
+        &self,
+        key: &[u8],
+        count: i64,
+    ) -> Result<Vec<(Bytes, Bytes)>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        let mut pairs: Vec<(&Bytes, &Bytes)> = match keyspace.get(key, now) {
+            Some(entry) => entry.value.as_hash()?.iter(now).collect(),
+            None => return Ok(vec![]),
+        };
+        let mut rng = Rng::new();
+        let picked = if count >= 0 {
+            // Partial shuffle: the first `count` pairs end up picked at random.
+            let count = (count as u64).min(pairs.len() as u64) as usize;
+            for i in 0..count {
+                let j = i + rng.below(pairs.len() - i);
+                pairs.swap(i, j);
+            }
+            pairs.truncate(count);
+            pairs
+        } else {
+            // Grows with the picks: the count may be far larger than anything allocatable.
+            let count = count.unsigned_abs();
+            let mut picked = Vec::with_capacity(count.min(pairs.len() as u64) as usize);
+            for _ in 0..count {
+                picked.push(pairs[rng.below(pairs.len())]);
+            }
+            picked
+        };
+        Ok(picked
+            .into_iter()
+            .map(|(field, value)| (field.clone(), value.clone()))
+            .collect())
+    }
+
+    /// Returns a page of fields matching the pattern along with their values and the cursor of
+    /// the next page, 0 if it is the last one.
+    pub async fn hscan(&self, request: &Scan) -> Result<(u64, Vec<(Bytes, Bytes)>), RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        let hash = match keyspace.get(&request.key, now) {
+            Some(entry) => entry.value.as_hash()?,
+            None => return Ok((0, vec![])),
+        };
+        let (cursor, pairs) = scan(
+            hash.iter(now).map(|pair| (scan_position(pair.0), pair)),
+            request.cursor,
+            request.count,
+        );
+        let pairs = pairs
+            .into_iter()
+            .filter(|(field, _)| match &request.pattern {
+                Some(pattern) => glob_match(pattern, field, false),
+                None => true,
+            })
+            .map(|(field, value)| (field.clone(), value.clone()))
+            .collect();
+        Ok((cursor, pairs))
+    }
+
+    /// Sets deadlines of the fields if the condition of the request holds. A deadline in the past
+    /// removes the field. Returns the status of each field: -2 if it doesn't exist, 0 if the
+    /// condition doesn't hold, 1 if the deadline is set and 2 if the field is removed.
+    pub async fn hexpire(&self, request: HExpire) -> Result<Vec<i64>, RedisError> {
+        let now = now_ms();
+        let deadline = request.expiration.deadline(now);
+        if deadline > crate::protocol::MAX_FIELD_DEADLINE {
+            return Err(RedisError::InvalidExpireTime(request.command));
+        }
+        let mut keyspace = self.inner.write().await;
+        let hash = match keyspace.get_live_mut(&request.key, now) {
+            Some(entry) => entry.value.as_hash_mut()?,
+            None => return Ok(vec![FIELD_ABSENT; request.fields.len()]),
+        };
+        let statuses = request
+            .fields
+            .iter()
+            .map(|name| {
+                let current = match hash.get(name, now) {
+                    Some(field) => field.expires_at,
+                    None => return FIELD_ABSENT,
+                };
+                if request
+                    .condition
+                    .is_some_and(|condition| !condition.holds(current, deadline))
+                {
+                    return CONDITION_FAILED;
+                }
+                if deadline <= now as i64 {
+                    hash.remove(name);
+                    return FIELD_REMOVED;
+                }
+                hash.set_expires_at(name, Some(deadline as u64));
+                DEADLINE_SET
+            })
+            .collect();
+        if hash.has_deadlines() {
+            keyspace.volatile_fields.insert(&request.key);
+        }
+        keyspace.remove_if_empty(&request.key);
+        Ok(statuses)
+    }
+
+    /// Returns deadlines of the fields: `None` if a field doesn't exist and `Some(None)` if it has
+    /// no deadline.
+    pub async fn hexpires_at(
+        &self,
+        key: &[u8],
+        fields: &[Bytes],
+    ) -> Result<Vec<Option<Option<u64>>>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        let hash = match keyspace.get(key, now) {
+            Some(entry) => Some(entry.value.as_hash()?),
+            None => None,
+        };
+        Ok(fields
+            .iter()
+            .map(|name| {
+                hash.and_then(|hash| hash.get(name, now))
+                    .map(|field| field.expires_at)
+            })
+            .collect())
+    }
+
+    /// Removes deadlines of the fields. Returns the status of each field: -2 if it doesn't
+    /// exist, -1 if it has no deadline and 1 if the deadline is removed.
+    pub async fn hpersist(&self, key: &[u8], fields: &[Bytes]) -> Result<Vec<i64>, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let hash = match keyspace.get_live_mut(key, now) {
+            Some(entry) => entry.value.as_hash_mut()?,
+            None => return Ok(vec![FIELD_ABSENT; fields.len()]),
+        };
+        Ok(fields
+            .iter()
+            .map(
+                |name| match hash.get(name, now).map(|field| field.expires_at) {
+                    None => FIELD_ABSENT,
+                    Some(None) => NO_DEADLINE,
+                    Some(Some(_)) => {
+                        hash.set_expires_at(name, None);
+                        DEADLINE_REMOVED
+                    }
+                },
+            )
+            .collect())
+    }
+
+    /// Returns values of the fields and changes deadlines of the existing ones as requested. A
+    /// deadline in the past removes the fields.
+    pub async fn hgetex(&self, request: HGetEx) -> Result<Vec<Option<Bytes>>, RedisError> {
+        let now = now_ms();
+        let deadline = request
+            .expiration
+            .map(|expiration| expiration.deadline(now));
+        let mut keyspace = self.inner.write().await;
+        let hash = match keyspace.get_live_mut(&request.key, now) {
+            Some(entry) => entry.value.as_hash_mut()?,
+            None => return Ok(vec![None; request.fields.len()]),
+        };
+        let values = request
+            .fields
+            .iter()
+            .map(|name| {
+                let value = hash.get(name, now)?.value.clone();
+                match deadline {
+                    Some(deadline) if deadline <= now as i64 => {
+                        hash.remove(name);
+                    }
+                    Some(deadline) => hash.set_expires_at(name, Some(deadline as u64)),
+                    None if request.persist => hash.set_expires_at(name, None),
+                    None => {}
+                }
+                Some(value)
+            })
+            .collect();
+        if hash.has_deadlines() {
+            keyspace.volatile_fields.insert(&request.key);
+        }
+        keyspace.remove_if_empty(&request.key);
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{protocol::Expiration, storage::test::set};
+
+    fn pairs(pairs: &[(&'static str, &'static str)]) -> Vec<(Bytes, Bytes)> {
+        pairs
+            .iter()
+            .map(|(field, value)| (Bytes::from(*field), Bytes::from(*value)))
+            .collect()
+    }
+
+    fn sorted(mut pairs: Vec<(Bytes, Bytes)>) -> Vec<(Bytes, Bytes)> {
+        pairs.sort();
+        pairs
+    }
+
+    #[tokio::test]
+    async fn set_get_and_delete() {
+        let storage = Storage::instance();
+        let key = || Bytes::from("hash:a");
+        assert_eq!(
+            storage
+                .hset(key(), pairs(&[("f1", "1"), ("f2", "2"), ("f1", "3")]))
+                .await,
+            Ok(2)
+        );
+        assert_eq!(storage.hset(key(), pairs(&[("f2", "4")])).await, Ok(0));
+        assert_eq!(
+            storage
+                .hsetnx(key(), Bytes::from("f2"), Bytes::from("5"))
+                .await,
+            Ok(false)
+        );
+        assert_eq!(
+            storage
+                .hsetnx(key(), Bytes::from("f3"), Bytes::from("5"))
+                .await,
+            Ok(true)
+        );
+        assert_eq!(
+            storage
+                .hmget(b"hash:a", &[Bytes::from("f1"), Bytes::from("absent")])
+                .await,
+            Ok(vec![Some(Bytes::from("3")), None])
+        );
+        assert_eq!(
+            storage.hgetall(b"hash:a").await.map(sorted),
+            Ok(pairs(&[("f1", "3"), ("f2", "4"), ("f3", "5")]))
+        );
+        assert_eq!(storage.hlen(b"hash:a").await, Ok(3));
+        assert_eq!(
+            storage
+                .hdel(b"hash:a", &[Bytes::from("f1"), Bytes::from("absent")])
+                .await,
+            Ok(1)
+        );
+        assert_eq!(
+            storage
+                .hdel(b"hash:a", &[Bytes::from("f2"), Bytes::from("f3")])
+                .await,
+            Ok(2)
+        );
+        // The emptied hash is removed.
+        assert_eq!(storage.key_type(b"hash:a").await, "none");
+        assert_eq!(storage.hgetall(b"hash:a").await, Ok(vec![]));
+    }
+
+    #[tokio::test]
+    async fn increments() {
+        let storage = Storage::instance();
+        let key = || Bytes::from("hash:incr");
+        let field = || Bytes::from("n");
+        assert_eq!(storage.hincrby(key(), field(), 5).await, Ok(5));
+        assert_eq!(storage.hincrby(key(), field(), -7).await, Ok(-2));
+        assert_eq!(
+            storage.hincrby(key(), field(), i64::MIN).await,
+            Err(RedisError::Other(
+                "increment or decrement would overflow".to_owned()
+            ))
+        );
+        assert_eq!(
+            storage.hincrbyfloat(key(), field(), 0.5).await,
+            Ok(Bytes::from("-1.5"))
+        );
+        assert_eq!(
+            storage.hincrbyfloat(key(), Bytes::from("big"), 1e20).await,
+            Ok(Bytes::from("100000000000000000000"))
+        );
+        assert_eq!(
+            storage.hincrbyfloat(key(), Bytes::from("tiny"), 1e-5).await,
+            Ok(Bytes::from("0.00001"))
+        );
+        assert_eq!(
+            storage.hincrby(key(), field(), 1).await,
+            Err(RedisError::Other("hash value is not an integer".to_owned()))
+        );
+        storage
+            .hset(key(), pairs(&[("text", "abc")]))
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.hincrbyfloat(key(), Bytes::from("text"), 1.0).await,
+            Err(RedisError::Other("hash value is not a float".to_owned()))
+        );
+    }
+
+    #[tokio::test]
+    async fn random_fields() {
+        let storage = Storage::instance();
+        let all = pairs(&[("a", "1"), ("b", "2"), ("c", "3")]);
+        storage
+            .hset(Bytes::from("hash:random"), all.clone())
+            .await
+            .unwrap();
+        let picked = storage.hrandfield(b"hash:random", 2).await.unwrap();
+        assert_eq!(picked.len(), 2);
+        assert_ne!(picked[0], picked[1]);
+        assert!(picked.iter().all(|pair| all.contains(pair)));
+        assert_eq!(
+            storage.hrandfield(b"hash:random", 10).await.map(sorted),
+            Ok(all.clone())
+        );
+        let picked = storage.hrandfield(b"hash:random", -10).await.unwrap();
+        assert_eq!(picked.len(), 10);
+        assert!(picked.iter().all(|pair| all.contains(pair)));
+        assert_eq!(storage.hrandfield(b"hash:absent", -10).await, Ok(vec![]));
+    }
+
+    #[tokio::test]
+    async fn scan_all_fields() {
+        let storage = Storage::instance();
+        let all: Vec<(Bytes, Bytes)> = (0..25)
+            .map(|i| (Bytes::from(format!("f{i}")), Bytes::from(i.to_string())))
+            .collect();
+        storage
+            .hset(Bytes::from("hash:scan"), all.clone())
+            .await
+            .unwrap();
+        let mut request = Scan {
+            key: Bytes::from("hash:scan"),
+            cursor: 0,
+            pattern: None,
+            count: 10,
+            no_values: false,
+        };
+        let mut scanned = vec![];
+        loop {
+            let (cursor, pairs) = storage.hscan(&request).await.unwrap();
+            scanned.extend(pairs);
+            if cursor == 0 {
+                break;
+            }
+            request.cursor = cursor;
+        }
+        assert_eq!(sorted(scanned), sorted(all));
+
+        request.cursor = 0;
+        request.count = 100;
+        request.pattern = Some(Bytes::from("f1?"));
+        let (cursor, pairs) = storage.hscan(&request).await.unwrap();
+        assert_eq!(cursor, 0);
+        assert_eq!(pairs.len(), 10);
+    }
+
+    #[tokio::test]
+    async fn wrong_type() {
+        let storage = Storage::instance();
+        set("hash:string", None).await;
+        assert_eq!(
+            storage
+                .hset(Bytes::from("hash:string"), pairs(&[("f", "v")]))
+                .await,
+            Err(RedisError::WrongType)
+        );
+        assert_eq!(
+            storage.hlen(b"hash:string").await,
+            Err(RedisError::WrongType)
+        );
+    }
+
+    fn hexpire(key: &'static str, milliseconds: i64, fields: &[&'static str]) -> HExpire {
+        HExpire {
+            key: Bytes::from(key),
+            expiration: Expiration::After(milliseconds),
+            condition: None,
+            fields: fields.iter().map(|field| Bytes::from(*field)).collect(),
+            command: "hpexpire".to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn field_expiration() {
+        let storage = Storage::instance();
+        let key = || Bytes::from("hash:ttl");
+        let fields = |fields: &[&'static str]| -> Vec<Bytes> {
+            fields.iter().map(|field| Bytes::from(*field)).collect()
+        };
+        storage
+            .hset(key(), pairs(&[("a", "1"), ("b", "2"), ("c", "3")]))
+            .await
+            .unwrap();
+        assert_eq!(
+            storage
+                .hexpire(hexpire("hash:ttl", 60_000, &["a", "absent"]))
+                .await,
+            Ok(vec![1, -2])
+        );
+        assert_eq!(
+            storage.hexpire(hexpire("hash:ttl", 0, &["c"])).await,
+            Ok(vec![2])
+        );
+        assert_eq!(
+            storage.hexpire(hexpire("hash:ttl", 120_000, &["b"])).await,
+            Ok(vec![1])
+        );
+        let deadlines = storage
+            .hexpires_at(b"hash:ttl", &fields(&["a", "c"]))
+            .await
+            .unwrap();
+        assert!(matches!(deadlines[..], [Some(Some(_)), None]));
+
+        // Increments keep the deadline, HSET removes it.
+        storage.hincrby(key(), Bytes::from("a"), 1).await.unwrap();
+        storage.hset(key(), pairs(&[("b", "4")])).await.unwrap();
+        let deadlines = storage
+            .hexpires_at(b"hash:ttl", &fields(&["a", "b"]))
+            .await
+            .unwrap();
+        assert!(matches!(deadlines[..], [Some(Some(_)), Some(None)]));
+        assert_eq!(
+            storage
+                .hpersist(b"hash:ttl", &fields(&["a", "b", "absent"]))
+                .await,
+            Ok(vec![1, -1, -2])
+        );
+
+        // A hash whose fields all expire is gone.
+        storage
+            .hexpire(hexpire("hash:ttl", 1, &["a", "b"]))
+            .await
+            .unwrap();
+        assert_eq!(storage.hlen(b"hash:ttl").await, Ok(2));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(storage.hlen(b"hash:ttl").await, Ok(0));
+        assert_eq!(storage.key_type(b"hash:ttl").await, "none");
+    }
+
+    #[tokio::test]
+    async fn hgetex() {
+        let storage = Storage::instance();
+        storage
+            .hset(Bytes::from("hash:getex"), pairs(&[("a", "1"), ("b", "2")]))
+            .await
+            .unwrap();
+        let request = |expiration, persist| HGetEx {
+            key: Bytes::from("hash:getex"),
+            expiration,
+            persist,
+            fields: vec![Bytes::from("a"), Bytes::from("absent")],
+        };
+        let expiration = Some(Expiration::After(60_000));
+        assert_eq!(
+            storage.hgetex(request(expiration, false)).await,
+            Ok(vec![Some(Bytes::from("1")), None])
+        );
+        assert!(matches!(
+            storage
+                .hexpires_at(b"hash:getex", &[Bytes::from("a")])
+                .await
+                .as_deref(),
+            Ok([Some(Some(_))])
+        ));
+        storage.hgetex(request(None, true)).await.unwrap();
+        assert_eq!(
+            storage
+                .hexpires_at(b"hash:getex", &[Bytes::from("a")])
+                .await,
+            Ok(vec![Some(None)])
+        );
+        let expiration = Some(Expiration::At(1));
+        assert_eq!(
+            storage.hgetex(request(expiration, false)).await,
+            Ok(vec![Some(Bytes::from("1")), None])
+        );
+        assert_eq!(storage.hlen(b"hash:getex").await, Ok(1));
+    }
+
+    #[test]
+    fn active_field_expiration() {
+        let mut keyspace = Keyspace::default();
+        let now = now_ms();
+        for i in 0..100 {
+            let mut hash = Hash::default();
+            hash.insert(Bytes::from("expired"), Bytes::from("v"), false);
+            hash.insert(Bytes::from("alive"), Bytes::from("v"), false);
+            hash.set_expires_at(b"expired", Some(now - 1));
+            keyspace.insert(Bytes::from(format!("hash{i}")), Value::Hash(hash), None);
+        }
+        let mut hash = Hash::default();
+        hash.insert(Bytes::from("expired"), Bytes::from("v"), false);
+        hash.set_expires_at(b"expired", Some(now - 1));
+        keyspace.insert(Bytes::from("all_expired"), Value::Hash(hash), None);
+        assert_eq!(keyspace.volatile_fields.len(), 101);
+
+        let removed = keyspace.active_expire_fields_cycle(&mut Rng::new(), Duration::from_secs(10));
+
+        // Every sampled hash had an expired field, so sampling goes on until none are left.
+        assert_eq!(removed, 101);
+        assert_eq!(keyspace.volatile_fields.len(), 0);
+        assert_eq!(keyspace.entries.len(), 100);
+        assert!(!keyspace.entries.contains_key(&b"all_expired"[..]));
+    }
+}
@@ -0,0 +1,695 @@
+//! HyperLogLog commands.
+//!
+//! HyperLogLogs are strings in the exact layout Redis uses, so they can be copied between
+//! servers with GET and SET. A 16 byte header holds the `HYLL` magic, the encoding and the
+//! cached cardinality, whose most significant bit marks the cache as invalid. 16384 registers
+//! of 6 bits follow, either packed in the dense encoding or run-length encoded in the sparse
+//! one, which new HyperLogLogs start in:
+//!
+//! * `00xxxxxx`: `xxxxxx + 1` zero registers.
+//! * `01xxxxxx yyyyyyyy`: `xxxxxxyyyyyyyy + 1` zero registers.
+//! * `1vvvvvxx`: `xx + 1` registers set to `vvvvv + 1`.
+//!
+//! The sparse encoding is converted to the dense one when a register exceeds the range of
+//! `1vvvvvxx` or the string grows over `hll-sparse-max-bytes`.
+use super::{now_ms, Keyspace, Storage, Value};
+use crate::{config, error::RedisError};
+use bytes::Bytes;
+
+/// Number of bits of the hash addressing a register.
+const P: u32 = 14;
+const REGISTERS: usize = 1 << P;
+/// Number of bits of the hash the register values are counted in.
+const Q: u32 = 64 - P;
+const BITS: usize = 6;
+const REGISTER_MAX: u8 = (1 << BITS) - 1;
+const HEADER_SIZE: usize = 16;
+const DENSE_SIZE: usize = HEADER_SIZE + REGISTERS * BITS / 8;
+const DENSE: u8 = 0;
+const SPARSE: u8 = 1;
+/// Offset of the cached cardinality in the header.
+const CARDINALITY: usize = 8;
+const SPARSE_VAL_MAX_VALUE: u8 = 32;
+const SPARSE_VAL_MAX_LEN: usize = 4;
+const SPARSE_ZERO_MAX_LEN: usize = 64;
+const SPARSE_XZERO_MAX_LEN: usize = 16384;
+/// 0.5 / ln(2).
+const ALPHA_INF: f64 = 0.721_347_520_444_481_7;
+
+/// MurmurHash2, 64-bit version, by Austin Appleby, reading the key as little endian.
+fn murmur_hash64a(key: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4_a793_5bd1_e995;
+    const R: u32 = 47;
+    let mut h = seed ^ (key.len() as u64).wrapping_mul(M);
+    let mut chunks = key.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().expect("Chunk of 8 bytes"));
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, byte) in tail.iter().enumerate() {
+            h ^= (*byte as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+/// Returns the register of the element and the length of the pattern `000..1` in its hash,
+/// which is the value the register is raised to.
+fn pattern_len(element: &[u8]) -> (usize, u8) {
+    let hash = murmur_hash64a(element, 0xadc8_3b19);
+    let index = (hash & (REGISTERS as u64 - 1)) as usize;
+    // The extra bit makes the count at most Q + 1.
+    let hash = (hash >> P) | (1 << Q);
+    (index, hash.trailing_zeros() as u8 + 1)
+}
+
+fn dense_get(registers: &[u8], index: usize) -> u8 {
+    let byte = index * BITS / 8;
+    let bit = (index * BITS) & 7;
+    // The last register ends within its first byte.
+    let b0 = registers[byte] as u16;
+    let b1 = registers.get(byte + 1).copied().unwrap_or_default() as u16;
+    ((b0 >> bit | b1 << (8 - bit)) & REGISTER_MAX as u16) as u8
+}
+
+fn dense_set(registers: &mut [u8], index: usize, value: u8) {
+    let byte = index * BITS / 8;
+    let bit = (index * BITS) & 7;
+    let value = value as u16;
+    let max = REGISTER_MAX as u16;
+    registers[byte] &= !(max << bit) as u8;
+    registers[byte] |= (value << bit) as u8;
+    if let Some(next) = registers.get_mut(byte + 1) {
+        *next &= !(max >> (8 - bit)) as u8;
+        *next |= (value >> (8 - bit)) as u8;
+    }
+}
+
+fn corrupted() -> RedisError {
+    RedisError::CorruptedHll
+}
+
+/// A sparse opcode.
+enum Opcode {
+    Zero(usize),
+    XZero(usize),
+    Val(u8, usize),
+}
+
+impl Opcode {
+    /// Reads the opcode at the position.
+    fn read(data: &[u8], p: usize) -> Result<Self, RedisError> {
+        let byte = data[p];
+        Ok(match byte & 0xc0 {
+            0x00 => Opcode::Zero((byte & 0x3f) as usize + 1),
+            0x40 => {
+                let low = *data.get(p + 1).ok_or_else(corrupted)?;
+                Opcode::XZero((((byte & 0x3f) as usize) << 8 | low as usize) + 1)
+            }
+            _ => Opcode::Val((byte >> 2 & 0x1f) + 1, (byte & 0x3) as usize + 1),
+        })
+    }
+
+    fn run_len(&self) -> usize {
+        match self {
+            Opcode::Zero(len) | Opcode::XZero(len) | Opcode::Val(_, len) => *len,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Opcode::XZero(_) => 2,
+            _ => 1,
+        }
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        match *self {
+            Opcode::Zero(len) => out.push((len - 1) as u8),
+            Opcode::XZero(len) => {
+                let len = len - 1;
+                out.push((len >> 8) as u8 | 0x40);
+                out.push((len & 0xff) as u8);
+            }
+            Opcode::Val(value, len) => out.push((value - 1) << 2 | (len - 1) as u8 | 0x80),
+        }
+    }
+
+    /// A run of zero registers, which may need the two byte opcode.
+    fn zeros(len: usize) -> Self {
+        match len > SPARSE_ZERO_MAX_LEN {
+            true => Opcode::XZero(len),
+            false => Opcode::Zero(len),
+        }
+    }
+}
+
+/// Calls `f` with the first register, the length and the value of each run of registers of the
+/// sparse encoding. Fails if the runs don't cover exactly all the registers.
+fn sparse_runs(data: &[u8], mut f: impl FnMut(usize, usize, u8)) -> Result<(), RedisError> {
+    let mut p = 0;
+    let mut index = 0;
+    while p < data.len() {
+        let opcode = Opcode::read(data, p)?;
+        let len = opcode.run_len();
+        if index + len > REGISTERS {
+            return Err(corrupted());
+        }
+        let value = match opcode {
+            Opcode::Val(value, _) => value,
+            _ => 0,
+        };
+        f(index, len, value);
+        index += len;
+        p += opcode.size();
+    }
+    match index == REGISTERS {
+        true => Ok(()),
+        false => Err(corrupted()),
+    }
+}
+
+/// Returns an empty HyperLogLog: sparse registers in XZERO opcodes and a cached cardinality
+/// of 0.
+fn new_hll() -> Vec<u8> {
+    let mut hll = Vec::with_capacity(HEADER_SIZE + 2);
+    hll.extend_from_slice(b"HYLL");
+    hll.push(SPARSE);
+    hll.resize(HEADER_SIZE, 0);
+    let mut left = REGISTERS;
+    while left > 0 {
+        let len = left.min(SPARSE_XZERO_MAX_LEN);
+        Opcode::XZero(len).write(&mut hll);
+        left -= len;
+    }
+    hll
+}
+
+/// Checks that the string is a HyperLogLog.
+fn validate(hll: &[u8]) -> Result<(), RedisError> {
+    let valid = hll.len() >= HEADER_SIZE
+        && &hll[..4] == b"HYLL"
+        && hll[4] <= SPARSE
+        && (hll[4] != DENSE || hll.len() == DENSE_SIZE);
+    match valid {
+        true => Ok(()),
+        false => Err(RedisError::InvalidHll),
+    }
+}
+
+fn invalidate_cache(hll: &mut [u8]) {
+    hll[CARDINALITY + 7] |= 0x80;
+}
+
+fn sparse_to_dense(hll: &mut Vec<u8>) -> Result<(), RedisError> {
+    if hll[4] == DENSE {
+        return Ok(());
+    }
+    // The header keeps the cached cardinality.
+    let mut dense = vec![0; DENSE_SIZE];
+    dense[..HEADER_SIZE].copy_from_slice(&hll[..HEADER_SIZE]);
+    dense[4] = DENSE;
+    let registers = &mut dense[HEADER_SIZE..];
+    sparse_runs(&hll[HEADER_SIZE..], |index, len, value| {
+        if value > 0 {
+            for register in index..index + len {
+                dense_set(registers, register, value);
+            }
+        }
+    })?;
+    *hll = dense;
+    Ok(())
+}
+
+/// Raises the register to the value in the sparse encoding, converting it to the dense one if
+/// needed. Returns false if the register is already at least the value.
+fn sparse_set(
+    hll: &mut Vec<u8>,
+    index: usize,
+    value: u8,
+    sparse_max_bytes: usize,
+) -> Result<bool, RedisError> {
+    if value > SPARSE_VAL_MAX_VALUE {
+        return promote(hll, index, value);
+    }
+    // Find the opcode covering the register.
+    let end = hll.len();
+    let mut p = HEADER_SIZE;
+    let mut first = 0;
+    let mut prev = None;
+    let mut opcode = None;
+    while p < end {
+        let current = Opcode::read(hll, p)?;
+        if index < first + current.run_len() {
+            opcode = Some(current);
+            break;
+        }
+        prev = Some(p);
+        p += current.size();
+        first += current.run_len();
+    }
+    let opcode = opcode.ok_or_else(corrupted)?;
+    let last = first + opcode.run_len() - 1;
+    match opcode {
+        Opcode::Val(current, _) if current >= value => return Ok(false),
+        // A run of just this register is updated in place.
+        Opcode::Val(_, 1) | Opcode::Zero(1) => {
+            hll.splice(p..p + 1, val_byte(value, 1));
+        }
+        // Otherwise the run is split around the register, at worst into XZERO-VAL-XZERO.
+        _ => {
+            let mut sequence = Vec::with_capacity(5);
+            let split = |len: usize, sequence: &mut Vec<u8>| match opcode {
+                Opcode::Val(current, _) => Opcode::Val(current, len).write(sequence),
+                _ => Opcode::zeros(len).write(sequence),
+            };
+            if index != first {
+                split(index - first, &mut sequence);
+            }
+            Opcode::Val(value, 1).write(&mut sequence);
+            if index != last {
+                split(last - index, &mut sequence);
+            }
+            let grows = sequence.len() > opcode.size();
+            if grows && hll.len() + sequence.len() - opcode.size() > sparse_max_bytes {
+                return promote(hll, index, value);
+            }
+            hll.splice(p..p + opcode.size(), sequence);
+        }
+    }
+    merge_adjacent(hll, prev.unwrap_or(HEADER_SIZE));
+    invalidate_cache(hll);
+    Ok(true)
+}
+
+/// Returns the encoded VAL opcode.
+fn val_byte(value: u8, len: usize) -> Option<u8> {
+    let mut byte = Vec::with_capacity(1);
+    Opcode::Val(value, len).write(&mut byte);
+    byte.pop()
+}
+
+/// Merges adjacent VAL opcodes of the same value, scanning up to 5 opcodes from the position.
+fn merge_adjacent(hll: &mut Vec<u8>, mut p: usize) {
+    let mut scan = 5;
+    while p < hll.len() && scan > 0 {
+        scan -= 1;
+        match hll[p] & 0xc0 {
+            0x40 => {
+                p += 2;
+                continue;
+            }
+            0x00 => {
+                p += 1;
+                continue;
+            }
+            _ => {}
+        }
+        if p + 1 < hll.len() && hll[p + 1] & 0x80 != 0 {
+            let value = (hll[p] >> 2 & 0x1f) + 1;
+            let next_value = (hll[p + 1] >> 2 & 0x1f) + 1;
+            let len = (hll[p] & 0x3) as usize + 1 + (hll[p + 1] & 0x3) as usize + 1;
+            if value == next_value && len <= SPARSE_VAL_MAX_LEN {
+                hll.splice(p..p + 2, val_byte(value, len));
+                // The merged opcode may merge with the next one too.
+                continue;
+            }
+        }
+        p += 1;
+    }
+}
+
+/// Converts the HyperLogLog to the dense encoding and raises the register, which is known to
+/// need the update.
+fn promote(hll: &mut Vec<u8>, index: usize, value: u8) -> Result<bool, RedisError> {
+    sparse_to_dense(hll)?;
+    dense_set(&mut hll[HEADER_SIZE..], index, value);
+    Ok(true)
+}
+
+/// Raises the register to the value. Returns false if it is already at least the value.
+fn set(
+    hll: &mut Vec<u8>,
+    index: usize,
+    value: u8,
+    sparse_max_bytes: usize,
+) -> Result<bool, RedisError> {
+    if hll[4] == SPARSE {
+        return sparse_set(hll, index, value, sparse_max_bytes);
+    }
+    let registers = &mut hll[HEADER_SIZE..];
+    if dense_get(registers, index) >= value {
+        return Ok(false);
+    }
+    dense_set(registers, index, value);
+    Ok(true)
+}
+
+/// Raises each register of `max` to the register of the HyperLogLog if it is greater.
+fn merge(max: &mut [u8], hll: &[u8]) -> Result<(), RedisError> {
+    let registers = &hll[HEADER_SIZE..];
+    if hll[4] == DENSE {
+        for (index, max) in max.iter_mut().enumerate() {
+            *max = (*max).max(dense_get(registers, index));
+        }
+        return Ok(());
+    }
+    sparse_runs(registers, |index, len, value| {
+        for max in &mut max[index..index + len] {
+            *max = (*max).max(value);
+        }
+    })
+}
+
+/// Returns the histogram of register values.
+fn histogram(hll: &[u8]) -> Result<[u32; 64], RedisError> {
+    let mut histogram = [0; 64];
+    let registers = &hll[HEADER_SIZE..];
+    if hll[4] == DENSE {
+        for index in 0..REGISTERS {
+            histogram[dense_get(registers, index) as usize] += 1;
+        }
+    } else {
+        sparse_runs(registers, |_, len, value| {
+            histogram[value as usize] += len as u32;
+        })?;
+    }
+    Ok(histogram)
+}
+
+fn sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+    let mut y = 1.0;
+    let mut z = x;
+    loop {
+        x *= x;
+        let previous = z;
+        z += x * y;
+        y += y;
+        if previous == z {
+            return z;
+        }
+    }
+}
+
+fn tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+    let mut y = 1.0;
+    let mut z = 1.0 - x;
+    loop {
+        x = x.sqrt();
+        let previous = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if previous == z {
+            return z / 3.0;
+        }
+    }
+}
+
+/// Estimates the cardinality from the histogram of register values with the improved
+/// estimator of Otmar Ertl, the same as Redis.
+fn estimate(histogram: &[u32; 64]) -> u64 {
+    let m = REGISTERS as f64;
+    let mut z = m * tau((m - histogram[Q as usize + 1] as f64) / m);
+    for count in histogram[1..=Q as usize].iter().rev() {
+        z += *count as f64;
+        z *= 0.5;
+    }
+    z += m * sigma(histogram[0] as f64 / m);
+    (ALPHA_INF * m * m / z).round() as u64
+}
+
+/// Returns the cardinality, from the cache if it is valid. Otherwise the cardinality is
+/// computed and cached, and true is returned along with it.
+fn count(hll: &mut [u8]) -> Result<(u64, bool), RedisError> {
+    let cached: [u8; 8] = hll[CARDINALITY..HEADER_SIZE]
+        .try_into()
+        .expect("Cardinality of 8 bytes");
+    if cached[7] & 0x80 == 0 {
+        return Ok((u64::from_le_bytes(cached), false));
+    }
+    let cardinality = estimate(&histogram(hll)?);
+    hll[CARDINALITY..HEADER_SIZE].copy_from_slice(&cardinality.to_le_bytes());
+    Ok((cardinality, true))
+}
+
+/// A HyperLogLog copied out of the keyspace, with the deadline of its key.
+type Copied = (Vec<u8>, Option<u64>);
+
+impl Keyspace {
+    /// Returns the HyperLogLog at the key and its deadline, `None` if the key is absent.
+    fn hyperloglog(&mut self, key: &[u8], now: u64) -> Result<Option<Copied>, RedisError> {
+        let entry = match self.get_live(key, now) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let hll = entry.value.as_string()?;
+        validate(hll)?;
+        Ok(Some((hll.to_vec(), entry.expires_at)))
+    }
+}
+
+impl Storage {
+    /// Adds the elements to the HyperLogLog, creating it if it is absent. Returns true if the
+    /// key was created or the estimate may have changed.
+    pub async fn pfadd(&self, key: Bytes, elements: &[Bytes]) -> Result<bool, RedisError> {
+        let sparse_max_bytes = config::current().hll_sparse_max_bytes;
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let (mut hll, expires_at, mut updated) = match keyspace.hyperloglog(&key, now)? {
+            Some((hll, expires_at)) => (hll, expires_at, false),
+            None => (new_hll(), None, true),
+        };
+        for element in elements {
+            let (index, value) = pattern_len(element);
+            updated |= set(&mut hll, index, value, sparse_max_bytes)?;
+        }
+        if updated {
+            invalidate_cache(&mut hll);
+            keyspace.insert(key, Value::String(Bytes::from(hll)), expires_at);
+        }
+        Ok(updated)
+    }
+
+    /// Returns the estimated cardinality of the union of the HyperLogLogs. Absent keys count as
+    /// empty. The cardinality of a single HyperLogLog is cached in it.
+    pub async fn pfcount(&self, keys: &[Bytes]) -> Result<u64, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        if let [key] = keys {
+            let (mut hll, expires_at) = match keyspace.hyperloglog(key, now)? {
+                Some(found) => found,
+                None => return Ok(0),
+            };
+            let (cardinality, computed) = count(&mut hll)?;
+            if computed {
+                keyspace.insert(key.clone(), Value::String(Bytes::from(hll)), expires_at);
+            }
+            return Ok(cardinality);
+        }
+        let mut max = vec![0; REGISTERS];
+        for key in keys {
+            if let Some((hll, _)) = keyspace.hyperloglog(key, now)? {
+                merge(&mut max, &hll)?;
+            }
+        }
+        let mut histogram = [0; 64];
+        for value in max {
+            histogram[value as usize] += 1;
+        }
+        Ok(estimate(&histogram))
+    }
+
+    /// Stores the union of the HyperLogLogs, including the destination itself, at the
+    /// destination. The result is dense if any of them is dense.
+    pub async fn pfmerge(&self, destination: Bytes, keys: &[Bytes]) -> Result<(), RedisError> {
+        let sparse_max_bytes = config::current().hll_sparse_max_bytes;
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let mut max = vec![0; REGISTERS];
+        let mut dense = false;
+        for key in std::iter::once(&destination).chain(keys) {
+            if let Some((hll, _)) = keyspace.hyperloglog(key, now)? {
+                dense |= hll[4] == DENSE;
+                merge(&mut max, &hll)?;
+            }
+        }
+        let (mut hll, expires_at) = keyspace
+            .hyperloglog(&destination, now)?
+            .unwrap_or_else(|| (new_hll(), None));
+        if dense {
+            sparse_to_dense(&mut hll)?;
+        }
+        for (index, value) in max.into_iter().enumerate() {
+            if value > 0 {
+                set(&mut hll, index, value, sparse_max_bytes)?;
+            }
+        }
+        invalidate_cache(&mut hll);
+        keyspace.insert(destination, Value::String(Bytes::from(hll)), expires_at);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn elements(prefix: &str, count: usize) -> Vec<Bytes> {
+        (0..count)
+            .map(|i| Bytes::from(format!("{}{}", prefix, i)))
+            .collect()
+    }
+
+    #[test]
+    fn hashing() {
+        // Reference values of the C implementation.
+        assert_eq!(murmur_hash64a(b"", 0xadc8_3b19), 0xd8df_ea65_85bc_9732);
+        assert_eq!(murmur_hash64a(b"foo", 0xadc8_3b19), 0xe646_09b8_b014_1cb4);
+        assert_eq!(
+            murmur_hash64a(b"hello world!", 0xadc8_3b19),
+            0x0fc4_4401_1f57_220c
+        );
+        let (index, count) = pattern_len(b"foo");
+        assert!(index < REGISTERS);
+        assert!((1..=Q as u8 + 1).contains(&count));
+    }
+
+    #[test]
+    fn registers() {
+        let mut registers = vec![0; DENSE_SIZE - HEADER_SIZE];
+        for index in [0, 1, 2, 3, 4, 5000, REGISTERS - 1] {
+            dense_set(&mut registers, index, (index % 63 + 1) as u8);
+        }
+        for index in [0, 1, 2, 3, 4, 5000, REGISTERS - 1] {
+            assert_eq!(dense_get(&registers, index), (index % 63 + 1) as u8);
+        }
+        assert_eq!(dense_get(&registers, 6), 0);
+    }
+
+    #[test]
+    fn sparse_encoding() {
+        let mut hll = new_hll();
+        assert_eq!(hll.len(), HEADER_SIZE + 2);
+        assert_eq!(&hll[HEADER_SIZE..], &[0x7f, 0xff]);
+        assert_eq!(count(&mut hll), Ok((0, false)));
+
+        // XZERO-VAL-XZERO, then the VAL runs merge.
+        assert_eq!(set(&mut hll, 1000, 3, 3000), Ok(true));
+        assert_eq!(&hll[HEADER_SIZE..], &[0x43, 0xe7, 0x88, 0x7c, 0x16]);
+        assert_eq!(set(&mut hll, 1000, 2, 3000), Ok(false));
+        assert_eq!(set(&mut hll, 1001, 3, 3000), Ok(true));
+        assert_eq!(&hll[HEADER_SIZE..], &[0x43, 0xe7, 0x89, 0x7c, 0x15]);
+        assert_eq!(count(&mut hll).unwrap(), (2, true));
+
+        // A value out of the range of VAL opcodes converts to dense.
+        assert_eq!(set(&mut hll, 7, 40, 3000), Ok(true));
+        assert_eq!(hll.len(), DENSE_SIZE);
+        assert_eq!(hll[4], DENSE);
+        assert_eq!(dense_get(&hll[HEADER_SIZE..], 1001), 3);
+        assert_eq!(dense_get(&hll[HEADER_SIZE..], 7), 40);
+    }
+
+    #[test]
+    fn sparse_matches_registers() {
+        let mut hll = new_hll();
+        let mut expected = vec![0; REGISTERS];
+        for element in elements("element", 600) {
+            let (index, value) = pattern_len(&element);
+            let updated = set(&mut hll, index, value, usize::MAX).unwrap();
+            assert_eq!(updated, value > expected[index]);
+            expected[index] = expected[index].max(value);
+        }
+        assert_eq!(hll[4], SPARSE);
+        let mut registers = vec![0; REGISTERS];
+        merge(&mut registers, &hll).unwrap();
+        assert_eq!(registers, expected);
+
+        // Growing over the limit converts to dense.
+        let limit = hll.len();
+        for element in elements("more", 100) {
+            let (index, value) = pattern_len(&element);
+            set(&mut hll, index, value, limit).unwrap();
+            expected[index] = expected[index].max(value);
+        }
+        assert_eq!(hll[4], DENSE);
+        let mut registers = vec![0; REGISTERS];
+        merge(&mut registers, &hll).unwrap();
+        assert_eq!(registers, expected);
+    }
+
+    #[test]
+    fn estimates() {
+        let mut hll = new_hll();
+        for element in elements("item", 10_000) {
+            let (index, value) = pattern_len(&element);
+            set(&mut hll, index, value, 3000).unwrap();
+        }
+        assert_eq!(hll[4], DENSE);
+        let (cardinality, _) = count(&mut hll).unwrap();
+        assert!((9_800..=10_200).contains(&cardinality), "{}", cardinality);
+    }
+
+    #[tokio::test]
+    async fn commands() {
+        let storage = Storage::instance();
+        let key = Bytes::from("hll:visitors");
+        assert_eq!(
+            storage.pfadd(key.clone(), &elements("user", 100)).await,
+            Ok(true)
+        );
+        assert_eq!(
+            storage.pfadd(key.clone(), &elements("user", 100)).await,
+            Ok(false)
+        );
+        let cardinality = storage.pfcount(std::slice::from_ref(&key)).await.unwrap();
+        assert!((98..=102).contains(&cardinality));
+        let raw = storage.get(&key).await.unwrap().unwrap();
+        assert_eq!(&raw[..5], b"HYLL\x01");
+        // The cardinality is cached.
+        assert_eq!(&raw[8..16], &cardinality.to_le_bytes());
+
+        let other = Bytes::from("hll:other");
+        storage
+            .pfadd(other.clone(), &elements("guest", 50))
+            .await
+            .unwrap();
+        let union = storage
+            .pfcount(&[key.clone(), other.clone()])
+            .await
+            .unwrap();
+        assert!((145..=155).contains(&union));
+        let merged = Bytes::from("hll:merged");
+        storage
+            .pfmerge(merged.clone(), &[key.clone(), other])
+            .await
+            .unwrap();
+        assert_eq!(storage.pfcount(&[merged]).await, Ok(union));
+
+        assert_eq!(storage.pfadd(Bytes::from("hll:empty"), &[]).await, Ok(true));
+        assert_eq!(storage.pfcount(&[Bytes::from("hll:absent")]).await, Ok(0));
+        storage
+            .set_range(Bytes::from("hll:string"), 0, b"not a hyperloglog")
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.pfcount(&[Bytes::from("hll:string")]).await,
+            Err(RedisError::InvalidHll)
+        );
+    }
+}
@@ -0,0 +1,651 @@
+//! List commands.
+use super::{now_ms, Keyspace, Popped, Storage, Value};
+use crate::{
+    error::RedisError,
+    protocol::{LPos, Side, Timeout},
+};
+use bytes::Bytes;
+use std::collections::VecDeque;
+
+/// Converts inclusive indexes, which count from the tail if negative, into a range of the list
+/// the way LRANGE does. Returns `None` if the range is empty.
+fn list_range(length: usize, start: i64, end: i64) -> Option<(usize, usize)> {
+    let length = length as i64;
+    let start = if start < 0 { length + start } else { start }.max(0);
+    let end = if end < 0 { length + end } else { end }.min(length - 1);
+    if start > end || start >= length {
+        return None;
+    }
+    Some((start as usize, end as usize))
+}
+
+/// Converts an index, which counts from the tail if negative, into a position in the list.
+fn list_index(length: usize, index: i64) -> Option<usize> {
+    let index = if index < 0 {
+        length as i64 + index
+    } else {
+        index
+    };
+    usize::try_from(index).ok().filter(|index| *index < length)
+}
+
+fn push(list: &mut VecDeque<Bytes>, side: Side, element: Bytes) {
+    match side {
+        Side::Left => list.push_front(element),
+        Side::Right => list.push_back(element),
+    }
+}
+
+fn pop(list: &mut VecDeque<Bytes>, side: Side) -> Option<Bytes> {
+    match side {
+        Side::Left => list.pop_front(),
+        Side::Right => list.pop_back(),
+    }
+}
+
+/// Operation on a list, performed right away or once a blocked client is served.
+#[derive(Clone, Debug)]
+pub(super) enum ListOperation {
+    /// Pops up to `count` elements.
+    Pop { side: Side, count: usize },
+    /// Pops an element and pushes it to the destination list.
+    Move {
+        destination: Bytes,
+        from: Side,
+        to: Side,
+    },
+}
+
+impl Keyspace {
+    /// Performs the operation on the list at the key. Returns the popped elements or `None` if
+    /// there is no list.
+    pub(super) fn list_operation(
+        &mut self,
+        key: &[u8],
+        operation: &ListOperation,
+        now: u64,
+    ) -> Result<Option<Vec<Bytes>>, RedisError> {
+        match operation {
+            ListOperation::Pop { side, count } => {
+                let list = match self.get_live_mut(key, now) {
+                    Some(entry) => entry.value.as_list_mut()?,
+                    None => return Ok(None),
+                };
+                let count = (*count).min(list.len());
+                let elements = (0..count).filter_map(|_| pop(list, *side)).collect();
+                self.remove_if_empty(key);
+                Ok(Some(elements))
+            }
+            ListOperation::Move {
+                destination,
+                from,
+                to,
+            } => {
+                // Nothing is popped if the element can't be pushed.
+                if let Some(entry) = self.get_live(destination, now) {
+                    entry.value.as_list()?;
+                }
+                let element = match self.get_live_mut(key, now) {
+                    Some(entry) => pop(entry.value.as_list_mut()?, *from),
+                    None => return Ok(None),
+                };
+                self.remove_if_empty(key);
+                let element = match element {
+                    Some(element) => element,
+                    None => return Ok(None),
+                };
+                let entry = self
+                    .get_or_insert_with(destination.clone(), now, || Value::List(VecDeque::new()));
+                push(entry.value.as_list_mut()?, *to, element.clone());
+                self.blocked.signal(destination);
+                Ok(Some(vec![element]))
+            }
+        }
+    }
+
+    /// Performs the operation on the first of the keys holding a list.
+    pub(super) fn pop_first(
+        &mut self,
+        keys: &[Bytes],
+        operation: &ListOperation,
+        now: u64,
+    ) -> Result<Option<Popped>, RedisError> {
+        for key in keys {
+            if let Some(elements) = self.list_operation(key, operation, now)? {
+                return Ok(Some(Popped {
+                    key: key.clone(),
+                    elements,
+                }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Storage {
+    /// Pushes the elements one by one to a side of the list, creating the list unless
+    /// `only_existing` is set. Then serves clients blocked on the list. Returns the length of the
+    /// list after the push, 0 if it doesn't exist.
+    pub async fn push(
+        &self,
+        key: Bytes,
+        elements: Vec<Bytes>,
+        side: Side,
+        only_existing: bool,
+    ) -> Result<usize, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let entry = if only_existing {
+            match keyspace.get_live_mut(&key, now) {
+                Some(entry) => entry,
+                None => return Ok(0),
+            }
+        } else {
+            keyspace.get_or_insert_with(key.clone(), now, || Value::List(VecDeque::new()))
+        };
+        let list = entry.value.as_list_mut()?;
+        for element in elements {
+            push(list, side, element);
+        }
+        let length = list.len();
+        keyspace.blocked.signal(&key);
+        keyspace.serve_blocked(now);
+        Ok(length)
+    }
+
+    /// Removes up to `count` elements from a side of the list. Returns `None` if the list
+    /// doesn't exist.
+    pub async fn pop(
+        &self,
+        key: &[u8],
+        side: Side,
+        count: usize,
+    ) -> Result<Option<Vec<Bytes>>, RedisError> {
+        let now = now_ms();
+        let operation = ListOperation::Pop { side, count };
+        self.inner
+            .write()
+            .await
+            .list_operation(key, &operation, now)
+    }
+
+    /// Removes up to `count` elements from a side of the first non-empty list of the keys.
+    /// Returns `None` if all the lists are empty.
+    pub async fn mpop(
+        &self,
+        keys: &[Bytes],
+        side: Side,
+        count: usize,
+    ) -> Result<Option<Popped>, RedisError> {
+        let now = now_ms();
+        let operation = ListOperation::Pop { side, count };
+        self.inner.write().await.pop_first(keys, &operation, now)
+    }
+
+    /// Removes up to `count` elements from a side of the first non-empty list of the keys like
+    /// `mpop` does. If all the lists are empty, blocks until an element is pushed to one of them
+    /// or the timeout expires. Returns `None` on timeout.
+    pub async fn blocking_pop(
+        &self,
+        keys: Vec<Bytes>,
+        side: Side,
+        count: usize,
+        timeout: Timeout,
+    ) -> Result<Option<Popped>, RedisError> {
+        self.block_on_lists(keys, ListOperation::Pop { side, count }, timeout)
+            .await
+    }
+
+    /// Returns the length of the list, 0 if it doesn't exist.
+    pub async fn llen(&self, key: &[u8]) -> Result<usize, RedisError> {
+        let now = now_ms();
+        match self.inner.read().await.get(key, now) {
+            Some(entry) => Ok(entry.value.as_list()?.len()),
+            None => Ok(0),
+        }
+    }
+
+    /// Returns elements between the inclusive indexes.
+    pub async fn lrange(&self, key: &[u8], start: i64, end: i64) -> Result<Vec<Bytes>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        let list = match keyspace.get(key, now) {
+            Some(entry) => entry.value.as_list()?,
+            None => return Ok(vec![]),
+        };
+        Ok(match list_range(list.len(), start, end) {
+            Some((start, end)) => list.range(start..=end).cloned().collect(),
+            None => vec![],
+        })
+    }
+
+    /// Returns the element at the index.
+    pub async fn lindex(&self, key: &[u8], index: i64) -> Result<Option<Bytes>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        let list = match keyspace.get(key, now) {
+            Some(entry) => entry.value.as_list()?,
+            None => return Ok(None),
+        };
+        Ok(list_index(list.len(), index).map(|index| list[index].clone()))
+    }
+
+    /// Replaces the element at the index.
+    pub async fn lset(&self, key: &[u8], index: i64, element: Bytes) -> Result<(), RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let list = match keyspace.get_live_mut(key, now) {
+            Some(entry) => entry.value.as_list_mut()?,
+            None => return Err(RedisError::NoSuchKey),
+        };
+        let index = list_index(list.len(), index)
+            .ok_or_else(|| RedisError::Other("index out of range".to_owned()))?;
+        list[index] = element;
+        Ok(())
+    }
+
+    /// Inserts the element before or after the first occurrence of the pivot. Returns the new
+    /// length of the list, 0 if the list doesn't exist and -1 if the pivot is not found.
+    pub async fn linsert(
+        &self,
+        key: &[u8],
+        before: bool,
+        pivot: &[u8],
+        element: Bytes,
+    ) -> Result<i64, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let list = match keyspace.get_live_mut(key, now) {
+            Some(entry) => entry.value.as_list_mut()?,
+            None => return Ok(0),
+        };
+        match list.iter().position(|item| item == pivot) {
+            Some(position) => {
+                list.insert(if before { position } else { position + 1 }, element);
+                Ok(list.len() as i64)
+            }
+            None => Ok(-1),
+        }
+    }
+
+    /// Removes the first `count` occurrences of the element, the last `-count` ones if the count
+    /// is negative or all of them if it is 0. Returns the number of removed elements.
+    pub async fn lrem(&self, key: &[u8], count: i64, element: &[u8]) -> Result<usize, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let list = match keyspace.get_live_mut(key, now) {
+            Some(entry) => entry.value.as_list_mut()?,
+            None => return Ok(0),
+        };
+        let limit = match count {
+            0 => usize::MAX,
+            count => count.unsigned_abs().try_into().unwrap_or(usize::MAX),
+        };
+        let mut removed = 0;
+        if count >= 0 {
+            let mut index = 0;
+            while index < list.len() && removed < limit {
+                if list[index] == element {
+                    list.remove(index);
+                    removed += 1;
+                } else {
+                    index += 1;
+                }
+            }
+        } else {
+            let mut index = list.len();
+            while index > 0 && removed < limit {
+                index -= 1;
+                if list[index] == element {
+                    list.remove(index);
+                    removed += 1;
+                }
+            }
+        }
+        keyspace.remove_if_empty(key);
+        Ok(removed)
+    }
+
+    /// Keeps only elements between the inclusive indexes.
+    pub async fn ltrim(&self, key: &[u8], start: i64, end: i64) -> Result<(), RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let list = match keyspace.get_live_mut(key, now) {
+            Some(entry) => entry.value.as_list_mut()?,
+            None => return Ok(()),
+        };
+        match list_range(list.len(), start, end) {
+            Some((start, end)) => {
+                list.truncate(end + 1);
+                list.drain(..start);
+            }
+            None => list.clear(),
+        }
+        keyspace.remove_if_empty(key);
+        Ok(())
+    }
+
+    /// Returns positions of matching elements as requested.
+    pub async fn lpos(&self, request: LPos) -> Result<Vec<usize>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        let list = match keyspace.get(&request.key, now) {
+            Some(entry) => entry.value.as_list()?,
+            None => return Ok(vec![]),
+        };
+        let max_len = match request.max_len {
+            0 => list.len(),
+            max_len => max_len.min(list.len()),
+        };
+        let count = match request.count {
+            Some(0) => usize::MAX,
+            Some(count) => count,
+            None => 1,
+        };
+        let skip = (request.rank.unsigned_abs() - 1)
+            .try_into()
+            .unwrap_or(usize::MAX);
+        let positions: Box<dyn Iterator<Item = usize>> = if request.rank > 0 {
+            Box::new(0..max_len)
+        } else {
+            Box::new((list.len() - max_len..list.len()).rev())
+        };
+        Ok(positions
+            .filter(|position| list[*position] == request.element)
+            .skip(skip)
+            .take(count)
+            .collect())
+    }
+
+    /// Pops an element from a side of the source list and pushes it to a side of the destination
+    /// list, which is created if needed. Returns the element or `None` if the source list doesn't
+    /// exist.
+    pub async fn lmove(
+        &self,
+        source: &[u8],
+        destination: Bytes,
+        from: Side,
+        to: Side,
+    ) -> Result<Option<Bytes>, RedisError> {
+        let now = now_ms();
+        let operation = ListOperation::Move {
+            destination,
+            from,
+            to,
+        };
+        let mut keyspace = self.inner.write().await;
+        let elements = keyspace.list_operation(source, &operation, now)?;
+        keyspace.serve_blocked(now);
+        Ok(elements.and_then(|elements| elements.into_iter().next()))
+    }
+
+    /// Moves an element between lists like `lmove` does. If the source list is empty, blocks
+    /// until an element is pushed to it or the timeout expires. Returns `None` on timeout.
+    pub async fn blocking_move(
+        &self,
+        source: Bytes,
+        destination: Bytes,
+        from: Side,
+        to: Side,
+        timeout: Timeout,
+    ) -> Result<Option<Bytes>, RedisError> {
+        let operation = ListOperation::Move {
+            destination,
+            from,
+            to,
+        };
+        let popped = self
+            .block_on_lists(vec![source], operation, timeout)
+            .await?;
+        Ok(popped.and_then(|popped| popped.elements.into_iter().next()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::test::set;
+
+    fn elements(elements: &[&'static str]) -> Vec<Bytes> {
+        elements
+            .iter()
+            .map(|element| Bytes::from(*element))
+            .collect()
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(list_range(5, 0, -1), Some((0, 4)));
+        assert_eq!(list_range(5, -2, 100), Some((3, 4)));
+        assert_eq!(list_range(5, -100, 1), Some((0, 1)));
+        assert_eq!(list_range(5, 3, 2), None);
+        assert_eq!(list_range(5, 5, 10), None);
+        assert_eq!(list_range(0, 0, -1), None);
+        assert_eq!(list_index(3, -1), Some(2));
+        assert_eq!(list_index(3, 3), None);
+        assert_eq!(list_index(3, -4), None);
+    }
+
+    #[tokio::test]
+    async fn push_and_pop() {
+        let storage = Storage::instance();
+        let key = || Bytes::from("list:push");
+        assert_eq!(
+            storage
+                .push(key(), elements(&["a"]), Side::Left, true)
+                .await,
+            Ok(0)
+        );
+        assert_eq!(
+            storage
+                .push(key(), elements(&["b", "a"]), Side::Left, false)
+                .await,
+            Ok(2)
+        );
+        assert_eq!(
+            storage
+                .push(key(), elements(&["c", "d"]), Side::Right, true)
+                .await,
+            Ok(4)
+        );
+        assert_eq!(
+            storage.lrange(b"list:push", 0, -1).await,
+            Ok(elements(&["a", "b", "c", "d"]))
+        );
+        assert_eq!(
+            storage.pop(b"list:push", Side::Right, 3).await,
+            Ok(Some(elements(&["d", "c", "b"])))
+        );
+        assert_eq!(
+            storage.pop(b"list:push", Side::Left, 5).await,
+            Ok(Some(elements(&["a"])))
+        );
+        // The emptied list is removed.
+        assert_eq!(storage.pop(b"list:push", Side::Left, 1).await, Ok(None));
+        assert_eq!(storage.key_type(b"list:push").await, "none");
+    }
+
+    #[tokio::test]
+    async fn access_by_index() {
+        let storage = Storage::instance();
+        storage
+            .push(
+                Bytes::from("list:index"),
+                elements(&["a", "b", "c"]),
+                Side::Right,
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(storage.llen(b"list:index").await, Ok(3));
+        assert_eq!(
+            storage.lindex(b"list:index", -1).await,
+            Ok(Some(Bytes::from("c")))
+        );
+        assert_eq!(storage.lindex(b"list:index", 3).await, Ok(None));
+        storage
+            .lset(b"list:index", 1, Bytes::from("B"))
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.lset(b"list:index", 3, Bytes::from("x")).await,
+            Err(RedisError::Other("index out of range".to_owned()))
+        );
+        assert_eq!(
+            storage.lset(b"list:absent", 0, Bytes::from("x")).await,
+            Err(RedisError::NoSuchKey)
+        );
+        assert_eq!(
+            storage
+                .linsert(b"list:index", true, b"B", Bytes::from("x"))
+                .await,
+            Ok(4)
+        );
+        assert_eq!(
+            storage
+                .linsert(b"list:index", false, b"c", Bytes::from("y"))
+                .await,
+            Ok(5)
+        );
+        assert_eq!(
+            storage
+                .linsert(b"list:index", false, b"z", Bytes::from("y"))
+                .await,
+            Ok(-1)
+        );
+        assert_eq!(
+            storage.lrange(b"list:index", 0, -1).await,
+            Ok(elements(&["a", "x", "B", "c", "y"]))
+        );
+    }
+
+    #[tokio::test]
+    async fn remove_and_trim() {
+        let storage = Storage::instance();
+        let key = || Bytes::from("list:rem");
+        storage
+            .push(
+                key(),
+                elements(&["a", "b", "a", "c", "a", "b"]),
+                Side::Right,
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(storage.lrem(b"list:rem", -1, b"b").await, Ok(1));
+        assert_eq!(storage.lrem(b"list:rem", 2, b"a").await, Ok(2));
+        assert_eq!(
+            storage.lrange(b"list:rem", 0, -1).await,
+            Ok(elements(&["b", "c", "a"]))
+        );
+        storage.ltrim(b"list:rem", 1, -1).await.unwrap();
+        assert_eq!(
+            storage.lrange(b"list:rem", 0, -1).await,
+            Ok(elements(&["c", "a"]))
+        );
+        storage.ltrim(b"list:rem", 5, 10).await.unwrap();
+        assert_eq!(storage.llen(b"list:rem").await, Ok(0));
+        assert_eq!(storage.key_type(b"list:rem").await, "none");
+    }
+
+    #[tokio::test]
+    async fn positions() {
+        let storage = Storage::instance();
+        storage
+            .push(
+                Bytes::from("list:pos"),
+                elements(&["a", "b", "c", "1", "2", "3", "c", "c"]),
+                Side::Right,
+                false,
+            )
+            .await
+            .unwrap();
+        let lpos = |rank, count, max_len| LPos {
+            key: Bytes::from("list:pos"),
+            element: Bytes::from("c"),
+            rank,
+            count,
+            max_len,
+        };
+        assert_eq!(storage.lpos(lpos(1, None, 0)).await, Ok(vec![2]));
+        assert_eq!(storage.lpos(lpos(2, None, 0)).await, Ok(vec![6]));
+        assert_eq!(storage.lpos(lpos(1, Some(0), 0)).await, Ok(vec![2, 6, 7]));
+        assert_eq!(storage.lpos(lpos(-1, Some(2), 0)).await, Ok(vec![7, 6]));
+        assert_eq!(storage.lpos(lpos(1, Some(0), 3)).await, Ok(vec![2]));
+        assert_eq!(storage.lpos(lpos(-1, Some(0), 3)).await, Ok(vec![7, 6]));
+        assert_eq!(storage.lpos(lpos(4, None, 0)).await, Ok(vec![]));
+    }
+
+    #[tokio::test]
+    async fn move_between_lists() {
+        let storage = Storage::instance();
+        storage
+            .push(
+                Bytes::from("list:source"),
+                elements(&["a", "b"]),
+                Side::Right,
+                false,
+            )
+            .await
+            .unwrap();
+        let destination = || Bytes::from("list:destination");
+        assert_eq!(
+            storage
+                .lmove(b"list:source", destination(), Side::Right, Side::Left)
+                .await,
+            Ok(Some(Bytes::from("b")))
+        );
+        assert_eq!(
+            storage
+                .lmove(b"list:source", destination(), Side::Left, Side::Right)
+                .await,
+            Ok(Some(Bytes::from("a")))
+        );
+        assert_eq!(
+            storage
+                .lmove(b"list:source", destination(), Side::Left, Side::Right)
+                .await,
+            Ok(None)
+        );
+        assert_eq!(
+            storage.lrange(b"list:destination", 0, -1).await,
+            Ok(elements(&["b", "a"]))
+        );
+        // Rotation of a single list.
+        assert_eq!(
+            storage
+                .lmove(b"list:destination", destination(), Side::Left, Side::Right)
+                .await,
+            Ok(Some(Bytes::from("b")))
+        );
+        assert_eq!(
+            storage.lrange(b"list:destination", 0, -1).await,
+            Ok(elements(&["a", "b"]))
+        );
+
+        set("list:string", None).await;
+        assert_eq!(
+            storage
+                .lmove(
+                    b"list:destination",
+                    Bytes::from("list:string"),
+                    Side::Left,
+                    Side::Right
+                )
+                .await,
+            Err(RedisError::WrongType)
+        );
+        assert_eq!(storage.llen(b"list:destination").await, Ok(2));
+        assert_eq!(
+            storage
+                .push(
+                    Bytes::from("list:string"),
+                    elements(&["a"]),
+                    Side::Left,
+                    false
+                )
+                .await,
+            Err(RedisError::WrongType)
+        );
+    }
+}
@@ -0,0 +1,839 @@
+//! This module provides a simple in-memory key-value storage.
+//!
+//! Keys may carry a deadline. Expired keys are reclaimed in two ways, the same as Redis does:
+//! passively, when a client touches the key, and actively, by a background task that samples
+//! keys with a deadline and deletes the expired ones. Fields of hashes may carry deadlines too,
+//! see `hash`.
+//!
+//! Values are typed. Commands of each type are implemented in a submodule of their own and fail
+//! with `WRONGTYPE` when applied to a key holding a value of another type.
+//!
+//! Clients may block until a list is pushed to, see `blocking`.
+mod bitmap;
+mod blocking;
+mod geo;
+mod hash;
+mod hyperloglog;
+mod list;
+mod set;
+mod skiplist;
+mod sorted_set;
+mod stream;
+mod stream_group;
+mod string;
+
+pub use blocking::Popped;
+pub use stream::{StreamEntry, StreamRead};
+
+use hash::Hash;
+
+use crate::{
+    config,
+    error::RedisError,
+    protocol::{Copy, Expire},
+};
+use bytes::Bytes;
+use lazy_static::lazy_static;
+use std::{
+    collections::{
+        hash_map::{DefaultHasher, RandomState},
+        HashMap, VecDeque,
+    },
+    hash::{BuildHasher, Hasher},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::RwLock;
+
+/// Number of keys with a deadline sampled per iteration of the active expiration cycle.
+const ACTIVE_EXPIRE_CYCLE_KEYS_PER_LOOP: usize = 20;
+/// The cycle keeps sampling while more than this percentage of sampled keys were expired.
+const ACTIVE_EXPIRE_CYCLE_ACCEPTABLE_STALE_PERCENT: usize = 25;
+/// Upper bound for the time a single active expiration cycle may hold the storage lock,
+/// in percents of the cycle period.
+const ACTIVE_EXPIRE_CYCLE_TIME_LIMIT_PERCENT: u32 = 25;
+/// UNLINK frees values in the background when the effort of freeing them exceeds this.
+const LAZYFREE_THRESHOLD: usize = 64;
+/// Bytes of a string value that count as a unit of the freeing effort.
+const LAZYFREE_STRING_BYTES_PER_EFFORT: usize = 1024;
+
+lazy_static! {
+    static ref STORAGE: RwLock<Keyspace> = RwLock::new(Keyspace::default());
+}
+
+/// Returns current unix time in milliseconds.
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time is before unix epoch")
+        .as_millis() as u64
+}
+
+/// A value stored under a key.
+#[derive(Clone, Debug)]
+enum Value {
+    String(Bytes),
+    List(VecDeque<Bytes>),
+    Hash(Hash),
+    Set(set::Set),
+    SortedSet(sorted_set::SortedSet),
+    Stream(stream::Stream),
+}
+
+impl Value {
+    /// Name of the type as reported by TYPE.
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Hash(_) => "hash",
+            Value::Set(_) => "set",
+            Value::SortedSet(_) => "zset",
+            Value::Stream(_) => "stream",
+        }
+    }
+
+    /// Returns true for an empty collection. Keys never hold empty collections, except for
+    /// streams, which stay when all their entries are deleted.
+    fn is_empty(&self) -> bool {
+        match self {
+            Value::String(_) | Value::Stream(_) => false,
+            Value::List(list) => list.is_empty(),
+            Value::Hash(hash) => hash.is_empty(),
+            Value::Set(set) => set.is_empty(),
+            Value::SortedSet(set) => set.is_empty(),
+        }
+    }
+
+    fn as_string(&self) -> Result<&Bytes, RedisError> {
+        match self {
+            Value::String(value) => Ok(value),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    /// Takes the string out for modification, leaving an empty one in its place. The buffer
+    /// is reused without copying unless a reply being sent still holds the value.
+    fn take_string(&mut self) -> Result<Vec<u8>, RedisError> {
+        match self {
+            Value::String(value) => Ok(Vec::from(std::mem::take(value))),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    fn as_list(&self) -> Result<&VecDeque<Bytes>, RedisError> {
+        match self {
+            Value::List(list) => Ok(list),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    fn as_list_mut(&mut self) -> Result<&mut VecDeque<Bytes>, RedisError> {
+        match self {
+            Value::List(list) => Ok(list),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    fn as_hash(&self) -> Result<&Hash, RedisError> {
+        match self {
+            Value::Hash(hash) => Ok(hash),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    fn as_hash_mut(&mut self) -> Result<&mut Hash, RedisError> {
+        match self {
+            Value::Hash(hash) => Ok(hash),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    fn as_set(&self) -> Result<&set::Set, RedisError> {
+        match self {
+            Value::Set(set) => Ok(set),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    fn as_set_mut(&mut self) -> Result<&mut set::Set, RedisError> {
+        match self {
+            Value::Set(set) => Ok(set),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    fn as_sorted_set(&self) -> Result<&sorted_set::SortedSet, RedisError> {
+        match self {
+            Value::SortedSet(set) => Ok(set),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    fn as_sorted_set_mut(&mut self) -> Result<&mut sorted_set::SortedSet, RedisError> {
+        match self {
+            Value::SortedSet(set) => Ok(set),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    fn as_stream(&self) -> Result<&stream::Stream, RedisError> {
+        match self {
+            Value::Stream(stream) => Ok(stream),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+
+    fn as_stream_mut(&mut self) -> Result<&mut stream::Stream, RedisError> {
+        match self {
+            Value::Stream(stream) => Ok(stream),
+            _ => Err(RedisError::WrongType),
+        }
+    }
+}
+
+/// A value stored under a key along with its optional deadline (unix time in milliseconds).
+#[derive(Clone, Debug)]
+struct Entry {
+    value: Value,
+    expires_at: Option<u64>,
+}
+
+impl Entry {
+    /// Returns true if the key is expired or it holds a hash whose fields are all expired.
+    fn is_expired(&self, now: u64) -> bool {
+        match &self.value {
+            Value::Hash(hash) if hash.is_expired(now) => true,
+            _ => self.expires_at.is_some_and(|deadline| deadline <= now),
+        }
+    }
+
+    /// Rough cost of freeing the value, comparable with `LAZYFREE_THRESHOLD`.
+    fn free_effort(&self) -> usize {
+        match &self.value {
+            Value::String(value) => value.len() / LAZYFREE_STRING_BYTES_PER_EFFORT,
+            Value::List(list) => list.len(),
+            Value::Hash(hash) => hash.len(),
+            Value::Set(set) => set.len(),
+            Value::SortedSet(set) => set.len(),
+            Value::Stream(stream) => stream.len(),
+        }
+    }
+}
+
+/// Keys that have a deadline. Supports O(1) insertion, removal and random sampling.
+#[derive(Default, Debug)]
+struct VolatileKeys {
+    keys: Vec<Bytes>,
+    positions: HashMap<Bytes, usize>,
+}
+
+impl VolatileKeys {
+    fn insert(&mut self, key: &Bytes) {
+        if !self.positions.contains_key(key) {
+            self.positions.insert(key.clone(), self.keys.len());
+            self.keys.push(key.clone());
+        }
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        if let Some(position) = self.positions.remove(key) {
+            self.keys.swap_remove(position);
+            if let Some(moved) = self.keys.get(position) {
+                self.positions.insert(moved.clone(), position);
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn random(&self, rng: &mut Rng) -> Option<&Bytes> {
+        if self.keys.is_empty() {
+            return None;
+        }
+        self.keys.get(rng.below(self.keys.len()))
+    }
+}
+
+/// The key space. All modifications of keys go through its methods, so the index of keys with
+/// a deadline always stays in sync with the entries.
+#[derive(Default, Debug)]
+struct Keyspace {
+    entries: HashMap<Bytes, Entry>,
+    volatile: VolatileKeys,
+    /// Keys of hashes having fields with a deadline. May contain keys which no longer do.
+    volatile_fields: VolatileKeys,
+    blocked: blocking::BlockedClients,
+}
+
+impl Keyspace {
+    /// Returns a live entry by key. Expired entries are treated as absent.
+    fn get(&self, key: &[u8], now: u64) -> Option<&Entry> {
+        self.entries.get(key).filter(|entry| !entry.is_expired(now))
+    }
+
+    /// Returns a live entry by key. An expired entry is removed.
+    fn get_live(&mut self, key: &[u8], now: u64) -> Option<&Entry> {
+        self.remove_if_expired(key, now);
+        self.entries.get(key)
+    }
+
+    /// Returns a live entry by key for modification. An expired entry is removed. If the value
+    /// becomes an empty collection, `remove_if_empty` must be called afterwards.
+    fn get_live_mut(&mut self, key: &[u8], now: u64) -> Option<&mut Entry> {
+        self.remove_if_expired(key, now);
+        self.entries.get_mut(key)
+    }
+
+    /// Returns the entry by key for modification. An absent key is created with the value
+    /// made by `create`.
+    fn get_or_insert_with(
+        &mut self,
+        key: Bytes,
+        now: u64,
+        create: impl FnOnce() -> Value,
+    ) -> &mut Entry {
+        self.remove_if_expired(&key, now);
+        // New entries have no deadline, so the index of keys with a deadline stays intact.
+        self.entries.entry(key).or_insert_with(|| Entry {
+            value: create(),
+            expires_at: None,
+        })
+    }
+
+    fn insert(&mut self, key: Bytes, value: Value, expires_at: Option<u64>) {
+        self.insert_entry(key, Entry { value, expires_at });
+    }
+
+    fn insert_entry(&mut self, key: Bytes, entry: Entry) {
+        match entry.expires_at {
+            Some(_) => self.volatile.insert(&key),
+            None => self.volatile.remove(&key),
+        }
+        self.unblock_if_stream(&key);
+        match &entry.value {
+            Value::List(_) | Value::Stream(_) => self.blocked.signal(&key),
+            Value::Hash(hash) if hash.has_deadlines() => self.volatile_fields.insert(&key),
+            _ => self.volatile_fields.remove(&key),
+        }
+        self.entries.insert(key, entry);
+    }
+
+    /// Replaces the deadline of an existing key. Returns false if the key doesn't exist.
+    fn set_expires_at(&mut self, key: &[u8], expires_at: Option<u64>) -> bool {
+        let entry = match self.entries.get_mut(key) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        entry.expires_at = expires_at;
+        match expires_at {
+            Some(_) => {
+                // The index keeps a key of its own, so the entry's key is cloned from the map.
+                if let Some((key, _)) = self.entries.get_key_value(key) {
+                    let key = key.clone();
+                    self.volatile.insert(&key);
+                }
+            }
+            None => self.volatile.remove(key),
+        }
+        true
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Option<Entry> {
+        self.unblock_if_stream(key);
+        self.volatile.remove(key);
+        self.volatile_fields.remove(key);
+        self.entries.remove(key)
+    }
+
+    /// Fails clients blocked reading the stream at the key for a group, as the stream is about
+    /// to be deleted or overwritten.
+    fn unblock_if_stream(&mut self, key: &[u8]) {
+        if let Some(Entry {
+            value: Value::Stream(_),
+            ..
+        }) = self.entries.get(key)
+        {
+            self.blocked.unblock_group_readers(
+                key,
+                None,
+                RedisError::Unblocked("the stream key no longer exists".to_owned()),
+            );
+        }
+    }
+
+    /// Removes the key if it holds an empty collection.
+    fn remove_if_empty(&mut self, key: &[u8]) {
+        if self
+            .entries
+            .get(key)
+            .is_some_and(|entry| entry.value.is_empty())
+        {
+            self.remove(key);
+        }
+    }
+
+    /// Removes the key if it is expired. Returns true if the key was removed. Otherwise expired
+    /// fields of a hash are removed.
+    fn remove_if_expired(&mut self, key: &[u8], now: u64) -> bool {
+        let entry = match self.entries.get_mut(key) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        if entry.is_expired(now) {
+            self.remove(key);
+            return true;
+        }
+        if let Value::Hash(hash) = &mut entry.value {
+            hash.remove_expired(now);
+        }
+        false
+    }
+
+    /// Samples keys with a deadline and removes expired ones. Repeats while the share of expired
+    /// keys in a sample is high, but for no longer than `time_limit`.
+    /// Returns the number of removed keys.
+    fn active_expire_cycle(&mut self, rng: &mut Rng, time_limit: Duration) -> usize {
+        let started = Instant::now();
+        let mut removed = 0;
+        loop {
+            let now = now_ms();
+            let samples = ACTIVE_EXPIRE_CYCLE_KEYS_PER_LOOP.min(self.volatile.len());
+            if samples == 0 {
+                break;
+            }
+            let mut expired = 0;
+            for _ in 0..samples {
+                let key = match self.volatile.random(rng) {
+                    Some(key) => key.clone(),
+                    None => break,
+                };
+                if self.remove_if_expired(&key, now) {
+                    expired += 1;
+                }
+            }
+            removed += expired;
+            if expired * 100 <= samples * ACTIVE_EXPIRE_CYCLE_ACCEPTABLE_STALE_PERCENT
+                || started.elapsed() >= time_limit
+            {
+                break;
+            }
+        }
+        removed
+    }
+}
+
+/// Small xorshift pseudo-random generator. Good enough for sampling keys.
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        let seed = RandomState::new().build_hasher().finish();
+        Self(seed | 1)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Returns a random number in range [0, bound).
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/// Position of an element of a collection in the order SCAN-like commands iterate in. The
+/// order doesn't depend on the insertion order or the capacity of the collection, so it stays
+/// the same between calls.
+fn scan_position(element: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(element);
+    hasher.finish()
+}
+
+/// Returns up to about `count` items following the cursor in the order of their positions and
+/// the cursor to continue from, 0 when the iteration is complete. Items at the same position are
+/// returned together, so every item present during the whole iteration is returned exactly once.
+fn scan<T>(items: impl Iterator<Item = (u64, T)>, cursor: u64, count: usize) -> (u64, Vec<T>) {
+    let mut items: Vec<(u64, T)> = items.filter(|(position, _)| *position >= cursor).collect();
+    items.sort_unstable_by_key(|(position, _)| *position);
+    let mut end = count.min(items.len());
+    while end > 0 && end < items.len() && items[end].0 == items[end - 1].0 {
+        end += 1;
+    }
+    let next_cursor = items.get(end).map_or(0, |(position, _)| *position);
+    items.truncate(end);
+    (
+        next_cursor,
+        items.into_iter().map(|(_, item)| item).collect(),
+    )
+}
+
+/// Wrapper around the in-memory storage.
+pub struct Storage {
+    inner: &'static RwLock<Keyspace>,
+}
+
+impl Storage {
+    pub fn instance() -> Self {
+        Self { inner: &STORAGE }
+    }
+
+    /// Removes the keys. Returns the number of removed keys.
+    pub async fn del(&self, keys: &[Bytes]) -> usize {
+        self.remove_all(keys).await.len()
+    }
+
+    /// Removes the keys like `del` does, but frees large values on a background task, so the
+    /// request doesn't wait for it. Returns the number of removed keys.
+    pub async fn unlink(&self, keys: &[Bytes]) -> usize {
+        let entries = self.remove_all(keys).await;
+        let removed = entries.len();
+        let effort: usize = entries.iter().map(Entry::free_effort).sum();
+        if effort > LAZYFREE_THRESHOLD {
+            tokio::task::spawn_blocking(move || drop(entries));
+        }
+        removed
+    }
+
+    /// Removes the live keys and returns their entries.
+    async fn remove_all(&self, keys: &[Bytes]) -> Vec<Entry> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        // Expired entries are removed too, but they don't count.
+        keys.iter()
+            .filter_map(|key| keyspace.remove(key))
+            .filter(|entry| !entry.is_expired(now))
+            .collect()
+    }
+
+    /// Counts the keys that exist. A key mentioned multiple times is counted multiple times.
+    pub async fn exists(&self, keys: &[Bytes]) -> usize {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        keys.iter()
+            .filter(|key| keyspace.get(key, now).is_some())
+            .count()
+    }
+
+    /// Counts the keys that exist like `exists` does. Expired keys are removed.
+    pub async fn touch(&self, keys: &[Bytes]) -> usize {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        keys.iter()
+            .filter(|key| keyspace.get_live(key, now).is_some())
+            .count()
+    }
+
+    /// Returns the name of the type of the value stored at the key or `none` if the key is absent.
+    pub async fn key_type(&self, key: &[u8]) -> &'static str {
+        let now = now_ms();
+        match self.inner.read().await.get(key, now) {
+            Some(entry) => entry.value.type_name(),
+            None => "none",
+        }
+    }
+
+    /// Moves the value and the deadline of the key to the new key, overwriting it. If `nx` is
+    /// set, the new key must not exist. Returns false if the key wasn't renamed because of `nx`.
+    pub async fn rename(&self, key: &[u8], new_key: Bytes, nx: bool) -> Result<bool, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        if keyspace.get_live(key, now).is_none() {
+            return Err(RedisError::NoSuchKey);
+        }
+        if key == new_key {
+            return Ok(!nx);
+        }
+        if nx && keyspace.get_live(&new_key, now).is_some() {
+            return Ok(false);
+        }
+        if let Some(entry) = keyspace.remove(key) {
+            keyspace.insert_entry(new_key, entry);
+        }
+        keyspace.serve_blocked(now);
+        Ok(true)
+    }
+
+    /// Copies the value and the deadline of the source key to the destination key. Returns false
+    /// if the source key doesn't exist or the destination exists and `replace` is not set.
+    pub async fn copy(&self, request: Copy) -> bool {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let entry = match keyspace.get_live(&request.source, now) {
+            Some(entry) => entry.clone(),
+            None => return false,
+        };
+        if !request.replace && keyspace.get_live(&request.destination, now).is_some() {
+            return false;
+        }
+        keyspace.insert_entry(request.destination, entry);
+        keyspace.serve_blocked(now);
+        true
+    }
+
+    /// Sets the deadline of the key if all conditions of the request hold. A deadline in the
+    /// past removes the key. Returns false if the key doesn't exist or a condition doesn't hold.
+    pub async fn expire(&self, request: Expire) -> bool {
+        let now = now_ms();
+        let deadline = request.expiration.deadline(now);
+        let mut keyspace = self.inner.write().await;
+        let current = match keyspace.get_live(&request.key, now) {
+            Some(entry) => entry.expires_at,
+            None => return false,
+        };
+        if !request
+            .conditions
+            .iter()
+            .all(|condition| condition.holds(current, deadline))
+        {
+            return false;
+        }
+        if deadline <= now as i64 {
+            keyspace.remove(&request.key);
+        } else {
+            keyspace.set_expires_at(&request.key, Some(deadline as u64));
+        }
+        true
+    }
+
+    /// Returns the deadline of the key: `None` if the key doesn't exist and `Some(None)` if the
+    /// key has no deadline.
+    pub async fn expires_at(&self, key: &[u8]) -> Option<Option<u64>> {
+        let now = now_ms();
+        self.inner
+            .read()
+            .await
+            .get(key, now)
+            .map(|entry| entry.expires_at)
+    }
+
+    /// Removes the deadline of the key. Returns false if the key doesn't exist or has no
+    /// deadline.
+    pub async fn persist(&self, key: &[u8]) -> bool {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        match keyspace.get_live(key, now) {
+            Some(entry) if entry.expires_at.is_some() => keyspace.set_expires_at(key, None),
+            _ => false,
+        }
+    }
+
+    /// Periodically reclaims expired keys and hash fields that are never accessed again. The cycle runs `hz`
+    /// times per second. Runs until the runtime shuts down.
+    pub async fn run_active_expiration(self) {
+        let mut rng = Rng::new();
+        loop {
+            let period = Duration::from_secs(1) / config::current().hz;
+            tokio::time::sleep(period).await;
+            let time_limit = period * ACTIVE_EXPIRE_CYCLE_TIME_LIMIT_PERCENT / 100;
+            let started = Instant::now();
+            let mut keyspace = self.inner.write().await;
+            keyspace.active_expire_cycle(&mut rng, time_limit);
+            // Hash fields get the rest of the time.
+            keyspace
+                .active_expire_fields_cycle(&mut rng, time_limit.saturating_sub(started.elapsed()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocol::{Expiration, Set};
+
+    /// Sets the key to a string equal to the key.
+    pub(super) async fn set(key: &'static str, timeout_ms: Option<i64>) {
+        Storage::instance()
+            .set(Set {
+                key: Bytes::from(key),
+                value: Bytes::from(key),
+                expiration: timeout_ms.map(Expiration::After),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+
+    pub(super) fn keys(keys: &[&'static str]) -> Vec<Bytes> {
+        keys.iter().map(|key| Bytes::from(*key)).collect()
+    }
+
+    #[tokio::test]
+    async fn del_and_exists() {
+        let storage = Storage::instance();
+        set("del:a", None).await;
+        set("del:b", None).await;
+        set("del:expired", Some(1)).await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let all = keys(&["del:a", "del:b", "del:a", "del:expired", "del:absent"]);
+        assert_eq!(storage.exists(&all).await, 3);
+        assert_eq!(storage.touch(&all).await, 3);
+        assert_eq!(storage.del(&all).await, 2);
+        assert_eq!(storage.exists(&all).await, 0);
+    }
+
+    #[tokio::test]
+    async fn unlink_large_value() {
+        let storage = Storage::instance();
+        storage
+            .set(Set {
+                key: Bytes::from("unlink:large"),
+                value: Bytes::from(vec![b'x'; 1024 * 1024]),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        set("unlink:small", None).await;
+        assert_eq!(
+            storage
+                .unlink(&keys(&["unlink:large", "unlink:small", "unlink:absent"]))
+                .await,
+            2
+        );
+        assert_eq!(storage.get(b"unlink:large").await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn key_type() {
+        let storage = Storage::instance();
+        set("type:string", None).await;
+        assert_eq!(storage.key_type(b"type:string").await, "string");
+        assert_eq!(storage.key_type(b"type:absent").await, "none");
+    }
+
+    #[tokio::test]
+    async fn rename() {
+        let storage = Storage::instance();
+        set("rename:a", Some(60_000)).await;
+        set("rename:b", None).await;
+        assert_eq!(
+            storage
+                .rename(b"rename:absent", Bytes::from("x"), false)
+                .await,
+            Err(RedisError::NoSuchKey)
+        );
+        assert_eq!(
+            storage
+                .rename(b"rename:a", Bytes::from("rename:b"), true)
+                .await,
+            Ok(false)
+        );
+        assert_eq!(
+            storage
+                .rename(b"rename:a", Bytes::from("rename:a"), true)
+                .await,
+            Ok(false)
+        );
+        assert_eq!(
+            storage
+                .rename(b"rename:a", Bytes::from("rename:a"), false)
+                .await,
+            Ok(true)
+        );
+        assert_eq!(
+            storage
+                .rename(b"rename:a", Bytes::from("rename:b"), false)
+                .await,
+            Ok(true)
+        );
+        assert_eq!(storage.get(b"rename:a").await, Ok(None));
+        assert_eq!(
+            storage.get(b"rename:b").await,
+            Ok(Some(Bytes::from("rename:a")))
+        );
+        // The deadline moves with the value.
+        let keyspace = storage.inner.read().await;
+        assert!(keyspace.entries[&b"rename:b"[..]].expires_at.is_some());
+        assert!(keyspace.volatile.positions.contains_key(&b"rename:b"[..]));
+        assert!(!keyspace.volatile.positions.contains_key(&b"rename:a"[..]));
+    }
+
+    #[tokio::test]
+    async fn copy() {
+        let storage = Storage::instance();
+        set("copy:a", Some(60_000)).await;
+        set("copy:b", None).await;
+        let copy = |destination: &'static str, replace| Copy {
+            source: Bytes::from("copy:a"),
+            destination: Bytes::from(destination),
+            replace,
+        };
+        assert!(!storage.copy(copy("copy:b", false)).await);
+        assert!(storage.copy(copy("copy:b", true)).await);
+        assert!(storage.copy(copy("copy:c", false)).await);
+        assert_eq!(
+            storage.get(b"copy:b").await,
+            Ok(Some(Bytes::from("copy:a")))
+        );
+        assert_eq!(
+            storage.get(b"copy:c").await,
+            Ok(Some(Bytes::from("copy:a")))
+        );
+        assert!(storage.inner.read().await.entries[&b"copy:c"[..]]
+            .expires_at
+            .is_some());
+        assert!(
+            !storage
+                .copy(Copy {
+                    source: Bytes::from("copy:absent"),
+                    destination: Bytes::from("copy:d"),
+                    replace: true,
+                })
+                .await
+        );
+    }
+
+    #[test]
+    fn active_expire_cycle() {
+        let mut keyspace = Keyspace::default();
+        let now = now_ms();
+        for i in 0..100 {
+            keyspace.insert(
+                Bytes::from(format!("expired{i}")),
+                Value::String(Bytes::from("v")),
+                Some(now - 1),
+            );
+        }
+        let value = || Value::String(Bytes::from("v"));
+        keyspace.insert(Bytes::from("alive"), value(), Some(now + 60_000));
+        keyspace.insert(Bytes::from("persistent"), value(), None);
+
+        let removed = keyspace.active_expire_cycle(&mut Rng::new(), Duration::from_secs(10));
+
+        // Sampling stops once most of the sampled keys are alive, so a few expired keys may stay.
+        assert!(removed > 0);
+        assert_eq!(keyspace.entries.len(), 102 - removed);
+        assert_eq!(keyspace.volatile.len(), 101 - removed);
+        assert!(keyspace.entries.contains_key(&b"alive"[..]));
+        assert!(keyspace.entries.contains_key(&b"persistent"[..]));
+    }
+
+    #[test]
+    fn volatile_keys_remove() {
+        let mut volatile = VolatileKeys::default();
+        volatile.insert(&Bytes::from("a"));
+        volatile.insert(&Bytes::from("b"));
+        volatile.insert(&Bytes::from("c"));
+        volatile.remove(b"a");
+        assert_eq!(volatile.len(), 2);
+        assert_eq!(volatile.positions[&b"c"[..]], 0);
+        assert_eq!(volatile.positions[&b"b"[..]], 1);
+        volatile.remove(b"absent");
+        assert_eq!(volatile.len(), 2);
+    }
+
+    #[test]
+    fn scan_pages() {
+        let items = [(7, "c"), (3, "a"), (5, "b"), (5, "b"), (9, "d")];
+        // Items at the same position don't get split between pages.
+        assert_eq!(scan(items.into_iter(), 0, 2), (7, vec!["a", "b", "b"]));
+        assert_eq!(scan(items.into_iter(), 7, 2), (0, vec!["c", "d"]));
+        assert_eq!(scan(items.into_iter(), 6, 1), (9, vec!["c"]));
+        assert_eq!(scan(items.into_iter(), 10, 1), (0, vec![]));
+    }
+}
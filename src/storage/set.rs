@@ -0,0 +1,607 @@
+//! Set commands.
+//!
+//! Sets of integers are kept as a sorted array, the same as the intset encoding of Redis, which
+//! takes far less memory than a hash table. A set switches to a hash table once a member which
+//! is not an integer is added or it grows beyond `set-max-intset-entries`, and it never switches
+//! back.
+use super::{now_ms, Keyspace, Rng, Storage, Value};
+use crate::{
+    config,
+    error::RedisError,
+    protocol::{SInterCard, SetOperation},
+};
+use bytes::Bytes;
+use std::collections::HashSet;
+
+/// Members of a set.
+#[derive(Clone, Debug)]
+pub(super) enum Set {
+    /// Sorted integers.
+    Ints(Vec<i64>),
+    Members(HashSet<Bytes>),
+}
+
+impl Default for Set {
+    fn default() -> Self {
+        Set::Ints(Vec::new())
+    }
+}
+
+/// Returns the integer the member represents if the member is its canonical decimal form, so
+/// converting the integer back gives the same member.
+fn as_integer(member: &[u8]) -> Option<i64> {
+    let value: i64 = std::str::from_utf8(member).ok()?.parse().ok()?;
+    (value.to_string().as_bytes() == member).then_some(value)
+}
+
+fn integer_member(value: i64) -> Bytes {
+    Bytes::from(value.to_string())
+}
+
+impl Set {
+    /// Builds a set of the members, choosing the encoding the same way `insert` does.
+    fn from_members(members: Vec<Bytes>, max_intset_entries: usize) -> Self {
+        let mut set = Set::default();
+        for member in members {
+            set.insert(member, max_intset_entries);
+        }
+        set
+    }
+
+    pub(super) fn len(&self) -> usize {
+        match self {
+            Set::Ints(ints) => ints.len(),
+            Set::Members(members) => members.len(),
+        }
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn contains(&self, member: &[u8]) -> bool {
+        match self {
+            Set::Ints(ints) => {
+                as_integer(member).is_some_and(|value| ints.binary_search(&value).is_ok())
+            }
+            Set::Members(members) => members.contains(member),
+        }
+    }
+
+    /// Adds the member. Returns false if it is already there.
+    fn insert(&mut self, member: Bytes, max_intset_entries: usize) -> bool {
+        let ints = match self {
+            Set::Members(members) => return members.insert(member),
+            Set::Ints(ints) => ints,
+        };
+        if let Some(value) = as_integer(&member) {
+            match ints.binary_search(&value) {
+                Ok(_) => return false,
+                Err(position) if ints.len() < max_intset_entries => {
+                    ints.insert(position, value);
+                    return true;
+                }
+                Err(_) => {}
+            }
+        }
+        let mut members: HashSet<Bytes> = ints.iter().copied().map(integer_member).collect();
+        let added = members.insert(member);
+        *self = Set::Members(members);
+        added
+    }
+
+    /// Removes the member. Returns false if it is not there.
+    fn remove(&mut self, member: &[u8]) -> bool {
+        match self {
+            Set::Ints(ints) => match as_integer(member).map(|value| ints.binary_search(&value)) {
+                Some(Ok(position)) => {
+                    ints.remove(position);
+                    true
+                }
+                _ => false,
+            },
+            Set::Members(members) => members.remove(member),
+        }
+    }
+
+    fn members(&self) -> Vec<Bytes> {
+        match self {
+            Set::Ints(ints) => ints.iter().copied().map(integer_member).collect(),
+            Set::Members(members) => members.iter().cloned().collect(),
+        }
+    }
+
+    /// Returns up to `count` distinct members picked at random.
+    fn random_members(&self, count: usize, rng: &mut Rng) -> Vec<Bytes> {
+        let len = self.len();
+        let count = count.min(len);
+        // Floyd's algorithm: picks distinct positions without going through all of them.
+        let mut positions = HashSet::with_capacity(count);
+        for bound in len - count + 1..=len {
+            if !positions.insert(rng.below(bound)) {
+                positions.insert(bound - 1);
+            }
+        }
+        self.members_at(positions.into_iter().collect(), rng)
+    }
+
+    /// Returns `count` members picked at random which may repeat.
+    fn random_members_with_repeats(&self, count: u64, rng: &mut Rng) -> Vec<Bytes> {
+        // Grows with the picks: the count may be far larger than anything allocatable.
+        let mut positions = Vec::with_capacity(count.min(self.len() as u64) as usize);
+        for _ in 0..count {
+            positions.push(rng.below(self.len()));
+        }
+        self.members_at(positions, rng)
+    }
+
+    /// Returns the members at the positions in the iteration order, shuffled. A position may
+    /// repeat.
+    fn members_at(&self, mut positions: Vec<usize>, rng: &mut Rng) -> Vec<Bytes> {
+        positions.sort_unstable();
+        let mut picked = Vec::with_capacity(positions.len());
+        match self {
+            Set::Ints(ints) => picked.extend(
+                positions
+                    .iter()
+                    .map(|&position| integer_member(ints[position])),
+            ),
+            Set::Members(members) => {
+                // A single pass over the table with the positions in ascending order.
+                let mut members = members.iter().enumerate();
+                let mut current = None;
+                for &position in &positions {
+                    if current.map(|(index, _)| index) != Some(position) {
+                        current = members.find(|(index, _)| *index == position);
+                    }
+                    if let Some((_, member)) = current {
+                        picked.push(member.clone());
+                    }
+                }
+            }
+        }
+        for i in (1..picked.len()).rev() {
+            picked.swap(i, rng.below(i + 1));
+        }
+        picked
+    }
+}
+
+/// Combines the sets. Absent sets count as empty.
+fn combine(operation: SetOperation, sets: &[Option<&Set>]) -> Vec<Bytes> {
+    match operation {
+        SetOperation::Inter => intersection(sets, 0),
+        SetOperation::Union => {
+            let mut union = HashSet::new();
+            for set in sets.iter().flatten() {
+                union.extend(set.members());
+            }
+            union.into_iter().collect()
+        }
+        SetOperation::Diff => {
+            let (first, others) = match sets.split_first() {
+                Some((Some(first), others)) => (first, others),
+                _ => return vec![],
+            };
+            first
+                .members()
+                .into_iter()
+                .filter(|member| !others.iter().flatten().any(|set| set.contains(member)))
+                .collect()
+        }
+    }
+}
+
+/// Returns members of all sets, but no more than `limit` of them unless it is 0. Absent sets
+/// count as empty.
+fn intersection(sets: &[Option<&Set>], limit: usize) -> Vec<Bytes> {
+    let mut sets: Vec<&Set> = match sets.iter().copied().collect() {
+        Some(sets) => sets,
+        None => return vec![],
+    };
+    // Members of the smallest set are checked against the others.
+    sets.sort_by_key(|set| set.len());
+    let (smallest, others) = match sets.split_first() {
+        Some(split) => split,
+        None => return vec![],
+    };
+    let mut result = vec![];
+    for member in smallest.members() {
+        if others.iter().all(|set| set.contains(&member)) {
+            result.push(member);
+            if result.len() == limit {
+                break;
+            }
+        }
+    }
+    result
+}
+
+impl Keyspace {
+    /// Returns the sets stored at the keys, `None` for absent keys.
+    fn sets(&self, keys: &[Bytes], now: u64) -> Result<Vec<Option<&Set>>, RedisError> {
+        keys.iter()
+            .map(|key| match self.get(key, now) {
+                Some(entry) => entry.value.as_set().map(Some),
+                None => Ok(None),
+            })
+            .collect()
+    }
+}
+
+impl Storage {
+    /// Adds the members to the set, creating it if needed. Returns the number of added members.
+    pub async fn sadd(&self, key: Bytes, members: Vec<Bytes>) -> Result<usize, RedisError> {
+        let max_intset_entries = config::current().set_max_intset_entries;
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let set = keyspace
+            .get_or_insert_with(key, now, || Value::Set(Set::default()))
+            .value
+            .as_set_mut()?;
+        Ok(members
+            .into_iter()
+            .filter(|member| set.insert(member.clone(), max_intset_entries))
+            .count())
+    }
+
+    /// Removes the members from the set. Returns the number of removed members.
+    pub async fn srem(&self, key: &[u8], members: &[Bytes]) -> Result<usize, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let set = match keyspace.get_live_mut(key, now) {
+            Some(entry) => entry.value.as_set_mut()?,
+            None => return Ok(0),
+        };
+        let removed = members.iter().filter(|member| set.remove(member)).count();
+        keyspace.remove_if_empty(key);
+        Ok(removed)
+    }
+
+    /// Returns all members of the set.
+    pub async fn smembers(&self, key: &[u8]) -> Result<Vec<Bytes>, RedisError> {
+        let now = now_ms();
+        match self.inner.read().await.get(key, now) {
+            Some(entry) => Ok(entry.value.as_set()?.members()),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Checks whether each of the members belongs to the set.
+    pub async fn smismember(&self, key: &[u8], members: &[Bytes]) -> Result<Vec<bool>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        let set = match keyspace.get(key, now) {
+            Some(entry) => entry.value.as_set()?,
+            None => return Ok(vec![false; members.len()]),
+        };
+        Ok(members.iter().map(|member| set.contains(member)).collect())
+    }
+
+    /// Returns the number of members of the set.
+    pub async fn scard(&self, key: &[u8]) -> Result<usize, RedisError> {
+        let now = now_ms();
+        match self.inner.read().await.get(key, now) {
+            Some(entry) => Ok(entry.value.as_set()?.len()),
+            None => Ok(0),
+        }
+    }
+
+    /// Removes up to `count` random members and returns them.
+    pub async fn spop(&self, key: &[u8], count: usize) -> Result<Vec<Bytes>, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let set = match keyspace.get_live_mut(key, now) {
+            Some(entry) => entry.value.as_set_mut()?,
+            None => return Ok(vec![]),
+        };
+        let popped = set.random_members(count, &mut Rng::new());
+        for member in &popped {
+            set.remove(member);
+        }
+        keyspace.remove_if_empty(key);
+        Ok(popped)
+    }
+
+    /// Returns random members: up to `count` distinct members or, if the count is negative,
+    /// `-count` members which may repeat.
+    pub async fn srandmember(&self, key: &[u8], count: i64) -> Result<Vec<Bytes>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        let set = match keyspace.get(key, now) {
+            Some(entry) => entry.value.as_set()?,
+            None => return Ok(vec![]),
+        };
+        let mut rng = Rng::new();
+        if count >= 0 {
+            let count = (count as u64).min(set.len() as u64) as usize;
+            return Ok(set.random_members(count, &mut rng));
+        }
+        Ok(set.random_members_with_repeats(count.unsigned_abs(), &mut rng))
+    }
+
+    /// Moves the member from the source set to the destination set, creating it if needed.
+    /// Returns false if the member is not in the source set.
+    pub async fn smove(
+        &self,
+        source: &[u8],
+        destination: Bytes,
+        member: Bytes,
+    ) -> Result<bool, RedisError> {
+        let max_intset_entries = config::current().set_max_intset_entries;
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let contains = match keyspace.get_live(source, now) {
+            Some(entry) => entry.value.as_set()?.contains(&member),
+            None => return Ok(false),
+        };
+        if let Some(entry) = keyspace.get_live(&destination, now) {
+            entry.value.as_set()?;
+        }
+        if !contains || source == destination {
+            return Ok(contains);
+        }
+        if let Some(entry) = keyspace.get_live_mut(source, now) {
+            entry.value.as_set_mut()?.remove(&member);
+        }
+        keyspace.remove_if_empty(source);
+        keyspace
+            .get_or_insert_with(destination, now, || Value::Set(Set::default()))
+            .value
+            .as_set_mut()?
+            .insert(member, max_intset_entries);
+        Ok(true)
+    }
+
+    /// Combines the sets. Absent keys count as empty sets.
+    pub async fn set_algebra(
+        &self,
+        operation: SetOperation,
+        keys: &[Bytes],
+    ) -> Result<Vec<Bytes>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        Ok(combine(operation, &keyspace.sets(keys, now)?))
+    }
+
+    /// Combines the sets and stores the result at the destination, overwriting it. An empty
+    /// result removes the destination. Returns the number of members of the result.
+    pub async fn set_algebra_store(
+        &self,
+        operation: SetOperation,
+        destination: Bytes,
+        keys: &[Bytes],
+    ) -> Result<usize, RedisError> {
+        let max_intset_entries = config::current().set_max_intset_entries;
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let members = combine(operation, &keyspace.sets(keys, now)?);
+        let count = members.len();
+        if members.is_empty() {
+            keyspace.remove(&destination);
+        } else {
+            let set = Set::from_members(members, max_intset_entries);
+            keyspace.insert(destination, Value::Set(set), None);
+        }
+        Ok(count)
+    }
+
+    /// Counts members of the intersection of the sets, stopping at the limit of the request.
+    pub async fn sintercard(&self, request: &SInterCard) -> Result<usize, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        Ok(intersection(&keyspace.sets(&request.keys, now)?, request.limit).len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::test::set;
+
+    fn members(members: &[&'static str]) -> Vec<Bytes> {
+        members.iter().map(|member| Bytes::from(*member)).collect()
+    }
+
+    fn sorted(mut members: Vec<Bytes>) -> Vec<Bytes> {
+        members.sort();
+        members
+    }
+
+    #[test]
+    fn integer_encoding() {
+        let mut set = Set::default();
+        assert!(set.insert(Bytes::from("3"), 4));
+        assert!(set.insert(Bytes::from("-1"), 4));
+        assert!(!set.insert(Bytes::from("3"), 4));
+        assert!(matches!(&set, Set::Ints(ints) if ints == &[-1, 3]));
+        // Not the canonical form of an integer.
+        assert!(set.insert(Bytes::from("07"), 4));
+        assert!(matches!(set, Set::Members(_)));
+        assert!(set.contains(b"3"));
+        assert!(!set.contains(b"7"));
+
+        let mut set = Set::from_members(members(&["1", "2", "3"]), 2);
+        assert!(matches!(set, Set::Members(_)));
+        assert!(set.remove(b"2"));
+        assert_eq!(sorted(set.members()), members(&["1", "3"]));
+        let set = Set::from_members(members(&["5", "1"]), 2);
+        assert!(matches!(&set, Set::Ints(ints) if ints == &[1, 5]));
+        assert!(!set.contains(b"+5"));
+    }
+
+    #[test]
+    fn random_members() {
+        let mut rng = Rng::new();
+        for set in [
+            Set::from_members(members(&["1", "2", "3", "4"]), 8),
+            Set::from_members(members(&["a", "b", "c", "d"]), 8),
+        ] {
+            let all = sorted(set.members());
+            assert_eq!(sorted(set.random_members(10, &mut rng)), all);
+            let picked = sorted(set.random_members(3, &mut rng));
+            assert_eq!(picked.len(), 3);
+            assert!(picked.windows(2).all(|pair| pair[0] != pair[1]));
+            assert!(picked.iter().all(|member| set.contains(member)));
+            let picked = set.random_members_with_repeats(20, &mut rng);
+            assert_eq!(picked.len(), 20);
+            assert!(picked.iter().all(|member| set.contains(member)));
+        }
+    }
+
+    #[tokio::test]
+    async fn add_remove_and_pop() {
+        let storage = Storage::instance();
+        let key = || Bytes::from("set:a");
+        assert_eq!(
+            storage.sadd(key(), members(&["1", "2", "x", "1"])).await,
+            Ok(3)
+        );
+        assert_eq!(storage.scard(b"set:a").await, Ok(3));
+        assert_eq!(
+            storage.smismember(b"set:a", &members(&["x", "y"])).await,
+            Ok(vec![true, false])
+        );
+        assert_eq!(storage.srem(b"set:a", &members(&["x", "y"])).await, Ok(1));
+        assert_eq!(
+            storage.smembers(b"set:a").await.map(sorted),
+            Ok(members(&["1", "2"]))
+        );
+        assert_eq!(
+            storage.srandmember(b"set:a", 5).await.map(sorted),
+            Ok(members(&["1", "2"]))
+        );
+        assert_eq!(storage.srandmember(b"set:a", -3).await.unwrap().len(), 3);
+        assert_eq!(storage.spop(b"set:a", 1).await.unwrap().len(), 1);
+        assert_eq!(storage.spop(b"set:a", 5).await.unwrap().len(), 1);
+        // The emptied set is removed.
+        assert_eq!(storage.key_type(b"set:a").await, "none");
+        assert_eq!(storage.spop(b"set:a", 1).await, Ok(vec![]));
+    }
+
+    #[tokio::test]
+    async fn smove() {
+        let storage = Storage::instance();
+        storage
+            .sadd(Bytes::from("set:move:a"), members(&["m", "n"]))
+            .await
+            .unwrap();
+        set("set:move:string", None).await;
+        assert_eq!(
+            storage
+                .smove(
+                    b"set:move:a",
+                    Bytes::from("set:move:string"),
+                    Bytes::from("m")
+                )
+                .await,
+            Err(RedisError::WrongType)
+        );
+        assert_eq!(
+            storage
+                .smove(
+                    b"set:move:a",
+                    Bytes::from("set:move:b"),
+                    Bytes::from("absent")
+                )
+                .await,
+            Ok(false)
+        );
+        assert_eq!(
+            storage
+                .smove(b"set:move:a", Bytes::from("set:move:a"), Bytes::from("m"))
+                .await,
+            Ok(true)
+        );
+        assert_eq!(
+            storage
+                .smove(b"set:move:a", Bytes::from("set:move:b"), Bytes::from("m"))
+                .await,
+            Ok(true)
+        );
+        assert_eq!(storage.smembers(b"set:move:b").await, Ok(members(&["m"])));
+        assert_eq!(storage.smembers(b"set:move:a").await, Ok(members(&["n"])));
+    }
+
+    #[tokio::test]
+    async fn algebra() {
+        let storage = Storage::instance();
+        storage
+            .sadd(Bytes::from("set:alg:a"), members(&["1", "2", "3", "x"]))
+            .await
+            .unwrap();
+        storage
+            .sadd(Bytes::from("set:alg:b"), members(&["2", "3", "4"]))
+            .await
+            .unwrap();
+        let keys = members(&["set:alg:a", "set:alg:b"]);
+        assert_eq!(
+            storage
+                .set_algebra(SetOperation::Inter, &keys)
+                .await
+                .map(sorted),
+            Ok(members(&["2", "3"]))
+        );
+        assert_eq!(
+            storage
+                .set_algebra(SetOperation::Union, &keys)
+                .await
+                .map(sorted),
+            Ok(members(&["1", "2", "3", "4", "x"]))
+        );
+        assert_eq!(
+            storage
+                .set_algebra(SetOperation::Diff, &keys)
+                .await
+                .map(sorted),
+            Ok(members(&["1", "x"]))
+        );
+        let with_absent = members(&["set:alg:a", "set:alg:absent"]);
+        assert_eq!(
+            storage.set_algebra(SetOperation::Inter, &with_absent).await,
+            Ok(vec![])
+        );
+        assert_eq!(
+            storage
+                .sintercard(&SInterCard {
+                    keys: keys.clone(),
+                    limit: 1
+                })
+                .await,
+            Ok(1)
+        );
+
+        assert_eq!(
+            storage
+                .set_algebra_store(SetOperation::Inter, Bytes::from("set:alg:dest"), &keys)
+                .await,
+            Ok(2)
+        );
+        assert_eq!(
+            storage.smembers(b"set:alg:dest").await,
+            Ok(members(&["2", "3"]))
+        );
+        assert_eq!(
+            storage
+                .set_algebra_store(
+                    SetOperation::Inter,
+                    Bytes::from("set:alg:dest"),
+                    &with_absent
+                )
+                .await,
+            Ok(0)
+        );
+        assert_eq!(storage.key_type(b"set:alg:dest").await, "none");
+
+        set("set:alg:string", None).await;
+        assert_eq!(
+            storage
+                .set_algebra(
+                    SetOperation::Union,
+                    &members(&["set:alg:a", "set:alg:string"])
+                )
+                .await,
+            Err(RedisError::WrongType)
+        );
+    }
+}
@@ -0,0 +1,350 @@
+//! Skip list ordering members of a sorted set by score and then by member, the same as Redis
+//! does. Every link knows how many nodes it skips, so the rank of a node is found in O(log n)
+//! along with the node itself.
+//!
+//! Nodes live in a vector and refer to each other by index. Slots of removed nodes are reused.
+use bytes::Bytes;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// Maximum number of levels of a node.
+const MAX_LEVEL: usize = 32;
+/// Index of the head node, which holds no member and precedes all others.
+const HEAD: usize = 0;
+
+#[derive(Clone, Copy, Debug)]
+struct Link {
+    next: Option<usize>,
+    /// Number of nodes between this node and the next one, including the next one.
+    span: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    score: f64,
+    member: Bytes,
+    links: Vec<Link>,
+    previous: Option<usize>,
+}
+
+impl Node {
+    /// Returns true if the node goes before the given score and member.
+    fn precedes(&self, score: f64, member: &[u8]) -> bool {
+        self.score < score || self.score == score && self.member[..] < *member
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct SkipList {
+    nodes: Vec<Node>,
+    /// Slots of removed nodes.
+    free: Vec<usize>,
+    tail: Option<usize>,
+    len: usize,
+    /// Number of levels in use.
+    level: usize,
+    /// State of the generator of node levels.
+    seed: u64,
+}
+
+impl Default for SkipList {
+    fn default() -> Self {
+        let head = Node {
+            score: 0.0,
+            member: Bytes::new(),
+            links: vec![
+                Link {
+                    next: None,
+                    span: 0,
+                };
+                MAX_LEVEL
+            ],
+            previous: None,
+        };
+        Self {
+            nodes: vec![head],
+            free: vec![],
+            tail: None,
+            len: 0,
+            level: 1,
+            seed: RandomState::new().build_hasher().finish() | 1,
+        }
+    }
+}
+
+impl SkipList {
+    pub(super) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns a level for a new node. Each next level is taken with probability 1/4.
+    fn random_level(&mut self) -> usize {
+        let mut level = 1;
+        loop {
+            self.seed ^= self.seed << 13;
+            self.seed ^= self.seed >> 7;
+            self.seed ^= self.seed << 17;
+            if level == MAX_LEVEL || (self.seed >> 40) & 3 != 0 {
+                return level;
+            }
+            level += 1;
+        }
+    }
+
+    /// Inserts the member, which must not be in the list yet.
+    pub(super) fn insert(&mut self, score: f64, member: Bytes) {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut rank = [0; MAX_LEVEL];
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            rank[i] = if i + 1 == self.level { 0 } else { rank[i + 1] };
+            while let Some(next) = self.nodes[x].links[i].next {
+                if !self.nodes[next].precedes(score, &member) {
+                    break;
+                }
+                rank[i] += self.nodes[x].links[i].span;
+                x = next;
+            }
+            update[i] = x;
+        }
+        let level = self.random_level();
+        if level > self.level {
+            for i in self.level..level {
+                rank[i] = 0;
+                update[i] = HEAD;
+                self.nodes[HEAD].links[i].span = self.len;
+            }
+            self.level = level;
+        }
+
+        let node = Node {
+            score,
+            member,
+            links: vec![
+                Link {
+                    next: None,
+                    span: 0,
+                };
+                level
+            ],
+            previous: (update[0] != HEAD).then_some(update[0]),
+        };
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.nodes[id] = node;
+                id
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+        for i in 0..level {
+            let previous = self.nodes[update[i]].links[i];
+            let skipped = rank[0] - rank[i];
+            self.nodes[id].links[i] = Link {
+                next: previous.next,
+                span: previous.span - skipped,
+            };
+            self.nodes[update[i]].links[i] = Link {
+                next: Some(id),
+                span: skipped + 1,
+            };
+        }
+        for (i, previous) in update.iter().enumerate().take(self.level).skip(level) {
+            self.nodes[*previous].links[i].span += 1;
+        }
+        match self.nodes[id].links[0].next {
+            Some(next) => self.nodes[next].previous = Some(id),
+            None => self.tail = Some(id),
+        }
+        self.len += 1;
+    }
+
+    /// Removes the member with the score. Returns false if it is not in the list.
+    pub(super) fn remove(&mut self, score: f64, member: &[u8]) -> bool {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].links[i].next {
+                if !self.nodes[next].precedes(score, member) {
+                    break;
+                }
+                x = next;
+            }
+            update[i] = x;
+        }
+        let id = match self.nodes[x].links[0].next {
+            Some(id) if self.nodes[id].score == score && self.nodes[id].member == member => id,
+            _ => return false,
+        };
+        for (i, previous) in update.iter().enumerate().take(self.level) {
+            if self.nodes[*previous].links[i].next == Some(id) {
+                let removed = self.nodes[id].links[i];
+                let link = &mut self.nodes[*previous].links[i];
+                link.span = link.span + removed.span - 1;
+                link.next = removed.next;
+            } else {
+                self.nodes[*previous].links[i].span -= 1;
+            }
+        }
+        let previous = self.nodes[id].previous;
+        match self.nodes[id].links[0].next {
+            Some(next) => self.nodes[next].previous = previous,
+            None => self.tail = previous,
+        }
+        while self.level > 1 && self.nodes[HEAD].links[self.level - 1].next.is_none() {
+            self.level -= 1;
+        }
+        // The slot keeps no reference to the member.
+        self.nodes[id].member = Bytes::new();
+        self.nodes[id].links = vec![];
+        self.free.push(id);
+        self.len -= 1;
+        true
+    }
+
+    /// Returns the 0-based rank of the member with the score or `None` if it is not in the list.
+    pub(super) fn rank(&self, score: f64, member: &[u8]) -> Option<usize> {
+        let mut rank = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].links[i].next {
+                let node = &self.nodes[next];
+                if !(node.precedes(score, member) || node.score == score && node.member == member) {
+                    break;
+                }
+                rank += self.nodes[x].links[i].span;
+                x = next;
+            }
+            if x != HEAD && self.nodes[x].member == member {
+                return Some(rank - 1);
+            }
+        }
+        None
+    }
+
+    /// Returns the rank of the first node for which `before` doesn't hold. `before` must hold
+    /// for a prefix of the list. Returns the length if it holds for every node.
+    pub(super) fn seek(&self, before: impl Fn(f64, &Bytes) -> bool) -> usize {
+        let mut rank = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].links[i].next {
+                if !before(self.nodes[next].score, &self.nodes[next].member) {
+                    break;
+                }
+                rank += self.nodes[x].links[i].span;
+                x = next;
+            }
+        }
+        rank
+    }
+
+    /// Returns the node at the 0-based rank.
+    fn node_at(&self, rank: usize) -> Option<usize> {
+        let target = rank + 1;
+        let mut traversed = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].links[i].next {
+                let span = self.nodes[x].links[i].span;
+                if traversed + span > target {
+                    break;
+                }
+                traversed += span;
+                x = next;
+            }
+            if traversed == target {
+                return Some(x);
+            }
+        }
+        None
+    }
+
+    /// Iterates over members and their scores starting at the 0-based rank, towards the tail or,
+    /// if `reverse` is set, towards the head.
+    pub(super) fn iter_from(&self, rank: usize, reverse: bool) -> Iter<'_> {
+        Iter {
+            list: self,
+            node: self.node_at(rank),
+            reverse,
+        }
+    }
+}
+
+pub(super) struct Iter<'a> {
+    list: &'a SkipList,
+    node: Option<usize>,
+    reverse: bool,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a Bytes, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = &self.list.nodes[self.node?];
+        self.node = match self.reverse {
+            true => node.previous,
+            false => node.links[0].next,
+        };
+        Some((&node.member, node.score))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn members(list: &SkipList) -> Vec<(Bytes, f64)> {
+        list.iter_from(0, false)
+            .map(|(member, score)| (member.clone(), score))
+            .collect()
+    }
+
+    #[test]
+    fn ordered_by_score_and_member() {
+        let mut list = SkipList::default();
+        for (score, member) in [(2.0, "b"), (1.0, "z"), (2.0, "a"), (-1.0, "c")] {
+            list.insert(score, Bytes::from(member));
+        }
+        assert_eq!(
+            members(&list),
+            vec![
+                (Bytes::from("c"), -1.0),
+                (Bytes::from("z"), 1.0),
+                (Bytes::from("a"), 2.0),
+                (Bytes::from("b"), 2.0),
+            ]
+        );
+        assert_eq!(list.rank(2.0, b"a"), Some(2));
+        assert_eq!(list.rank(2.0, b"x"), None);
+        assert_eq!(list.seek(|score, _| score < 2.0), 2);
+        let reversed: Vec<f64> = list.iter_from(3, true).map(|(_, score)| score).collect();
+        assert_eq!(reversed, vec![2.0, 2.0, 1.0, -1.0]);
+        assert!(list.remove(1.0, b"z"));
+        assert!(!list.remove(1.0, b"z"));
+        assert_eq!(list.rank(2.0, b"b"), Some(2));
+    }
+
+    #[test]
+    fn ranks_of_many_nodes() {
+        let mut list = SkipList::default();
+        for i in (0..1000).rev() {
+            list.insert(i as f64, Bytes::from(format!("m{i}")));
+        }
+        for i in (0..1000).step_by(2) {
+            assert!(list.remove(i as f64, format!("m{i}").as_bytes()));
+        }
+        assert_eq!(list.len(), 500);
+        for (rank, i) in (1..1000).step_by(2).enumerate() {
+            assert_eq!(list.rank(i as f64, format!("m{i}").as_bytes()), Some(rank));
+            let (member, score) = list.iter_from(rank, false).next().unwrap();
+            assert_eq!(
+                (member.clone(), score),
+                (Bytes::from(format!("m{i}")), i as f64)
+            );
+        }
+        assert_eq!(list.iter_from(500, false).next(), None);
+    }
+}
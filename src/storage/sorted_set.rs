@@ -0,0 +1,519 @@
+//! Sorted set commands.
+//!
+//! A sorted set keeps the scores of members in a hash map for O(1) lookups and orders the
+//! members in a skip list, which answers rank and range queries in O(log n).
+use super::{now_ms, skiplist::SkipList, Keyspace, Rng, Storage, Value};
+use crate::{
+    error::RedisError,
+    protocol::{ZAdd, ZRange, ZRangeBy},
+};
+use bytes::Bytes;
+use std::{collections::HashMap, ops::Range};
+
+#[derive(Clone, Debug, Default)]
+pub(super) struct SortedSet {
+    scores: HashMap<Bytes, f64>,
+    list: SkipList,
+}
+
+/// What adding a member did to a sorted set.
+#[derive(PartialEq, Debug)]
+enum Added {
+    New,
+    Updated,
+    Unchanged,
+    /// The conditions of the request prevented the change.
+    Skipped,
+}
+
+impl SortedSet {
+    pub(super) fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    pub(super) fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// Sets the score of the member.
+    pub(super) fn insert(&mut self, member: Bytes, score: f64) {
+        if let Some(current) = self.scores.insert(member.clone(), score) {
+            self.list.remove(current, &member);
+        }
+        self.list.insert(score, member);
+    }
+
+    fn remove(&mut self, member: &[u8]) -> bool {
+        match self.scores.remove(member) {
+            Some(score) => self.list.remove(score, member),
+            None => false,
+        }
+    }
+
+    /// Adds the member or updates its score as the flags of the request allow. Returns what was
+    /// done and the resulting score.
+    fn add(
+        &mut self,
+        request: &ZAdd,
+        score: f64,
+        member: &Bytes,
+    ) -> Result<(Added, f64), RedisError> {
+        let current = match self.score(member) {
+            Some(current) => current,
+            None if request.xx => return Ok((Added::Skipped, score)),
+            None => {
+                self.insert(member.clone(), score);
+                return Ok((Added::New, score));
+            }
+        };
+        if request.nx {
+            return Ok((Added::Skipped, current));
+        }
+        let score = match request.incr {
+            true => current + score,
+            false => score,
+        };
+        if score.is_nan() {
+            return Err(RedisError::Other(
+                "resulting score is not a number (NaN)".to_owned(),
+            ));
+        }
+        if request.gt && score <= current || request.lt && score >= current {
+            return Ok((Added::Skipped, current));
+        }
+        if score == current {
+            return Ok((Added::Unchanged, current));
+        }
+        self.insert(member.clone(), score);
+        Ok((Added::Updated, score))
+    }
+
+    /// Returns the 0-based rank of the member, counting from the highest score if `reverse` is
+    /// set.
+    fn rank(&self, member: &[u8], reverse: bool) -> Option<usize> {
+        let rank = self.list.rank(self.score(member)?, member)?;
+        Some(match reverse {
+            true => self.len() - 1 - rank,
+            false => rank,
+        })
+    }
+
+    /// Returns ranks of the members in the range in ascending order. Ranks of a rank range count
+    /// from the highest score if `reverse` is set.
+    pub(super) fn ranks(&self, range: &ZRangeBy, reverse: bool) -> Range<usize> {
+        let len = self.len() as i64;
+        let ranks = match range {
+            ZRangeBy::Rank(start, stop) => {
+                let normalize = |rank: i64| if rank < 0 { len + rank } else { rank };
+                let start = normalize(*start).max(0);
+                let stop = normalize(*stop).min(len - 1);
+                if start > stop {
+                    return 0..0;
+                }
+                match reverse {
+                    true => (len - 1 - stop) as usize..(len - start) as usize,
+                    false => start as usize..stop as usize + 1,
+                }
+            }
+            ZRangeBy::Score(min, max) => {
+                self.list.seek(|score, _| min.is_below(score))
+                    ..self.list.seek(|score, _| !max.is_above(score))
+            }
+            ZRangeBy::Lex(min, max) => {
+                self.list.seek(|_, member| min.is_below(member))
+                    ..self.list.seek(|_, member| !max.is_above(member))
+            }
+        };
+        ranks.start..ranks.end.max(ranks.start)
+    }
+
+    /// Returns members and scores at the ranks, from the highest if `reverse` is set.
+    pub(super) fn entries(&self, ranks: Range<usize>, reverse: bool) -> Vec<(Bytes, f64)> {
+        let (first, count) = match reverse {
+            true => (ranks.end.saturating_sub(1), ranks.len()),
+            false => (ranks.start, ranks.len()),
+        };
+        if count == 0 {
+            return vec![];
+        }
+        self.list
+            .iter_from(first, reverse)
+            .take(count)
+            .map(|(member, score)| (member.clone(), score))
+            .collect()
+    }
+}
+
+impl Keyspace {
+    pub(super) fn sorted_set(
+        &self,
+        key: &[u8],
+        now: u64,
+    ) -> Result<Option<&SortedSet>, RedisError> {
+        match self.get(key, now) {
+            Some(entry) => entry.value.as_sorted_set().map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Storage {
+    /// Adds the members or updates their scores as the flags of the request allow. Returns the
+    /// number of added members, counting updated ones too if requested. With the INCR flag also
+    /// returns the new score, unless the flags prevented the change.
+    pub async fn zadd(&self, request: ZAdd) -> Result<(usize, Option<f64>), RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let set = keyspace
+            .get_or_insert_with(request.key.clone(), now, || {
+                Value::SortedSet(SortedSet::default())
+            })
+            .value
+            .as_sorted_set_mut()?;
+        let mut count = 0;
+        let mut last = None;
+        let mut result = Ok(());
+        for (score, member) in &request.pairs {
+            match set.add(&request, *score, member) {
+                Ok((added, score)) => {
+                    if added == Added::New || request.ch && added == Added::Updated {
+                        count += 1;
+                    }
+                    last = (added != Added::Skipped).then_some(score);
+                }
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        // A request adding nothing leaves no empty set behind.
+        keyspace.remove_if_empty(&request.key);
+        result.map(|_| (count, last.filter(|_| request.incr)))
+    }
+
+    /// Returns the number of members of the sorted set.
+    pub async fn zcard(&self, key: &[u8]) -> Result<usize, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        Ok(keyspace.sorted_set(key, now)?.map_or(0, SortedSet::len))
+    }
+
+    /// Returns scores of the members. Absent members are `None`.
+    pub async fn zmscore(
+        &self,
+        key: &[u8],
+        members: &[Bytes],
+    ) -> Result<Vec<Option<f64>>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        let set = keyspace.sorted_set(key, now)?;
+        Ok(members
+            .iter()
+            .map(|member| set.and_then(|set| set.score(member)))
+            .collect())
+    }
+
+    /// Removes the members. Returns the number of removed members.
+    pub async fn zrem(&self, key: &[u8], members: &[Bytes]) -> Result<usize, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let set = match keyspace.get_live_mut(key, now) {
+            Some(entry) => entry.value.as_sorted_set_mut()?,
+            None => return Ok(0),
+        };
+        let removed = members.iter().filter(|member| set.remove(member)).count();
+        keyspace.remove_if_empty(key);
+        Ok(removed)
+    }
+
+    /// Returns the 0-based rank of the member along with its score. Ranks count from the highest
+    /// score if `reverse` is set.
+    pub async fn zrank(
+        &self,
+        key: &[u8],
+        member: &[u8],
+        reverse: bool,
+    ) -> Result<Option<(usize, f64)>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        Ok(keyspace.sorted_set(key, now)?.and_then(|set| {
+            let rank = set.rank(member, reverse)?;
+            Some((rank, set.score(member)?))
+        }))
+    }
+
+    /// Returns members in the range of the request along with their scores.
+    pub async fn zrange(&self, request: &ZRange) -> Result<Vec<(Bytes, f64)>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        let set = match keyspace.sorted_set(&request.key, now)? {
+            Some(set) => set,
+            None => return Ok(vec![]),
+        };
+        let mut ranks = set.ranks(&request.range, request.reverse);
+        if let Some((offset, count)) = request.limit {
+            if offset < 0 {
+                return Ok(vec![]);
+            }
+            let offset = (offset as u64).min(ranks.len() as u64) as usize;
+            let count = match usize::try_from(count) {
+                Ok(count) => count.min(ranks.len() - offset),
+                Err(_) => ranks.len() - offset,
+            };
+            // Members are skipped from the side the iteration starts at.
+            ranks = match request.reverse {
+                true => ranks.end - offset - count..ranks.end - offset,
+                false => ranks.start + offset..ranks.start + offset + count,
+            };
+        }
+        Ok(set.entries(ranks, request.reverse))
+    }
+
+    /// Counts members in the range.
+    pub async fn zcount(&self, key: &[u8], range: &ZRangeBy) -> Result<usize, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        Ok(keyspace
+            .sorted_set(key, now)?
+            .map_or(0, |set| set.ranks(range, false).len()))
+    }
+
+    /// Removes members in the range. Returns the number of removed members.
+    pub async fn zremrange(&self, key: &[u8], range: &ZRangeBy) -> Result<usize, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let set = match keyspace.get_live_mut(key, now) {
+            Some(entry) => entry.value.as_sorted_set_mut()?,
+            None => return Ok(0),
+        };
+        let removed = set.entries(set.ranks(range, false), false);
+        for (member, _) in &removed {
+            set.remove(member);
+        }
+        keyspace.remove_if_empty(key);
+        Ok(removed.len())
+    }
+
+    /// Removes up to `count` members with the lowest scores, or the highest if `max` is set, and
+    /// returns them along with their scores.
+    pub async fn zpop(
+        &self,
+        key: &[u8],
+        count: usize,
+        max: bool,
+    ) -> Result<Vec<(Bytes, f64)>, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let set = match keyspace.get_live_mut(key, now) {
+            Some(entry) => entry.value.as_sorted_set_mut()?,
+            None => return Ok(vec![]),
+        };
+        let count = count.min(set.len());
+        let ranks = match max {
+            true => set.len() - count..set.len(),
+            false => 0..count,
+        };
+        let popped = set.entries(ranks, max);
+        for (member, _) in &popped {
+            set.remove(member);
+        }
+        keyspace.remove_if_empty(key);
+        Ok(popped)
+    }
+
+    /// Returns random members along with their scores: up to `count` distinct members or, if
+    /// the count is negative, `-count` members which may repeat.
+    pub async fn zrandmember(
+        &self,
+        key: &[u8],
+        count: i64,
+    ) -> Result<Vec<(Bytes, f64)>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        let mut entries: Vec<(&Bytes, &f64)> = match keyspace.sorted_set(key, now)? {
+            Some(set) => set.scores.iter().collect(),
+            None => return Ok(vec![]),
+        };
+        let mut rng = Rng::new();
+        let picked = if count >= 0 {
+            // Partial shuffle: the first `count` entries end up picked at random.
+            let count = (count as u64).min(entries.len() as u64) as usize;
+            for i in 0..count {
+                let j = i + rng.below(entries.len() - i);
+                entries.swap(i, j);
+            }
+            entries.truncate(count);
+            entries
+        } else {
+            // Grows with the picks: the count may be far larger than anything allocatable.
+            let count = count.unsigned_abs();
+            let mut picked = Vec::with_capacity(count.min(entries.len() as u64) as usize);
+            for _ in 0..count {
+                picked.push(entries[rng.below(entries.len())]);
+            }
+            picked
+        };
+        Ok(picked
+            .into_iter()
+            .map(|(member, score)| (member.clone(), *score))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        protocol::{LexBound, ScoreBound},
+        storage::test::set,
+    };
+
+    fn zadd(key: &'static str, pairs: &[(f64, &'static str)]) -> ZAdd {
+        ZAdd {
+            key: Bytes::from(key),
+            pairs: pairs
+                .iter()
+                .map(|(score, member)| (*score, Bytes::from(*member)))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn entries(entries: &[(&'static str, f64)]) -> Vec<(Bytes, f64)> {
+        entries
+            .iter()
+            .map(|(member, score)| (Bytes::from(*member), *score))
+            .collect()
+    }
+
+    fn zrange(key: &'static str, range: ZRangeBy, reverse: bool) -> ZRange {
+        ZRange {
+            key: Bytes::from(key),
+            range,
+            reverse,
+            limit: None,
+            with_scores: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn add_with_flags() {
+        let storage = Storage::instance();
+        let pairs = [(1.0, "a"), (2.0, "b")];
+        assert_eq!(storage.zadd(zadd("zset:add", &pairs)).await, Ok((2, None)));
+        let mut request = zadd("zset:add", &[(5.0, "a"), (0.0, "b"), (3.0, "c")]);
+        request.gt = true;
+        request.ch = true;
+        assert_eq!(storage.zadd(request).await, Ok((2, None)));
+        assert_eq!(
+            storage
+                .zmscore(b"zset:add", &[Bytes::from("a"), Bytes::from("b")])
+                .await,
+            Ok(vec![Some(5.0), Some(2.0)])
+        );
+        let mut request = zadd("zset:add", &[(1.5, "b")]);
+        request.incr = true;
+        assert_eq!(storage.zadd(request).await, Ok((0, Some(3.5))));
+        let mut request = zadd("zset:add", &[(1.0, "b")]);
+        request.incr = true;
+        request.lt = true;
+        assert_eq!(storage.zadd(request).await, Ok((0, None)));
+        let mut request = zadd("zset:add", &[(1.0, "new")]);
+        request.xx = true;
+        assert_eq!(storage.zadd(request).await, Ok((0, None)));
+        assert_eq!(storage.zcard(b"zset:add").await, Ok(3));
+
+        let mut request = zadd("zset:add", &[(f64::INFINITY, "inf")]);
+        storage.zadd(request).await.unwrap();
+        request = zadd("zset:add", &[(f64::NEG_INFINITY, "inf")]);
+        request.incr = true;
+        assert_eq!(
+            storage.zadd(request).await,
+            Err(RedisError::Other(
+                "resulting score is not a number (NaN)".to_owned()
+            ))
+        );
+
+        let mut request = zadd("zset:absent", &[(1.0, "a")]);
+        request.xx = true;
+        assert_eq!(storage.zadd(request).await, Ok((0, None)));
+        assert_eq!(storage.key_type(b"zset:absent").await, "none");
+    }
+
+    #[tokio::test]
+    async fn ranges() {
+        let storage = Storage::instance();
+        let pairs = [(1.0, "a"), (2.0, "b"), (2.0, "c"), (3.0, "d")];
+        storage.zadd(zadd("zset:range", &pairs)).await.unwrap();
+        assert_eq!(
+            storage
+                .zrange(&zrange("zset:range", ZRangeBy::Rank(1, -2), false))
+                .await,
+            Ok(entries(&[("b", 2.0), ("c", 2.0)]))
+        );
+        assert_eq!(
+            storage
+                .zrange(&zrange("zset:range", ZRangeBy::Rank(0, 1), true))
+                .await,
+            Ok(entries(&[("d", 3.0), ("c", 2.0)]))
+        );
+        let by_score = ZRangeBy::Score(ScoreBound::Exclusive(1.0), ScoreBound::Inclusive(3.0));
+        let mut request = zrange("zset:range", by_score.clone(), true);
+        request.limit = Some((1, 5));
+        assert_eq!(
+            storage.zrange(&request).await,
+            Ok(entries(&[("c", 2.0), ("b", 2.0)]))
+        );
+        assert_eq!(storage.zcount(b"zset:range", &by_score).await, Ok(3));
+        let by_lex = ZRangeBy::Lex(LexBound::Lowest, LexBound::Exclusive(Bytes::from("c")));
+        assert_eq!(storage.zcount(b"zset:range", &by_lex).await, Ok(2));
+        assert_eq!(
+            storage.zrank(b"zset:range", b"c", true).await,
+            Ok(Some((1, 2.0)))
+        );
+        assert_eq!(storage.zrank(b"zset:range", b"x", false).await, Ok(None));
+
+        assert_eq!(storage.zremrange(b"zset:range", &by_score).await, Ok(3));
+        assert_eq!(
+            storage.zpop(b"zset:range", 2, true).await,
+            Ok(entries(&[("a", 1.0)]))
+        );
+        assert_eq!(storage.key_type(b"zset:range").await, "none");
+    }
+
+    #[tokio::test]
+    async fn pop_and_random() {
+        let storage = Storage::instance();
+        let pairs = [(1.0, "a"), (2.0, "b"), (3.0, "c")];
+        storage.zadd(zadd("zset:pop", &pairs)).await.unwrap();
+        assert_eq!(
+            storage.zpop(b"zset:pop", 1, false).await,
+            Ok(entries(&[("a", 1.0)]))
+        );
+        assert_eq!(
+            storage.zpop(b"zset:pop", 1, true).await,
+            Ok(entries(&[("c", 3.0)]))
+        );
+        assert_eq!(
+            storage.zrandmember(b"zset:pop", 5).await,
+            Ok(entries(&[("b", 2.0)]))
+        );
+        assert_eq!(storage.zrandmember(b"zset:pop", -3).await.unwrap().len(), 3);
+        assert_eq!(
+            storage
+                .zrem(b"zset:pop", &[Bytes::from("b"), Bytes::from("x")])
+                .await,
+            Ok(1)
+        );
+        set("zset:string", None).await;
+        assert_eq!(
+            storage.zcard(b"zset:string").await,
+            Err(RedisError::WrongType)
+        );
+    }
+}
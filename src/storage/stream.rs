@@ -0,0 +1,478 @@
+//! Stream commands.
+//!
+//! Entries of a stream are kept in a B-tree keyed by their IDs, so ranges of IDs are found in
+//! O(log n) and trimming removes entries from the front.
+use super::{
+    blocking::BlockedOperation, blocking::Served, now_ms, stream_group::Group, Keyspace, Storage,
+    Value,
+};
+use crate::{
+    config,
+    error::RedisError,
+    protocol::{ReadAfter, StreamId, Timeout, Trim, TrimStrategy, XAdd, XAddId, XRange},
+};
+use bytes::Bytes;
+use std::collections::BTreeMap;
+
+/// An entry of a stream: its ID and field-value pairs, flattened. The pairs are `None` if the
+/// entry is deleted, which only the pending entries of a consumer may refer to.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct StreamEntry {
+    pub id: StreamId,
+    pub fields: Option<Vec<Bytes>>,
+}
+
+/// Entries read from the stream at the key.
+pub type StreamRead = (Bytes, Vec<StreamEntry>);
+
+/// Approximate trimming removes at most this many nodes by default.
+const DEFAULT_TRIM_LIMIT_NODES: usize = 100;
+
+#[derive(Clone, Debug, Default)]
+pub(super) struct Stream {
+    pub(super) entries: BTreeMap<StreamId, Vec<Bytes>>,
+    /// ID of the last added entry, which may be deleted since. New IDs must be greater.
+    pub(super) last_id: StreamId,
+    /// Number of entries ever added.
+    pub(super) entries_added: u64,
+    /// Greatest ID of entries deleted with XDEL.
+    pub(super) max_deleted_id: StreamId,
+    pub(super) groups: BTreeMap<Bytes, Group>,
+}
+
+fn error(message: &str) -> RedisError {
+    RedisError::Other(message.to_owned())
+}
+
+impl Stream {
+    pub(super) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the ID for a new entry.
+    fn next_id(&self, id: XAddId, now: u64) -> Result<StreamId, RedisError> {
+        if self.last_id == StreamId::MAX {
+            return Err(error(
+                "The stream has exhausted the last possible ID, unable to add more items",
+            ));
+        }
+        let too_small = || {
+            error("The ID specified in XADD is equal or smaller than the target stream top item")
+        };
+        match id {
+            XAddId::Auto if now > self.last_id.ms => Ok(StreamId { ms: now, seq: 0 }),
+            // The clock went backwards or entries were added in the same millisecond.
+            XAddId::Auto => self.last_id.next().ok_or_else(too_small),
+            XAddId::AutoSeq(ms) if ms > self.last_id.ms => Ok(StreamId { ms, seq: 0 }),
+            XAddId::AutoSeq(ms) if ms == self.last_id.ms => self
+                .last_id
+                .seq
+                .checked_add(1)
+                .map(|seq| StreamId { ms, seq })
+                .ok_or_else(too_small),
+            XAddId::AutoSeq(_) => Err(too_small()),
+            XAddId::Explicit(StreamId::MIN) => {
+                Err(error("The ID specified in XADD must be greater than 0-0"))
+            }
+            XAddId::Explicit(id) if id > self.last_id => Ok(id),
+            XAddId::Explicit(_) => Err(too_small()),
+        }
+    }
+
+    fn add(&mut self, id: StreamId, fields: Vec<Bytes>) {
+        self.entries.insert(id, fields);
+        self.last_id = id;
+        self.entries_added += 1;
+    }
+
+    /// Returns the ID of the first entry, 0-0 if there are none.
+    pub(super) fn first_id(&self) -> StreamId {
+        self.entries.keys().next().copied().unwrap_or(StreamId::MIN)
+    }
+
+    /// Returns true if entries with IDs from `start` may have been deleted with XDEL.
+    pub(super) fn has_tombstones_from(&self, start: StreamId) -> bool {
+        !self.entries.is_empty()
+            && self.max_deleted_id != StreamId::MIN
+            && start <= self.max_deleted_id
+    }
+
+    /// Estimates the number of entries added up to the ID, which is the number of entries a
+    /// group has read once it has read up to the ID. Returns `None` if it can't be known, e.g.
+    /// because entries before the ID were deleted.
+    pub(super) fn entries_added_until(&self, id: StreamId) -> Option<u64> {
+        if self.entries_added == 0 || id == self.last_id {
+            return Some(self.entries_added);
+        }
+        if self.entries.is_empty() && id < self.last_id {
+            return Some(self.entries_added);
+        }
+        if id > self.last_id {
+            return None;
+        }
+        let first_id = self.first_id();
+        // Without deletions after the first entry, all entries from it on are still there.
+        if self.max_deleted_id == StreamId::MIN || self.max_deleted_id < first_id {
+            let before_first = self.entries_added - self.len() as u64;
+            if id < first_id {
+                return Some(before_first);
+            }
+            if id == first_id {
+                return Some(before_first + 1);
+            }
+        }
+        None
+    }
+
+    /// Removes the oldest entries as the trimming requires. Returns the number of removed
+    /// entries.
+    fn trim(&mut self, trim: &Trim) -> usize {
+        let mut count = match trim.strategy {
+            TrimStrategy::MaxLen(max_len) => self.len().saturating_sub(max_len),
+            TrimStrategy::MinId(min_id) => self.entries.range(..min_id).count(),
+        };
+        if trim.approximate {
+            // Only whole nodes are removed, up to the limit.
+            let node_size = config::current().stream_node_max_entries;
+            if node_size > 0 {
+                count -= count % node_size;
+            }
+            let limit = match trim.limit {
+                Some(0) => usize::MAX,
+                Some(limit) => limit,
+                None => DEFAULT_TRIM_LIMIT_NODES.saturating_mul(node_size.max(1)),
+            };
+            count = count.min(limit);
+        }
+        for _ in 0..count {
+            self.entries.pop_first();
+        }
+        count
+    }
+
+    /// Returns up to `count` entries with IDs in the inclusive range, from the highest ID if
+    /// `reverse` is set.
+    pub(super) fn range(
+        &self,
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+        reverse: bool,
+    ) -> Vec<StreamEntry> {
+        if start > end {
+            return vec![];
+        }
+        let range = self.entries.range(start..=end);
+        let entry = |(id, fields): (&StreamId, &Vec<Bytes>)| StreamEntry {
+            id: *id,
+            fields: Some(fields.clone()),
+        };
+        let count = count.unwrap_or(usize::MAX);
+        match reverse {
+            true => range.rev().take(count).map(entry).collect(),
+            false => range.take(count).map(entry).collect(),
+        }
+    }
+
+    /// Returns up to `count` entries with IDs greater than `after`.
+    fn read_after(&self, after: StreamId, count: Option<usize>) -> Vec<StreamEntry> {
+        match after.next() {
+            Some(start) => self.range(start, StreamId::MAX, count, false),
+            None => vec![],
+        }
+    }
+}
+
+impl Keyspace {
+    pub(super) fn stream(&self, key: &[u8], now: u64) -> Result<Option<&Stream>, RedisError> {
+        match self.get(key, now) {
+            Some(entry) => entry.value.as_stream().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads entries of the streams after the IDs. Returns only streams with entries to read.
+    pub(super) fn read_streams(
+        &self,
+        streams: &[(Bytes, StreamId)],
+        count: Option<usize>,
+        now: u64,
+    ) -> Result<Vec<StreamRead>, RedisError> {
+        let mut read = vec![];
+        for (key, after) in streams {
+            if let Some(stream) = self.stream(key, now)? {
+                let entries = stream.read_after(*after, count);
+                if !entries.is_empty() {
+                    read.push((key.clone(), entries));
+                }
+            }
+        }
+        Ok(read)
+    }
+}
+
+impl Storage {
+    /// Adds an entry to the stream, creating the stream unless the request forbids it, and trims
+    /// the stream if requested. Then serves clients blocked on the stream. Returns the ID of the
+    /// entry or `None` if the stream doesn't exist and may not be created.
+    pub async fn xadd(&self, request: XAdd) -> Result<Option<StreamId>, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let id = match keyspace.stream(&request.key, now)? {
+            Some(stream) => stream.next_id(request.id, now)?,
+            None if request.no_mkstream => return Ok(None),
+            None => {
+                // The ID is validated before the stream is created.
+                let id = Stream::default().next_id(request.id, now)?;
+                keyspace.insert(request.key.clone(), Value::Stream(Stream::default()), None);
+                id
+            }
+        };
+        let stream = keyspace
+            .get_live_mut(&request.key, now)
+            .expect("stream exists")
+            .value
+            .as_stream_mut()?;
+        stream.add(id, request.fields);
+        if let Some(trim) = &request.trim {
+            stream.trim(trim);
+        }
+        keyspace.blocked.signal(&request.key);
+        keyspace.serve_blocked(now);
+        Ok(Some(id))
+    }
+
+    /// Returns the number of entries of the stream, 0 if it doesn't exist.
+    pub async fn xlen(&self, key: &[u8]) -> Result<usize, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        Ok(keyspace.stream(key, now)?.map_or(0, Stream::len))
+    }
+
+    /// Returns entries with IDs in the range of the request.
+    pub async fn xrange(&self, request: &XRange) -> Result<Vec<StreamEntry>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        Ok(keyspace
+            .stream(&request.key, now)?
+            .map_or(vec![], |stream| {
+                stream.range(request.start, request.end, request.count, request.reverse)
+            }))
+    }
+
+    /// Deletes entries with the IDs. Returns the number of deleted entries.
+    pub async fn xdel(&self, key: &[u8], ids: &[StreamId]) -> Result<usize, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let stream = match keyspace.get_live_mut(key, now) {
+            Some(entry) => entry.value.as_stream_mut()?,
+            None => return Ok(0),
+        };
+        let mut deleted = 0;
+        for id in ids {
+            if stream.entries.remove(id).is_some() {
+                stream.max_deleted_id = stream.max_deleted_id.max(*id);
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Trims the stream. Returns the number of removed entries.
+    pub async fn xtrim(&self, key: &[u8], trim: &Trim) -> Result<usize, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        match keyspace.get_live_mut(key, now) {
+            Some(entry) => Ok(entry.value.as_stream_mut()?.trim(trim)),
+            None => Ok(0),
+        }
+    }
+
+    /// Reads up to `count` entries from each stream after the positions. If there are none and
+    /// a timeout is given, blocks until entries are added to one of the streams or the timeout
+    /// expires. Returns `None` if there are no entries.
+    pub async fn xread(
+        &self,
+        streams: Vec<(Bytes, ReadAfter)>,
+        count: Option<usize>,
+        timeout: Option<Timeout>,
+    ) -> Result<Option<Vec<StreamRead>>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.write().await;
+        let mut after = Vec::with_capacity(streams.len());
+        for (key, position) in streams {
+            let id = match position {
+                ReadAfter::Id(id) => id,
+                ReadAfter::Last => keyspace
+                    .stream(&key, now)?
+                    .map_or(StreamId::MIN, |stream| stream.last_id),
+            };
+            after.push((key, id));
+        }
+        let read = keyspace.read_streams(&after, count, now)?;
+        if !read.is_empty() {
+            return Ok(Some(read));
+        }
+        let timeout = match timeout {
+            Some(timeout) => timeout,
+            None => return Ok(None),
+        };
+        let keys = after.iter().map(|(key, _)| key.clone()).collect();
+        let operation = BlockedOperation::ReadStreams { after, count };
+        match self.block(keyspace, keys, operation, timeout).await? {
+            Some(Served::Streams(read)) => Ok(Some(read)),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::test::set;
+
+    fn xadd(key: &'static str, id: XAddId) -> XAdd {
+        XAdd {
+            key: Bytes::from(key),
+            no_mkstream: false,
+            trim: None,
+            id,
+            fields: vec![Bytes::from("f"), Bytes::from("v")],
+        }
+    }
+
+    fn id(ms: u64, seq: u64) -> StreamId {
+        StreamId { ms, seq }
+    }
+
+    fn ids(entries: &[StreamEntry]) -> Vec<StreamId> {
+        entries.iter().map(|entry| entry.id).collect()
+    }
+
+    fn all(key: &'static str) -> XRange {
+        XRange {
+            key: Bytes::from(key),
+            start: StreamId::MIN,
+            end: StreamId::MAX,
+            count: None,
+            reverse: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn ids_of_added_entries() {
+        let storage = Storage::instance();
+        let key = "stream:ids";
+        assert_eq!(
+            storage
+                .xadd(xadd(key, XAddId::Explicit(StreamId::MIN)))
+                .await,
+            Err(error("The ID specified in XADD must be greater than 0-0"))
+        );
+        assert_eq!(storage.key_type(key.as_bytes()).await, "none");
+        assert_eq!(
+            storage.xadd(xadd(key, XAddId::AutoSeq(0))).await,
+            Ok(Some(id(0, 1)))
+        );
+        assert_eq!(
+            storage.xadd(xadd(key, XAddId::Explicit(id(5, 5)))).await,
+            Ok(Some(id(5, 5)))
+        );
+        assert_eq!(
+            storage.xadd(xadd(key, XAddId::AutoSeq(5))).await,
+            Ok(Some(id(5, 6)))
+        );
+        assert_eq!(
+            storage.xadd(xadd(key, XAddId::Explicit(id(5, 6)))).await,
+            Err(error(
+                "The ID specified in XADD is equal or smaller than the target stream top item"
+            ))
+        );
+        assert_eq!(
+            storage.xadd(xadd(key, XAddId::AutoSeq(4))).await,
+            Err(error(
+                "The ID specified in XADD is equal or smaller than the target stream top item"
+            ))
+        );
+        let generated = storage
+            .xadd(xadd(key, XAddId::Auto))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(generated > id(5, 6));
+
+        let mut request = xadd("stream:absent", XAddId::Auto);
+        request.no_mkstream = true;
+        assert_eq!(storage.xadd(request).await, Ok(None));
+        assert_eq!(storage.xlen(b"stream:absent").await, Ok(0));
+        set("stream:string", None).await;
+        assert_eq!(
+            storage.xadd(xadd("stream:string", XAddId::Auto)).await,
+            Err(RedisError::WrongType)
+        );
+    }
+
+    #[tokio::test]
+    async fn ranges_and_deletion() {
+        let storage = Storage::instance();
+        let key = "stream:range";
+        for ms in 1..=5 {
+            storage
+                .xadd(xadd(key, XAddId::Explicit(id(ms, 0))))
+                .await
+                .unwrap();
+        }
+        let mut request = all(key);
+        request.start = id(2, 0);
+        request.count = Some(2);
+        assert_eq!(
+            storage.xrange(&request).await.map(|entries| ids(&entries)),
+            Ok(vec![id(2, 0), id(3, 0)])
+        );
+        request.reverse = true;
+        assert_eq!(
+            storage.xrange(&request).await.map(|entries| ids(&entries)),
+            Ok(vec![id(5, 0), id(4, 0)])
+        );
+        assert_eq!(
+            storage.xdel(key.as_bytes(), &[id(5, 0), id(9, 0)]).await,
+            Ok(1)
+        );
+        // Deleted IDs are not reused.
+        assert_eq!(
+            storage.xadd(xadd(key, XAddId::AutoSeq(5))).await,
+            Ok(Some(id(5, 1)))
+        );
+        // All entries may be deleted, the stream stays.
+        let all_ids = ids(&storage.xrange(&all(key)).await.unwrap());
+        assert_eq!(storage.xdel(key.as_bytes(), &all_ids).await, Ok(5));
+        assert_eq!(storage.key_type(key.as_bytes()).await, "stream");
+    }
+
+    #[tokio::test]
+    async fn trimming() {
+        let storage = Storage::instance();
+        let key = "stream:trim";
+        for ms in 1..=250 {
+            storage
+                .xadd(xadd(key, XAddId::Explicit(id(ms, 0))))
+                .await
+                .unwrap();
+        }
+        let mut trim = Trim {
+            strategy: TrimStrategy::MaxLen(20),
+            approximate: true,
+            limit: None,
+        };
+        // Only whole nodes of 100 entries are removed.
+        assert_eq!(storage.xtrim(key.as_bytes(), &trim).await, Ok(200));
+        trim.limit = Some(10);
+        assert_eq!(storage.xtrim(key.as_bytes(), &trim).await, Ok(0));
+        trim.approximate = false;
+        trim.limit = None;
+        assert_eq!(storage.xtrim(key.as_bytes(), &trim).await, Ok(30));
+        trim.strategy = TrimStrategy::MinId(id(240, 0));
+        assert_eq!(storage.xtrim(key.as_bytes(), &trim).await, Ok(9));
+        assert_eq!(storage.xlen(key.as_bytes()).await, Ok(11));
+    }
+}
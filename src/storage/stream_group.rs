@@ -0,0 +1,1043 @@
+//! Consumer groups of streams.
+//!
+//! A group tracks the last entry delivered to it and the pending entries list: entries
+//! delivered to its consumers but not acknowledged yet. Each pending entry is owned by a
+//! consumer, which keeps the IDs of the entries it owns, so entries can be delivered again to
+//! the same consumer or claimed by another one.
+use super::{
+    blocking::{BlockedOperation, Served},
+    now_ms,
+    stream::{Stream, StreamEntry, StreamRead},
+    Keyspace, Storage, Value,
+};
+use crate::{
+    error::RedisError,
+    protocol::{
+        ClaimTime, PendingRange, ReadAfter, ReadGroup, StreamId, Timeout, XAutoClaim, XClaim,
+        AUTOCLAIM_ATTEMPTS_FACTOR,
+    },
+};
+use bytes::Bytes;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Bound,
+};
+
+#[derive(Clone, Debug)]
+pub(super) struct Group {
+    /// ID of the last entry delivered to the group.
+    last_id: StreamId,
+    /// Number of entries the group has read, if known.
+    entries_read: Option<u64>,
+    pending: BTreeMap<StreamId, PendingEntry>,
+    consumers: BTreeMap<Bytes, Consumer>,
+}
+
+/// Entry delivered to a consumer but not acknowledged yet.
+#[derive(Clone, Debug)]
+struct PendingEntry {
+    consumer: Bytes,
+    /// Unix time in milliseconds of the last delivery.
+    delivery_time: u64,
+    delivery_count: u64,
+}
+
+#[derive(Clone, Debug)]
+struct Consumer {
+    /// Unix time in milliseconds of the last attempt to read or claim entries.
+    seen_time: u64,
+    /// Unix time in milliseconds of the last successful attempt.
+    active_time: Option<u64>,
+    /// IDs of pending entries the consumer owns.
+    pending: BTreeSet<StreamId>,
+}
+
+impl Consumer {
+    fn new(now: u64) -> Self {
+        Self {
+            seen_time: now,
+            active_time: None,
+            pending: BTreeSet::new(),
+        }
+    }
+}
+
+/// Summary of pending entries of a group.
+#[derive(Eq, PartialEq, Debug)]
+pub struct PendingSummary {
+    pub count: usize,
+    /// Lowest and highest IDs of pending entries.
+    pub bounds: Option<(StreamId, StreamId)>,
+    /// Number of pending entries of each consumer which has some.
+    pub consumers: Vec<(Bytes, usize)>,
+}
+
+/// A pending entry as XPENDING reports it.
+#[derive(Eq, PartialEq, Debug)]
+pub struct PendingInfo {
+    pub id: StreamId,
+    pub consumer: Bytes,
+    /// Milliseconds since the last delivery.
+    pub idle: u64,
+    pub delivery_count: u64,
+}
+
+/// Result of XAUTOCLAIM.
+#[derive(Eq, PartialEq, Debug)]
+pub struct AutoClaimed {
+    /// ID to continue scanning at, 0-0 when the scan is complete.
+    pub cursor: StreamId,
+    pub claimed: Vec<StreamEntry>,
+    /// IDs of pending entries which were deleted from the stream, so they were dropped.
+    pub deleted: Vec<StreamId>,
+}
+
+/// A stream as XINFO STREAM reports it.
+#[derive(Eq, PartialEq, Debug)]
+pub struct StreamInfo {
+    pub length: usize,
+    pub last_generated_id: StreamId,
+    pub max_deleted_id: StreamId,
+    pub entries_added: u64,
+    pub first_id: StreamId,
+    pub groups: usize,
+    pub first_entry: Option<StreamEntry>,
+    pub last_entry: Option<StreamEntry>,
+}
+
+/// A group as XINFO GROUPS reports it.
+#[derive(Eq, PartialEq, Debug)]
+pub struct GroupInfo {
+    pub name: Bytes,
+    pub consumers: usize,
+    pub pending: usize,
+    pub last_delivered_id: StreamId,
+    pub entries_read: Option<u64>,
+    /// Number of entries not delivered to the group yet, if known.
+    pub lag: Option<u64>,
+}
+
+/// A consumer as XINFO CONSUMERS reports it.
+#[derive(Eq, PartialEq, Debug)]
+pub struct ConsumerInfo {
+    pub name: Bytes,
+    pub pending: usize,
+    /// Milliseconds since the last attempt to read or claim entries.
+    pub idle: u64,
+    /// Milliseconds since the last successful attempt, `None` if there was none.
+    pub inactive: Option<u64>,
+}
+
+fn key_required() -> RedisError {
+    RedisError::Other(
+        "The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to \
+         use the MKSTREAM option to create an empty stream automatically."
+            .to_owned(),
+    )
+}
+
+fn no_such_group(key: &[u8], group: &[u8]) -> RedisError {
+    RedisError::NoGroup(format!(
+        "No such consumer group '{}' for key name '{}'",
+        String::from_utf8_lossy(group),
+        String::from_utf8_lossy(key)
+    ))
+}
+
+fn no_such_key_or_group(key: &[u8], group: &[u8]) -> RedisError {
+    RedisError::NoGroup(format!(
+        "No such key '{}' or consumer group '{}'",
+        String::from_utf8_lossy(key),
+        String::from_utf8_lossy(group)
+    ))
+}
+
+impl Group {
+    fn new(last_id: StreamId, entries_read: Option<u64>) -> Self {
+        Self {
+            last_id,
+            entries_read,
+            pending: BTreeMap::new(),
+            consumers: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the consumer, creating it if needed, and marks it as seen.
+    fn consumer(&mut self, name: &Bytes, now: u64) -> &mut Consumer {
+        let consumer = self
+            .consumers
+            .entry(name.clone())
+            .or_insert_with(|| Consumer::new(now));
+        consumer.seen_time = now;
+        consumer
+    }
+
+    /// Makes the consumer own the pending entry, creating the entry if needed.
+    fn assign(&mut self, id: StreamId, consumer: &Bytes, delivery_time: u64, delivery_count: u64) {
+        let previous = self.pending.insert(
+            id,
+            PendingEntry {
+                consumer: consumer.clone(),
+                delivery_time,
+                delivery_count,
+            },
+        );
+        if let Some(previous) = previous {
+            if let Some(owner) = self.consumers.get_mut(&previous.consumer) {
+                owner.pending.remove(&id);
+            }
+        }
+        if let Some(owner) = self.consumers.get_mut(consumer) {
+            owner.pending.insert(id);
+        }
+    }
+
+    /// Removes the pending entry. Returns false if it is not pending.
+    fn acknowledge(&mut self, id: StreamId) -> bool {
+        match self.pending.remove(&id) {
+            Some(entry) => {
+                if let Some(owner) = self.consumers.get_mut(&entry.consumer) {
+                    owner.pending.remove(&id);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Delivers up to `count` entries never delivered to the group to the consumer.
+    fn read_new(
+        &mut self,
+        stream: &Stream,
+        consumer: &Bytes,
+        count: Option<usize>,
+        no_ack: bool,
+        now: u64,
+    ) -> Vec<StreamEntry> {
+        let entries = match self.last_id.next() {
+            Some(start) => stream.range(start, StreamId::MAX, count, false),
+            None => vec![],
+        };
+        self.consumer(consumer, now);
+        for entry in &entries {
+            self.last_id = entry.id;
+            // The counter is kept exact while no entries ahead may have been deleted.
+            self.entries_read = match self.entries_read {
+                Some(read) if !stream.has_tombstones_from(entry.id) => Some(read + 1),
+                _ => stream.entries_added_until(entry.id),
+            };
+            if !no_ack {
+                self.assign(entry.id, consumer, now, 1);
+            }
+        }
+        if !entries.is_empty() {
+            self.consumer(consumer, now).active_time = Some(now);
+        }
+        entries
+    }
+
+    /// Delivers again up to `count` pending entries of the consumer with IDs greater than
+    /// `after`. Entries deleted from the stream are returned without fields.
+    fn read_history(
+        &mut self,
+        stream: &Stream,
+        consumer: &Bytes,
+        after: StreamId,
+        count: Option<usize>,
+        now: u64,
+    ) -> Vec<StreamEntry> {
+        let ids: Vec<StreamId> = self
+            .consumer(consumer, now)
+            .pending
+            .range((Bound::Excluded(after), Bound::Unbounded))
+            .take(count.unwrap_or(usize::MAX))
+            .copied()
+            .collect();
+        ids.into_iter()
+            .map(|id| {
+                let fields = stream.entries.get(&id).cloned();
+                if fields.is_some() {
+                    if let Some(pending) = self.pending.get_mut(&id) {
+                        pending.delivery_time = now;
+                        pending.delivery_count += 1;
+                    }
+                }
+                StreamEntry { id, fields }
+            })
+            .collect()
+    }
+
+    /// Returns the number of entries not delivered to the group yet, if it can be known.
+    fn lag(&self, stream: &Stream) -> Option<u64> {
+        if stream.entries_added == 0 {
+            return Some(0);
+        }
+        let read = match self.entries_read {
+            Some(read) if !stream.has_tombstones_from(self.last_id) => Some(read),
+            _ => stream.entries_added_until(self.last_id),
+        };
+        read.map(|read| stream.entries_added.saturating_sub(read))
+    }
+}
+
+impl Stream {
+    /// Resolves the last delivered ID of a group given to XGROUP.
+    fn group_last_id(&self, last_id: ReadAfter) -> StreamId {
+        match last_id {
+            ReadAfter::Id(id) => id,
+            ReadAfter::Last => self.last_id,
+        }
+    }
+
+    /// Runs `f` on the group, which is taken out of the stream meanwhile, so `f` can look at
+    /// the stream too. Returns `None` if there is no such group.
+    fn with_group<T>(
+        &mut self,
+        name: &[u8],
+        f: impl FnOnce(&Stream, &mut Group) -> T,
+    ) -> Option<T> {
+        let (name, mut group) = self.groups.remove_entry(name)?;
+        let result = f(self, &mut group);
+        self.groups.insert(name, group);
+        Some(result)
+    }
+}
+
+/// Returns the entry at the ID in the stream, if it is there.
+fn stream_entry(stream: &Stream, id: StreamId) -> Option<StreamEntry> {
+    stream.entries.get(&id).map(|fields| StreamEntry {
+        id,
+        fields: Some(fields.clone()),
+    })
+}
+
+impl Keyspace {
+    fn stream_mut(&mut self, key: &[u8], now: u64) -> Result<Option<&mut Stream>, RedisError> {
+        match self.get_live_mut(key, now) {
+            Some(entry) => entry.value.as_stream_mut().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads entries of the stream for the consumer of the group: entries never delivered to
+    /// the group if `after` is `ReadAfter::Last`, or the pending entries of the consumer after
+    /// the ID otherwise.
+    pub(super) fn read_group(
+        &mut self,
+        key: &Bytes,
+        read: &ReadGroup,
+        after: ReadAfter,
+        count: Option<usize>,
+        now: u64,
+    ) -> Result<Vec<StreamEntry>, RedisError> {
+        let no_group = || {
+            RedisError::NoGroup(format!(
+                "No such key '{}' or consumer group '{}' in XREADGROUP with GROUP option",
+                String::from_utf8_lossy(key),
+                String::from_utf8_lossy(&read.group)
+            ))
+        };
+        let stream = self.stream_mut(key, now)?.ok_or_else(no_group)?;
+        stream
+            .with_group(&read.group, |stream, group| match after {
+                ReadAfter::Last => group.read_new(stream, &read.consumer, count, read.no_ack, now),
+                ReadAfter::Id(id) => group.read_history(stream, &read.consumer, id, count, now),
+            })
+            .ok_or_else(no_group)
+    }
+}
+
+impl Storage {
+    /// Creates a group of the stream, creating an empty stream first if it doesn't exist and
+    /// `mkstream` is set.
+    pub async fn xgroup_create(
+        &self,
+        key: Bytes,
+        group: Bytes,
+        last_id: ReadAfter,
+        mkstream: bool,
+        entries_read: Option<u64>,
+    ) -> Result<(), RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        if keyspace.stream(&key, now)?.is_none() {
+            if !mkstream {
+                return Err(key_required());
+            }
+            keyspace.insert(key.clone(), Value::Stream(Stream::default()), None);
+        }
+        let stream = keyspace.stream_mut(&key, now)?.ok_or_else(key_required)?;
+        if stream.groups.contains_key(&group) {
+            return Err(RedisError::BusyGroup);
+        }
+        let last_id = stream.group_last_id(last_id);
+        stream
+            .groups
+            .insert(group, Group::new(last_id, entries_read));
+        Ok(())
+    }
+
+    /// Sets the last delivered ID of the group.
+    pub async fn xgroup_setid(
+        &self,
+        key: &[u8],
+        group: &[u8],
+        last_id: ReadAfter,
+        entries_read: Option<u64>,
+    ) -> Result<(), RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let stream = keyspace.stream_mut(key, now)?.ok_or_else(key_required)?;
+        let last_id = stream.group_last_id(last_id);
+        let group = stream
+            .groups
+            .get_mut(group)
+            .ok_or_else(|| no_such_group(key, group))?;
+        group.last_id = last_id;
+        group.entries_read = entries_read;
+        Ok(())
+    }
+
+    /// Deletes the group, failing clients blocked reading for it. Returns false if there is no
+    /// such group.
+    pub async fn xgroup_destroy(&self, key: &[u8], group: &[u8]) -> Result<bool, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let stream = keyspace.stream_mut(key, now)?.ok_or_else(key_required)?;
+        let destroyed = stream.groups.remove(group).is_some();
+        if destroyed {
+            keyspace.blocked.unblock_group_readers(
+                key,
+                Some(group),
+                RedisError::Unblocked(
+                    "the consumer group this client was blocked on no longer exists".to_owned(),
+                ),
+            );
+        }
+        Ok(destroyed)
+    }
+
+    /// Creates a consumer of the group. Returns false if it already exists.
+    pub async fn xgroup_createconsumer(
+        &self,
+        key: &[u8],
+        group: &[u8],
+        consumer: Bytes,
+    ) -> Result<bool, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let stream = keyspace.stream_mut(key, now)?.ok_or_else(key_required)?;
+        let group = stream
+            .groups
+            .get_mut(group)
+            .ok_or_else(|| no_such_group(key, group))?;
+        if group.consumers.contains_key(&consumer) {
+            return Ok(false);
+        }
+        group.consumers.insert(consumer, Consumer::new(now));
+        Ok(true)
+    }
+
+    /// Deletes a consumer of the group along with its pending entries. Returns the number of
+    /// pending entries it had.
+    pub async fn xgroup_delconsumer(
+        &self,
+        key: &[u8],
+        group: &[u8],
+        consumer: &[u8],
+    ) -> Result<usize, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let stream = keyspace.stream_mut(key, now)?.ok_or_else(key_required)?;
+        let group = stream
+            .groups
+            .get_mut(group)
+            .ok_or_else(|| no_such_group(key, group))?;
+        let consumer = match group.consumers.remove(consumer) {
+            Some(consumer) => consumer,
+            None => return Ok(0),
+        };
+        for id in &consumer.pending {
+            group.pending.remove(id);
+        }
+        Ok(consumer.pending.len())
+    }
+
+    /// Reads entries of streams for the consumer of the group like `read_group` does. If there
+    /// are no new entries for the consumer and a timeout is given, blocks until entries are
+    /// added to one of the streams or the timeout expires. Returns `None` if there are no
+    /// entries. Pending entries are returned even if there are none.
+    pub async fn xreadgroup(
+        &self,
+        read: ReadGroup,
+        streams: Vec<(Bytes, ReadAfter)>,
+        count: Option<usize>,
+        timeout: Option<Timeout>,
+    ) -> Result<Option<Vec<StreamRead>>, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        // Nothing is read unless all the groups exist.
+        for (key, _) in &streams {
+            let exists = match keyspace.stream(key, now)? {
+                Some(stream) => stream.groups.contains_key(&read.group),
+                None => false,
+            };
+            if !exists {
+                // Reading reports the missing group.
+                keyspace.read_group(key, &read, ReadAfter::Last, Some(1), now)?;
+            }
+        }
+        let mut result = vec![];
+        let mut history = false;
+        for (key, after) in &streams {
+            let entries = keyspace.read_group(key, &read, *after, count, now)?;
+            history |= *after != ReadAfter::Last;
+            if !entries.is_empty() || *after != ReadAfter::Last {
+                result.push((key.clone(), entries));
+            }
+        }
+        if !result.is_empty() || history {
+            return Ok(Some(result));
+        }
+        let timeout = match timeout {
+            Some(timeout) => timeout,
+            None => return Ok(None),
+        };
+        let keys = streams.into_iter().map(|(key, _)| key).collect();
+        let operation = BlockedOperation::ReadGroup { read, count };
+        match self.block(keyspace, keys, operation, timeout).await? {
+            Some(Served::Streams(read)) => Ok(Some(read)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Acknowledges pending entries of the group. Returns the number of acknowledged entries.
+    pub async fn xack(
+        &self,
+        key: &[u8],
+        group: &[u8],
+        ids: &[StreamId],
+    ) -> Result<usize, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let group = match keyspace.stream_mut(key, now)? {
+            Some(stream) => stream.groups.get_mut(group),
+            None => None,
+        };
+        Ok(group.map_or(0, |group| {
+            ids.iter().filter(|id| group.acknowledge(**id)).count()
+        }))
+    }
+
+    /// Returns a summary of pending entries of the group.
+    pub async fn xpending_summary(
+        &self,
+        key: &[u8],
+        group: &[u8],
+    ) -> Result<PendingSummary, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        let group = keyspace
+            .stream(key, now)?
+            .and_then(|stream| stream.groups.get(group))
+            .ok_or_else(|| no_such_key_or_group(key, group))?;
+        let bounds = match (
+            group.pending.keys().next(),
+            group.pending.keys().next_back(),
+        ) {
+            (Some(first), Some(last)) => Some((*first, *last)),
+            _ => None,
+        };
+        Ok(PendingSummary {
+            count: group.pending.len(),
+            bounds,
+            consumers: group
+                .consumers
+                .iter()
+                .filter(|(_, consumer)| !consumer.pending.is_empty())
+                .map(|(name, consumer)| (name.clone(), consumer.pending.len()))
+                .collect(),
+        })
+    }
+
+    /// Returns pending entries of the group in the range.
+    pub async fn xpending_range(
+        &self,
+        key: &[u8],
+        group: &[u8],
+        range: &PendingRange,
+    ) -> Result<Vec<PendingInfo>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        let group = keyspace
+            .stream(key, now)?
+            .and_then(|stream| stream.groups.get(group))
+            .ok_or_else(|| no_such_key_or_group(key, group))?;
+        if range.start > range.end || range.count == 0 {
+            return Ok(vec![]);
+        }
+        let info = |id: StreamId| {
+            let entry = &group.pending[&id];
+            PendingInfo {
+                id,
+                consumer: entry.consumer.clone(),
+                idle: now.saturating_sub(entry.delivery_time),
+                delivery_count: entry.delivery_count,
+            }
+        };
+        let ids: Box<dyn Iterator<Item = StreamId>> = match &range.consumer {
+            Some(consumer) => match group.consumers.get(consumer) {
+                Some(consumer) => {
+                    Box::new(consumer.pending.range(range.start..=range.end).copied())
+                }
+                None => return Ok(vec![]),
+            },
+            None => Box::new(
+                group
+                    .pending
+                    .range(range.start..=range.end)
+                    .map(|(id, _)| *id),
+            ),
+        };
+        Ok(ids
+            .map(info)
+            .filter(|info| info.idle >= range.min_idle)
+            .take(range.count)
+            .collect())
+    }
+
+    /// Makes the consumer own the pending entries which are idle for long enough. Returns the
+    /// claimed entries. Pending entries deleted from the stream are dropped.
+    pub async fn xclaim(&self, request: &XClaim) -> Result<Vec<StreamEntry>, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let stream = keyspace
+            .stream_mut(&request.key, now)?
+            .ok_or_else(|| no_such_key_or_group(&request.key, &request.group))?;
+        let delivery_time = match request.time {
+            Some(ClaimTime::Idle(idle)) => now.saturating_sub(u64::try_from(idle).unwrap_or(0)),
+            Some(ClaimTime::At(time)) => u64::try_from(time).unwrap_or(now).min(now),
+            None => now,
+        };
+        stream
+            .with_group(&request.group, |stream, group| {
+                if let Some(last_id) = request.last_id {
+                    group.last_id = group.last_id.max(last_id);
+                }
+                group.consumer(&request.consumer, now);
+                let mut claimed = vec![];
+                for id in &request.ids {
+                    let entry = match stream_entry(stream, *id) {
+                        Some(entry) => entry,
+                        None => {
+                            group.acknowledge(*id);
+                            continue;
+                        }
+                    };
+                    let delivery_count = match group.pending.get(id) {
+                        Some(pending)
+                            if now.saturating_sub(pending.delivery_time) < request.min_idle =>
+                        {
+                            continue
+                        }
+                        Some(pending) => pending.delivery_count,
+                        None if request.force && request.min_idle == 0 => 0,
+                        None => continue,
+                    };
+                    let delivery_count = match (request.retry_count, request.just_id) {
+                        (Some(retry_count), _) => retry_count,
+                        (None, true) => delivery_count,
+                        (None, false) => delivery_count + 1,
+                    };
+                    group.assign(*id, &request.consumer, delivery_time, delivery_count);
+                    claimed.push(entry);
+                }
+                if !claimed.is_empty() {
+                    group.consumer(&request.consumer, now).active_time = Some(now);
+                }
+                claimed
+            })
+            .ok_or_else(|| no_such_key_or_group(&request.key, &request.group))
+    }
+
+    /// Scans pending entries of the group from the start of the request and makes the consumer
+    /// own those idle for long enough, up to the count of the request. Pending entries deleted
+    /// from the stream are dropped and count towards the limit.
+    pub async fn xautoclaim(&self, request: &XAutoClaim) -> Result<AutoClaimed, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let stream = keyspace
+            .stream_mut(&request.key, now)?
+            .ok_or_else(|| no_such_key_or_group(&request.key, &request.group))?;
+        stream
+            .with_group(&request.group, |stream, group| {
+                group.consumer(&request.consumer, now);
+                let attempts = request.count * AUTOCLAIM_ATTEMPTS_FACTOR;
+                // One more ID is the cursor if the scan stops before it.
+                let ids: Vec<StreamId> = group
+                    .pending
+                    .range(request.start..)
+                    .map(|(id, _)| *id)
+                    .take(attempts + 1)
+                    .collect();
+                let mut count = request.count;
+                let mut claimed = vec![];
+                let mut deleted = vec![];
+                let mut scanned = 0;
+                while scanned < ids.len().min(attempts) && count > 0 {
+                    let id = ids[scanned];
+                    scanned += 1;
+                    let entry = match stream_entry(stream, id) {
+                        Some(entry) => entry,
+                        None => {
+                            group.acknowledge(id);
+                            deleted.push(id);
+                            count -= 1;
+                            continue;
+                        }
+                    };
+                    let pending = &group.pending[&id];
+                    if now.saturating_sub(pending.delivery_time) < request.min_idle {
+                        continue;
+                    }
+                    let delivery_count = match request.just_id {
+                        true => pending.delivery_count,
+                        false => pending.delivery_count + 1,
+                    };
+                    group.assign(id, &request.consumer, now, delivery_count);
+                    claimed.push(entry);
+                    count -= 1;
+                }
+                if !claimed.is_empty() {
+                    group.consumer(&request.consumer, now).active_time = Some(now);
+                }
+                AutoClaimed {
+                    cursor: ids.get(scanned).copied().unwrap_or(StreamId::MIN),
+                    claimed,
+                    deleted,
+                }
+            })
+            .ok_or_else(|| no_such_key_or_group(&request.key, &request.group))
+    }
+
+    /// Returns information about the stream.
+    pub async fn xinfo_stream(&self, key: &[u8]) -> Result<StreamInfo, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        let stream = keyspace.stream(key, now)?.ok_or(RedisError::NoSuchKey)?;
+        let entry = |entry: Option<(&StreamId, &Vec<Bytes>)>| {
+            entry.map(|(id, fields)| StreamEntry {
+                id: *id,
+                fields: Some(fields.clone()),
+            })
+        };
+        Ok(StreamInfo {
+            length: stream.len(),
+            last_generated_id: stream.last_id,
+            max_deleted_id: stream.max_deleted_id,
+            entries_added: stream.entries_added,
+            first_id: stream.first_id(),
+            groups: stream.groups.len(),
+            first_entry: entry(stream.entries.iter().next()),
+            last_entry: entry(stream.entries.iter().next_back()),
+        })
+    }
+
+    /// Returns information about groups of the stream.
+    pub async fn xinfo_groups(&self, key: &[u8]) -> Result<Vec<GroupInfo>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        let stream = keyspace.stream(key, now)?.ok_or(RedisError::NoSuchKey)?;
+        Ok(stream
+            .groups
+            .iter()
+            .map(|(name, group)| GroupInfo {
+                name: name.clone(),
+                consumers: group.consumers.len(),
+                pending: group.pending.len(),
+                last_delivered_id: group.last_id,
+                entries_read: group.entries_read,
+                lag: group.lag(stream),
+            })
+            .collect())
+    }
+
+    /// Returns information about consumers of the group.
+    pub async fn xinfo_consumers(
+        &self,
+        key: &[u8],
+        group: &[u8],
+    ) -> Result<Vec<ConsumerInfo>, RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        let stream = keyspace.stream(key, now)?.ok_or(RedisError::NoSuchKey)?;
+        let group = stream
+            .groups
+            .get(group)
+            .ok_or_else(|| no_such_group(key, group))?;
+        Ok(group
+            .consumers
+            .iter()
+            .map(|(name, consumer)| ConsumerInfo {
+                name: name.clone(),
+                pending: consumer.pending.len(),
+                idle: now.saturating_sub(consumer.seen_time),
+                inactive: consumer
+                    .active_time
+                    .map(|active_time| now.saturating_sub(active_time)),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocol::{XAdd, XAddId};
+
+    fn id(ms: u64, seq: u64) -> StreamId {
+        StreamId { ms, seq }
+    }
+
+    async fn add(key: &'static str, ms: u64) {
+        Storage::instance()
+            .xadd(XAdd {
+                key: Bytes::from(key),
+                no_mkstream: false,
+                trim: None,
+                id: XAddId::Explicit(id(ms, 0)),
+                fields: vec![Bytes::from("f"), Bytes::from("v")],
+            })
+            .await
+            .unwrap();
+    }
+
+    fn reader(consumer: &'static str) -> ReadGroup {
+        ReadGroup {
+            group: Bytes::from("g"),
+            consumer: Bytes::from(consumer),
+            no_ack: false,
+        }
+    }
+
+    async fn read(
+        key: &'static str,
+        consumer: &'static str,
+        after: ReadAfter,
+    ) -> Result<Vec<StreamId>, RedisError> {
+        let read = Storage::instance()
+            .xreadgroup(
+                reader(consumer),
+                vec![(Bytes::from(key), after)],
+                None,
+                None,
+            )
+            .await?;
+        Ok(read
+            .into_iter()
+            .flatten()
+            .flat_map(|(_, entries)| entries)
+            .map(|entry| entry.id)
+            .collect())
+    }
+
+    fn claim(key: &'static str, consumer: &'static str, ids: Vec<StreamId>) -> XClaim {
+        XClaim {
+            key: Bytes::from(key),
+            group: Bytes::from("g"),
+            consumer: Bytes::from(consumer),
+            min_idle: 0,
+            ids,
+            time: None,
+            retry_count: None,
+            force: false,
+            just_id: false,
+            last_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn groups() {
+        let storage = Storage::instance();
+        let key = Bytes::from("group:manage");
+        assert_eq!(
+            storage
+                .xgroup_create(key.clone(), Bytes::from("g"), ReadAfter::Last, false, None)
+                .await,
+            Err(key_required())
+        );
+        storage
+            .xgroup_create(key.clone(), Bytes::from("g"), ReadAfter::Last, true, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            storage
+                .xgroup_create(key.clone(), Bytes::from("g"), ReadAfter::Last, true, None)
+                .await,
+            Err(RedisError::BusyGroup)
+        );
+        assert_eq!(
+            storage
+                .xgroup_createconsumer(&key, b"g", Bytes::from("c"))
+                .await,
+            Ok(true)
+        );
+        assert_eq!(
+            storage
+                .xgroup_createconsumer(&key, b"g", Bytes::from("c"))
+                .await,
+            Ok(false)
+        );
+        assert_eq!(
+            storage
+                .xgroup_setid(&key, b"x", ReadAfter::Last, None)
+                .await,
+            Err(no_such_group(&key, b"x"))
+        );
+        assert_eq!(storage.xgroup_destroy(&key, b"g").await, Ok(true));
+        assert_eq!(storage.xgroup_destroy(&key, b"g").await, Ok(false));
+        assert_eq!(storage.xinfo_groups(&key).await, Ok(vec![]));
+    }
+
+    #[tokio::test]
+    async fn delivery_and_acknowledgement() {
+        let storage = Storage::instance();
+        let key = "group:deliver";
+        for ms in 1..=3 {
+            add(key, ms).await;
+        }
+        storage
+            .xgroup_create(
+                Bytes::from(key),
+                Bytes::from("g"),
+                ReadAfter::Id(StreamId::MIN),
+                false,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            read(key, "a", ReadAfter::Last).await,
+            Ok(vec![id(1, 0), id(2, 0), id(3, 0)])
+        );
+        assert_eq!(read(key, "b", ReadAfter::Last).await, Ok(vec![]));
+        assert_eq!(
+            storage
+                .xack(key.as_bytes(), b"g", &[id(1, 0), id(9, 0)])
+                .await,
+            Ok(1)
+        );
+        // The history holds pending entries only, including deleted ones.
+        storage.xdel(key.as_bytes(), &[id(3, 0)]).await.unwrap();
+        let history = storage
+            .xreadgroup(
+                reader("a"),
+                vec![(Bytes::from(key), ReadAfter::Id(StreamId::MIN))],
+                None,
+                None,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(history[0].1.len(), 2);
+        assert_eq!(history[0].1[1].fields, None);
+
+        let summary = storage
+            .xpending_summary(key.as_bytes(), b"g")
+            .await
+            .unwrap();
+        assert_eq!(summary.count, 2);
+        assert_eq!(summary.bounds, Some((id(2, 0), id(3, 0))));
+        assert_eq!(summary.consumers, vec![(Bytes::from("a"), 2)]);
+        let range = PendingRange {
+            min_idle: 0,
+            start: StreamId::MIN,
+            end: StreamId::MAX,
+            count: 10,
+            consumer: None,
+        };
+        let pending = storage
+            .xpending_range(key.as_bytes(), b"g", &range)
+            .await
+            .unwrap();
+        // Delivered again by reading the history.
+        assert_eq!(pending[0].delivery_count, 2);
+
+        let groups = storage.xinfo_groups(key.as_bytes()).await.unwrap();
+        assert_eq!(groups[0].entries_read, Some(3));
+        assert_eq!(groups[0].lag, Some(0));
+        assert_eq!(
+            storage.xgroup_delconsumer(key.as_bytes(), b"g", b"a").await,
+            Ok(2)
+        );
+        assert_eq!(
+            read("group:absent", "a", ReadAfter::Last).await,
+            Err(RedisError::NoGroup(
+                "No such key 'group:absent' or consumer group 'g' in XREADGROUP with GROUP option"
+                    .to_owned()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn claiming() {
+        let storage = Storage::instance();
+        let key = "group:claim";
+        for ms in 1..=4 {
+            add(key, ms).await;
+        }
+        storage
+            .xgroup_create(
+                Bytes::from(key),
+                Bytes::from("g"),
+                ReadAfter::Id(StreamId::MIN),
+                false,
+                None,
+            )
+            .await
+            .unwrap();
+        read(key, "a", ReadAfter::Last).await.unwrap();
+
+        let mut request = claim(key, "b", vec![id(1, 0), id(2, 0)]);
+        request.min_idle = 60_000;
+        assert_eq!(storage.xclaim(&request).await, Ok(vec![]));
+        request.min_idle = 0;
+        let claimed = storage.xclaim(&request).await.unwrap();
+        assert_eq!(claimed.len(), 2);
+        let consumers = storage.xinfo_consumers(key.as_bytes(), b"g").await.unwrap();
+        assert_eq!(
+            consumers
+                .iter()
+                .map(|consumer| consumer.pending)
+                .collect::<Vec<_>>(),
+            vec![2, 2]
+        );
+
+        storage.xdel(key.as_bytes(), &[id(3, 0)]).await.unwrap();
+        let request = XAutoClaim {
+            key: Bytes::from(key),
+            group: Bytes::from("g"),
+            consumer: Bytes::from("c"),
+            min_idle: 0,
+            start: id(2, 0),
+            count: 2,
+            just_id: true,
+        };
+        let claimed = storage.xautoclaim(&request).await.unwrap();
+        assert_eq!(claimed.cursor, id(4, 0));
+        assert_eq!(
+            claimed
+                .claimed
+                .iter()
+                .map(|entry| entry.id)
+                .collect::<Vec<_>>(),
+            vec![id(2, 0)]
+        );
+        assert_eq!(claimed.deleted, vec![id(3, 0)]);
+        let summary = storage
+            .xpending_summary(key.as_bytes(), b"g")
+            .await
+            .unwrap();
+        assert_eq!(summary.count, 3);
+    }
+}
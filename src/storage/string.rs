@@ -0,0 +1,630 @@
+//! String commands.
+use super::{now_ms, Storage, Value};
+use crate::{
+    error::RedisError,
+    protocol::{format_stored_double, parse_f64, parse_i64, GetEx, Set, SetCondition},
+};
+use bytes::Bytes;
+
+/// Maximum length of a string value.
+const MAX_STRING_LENGTH: usize = 512 * 1024 * 1024;
+
+/// Returns the part of the value between the inclusive indexes the way GETRANGE does. Negative
+/// indexes count from the end and indexes out of the value are clamped to it.
+fn substring(value: &Bytes, start: i64, end: i64) -> Bytes {
+    let length = value.len() as i64;
+    if start < 0 && end < 0 && start > end {
+        return Bytes::new();
+    }
+    let start = if start < 0 { length + start } else { start }.max(0);
+    let end = if end < 0 { length + end } else { end }
+        .max(0)
+        .min(length - 1);
+    if start > end || length == 0 {
+        return Bytes::new();
+    }
+    value.slice(start as usize..=end as usize)
+}
+
+impl Storage {
+    /// Get value from storage by key. An expired key is deleted and reported as absent.
+    pub async fn get(&self, key: &[u8]) -> Result<Option<Bytes>, RedisError> {
+        let now = now_ms();
+        {
+            let keyspace = self.inner.read().await;
+            match keyspace.entries.get(key) {
+                None => return Ok(None),
+                Some(entry) if !entry.is_expired(now) => {
+                    return entry.value.as_string().cloned().map(Some)
+                }
+                Some(_) => {}
+            }
+        }
+        // The key is expired. Delete it unless it was overwritten in the meantime.
+        let mut keyspace = self.inner.write().await;
+        keyspace.remove_if_expired(key, now);
+        keyspace
+            .get(key, now)
+            .map(|entry| entry.value.as_string().cloned())
+            .transpose()
+    }
+
+    /// Gets values of the keys at once. Absent keys and keys holding values of other types
+    /// are `None`.
+    pub async fn get_many(&self, keys: &[Bytes]) -> Vec<Option<Bytes>> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        keys.iter()
+            .map(|key| match keyspace.get(key, now) {
+                Some(entry) => entry.value.as_string().ok().cloned(),
+                None => None,
+            })
+            .collect()
+    }
+
+    /// Gets string values of two keys at once. Absent keys are `None`.
+    pub async fn get_pair(
+        &self,
+        first: &[u8],
+        second: &[u8],
+    ) -> Result<(Option<Bytes>, Option<Bytes>), RedisError> {
+        let now = now_ms();
+        let keyspace = self.inner.read().await;
+        let get = |key| {
+            keyspace
+                .get(key, now)
+                .map(|entry| entry.value.as_string().cloned())
+                .transpose()
+        };
+        Ok((get(first)?, get(second)?))
+    }
+
+    /// Sets the keys to the values at once. The keys lose their deadlines.
+    pub async fn mset(&self, pairs: Vec<(Bytes, Bytes)>) {
+        let mut keyspace = self.inner.write().await;
+        for (key, value) in pairs {
+            keyspace.insert(key, Value::String(value), None);
+        }
+    }
+
+    /// Sets the keys to the values at once if none of the keys exist. Returns false if any of
+    /// them exists, in which case nothing is set.
+    pub async fn msetnx(&self, pairs: Vec<(Bytes, Bytes)>) -> bool {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        if pairs
+            .iter()
+            .any(|(key, _)| keyspace.get(key, now).is_some())
+        {
+            return false;
+        }
+        for (key, value) in pairs {
+            keyspace.insert(key, Value::String(value), None);
+        }
+        true
+    }
+
+    /// Removes the key and returns its value.
+    pub async fn get_del(&self, key: &[u8]) -> Result<Option<Bytes>, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let value = match keyspace.get_live(key, now) {
+            Some(entry) => entry.value.as_string()?.clone(),
+            None => return Ok(None),
+        };
+        keyspace.remove(key);
+        Ok(Some(value))
+    }
+
+    /// Returns the value of the key and changes its deadline as requested. A deadline in the
+    /// past removes the key.
+    pub async fn get_ex(&self, request: GetEx) -> Result<Option<Bytes>, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let value = match keyspace.get_live(&request.key, now) {
+            Some(entry) => entry.value.as_string()?.clone(),
+            None => return Ok(None),
+        };
+        if request.persist {
+            keyspace.set_expires_at(&request.key, None);
+        } else if let Some(expiration) = request.expiration {
+            let deadline = expiration.deadline(now);
+            if deadline <= now as i64 {
+                keyspace.remove(&request.key);
+            } else {
+                keyspace.set_expires_at(&request.key, Some(deadline as u64));
+            }
+        }
+        Ok(Some(value))
+    }
+
+    /// Returns the length of the string stored at the key, 0 if the key is absent.
+    pub async fn strlen(&self, key: &[u8]) -> Result<usize, RedisError> {
+        let now = now_ms();
+        match self.inner.read().await.get(key, now) {
+            Some(entry) => Ok(entry.value.as_string()?.len()),
+            None => Ok(0),
+        }
+    }
+
+    /// Returns the substring between the inclusive indexes. Negative indexes count from the end.
+    pub async fn get_range(&self, key: &[u8], start: i64, end: i64) -> Result<Bytes, RedisError> {
+        let now = now_ms();
+        match self.inner.read().await.get(key, now) {
+            Some(entry) => Ok(substring(entry.value.as_string()?, start, end)),
+            None => Ok(Bytes::new()),
+        }
+    }
+
+    /// Appends the value to the string stored at the key, creating the key if it is absent.
+    /// Returns the new length of the string.
+    pub async fn append(&self, key: Bytes, value: &[u8]) -> Result<usize, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let current = match keyspace.get_live(&key, now) {
+            Some(entry) => entry.value.as_string()?.len(),
+            None => 0,
+        };
+        if current + value.len() > MAX_STRING_LENGTH {
+            return Err(RedisError::StringTooLong);
+        }
+        let entry = keyspace.get_or_insert_with(key, now, || Value::String(Bytes::new()));
+        let mut appended = entry.value.take_string()?;
+        appended.extend_from_slice(value);
+        let length = appended.len();
+        entry.value = Value::String(Bytes::from(appended));
+        Ok(length)
+    }
+
+    /// Overwrites the string stored at the key starting at the offset. The string is padded with
+    /// zero bytes up to the offset if needed. Returns the new length of the string.
+    pub async fn set_range(
+        &self,
+        key: Bytes,
+        offset: usize,
+        value: &[u8],
+    ) -> Result<usize, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let current = match keyspace.get_live(&key, now) {
+            Some(entry) => entry.value.as_string()?.len(),
+            None => 0,
+        };
+        // An empty value changes nothing and doesn't create the key.
+        if value.is_empty() {
+            return Ok(current);
+        }
+        let end = offset
+            .checked_add(value.len())
+            .filter(|end| *end <= MAX_STRING_LENGTH)
+            .ok_or(RedisError::StringTooLong)?;
+        let entry = keyspace.get_or_insert_with(key, now, || Value::String(Bytes::new()));
+        let mut updated = entry.value.take_string()?;
+        if updated.len() < end {
+            updated.resize(end, 0);
+        }
+        updated[offset..end].copy_from_slice(value);
+        let length = updated.len();
+        entry.value = Value::String(Bytes::from(updated));
+        Ok(length)
+    }
+
+    /// Store key-value pair in the storage if the condition of the request holds. A value of
+    /// any type is overwritten, but the old value can only be returned for a string.
+    /// Returns whether the pair was stored and the old value of the key.
+    pub async fn set(&self, request: Set) -> Result<(bool, Option<Bytes>), RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let (exists, old_value, old_expires_at) = match keyspace.get_live(&request.key, now) {
+            Some(entry) => {
+                let old_value = match &entry.value {
+                    Value::String(value) => Some(value.clone()),
+                    _ if request.get => return Err(RedisError::WrongType),
+                    _ => None,
+                };
+                (true, old_value, entry.expires_at)
+            }
+            None => (false, None, None),
+        };
+        let holds = match request.condition {
+            None => true,
+            Some(SetCondition::Nx) => !exists,
+            Some(SetCondition::Xx) => exists,
+        };
+        if !holds {
+            return Ok((false, old_value));
+        }
+
+        let expires_at = if request.keep_ttl {
+            old_expires_at.map(|deadline| deadline as i64)
+        } else {
+            request
+                .expiration
+                .map(|expiration| expiration.deadline(now))
+        };
+        match expires_at {
+            // The key would expire right away, so it is not stored at all.
+            Some(deadline) if deadline <= now as i64 => {
+                keyspace.remove(&request.key);
+            }
+            _ => keyspace.insert(
+                request.key,
+                Value::String(request.value),
+                expires_at.map(|deadline| deadline as u64),
+            ),
+        }
+        Ok((true, old_value))
+    }
+
+    /// Adds the increment to the integer stored at the key and returns the result. An absent key
+    /// counts as 0. The key retains its deadline.
+    pub async fn incr_by(&self, key: Bytes, increment: i64) -> Result<i64, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let (current, expires_at) = match keyspace.get_live(&key, now) {
+            Some(entry) => (parse_i64(entry.value.as_string()?)?, entry.expires_at),
+            None => (0, None),
+        };
+        let value = current
+            .checked_add(increment)
+            .ok_or(RedisError::NotInteger)?;
+        keyspace.insert(
+            key,
+            Value::String(Bytes::from(value.to_string())),
+            expires_at,
+        );
+        Ok(value)
+    }
+
+    /// Adds the increment to the number stored at the key and returns the result formatted the
+    /// same way it is stored. An absent key counts as 0. The key retains its deadline.
+    pub async fn incr_by_float(&self, key: Bytes, increment: f64) -> Result<Bytes, RedisError> {
+        let now = now_ms();
+        let mut keyspace = self.inner.write().await;
+        let (current, expires_at) = match keyspace.get_live(&key, now) {
+            Some(entry) => (parse_f64(entry.value.as_string()?)?, entry.expires_at),
+            None => (0.0, None),
+        };
+        let value = current + increment;
+        if !value.is_finite() {
+            return Err(RedisError::Other(
+                "increment would produce NaN or Infinity".to_owned(),
+            ));
+        }
+        let value = Bytes::from(format_stored_double(value));
+        keyspace.insert(key, Value::String(value.clone()), expires_at);
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        protocol::Expiration,
+        storage::test::{keys, set},
+    };
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn set_and_get() {
+        let storage = Storage::instance();
+        let request = Set {
+            key: Bytes::from("key"),
+            value: Bytes::from("value"),
+            ..Default::default()
+        };
+        storage.set(request).await.unwrap();
+        assert_eq!(storage.get(b"key").await, Ok(Some(Bytes::from("value"))));
+    }
+
+    #[tokio::test]
+    async fn get_absent_key() {
+        let storage = Storage::instance();
+        assert_eq!(storage.get(b"absent").await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn get_expired_key() {
+        let storage = Storage::instance();
+        let request = Set {
+            key: Bytes::from("expiring"),
+            value: Bytes::from("value"),
+            expiration: Some(Expiration::After(50)),
+            ..Default::default()
+        };
+        storage.set(request).await.unwrap();
+        assert_eq!(
+            storage.get(b"expiring").await,
+            Ok(Some(Bytes::from("value")))
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(storage.get(b"expiring").await, Ok(None));
+        assert!(!storage
+            .inner
+            .read()
+            .await
+            .entries
+            .contains_key(&b"expiring"[..]));
+    }
+
+    #[tokio::test]
+    async fn set_removes_deadline() {
+        let storage = Storage::instance();
+        storage
+            .set(Set {
+                key: Bytes::from("persisted"),
+                value: Bytes::from("old"),
+                expiration: Some(Expiration::After(50)),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        storage
+            .set(Set {
+                key: Bytes::from("persisted"),
+                value: Bytes::from("new"),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            storage.get(b"persisted").await,
+            Ok(Some(Bytes::from("new")))
+        );
+    }
+
+    #[tokio::test]
+    async fn incr_by() {
+        let storage = Storage::instance();
+        let key = || Bytes::from("incr:a");
+        assert_eq!(storage.incr_by(key(), 5).await, Ok(5));
+        assert_eq!(storage.incr_by(key(), -7).await, Ok(-2));
+        assert_eq!(storage.get(b"incr:a").await, Ok(Some(Bytes::from("-2"))));
+        assert_eq!(
+            storage.incr_by(key(), i64::MIN).await,
+            Err(RedisError::NotInteger)
+        );
+        assert_eq!(
+            storage.incr_by_float(key(), 0.5).await,
+            Ok(Bytes::from("-1.5"))
+        );
+        assert_eq!(storage.incr_by(key(), 1).await, Err(RedisError::NotInteger));
+        assert_eq!(
+            storage.incr_by_float(key(), f64::INFINITY).await,
+            Err(RedisError::Other(
+                "increment would produce NaN or Infinity".to_owned()
+            ))
+        );
+        assert_eq!(storage.get(b"incr:a").await, Ok(Some(Bytes::from("-1.5"))));
+
+        let key = || Bytes::from("incr:float");
+        assert_eq!(
+            storage.incr_by_float(key(), 1e-5).await,
+            Ok(Bytes::from("0.00001"))
+        );
+        assert_eq!(
+            storage.incr_by_float(key(), 1e20).await,
+            Ok(Bytes::from("100000000000000000000"))
+        );
+        assert_eq!(
+            storage.get(b"incr:float").await,
+            Ok(Some(Bytes::from("100000000000000000000")))
+        );
+
+        set("incr:text", None).await;
+        assert_eq!(
+            storage.incr_by_float(Bytes::from("incr:text"), 1.0).await,
+            Err(RedisError::NotFloat)
+        );
+    }
+
+    #[tokio::test]
+    async fn incr_by_keeps_deadline() {
+        let storage = Storage::instance();
+        storage
+            .set(Set {
+                key: Bytes::from("incr:volatile"),
+                value: Bytes::from("10"),
+                expiration: Some(Expiration::After(60_000)),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.incr_by(Bytes::from("incr:volatile"), 1).await,
+            Ok(11)
+        );
+        assert!(matches!(
+            storage.expires_at(b"incr:volatile").await,
+            Some(Some(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_incr() {
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                tokio::spawn(async {
+                    for _ in 0..100 {
+                        Storage::instance()
+                            .incr_by(Bytes::from("incr:concurrent"), 1)
+                            .await
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(
+            Storage::instance().get(b"incr:concurrent").await,
+            Ok(Some(Bytes::from("800")))
+        );
+    }
+
+    #[tokio::test]
+    async fn mset_and_mget() {
+        let storage = Storage::instance();
+        let pair = |key: &'static str, value: &'static str| (Bytes::from(key), Bytes::from(value));
+        set("mset:volatile", Some(60_000)).await;
+        storage
+            .mset(vec![pair("mset:a", "1"), pair("mset:volatile", "2")])
+            .await;
+        assert_eq!(storage.expires_at(b"mset:volatile").await, Some(None));
+        assert!(
+            !storage
+                .msetnx(vec![pair("mset:b", "3"), pair("mset:a", "4")])
+                .await
+        );
+        assert!(
+            storage
+                .msetnx(vec![pair("mset:b", "3"), pair("mset:c", "5")])
+                .await
+        );
+        assert_eq!(
+            storage
+                .get_many(&keys(&["mset:a", "mset:absent", "mset:b", "mset:c"]))
+                .await,
+            vec![
+                Some(Bytes::from("1")),
+                None,
+                Some(Bytes::from("3")),
+                Some(Bytes::from("5"))
+            ]
+        );
+    }
+
+    #[test]
+    fn substrings() {
+        let value = Bytes::from("This is a string");
+        assert_eq!(substring(&value, 0, 3), "This");
+        assert_eq!(substring(&value, -3, -1), "ing");
+        assert_eq!(substring(&value, 0, -1), value);
+        assert_eq!(substring(&value, 10, 100), "string");
+        assert_eq!(substring(&value, -100, 1), "Th");
+        assert_eq!(substring(&value, 5, 3), "");
+        assert_eq!(substring(&value, -1, -5), "");
+        assert_eq!(substring(&Bytes::new(), 0, -1), "");
+    }
+
+    #[tokio::test]
+    async fn append_and_set_range() {
+        let storage = Storage::instance();
+        let key = || Bytes::from("append:a");
+        assert_eq!(storage.append(key(), b"Hello").await, Ok(5));
+        assert_eq!(storage.append(key(), b" World").await, Ok(11));
+        assert_eq!(storage.set_range(key(), 6, b"Redis").await, Ok(11));
+        assert_eq!(
+            storage.get(b"append:a").await,
+            Ok(Some(Bytes::from("Hello Redis")))
+        );
+        assert_eq!(storage.strlen(b"append:a").await, Ok(11));
+        // A value held elsewhere keeps its contents when the stored string grows.
+        let held = storage.get(b"append:a").await.unwrap().unwrap();
+        assert_eq!(storage.append(key(), b"!").await, Ok(12));
+        assert_eq!(storage.set_range(key(), 0, b"J").await, Ok(12));
+        assert_eq!(held, Bytes::from("Hello Redis"));
+        assert_eq!(
+            storage.get(b"append:a").await,
+            Ok(Some(Bytes::from("Jello Redis!")))
+        );
+
+        assert_eq!(
+            storage.set_range(Bytes::from("append:b"), 3, b"").await,
+            Ok(0)
+        );
+        assert_eq!(storage.exists(&[Bytes::from("append:b")]).await, 0);
+        assert_eq!(
+            storage.set_range(Bytes::from("append:b"), 3, b"x").await,
+            Ok(4)
+        );
+        assert_eq!(
+            storage.get(b"append:b").await,
+            Ok(Some(Bytes::from_static(b"\0\0\0x")))
+        );
+        assert_eq!(
+            storage
+                .set_range(Bytes::from("append:b"), MAX_STRING_LENGTH, b"x")
+                .await,
+            Err(RedisError::StringTooLong)
+        );
+    }
+
+    #[tokio::test]
+    async fn get_del_and_get_ex() {
+        let storage = Storage::instance();
+        set("getdel:a", None).await;
+        assert_eq!(
+            storage.get_del(b"getdel:a").await,
+            Ok(Some(Bytes::from("getdel:a")))
+        );
+        assert_eq!(storage.get_del(b"getdel:a").await, Ok(None));
+
+        set("getex:a", None).await;
+        let get_ex = |expiration, persist| GetEx {
+            key: Bytes::from("getex:a"),
+            expiration,
+            persist,
+        };
+        assert_eq!(
+            storage
+                .get_ex(get_ex(Some(Expiration::After(60_000)), false))
+                .await,
+            Ok(Some(Bytes::from("getex:a")))
+        );
+        assert!(matches!(
+            storage.expires_at(b"getex:a").await,
+            Some(Some(_))
+        ));
+        storage.get_ex(get_ex(None, true)).await.unwrap();
+        assert_eq!(storage.expires_at(b"getex:a").await, Some(None));
+        storage
+            .get_ex(get_ex(Some(Expiration::At(1)), false))
+            .await
+            .unwrap();
+        assert_eq!(storage.expires_at(b"getex:a").await, None);
+    }
+
+    #[tokio::test]
+    async fn wrong_type() {
+        let storage = Storage::instance();
+        storage.inner.write().await.insert(
+            Bytes::from("string:list"),
+            Value::List(Default::default()),
+            None,
+        );
+        let key = || Bytes::from("string:list");
+        assert_eq!(
+            storage.get(b"string:list").await,
+            Err(RedisError::WrongType)
+        );
+        assert_eq!(storage.get_many(&[key()]).await, vec![None]);
+        assert_eq!(
+            storage.append(key(), b"x").await,
+            Err(RedisError::WrongType)
+        );
+        assert_eq!(storage.incr_by(key(), 1).await, Err(RedisError::WrongType));
+        let request = || Set {
+            key: key(),
+            value: Bytes::from("v"),
+            ..Default::default()
+        };
+        assert_eq!(
+            storage
+                .set(Set {
+                    get: true,
+                    ..request()
+                })
+                .await,
+            Err(RedisError::WrongType)
+        );
+        assert_eq!(storage.set(request()).await, Ok((true, None)));
+        assert_eq!(
+            storage.get(b"string:list").await,
+            Ok(Some(Bytes::from("v")))
+        );
+    }
+}